@@ -12,6 +12,8 @@
 //! - https://github.com/bitcoin/bips/blob/master/bip-0350.mediawiki
 //! - https://github.com/bitcoin/bips/blob/master/bip-0351.mediawiki
 
+use crate::network_params::NetworkConstants;
+use crate::{crypto, scripts};
 use bech32::{FromBase32, ToBase32, Variant};
 
 /// Bitcoin address encoding error
@@ -22,6 +24,8 @@ pub enum AddressError {
     InvalidEncoding,
     UnsupportedVariant,
     InvalidHRP,
+    /// Well-formed, but for a network other than the named one
+    WrongNetwork(String),
 }
 
 impl std::fmt::Display for AddressError {
@@ -32,6 +36,9 @@ impl std::fmt::Display for AddressError {
             AddressError::InvalidEncoding => write!(f, "Invalid address encoding"),
             AddressError::UnsupportedVariant => write!(f, "Unsupported address variant"),
             AddressError::InvalidHRP => write!(f, "Invalid human-readable part"),
+            AddressError::WrongNetwork(network) => {
+                write!(f, "Address does not belong to network {network}")
+            }
         }
     }
 }
@@ -55,6 +62,163 @@ impl Network {
             Network::Regtest => "bcrt",
         }
     }
+
+    /// Network using a human-readable part, matched case-insensitively
+    pub fn from_hrp(hrp: &str) -> Option<Self> {
+        [Network::Mainnet, Network::Testnet, Network::Regtest]
+            .into_iter()
+            .find(|network| network.hrp().eq_ignore_ascii_case(hrp))
+    }
+}
+
+/// Longest valid Bech32/Bech32m string (BIP173)
+const MAX_ADDRESS_LENGTH: usize = 90;
+
+/// Encode a segwit address: Bech32 for version 0, Bech32m for version 1+
+///
+/// The program must satisfy the BIP141 length rules: 2 to 40 bytes, and
+/// exactly 20 or 32 bytes for version 0.
+pub fn encode_segwit(
+    hrp: &str,
+    witness_version: u8,
+    program: &[u8],
+) -> Result<String, AddressError> {
+    check_witness_program(witness_version, program)?;
+    let mut data = vec![bech32::u5::try_from_u8(witness_version)
+        .map_err(|_| AddressError::InvalidWitnessVersion)?];
+    data.extend_from_slice(&witness_program_to_base32(program));
+    bech32::encode(hrp, &data, variant_for(witness_version))
+        .map_err(|_| AddressError::InvalidEncoding)
+}
+
+/// Decode a segwit address into its witness version and program
+///
+/// Checks the checksum variant against the version (BIP350), the program
+/// length rules, and that the human-readable part belongs to a known network.
+pub fn decode_segwit(addr: &str) -> Result<(u8, Vec<u8>), AddressError> {
+    let (_, witness_version, program) = decode_with_network(addr)?;
+    Ok((witness_version, program))
+}
+
+fn decode_with_network(addr: &str) -> Result<(Network, u8, Vec<u8>), AddressError> {
+    let (hrp, data, variant) = split_bech32(addr)?;
+    let network = Network::from_hrp(&hrp).ok_or(AddressError::InvalidHRP)?;
+    let (witness_version, program) = witness_program_from(&data, variant)?;
+    Ok((network, witness_version, program))
+}
+
+fn split_bech32(addr: &str) -> Result<(String, Vec<bech32::u5>, Variant), AddressError> {
+    if addr.len() > MAX_ADDRESS_LENGTH {
+        return Err(AddressError::InvalidEncoding);
+    }
+    bech32::decode(addr).map_err(|_| AddressError::InvalidEncoding)
+}
+
+/// Witness version and program from the decoded data part
+fn witness_program_from(
+    data: &[bech32::u5],
+    variant: Variant,
+) -> Result<(u8, Vec<u8>), AddressError> {
+    let (version, program) = data.split_first().ok_or(AddressError::InvalidEncoding)?;
+    let witness_version = version.to_u8();
+    if witness_version > 16 {
+        return Err(AddressError::InvalidWitnessVersion);
+    }
+    if variant != variant_for(witness_version) {
+        return Err(AddressError::UnsupportedVariant);
+    }
+    let program = base32_to_witness_program(program)?;
+    check_witness_program(witness_version, &program)?;
+    Ok((witness_version, program))
+}
+
+/// Output script paying to `addr`, which must belong to network `net`
+///
+/// Accepts base58check P2PKH and P2SH addresses, matched against the
+/// network's version bytes, and segwit addresses of any witness version,
+/// matched against its human-readable part. A well-formed address for
+/// another network fails with `AddressError::WrongNetwork`.
+pub fn address_to_script(addr: &str, net: &NetworkConstants) -> Result<Vec<u8>, AddressError> {
+    let wrong_network = || AddressError::WrongNetwork(net.network_name.clone());
+
+    if let Ok((hrp, data, variant)) = split_bech32(addr) {
+        if !hrp.eq_ignore_ascii_case(&net.bech32_hrp) {
+            return Err(wrong_network());
+        }
+        let (witness_version, program) = witness_program_from(&data, variant)?;
+        return Ok(scripts::witness_program_script(witness_version, &program));
+    }
+
+    let payload = base58check_decode(addr)?;
+    let (&prefix, hash) = payload.split_first().ok_or(AddressError::InvalidEncoding)?;
+    let hash: [u8; 20] = hash.try_into().map_err(|_| AddressError::InvalidEncoding)?;
+    if prefix == net.pubkey_address_prefix {
+        Ok(scripts::p2pkh_script(hash))
+    } else if prefix == net.script_address_prefix {
+        Ok(scripts::p2sh_script(hash))
+    } else {
+        Err(wrong_network())
+    }
+}
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Decode base58 and strip the 4-byte double-SHA256 checksum
+fn base58check_decode(s: &str) -> Result<Vec<u8>, AddressError> {
+    // Base-256 digits, least significant first
+    let mut digits: Vec<u8> = Vec::new();
+    for ch in s.bytes() {
+        let mut carry = BASE58_ALPHABET
+            .iter()
+            .position(|&symbol| symbol == ch)
+            .ok_or(AddressError::InvalidEncoding)? as u32;
+        for digit in digits.iter_mut() {
+            carry += *digit as u32 * 58;
+            *digit = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            digits.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    // Each leading '1' encodes a leading zero byte
+    let zeros = s.bytes().take_while(|&ch| ch == b'1').count();
+    let mut bytes = vec![0u8; zeros];
+    bytes.extend(digits.iter().rev());
+
+    if bytes.len() < 4 {
+        return Err(AddressError::InvalidEncoding);
+    }
+    let (payload, checksum) = bytes.split_at(bytes.len() - 4);
+    if crypto::sha256d(payload)[..4] != *checksum {
+        return Err(AddressError::InvalidEncoding);
+    }
+    Ok(payload.to_vec())
+}
+
+/// BIP173 checksum for version 0, BIP350 for later versions
+fn variant_for(witness_version: u8) -> Variant {
+    if witness_version == 0 {
+        Variant::Bech32
+    } else {
+        Variant::Bech32m
+    }
+}
+
+/// BIP141 witness program rules
+fn check_witness_program(witness_version: u8, program: &[u8]) -> Result<(), AddressError> {
+    if witness_version > 16 {
+        return Err(AddressError::InvalidWitnessVersion);
+    }
+    let valid_length = match witness_version {
+        0 => program.len() == 20 || program.len() == 32,
+        _ => (2..=40).contains(&program.len()),
+    };
+    if !valid_length {
+        return Err(AddressError::InvalidWitnessLength);
+    }
+    Ok(())
 }
 
 /// Encoded Bitcoin address
@@ -70,37 +234,16 @@ pub struct BitcoinAddress {
 
 impl BitcoinAddress {
     /// Create a new Bech32/Bech32m address
+    ///
+    /// Accepts exactly the programs `decode` does: version 0 must be 20 or
+    /// 32 bytes, later versions 2 to 40 bytes. Version 1 programs other than
+    /// 32 bytes are valid addresses, just not Taproot ones.
     pub fn new(
         network: Network,
         witness_version: u8,
         witness_program: Vec<u8>,
     ) -> Result<Self, AddressError> {
-        // Validate witness version
-        if witness_version > 16 {
-            return Err(AddressError::InvalidWitnessVersion);
-        }
-
-        // Validate witness program length
-        match witness_version {
-            0 => {
-                // SegWit v0: P2WPKH (20 bytes) or P2WSH (32 bytes)
-                if witness_program.len() != 20 && witness_program.len() != 32 {
-                    return Err(AddressError::InvalidWitnessLength);
-                }
-            }
-            1 => {
-                // Taproot v1: P2TR (32 bytes)
-                if witness_program.len() != 32 {
-                    return Err(AddressError::InvalidWitnessLength);
-                }
-            }
-            _ => {
-                // Future witness versions: 2-16 bytes (as per BIP342)
-                if witness_program.len() < 2 || witness_program.len() > 40 {
-                    return Err(AddressError::InvalidWitnessLength);
-                }
-            }
-        }
+        check_witness_program(witness_version, &witness_program)?;
 
         Ok(BitcoinAddress {
             network,
@@ -114,73 +257,16 @@ impl BitcoinAddress {
     /// BIP173: Witness version 0 uses Bech32
     /// BIP350: Witness version 1+ uses Bech32m
     pub fn encode(&self) -> Result<String, AddressError> {
-        let hrp = self.network.hrp();
-
-        // Convert witness program to base32 (u5)
-        let program_base32 = witness_program_to_base32(&self.witness_program);
-
-        // Combine witness version and program as u5 values
-        // Witness version needs to be converted to u5 (it's 0-16, fits in u5)
-        let mut data = vec![bech32::u5::try_from_u8(self.witness_version)
-            .map_err(|_| AddressError::InvalidWitnessVersion)?];
-        data.extend_from_slice(&program_base32);
-
-        // Encode using appropriate variant
-        let encoded = if self.witness_version == 0 {
-            // BIP173: Bech32 for version 0
-            bech32::encode(hrp, &data, Variant::Bech32)
-                .map_err(|_| AddressError::InvalidEncoding)?
-        } else {
-            // BIP350: Bech32m for version 1+
-            bech32::encode(hrp, &data, Variant::Bech32m)
-                .map_err(|_| AddressError::InvalidEncoding)?
-        };
-
-        Ok(encoded)
+        encode_segwit(
+            self.network.hrp(),
+            self.witness_version,
+            &self.witness_program,
+        )
     }
 
     /// Decode Bech32 or Bech32m address
     pub fn decode(encoded: &str) -> Result<Self, AddressError> {
-        // Try Bech32m first (Taproot), then Bech32 (SegWit)
-        let (hrp, data, variant) =
-            bech32::decode(encoded).map_err(|_| AddressError::InvalidEncoding)?;
-
-        // Determine network from HRP
-        let network = match hrp.as_str() {
-            "bc" => Network::Mainnet,
-            "tb" => Network::Testnet,
-            "bcrt" => Network::Regtest,
-            _ => return Err(AddressError::InvalidHRP),
-        };
-
-        if data.is_empty() {
-            return Err(AddressError::InvalidEncoding);
-        }
-
-        // First u5 value is witness version
-        let witness_version_u5 = data[0];
-        let witness_version = witness_version_u5.to_u8();
-        if witness_version > 16 {
-            return Err(AddressError::InvalidWitnessVersion);
-        }
-
-        // Remaining u5 values are witness program (base32 encoded)
-        let program_base32 = &data[1..];
-        let witness_program = base32_to_witness_program(program_base32)?;
-
-        // Validate variant matches witness version
-        match (witness_version, variant) {
-            (0, Variant::Bech32) => {
-                // Correct: SegWit v0 uses Bech32
-            }
-            (1..=16, Variant::Bech32m) => {
-                // Correct: Taproot v1+ uses Bech32m
-            }
-            _ => {
-                return Err(AddressError::UnsupportedVariant);
-            }
-        }
-
+        let (network, witness_version, witness_program) = decode_with_network(encoded)?;
         Ok(BitcoinAddress {
             network,
             witness_version,
@@ -294,11 +380,209 @@ mod tests {
     }
 
     #[test]
-    fn test_invalid_witness_length_taproot() {
-        // Taproot must be 32 bytes
-        let program = vec![0x75; 20]; // Wrong length
-        let result = BitcoinAddress::new(Network::Mainnet, 1, program);
-        assert!(result.is_err());
+    fn test_invalid_witness_length() {
+        for (version, len) in [(0, 21), (1, 1), (1, 41), (16, 41)] {
+            assert_eq!(
+                BitcoinAddress::new(Network::Mainnet, version, vec![0x75; len]),
+                Err(AddressError::InvalidWitnessLength),
+                "v{version} with {len} bytes"
+            );
+        }
+
+        // Only 32-byte v1 programs are Taproot, but others are still addresses
+        let addr = BitcoinAddress::new(Network::Mainnet, 1, vec![0x75; 20]).unwrap();
+        assert!(!addr.is_taproot());
+        assert_eq!(addr.address_type(), "Unknown");
+    }
+
+    #[test]
+    fn test_decoded_address_can_be_recreated() {
+        let short_v1 = BitcoinAddress::new(Network::Mainnet, 1, vec![0x75, 0x1e]).unwrap();
+        let decoded = BitcoinAddress::decode(&short_v1.encode().unwrap()).unwrap();
+        assert_eq!(decoded, short_v1);
+
+        let recreated = BitcoinAddress::new(
+            decoded.network,
+            decoded.witness_version,
+            decoded.witness_program.clone(),
+        )
+        .unwrap();
+        assert_eq!(recreated, decoded);
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// BIP350 valid segwit addresses with their scriptPubKeys
+    const VALID_ADDRESSES: [(&str, &str); 8] = [
+        (
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+        ),
+        (
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+            "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+        ),
+        (
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+            "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+        ),
+        ("BC1SW50QGDZ25J", "6002751e"),
+        (
+            "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs",
+            "5210751e76e8199196d454941c45d1b3a323",
+        ),
+        (
+            "tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy",
+            "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+        ),
+        (
+            "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+            "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+        ),
+        (
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        ),
+    ];
+
+    #[test]
+    fn test_bip350_valid_addresses() {
+        for (addr, script_hex) in VALID_ADDRESSES {
+            let script = hex(script_hex);
+            let (version, program) = decode_segwit(addr).unwrap_or_else(|e| panic!("{addr}: {e}"));
+            // OP_0 or OP_1..OP_16, then the program push
+            let expected_version = match script[0] {
+                0 => 0,
+                op => op - 0x50,
+            };
+            assert_eq!(version, expected_version, "{addr}");
+            assert_eq!(program, script[2..], "{addr}");
+
+            let hrp = &addr[..addr.rfind('1').unwrap()];
+            let encoded = encode_segwit(&hrp.to_lowercase(), version, &program).unwrap();
+            assert_eq!(encoded, addr.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_bip350_invalid_addresses() {
+        let invalid = [
+            // Unknown human-readable part
+            "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut",
+            // Bech32 checksum on a version 1+ program
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+            "tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf",
+            "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+            // Bech32m checksum on a version 0 program
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+            "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47",
+            // Invalid character in the data part
+            "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+            // Witness version 17
+            "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R",
+            // Program of 1 and 41 bytes
+            "bc1pw5dgrnzv",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+            // 16-byte version 0 program
+            "BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P",
+            // Mixed case
+            "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq",
+            // More than 4 bits of zero padding, then non-zero padding
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf",
+            "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j",
+            // Empty data section
+            "bc1gmk9yu",
+            // First valid vector with its last character changed
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T5",
+        ];
+        for addr in invalid {
+            assert!(decode_segwit(addr).is_err(), "{addr} accepted");
+        }
+    }
+
+    #[test]
+    fn test_encode_segwit_checks_program() {
+        assert_eq!(
+            encode_segwit("bc", 0, &[0; 16]),
+            Err(AddressError::InvalidWitnessLength)
+        );
+        assert_eq!(
+            encode_segwit("bc", 17, &[0; 32]),
+            Err(AddressError::InvalidWitnessVersion)
+        );
+        // Version 1 programs other than 32 bytes are valid, just not Taproot
+        let addr = encode_segwit("bc", 1, &[0x75, 0x1e]).unwrap();
+        assert_eq!(decode_segwit(&addr).unwrap(), (1, vec![0x75, 0x1e]));
+    }
+
+    #[test]
+    fn test_address_to_script() {
+        use crate::validation::{classify_script, ScriptType};
+
+        let mainnet = NetworkConstants::mainnet().unwrap();
+        let testnet = NetworkConstants::testnet().unwrap();
+        let pubkey_hash = hex("77bff20c60e522dfaa3350c39b030a5d004e839a");
+
+        let p2pkh = address_to_script("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &mainnet).unwrap();
+        assert_eq!(classify_script(&p2pkh), ScriptType::P2PKH);
+        assert_eq!(p2pkh[3..23], pubkey_hash);
+        // Same key hash under the testnet version byte
+        let p2pkh = address_to_script("mrS8eVKXguwufwvsVe9GtgGb7fif9UQeAu", &testnet).unwrap();
+        assert_eq!(p2pkh[3..23], pubkey_hash);
+
+        let p2sh = address_to_script("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", &mainnet).unwrap();
+        assert_eq!(
+            p2sh,
+            scripts::p2sh_script(
+                hex("b472a266d0bd89c13706a4132ccfb16f7c3b9fcb")
+                    .try_into()
+                    .unwrap()
+            )
+        );
+
+        let p2wpkh =
+            address_to_script("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", &mainnet).unwrap();
+        assert_eq!(classify_script(&p2wpkh), ScriptType::P2WPKH);
+
+        let p2tr = address_to_script(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            &mainnet,
+        )
+        .unwrap();
+        assert_eq!(classify_script(&p2tr), ScriptType::P2TR);
+
+        for (addr, script_hex) in VALID_ADDRESSES {
+            let net = if addr.to_lowercase().starts_with("bc") {
+                &mainnet
+            } else {
+                &testnet
+            };
+            assert_eq!(address_to_script(addr, net).unwrap(), hex(script_hex));
+        }
+    }
+
+    #[test]
+    fn test_address_to_script_rejects_wrong_network() {
+        let testnet = NetworkConstants::testnet().unwrap();
+        for addr in [
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+        ] {
+            let err = address_to_script(addr, &testnet).unwrap_err();
+            assert_eq!(err, AddressError::WrongNetwork("testnet".to_string()));
+            assert!(err.to_string().contains("testnet"), "{err}");
+        }
+
+        // A corrupted checksum is an encoding error, not a network mismatch
+        assert_eq!(
+            address_to_script("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3", &testnet),
+            Err(AddressError::InvalidEncoding)
+        );
     }
 
     #[test]
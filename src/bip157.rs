@@ -12,7 +12,7 @@ use sha2::{Digest, Sha256};
 /// Filter header - commits to previous filter header and current filter
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FilterHeader {
-    /// Filter hash (double SHA256 of filter data)
+    /// Filter hash (double SHA256 of the serialized filter)
     pub filter_hash: Hash,
     /// Previous filter header hash
     pub prev_header_hash: Hash,
@@ -21,17 +21,7 @@ pub struct FilterHeader {
 impl FilterHeader {
     /// Calculate filter header from filter and previous header
     pub fn new(filter: &CompactBlockFilter, prev_header: Option<&FilterHeader>) -> Self {
-        // Filter hash = SHA256(SHA256(filter_data))
-        let mut hasher = Sha256::new();
-        hasher.update(&filter.filter_data);
-        let first_hash = hasher.finalize();
-
-        let mut hasher2 = Sha256::new();
-        hasher2.update(first_hash);
-        let filter_hash_bytes = hasher2.finalize();
-
-        let mut filter_hash = [0u8; 32];
-        filter_hash.copy_from_slice(&filter_hash_bytes);
+        let filter_hash = filter_hash(filter);
 
         // Previous header hash
         let prev_header_hash = if let Some(prev) = prev_header {
@@ -82,6 +72,21 @@ impl FilterHeader {
     }
 }
 
+/// Filter hash as carried in cfheaders
+///
+/// Double SHA256 of the serialized filter (element count varint, then the
+/// Golomb-Rice data), as sent in a cfilter message.
+pub fn filter_hash(filter: &CompactBlockFilter) -> Hash {
+    crate::crypto::sha256d(&filter.serialize())
+}
+
+/// Filter header chaining a filter hash onto the previous filter header
+pub fn chain_filter_header(filter_hash: &Hash, prev_filter_header: &Hash) -> Hash {
+    let mut preimage = filter_hash.to_vec();
+    preimage.extend_from_slice(prev_filter_header);
+    crate::crypto::sha256d(&preimage)
+}
+
 /// Filter type (currently only Basic Compact Filters)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterType {
@@ -103,7 +108,7 @@ impl FilterType {
 }
 
 /// getcfilters message - request filters for block range
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GetCfilters {
     /// Filter type
     pub filter_type: FilterType,
@@ -114,7 +119,7 @@ pub struct GetCfilters {
 }
 
 /// cfilter message - compact block filter response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CFilter {
     /// Filter type
     pub filter_type: FilterType,
@@ -125,7 +130,7 @@ pub struct CFilter {
 }
 
 /// getcfheaders message - request filter headers
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GetCfheaders {
     /// Filter type
     pub filter_type: FilterType,
@@ -136,20 +141,20 @@ pub struct GetCfheaders {
 }
 
 /// cfheaders message - filter headers response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cfheaders {
     /// Filter type
     pub filter_type: FilterType,
     /// Stop block hash
     pub stop_hash: Hash,
-    /// Previous filter header
-    pub prev_header: FilterHeader,
-    /// Filter headers (one per block in range)
-    pub filter_headers: Vec<Hash>,
+    /// Filter header of the block before the first one in the range
+    pub prev_filter_header: Hash,
+    /// Filter hashes, one per block in the range
+    pub filter_hashes: Vec<Hash>,
 }
 
 /// getcfcheckpt message - request filter checkpoints
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GetCfcheckpt {
     /// Filter type
     pub filter_type: FilterType,
@@ -158,7 +163,7 @@ pub struct GetCfcheckpt {
 }
 
 /// cfcheckpt message - filter checkpoint response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cfcheckpt {
     /// Filter type
     pub filter_type: FilterType,
@@ -171,6 +176,15 @@ pub struct Cfcheckpt {
 /// BIP157 service flag bit
 pub const NODE_COMPACT_FILTERS: u64 = 1 << 6;
 
+/// Most filters a single getcfilters request may span
+pub const MAX_GETCFILTERS_SIZE: u64 = 1000;
+
+/// Most filter hashes a single getcfheaders request may span
+pub const MAX_GETCFHEADERS_SIZE: u64 = 2000;
+
+/// Spacing of the filter headers returned in a cfcheckpt message
+pub const CFCHECKPT_INTERVAL: u64 = 1000;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -191,4 +205,28 @@ mod tests {
         assert_ne!(header1.header_hash(), header2.header_hash());
         assert_eq!(header2.prev_header_hash, header1.header_hash());
     }
+
+    /// Hex of a hash as displayed (byte-reversed)
+    fn display_hex(hash: &Hash) -> String {
+        hash.iter()
+            .rev()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_header_vector() {
+        // BIP158 test vector: testnet genesis block, basic filter 019dfca8
+        let filter = CompactBlockFilter {
+            filter_data: vec![0x9d, 0xfc, 0xa8],
+            num_elements: 1,
+        };
+        assert_eq!(filter.serialize(), vec![0x01, 0x9d, 0xfc, 0xa8]);
+
+        let header = chain_filter_header(&filter_hash(&filter), &[0u8; 32]);
+        assert_eq!(
+            display_hex(&header),
+            "21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750"
+        );
+    }
 }
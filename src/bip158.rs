@@ -26,6 +26,16 @@ pub struct CompactBlockFilter {
     pub num_elements: u32,
 }
 
+impl CompactBlockFilter {
+    /// Serialized filter: the element count as a varint, then the Golomb-Rice data
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.filter_data.len() + 5);
+        crate::encoding::write_varint(&mut buf, self.num_elements as u64);
+        buf.extend_from_slice(&self.filter_data);
+        buf
+    }
+}
+
 /// Hash a script to a number in range [0, N*M)
 fn hash_to_range(script: &[u8], n: u64, m: u64) -> u64 {
     // Hash script with SHA256
@@ -0,0 +1,328 @@
+//! BIP37: Connection Bloom Filtering
+//!
+//! Specification: https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki
+//!
+//! SPV clients load a bloom filter on a peer connection so the peer only
+//! relays transactions that may be relevant to them.
+//!
+//! Privacy caveat: a bloom filter leaks which addresses and outpoints a client
+//! is interested in. Even with a high false-positive rate, several filters from
+//! the same client can be intersected to recover its wallet. BIP157/158
+//! compact filters (see `bip157`/`bip158`) avoid this and should be preferred.
+
+use crate::encoding;
+use crate::Transaction;
+
+/// Maximum serialized filter size in bytes
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+
+/// Maximum number of hash functions a filter may use
+pub const MAX_HASH_FUNCS: u32 = 50;
+
+/// Maximum size of an element added with `filteradd`
+pub const MAX_FILTER_ADD_SIZE: usize = 520;
+
+/// Never update the filter when an output matches
+pub const BLOOM_UPDATE_NONE: u8 = 0;
+/// Add the outpoint of every matching output to the filter
+pub const BLOOM_UPDATE_ALL: u8 = 1;
+/// Only add outpoints of matching pay-to-pubkey and bare multisig outputs
+pub const BLOOM_UPDATE_P2PUBKEY_ONLY: u8 = 2;
+
+/// Multiplier separating the seeds of successive hash functions
+const HASH_SEED_MULTIPLIER: u32 = 0xfba4_c795;
+
+/// Bloom filter loaded by a `filterload` message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    /// Filter bit field
+    pub data: Vec<u8>,
+    /// Number of hash functions (`nHashFuncs`)
+    pub hash_funcs: u32,
+    /// Random value added to every hash seed (`nTweak`)
+    pub tweak: u32,
+    /// Update behaviour on matches (`nFlags`)
+    pub flags: u8,
+}
+
+impl BloomFilter {
+    /// Create an empty filter sized for `elements` items at `fp_rate` false positives
+    pub fn new(elements: u32, fp_rate: f64, tweak: u32, flags: u8) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bits = -1.0 / (ln2 * ln2) * elements as f64 * fp_rate.ln();
+        let size = ((bits / 8.0) as usize).clamp(1, MAX_BLOOM_FILTER_SIZE);
+        let hash_funcs = ((size * 8) as f64 / elements.max(1) as f64 * ln2) as u32;
+
+        Self {
+            data: vec![0u8; size],
+            hash_funcs: hash_funcs.clamp(1, MAX_HASH_FUNCS),
+            tweak,
+            flags,
+        }
+    }
+
+    /// Whether the filter is within the BIP37 size and hash function limits
+    pub fn is_within_limits(&self) -> bool {
+        self.data.len() <= MAX_BLOOM_FILTER_SIZE && self.hash_funcs <= MAX_HASH_FUNCS
+    }
+
+    fn bit_index(&self, hash_num: u32, element: &[u8]) -> usize {
+        let seed = hash_num
+            .wrapping_mul(HASH_SEED_MULTIPLIER)
+            .wrapping_add(self.tweak);
+        murmur3_32(seed, element) as usize % (self.data.len() * 8)
+    }
+
+    /// Add an element to the filter
+    pub fn insert(&mut self, element: &[u8]) {
+        if self.data.is_empty() {
+            return;
+        }
+        for hash_num in 0..self.hash_funcs {
+            let index = self.bit_index(hash_num, element);
+            self.data[index >> 3] |= 1 << (7 & index);
+        }
+    }
+
+    /// Whether an element may have been added to the filter
+    pub fn contains(&self, element: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        (0..self.hash_funcs).all(|hash_num| {
+            let index = self.bit_index(hash_num, element);
+            self.data[index >> 3] & (1 << (7 & index)) != 0
+        })
+    }
+
+    /// Whether a transaction is relevant to the filter
+    ///
+    /// Matches the txid, any data push in an output script, any spent outpoint
+    /// or any data push in an input script. The filter itself is not updated,
+    /// so `flags` have no effect here.
+    pub fn matches_tx(&self, tx: &Transaction) -> bool {
+        if self.contains(&encoding::txid(tx)) {
+            return true;
+        }
+        if tx
+            .outputs
+            .iter()
+            .any(|output| self.matches_script(&output.script_pubkey))
+        {
+            return true;
+        }
+        tx.inputs.iter().any(|input| {
+            let mut outpoint = input.prevout.hash.to_vec();
+            outpoint.extend_from_slice(&(input.prevout.index as u32).to_le_bytes());
+            self.contains(&outpoint) || self.matches_script(&input.script_sig)
+        })
+    }
+
+    fn matches_script(&self, script: &[u8]) -> bool {
+        data_pushes(script)
+            .into_iter()
+            .any(|data| !data.is_empty() && self.contains(data))
+    }
+
+    /// Serialize as the payload of a `filterload` message
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.data.len() + 12);
+        encoding::write_varint(&mut buf, self.data.len() as u64);
+        buf.extend_from_slice(&self.data);
+        buf.extend_from_slice(&self.hash_funcs.to_le_bytes());
+        buf.extend_from_slice(&self.tweak.to_le_bytes());
+        buf.push(self.flags);
+        buf
+    }
+}
+
+/// Data pushed by a script, stopping at the first malformed push
+fn data_pushes(script: &[u8]) -> Vec<&[u8]> {
+    let mut pushes = Vec::new();
+    let mut i = 0;
+    while i < script.len() {
+        let opcode = script[i];
+        i += 1;
+        let (len, header) = match opcode {
+            0x01..=0x4b => (opcode as usize, 0),
+            0x4c => match script.get(i) {
+                Some(len) => (*len as usize, 1),
+                None => break,
+            },
+            0x4d => match script.get(i..i + 2) {
+                Some(len) => (u16::from_le_bytes([len[0], len[1]]) as usize, 2),
+                None => break,
+            },
+            0x4e => match script.get(i..i + 4) {
+                Some(len) => (
+                    u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize,
+                    4,
+                ),
+                None => break,
+            },
+            _ => continue,
+        };
+        i += header;
+        match script.get(i..i + len) {
+            Some(data) => pushes.push(data),
+            None => break,
+        }
+        i += len;
+    }
+    pushes
+}
+
+/// MurmurHash3 (x86, 32-bit) as used by BIP37
+fn murmur3_32(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mut h1 = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k1 = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k1 = k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 ^= k1;
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k1 = 0u32;
+        for (i, byte) in tail.iter().enumerate() {
+            k1 ^= (*byte as u32) << (8 * i);
+        }
+        k1 = k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 ^= k1;
+    }
+
+    h1 ^= data.len() as u32;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85eb_ca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2_ae35);
+    h1 ^= h1 >> 16;
+    h1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OutPoint, TransactionInput, TransactionOutput};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_murmur3_vectors() {
+        assert_eq!(murmur3_32(0, &[]), 0);
+        assert_eq!(murmur3_32(0xfba4_c795, &[]), 0x6a39_6f08);
+        assert_eq!(murmur3_32(0, &[0x00]), 0x514e_28b7);
+        assert_eq!(murmur3_32(0xfba4_c795, &[0x00]), 0xea3f_0b17);
+        assert_eq!(murmur3_32(0, &[0xff]), 0xfd6c_f10d);
+        assert_eq!(murmur3_32(0, &hex("21436587")), 0xf55b_516b);
+        assert_eq!(murmur3_32(0x5082_edee, &hex("21436587")), 0x2362_f9de);
+        assert_eq!(murmur3_32(0, &hex("214365")), 0x7e4a_8634);
+        assert_eq!(murmur3_32(0, &hex("2143")), 0xa0f7_b07a);
+        assert_eq!(murmur3_32(0, &hex("21")), 0x7266_1cf4);
+    }
+
+    /// Worked example from Bitcoin Core's bloom_create_insert_serialize test
+    #[test]
+    fn test_bip37_create_insert_serialize() {
+        let mut filter = BloomFilter::new(3, 0.01, 0, BLOOM_UPDATE_ALL);
+
+        filter.insert(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+        assert!(filter.contains(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+        // One bit different in the first byte
+        assert!(!filter.contains(&hex("19108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+
+        filter.insert(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"));
+        assert!(filter.contains(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee")));
+        filter.insert(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+        assert!(filter.contains(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5")));
+
+        assert_eq!(filter.serialize(), hex("03614e9b050000000000000001"));
+    }
+
+    #[test]
+    fn test_bip37_create_insert_serialize_with_tweak() {
+        let mut filter = BloomFilter::new(3, 0.01, 2_147_483_649, BLOOM_UPDATE_ALL);
+
+        filter.insert(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+        filter.insert(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"));
+        filter.insert(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+
+        assert_eq!(filter.serialize(), hex("03ce4299050000000100008001"));
+    }
+
+    fn tx() -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0x22; 32],
+                    index: 3,
+                },
+                script_sig: [vec![0x21], vec![0x02; 33]].concat(),
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 1000,
+                // P2PKH to a 20-byte hash
+                script_pubkey: [vec![0x76, 0xa9, 0x14], vec![0xab; 20], vec![0x88, 0xac]].concat(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_matches_tx() {
+        let tx = tx();
+        let empty = BloomFilter::new(10, 0.000001, 0, BLOOM_UPDATE_NONE);
+        assert!(!empty.matches_tx(&tx));
+
+        let mut by_txid = empty.clone();
+        by_txid.insert(&encoding::txid(&tx));
+        assert!(by_txid.matches_tx(&tx));
+
+        let mut by_output = empty.clone();
+        by_output.insert(&[0xab; 20]);
+        assert!(by_output.matches_tx(&tx));
+
+        let mut by_outpoint = empty.clone();
+        by_outpoint.insert(&[[0x22; 32].as_slice(), &3u32.to_le_bytes()].concat());
+        assert!(by_outpoint.matches_tx(&tx));
+
+        let mut by_input_push = empty.clone();
+        by_input_push.insert(&[0x02; 33]);
+        assert!(by_input_push.matches_tx(&tx));
+    }
+
+    #[test]
+    fn test_data_pushes() {
+        let script = [
+            vec![0x00, 0x02, 0xaa, 0xbb, 0x4c, 0x01, 0xcc, 0x51],
+            vec![0x05],
+        ]
+        .concat();
+        assert_eq!(data_pushes(&script), vec![&[0xaa, 0xbb][..], &[0xcc][..]]);
+    }
+
+    #[test]
+    fn test_filter_limits() {
+        assert!(BloomFilter::new(1_000, 0.0001, 0, 0).is_within_limits());
+
+        let oversized = BloomFilter {
+            data: vec![0; MAX_BLOOM_FILTER_SIZE + 1],
+            hash_funcs: 1,
+            tweak: 0,
+            flags: 0,
+        };
+        assert!(!oversized.is_within_limits());
+    }
+}
@@ -0,0 +1,350 @@
+//! In-Memory Chain State
+//!
+//! A `ChainStateAccess` implementation backed by plain maps, so the network
+//! message handlers can be driven end to end without the node layer's storage.
+//!
+//! Blocks are assumed to be inserted in chain order; the header index is the
+//! insertion order and is what the block locator algorithm walks.
+
+use crate::encoding;
+use crate::network::{ChainObject, ChainStateAccess, MAX_HEADERS_RESULTS};
+use crate::{Block, BlockHeader, Hash, Transaction};
+use std::collections::HashMap;
+
+/// Chain state held entirely in memory
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryChainState {
+    objects: HashMap<Hash, ChainObject>,
+    /// Block hashes and headers in height order
+    headers: Vec<(Hash, BlockHeader)>,
+    /// Mempool txids in insertion order
+    mempool: Vec<Hash>,
+    fee_rates: HashMap<Hash, u64>,
+}
+
+impl InMemoryChainState {
+    /// Create an empty chain state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a block on top of the current tip
+    pub fn with_block(mut self, block: Block) -> Self {
+        self.insert_block(block);
+        self
+    }
+
+    /// Add a mempool transaction with an optional fee rate (sat/vbyte)
+    pub fn with_mempool_tx(mut self, tx: Transaction, fee_rate: Option<u64>) -> Self {
+        self.insert_mempool_tx(tx, fee_rate);
+        self
+    }
+
+    /// Add a block on top of the current tip, returning its hash
+    ///
+    /// Inserting a block that is already known does nothing. Any of its
+    /// transactions still in the mempool are removed.
+    pub fn insert_block(&mut self, block: Block) -> Hash {
+        let hash = encoding::block_hash(&block.header);
+        if self.objects.contains_key(&hash) {
+            return hash;
+        }
+
+        for tx in &block.transactions {
+            let txid = encoding::txid(tx);
+            if let Some(position) = self.mempool.iter().position(|id| *id == txid) {
+                self.mempool.remove(position);
+                self.fee_rates.remove(&txid);
+                self.objects.remove(&txid);
+            }
+        }
+        self.headers.push((hash, block.header.clone()));
+        self.objects.insert(hash, ChainObject::Block(block));
+        hash
+    }
+
+    /// Add a transaction to the mempool, returning its txid
+    pub fn insert_mempool_tx(&mut self, tx: Transaction, fee_rate: Option<u64>) -> Hash {
+        let txid = encoding::txid(&tx);
+        if !self.objects.contains_key(&txid) {
+            self.mempool.push(txid);
+            self.objects.insert(txid, ChainObject::Transaction(tx));
+        }
+        if let Some(fee_rate) = fee_rate {
+            self.fee_rates.insert(txid, fee_rate);
+        }
+        txid
+    }
+
+    /// Height of the tip, if any block was inserted
+    pub fn tip_height(&self) -> Option<u64> {
+        self.headers
+            .len()
+            .checked_sub(1)
+            .map(|height| height as u64)
+    }
+
+    /// Hash of the block at `height`
+    pub fn block_hash_at(&self, height: u64) -> Option<Hash> {
+        self.headers.get(height as usize).map(|(hash, _)| *hash)
+    }
+
+    /// Height of a known block
+    pub fn height_of(&self, hash: &Hash) -> Option<u64> {
+        self.headers
+            .iter()
+            .position(|(known, _)| known == hash)
+            .map(|height| height as u64)
+    }
+
+    /// Block locator for the current tip: dense for the last 10 blocks, then
+    /// doubling steps back, always ending at the first block
+    pub fn locator(&self) -> Vec<Hash> {
+        let Some(tip) = self.tip_height() else {
+            return Vec::new();
+        };
+
+        let mut locator = Vec::new();
+        let mut height = tip;
+        let mut step = 1;
+        loop {
+            locator.push(self.headers[height as usize].0);
+            if height == 0 {
+                break;
+            }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
+        }
+        locator
+    }
+}
+
+impl ChainStateAccess for InMemoryChainState {
+    fn has_object(&self, hash: &Hash) -> bool {
+        self.objects.contains_key(hash)
+    }
+
+    fn get_object(&self, hash: &Hash) -> Option<ChainObject> {
+        self.objects.get(hash).cloned()
+    }
+
+    /// Headers after the first locator hash we know, through `stop` inclusive
+    ///
+    /// An unrecognised locator forks at the first block, as with a peer that
+    /// only shares our genesis.
+    fn get_headers_for_locator(&self, locator: &[Hash], stop: &Hash) -> Vec<BlockHeader> {
+        let fork = locator
+            .iter()
+            .find_map(|hash| self.height_of(hash))
+            .unwrap_or(0) as usize;
+
+        let mut headers = Vec::new();
+        for (hash, header) in self.headers.iter().skip(fork + 1) {
+            headers.push(header.clone());
+            if hash == stop || headers.len() == MAX_HEADERS_RESULTS {
+                break;
+            }
+        }
+        headers
+    }
+
+    fn get_mempool_transactions(&self) -> Vec<Transaction> {
+        self.mempool
+            .iter()
+            .filter_map(|txid| self.objects.get(txid))
+            .filter_map(|obj| obj.as_transaction().cloned())
+            .collect()
+    }
+
+    fn get_mempool_fee_rate(&self, tx: &Transaction) -> Option<u64> {
+        self.fee_rates.get(&encoding::txid(tx)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{
+        process_network_message, GetDataMessage, GetHeadersMessage, InvMessage, InvType,
+        InventoryVector, MessageLimits, NetworkMessage, NetworkResponse, PeerState,
+        PROTOCOL_VERSION,
+    };
+    use crate::validation::bip34_height_script;
+    use crate::{
+        BitcoinProtocolEngine, OutPoint, ProtocolVersion, TransactionInput, TransactionOutput,
+        UtxoSet,
+    };
+
+    fn regtest_block(height: u64, prev_block_hash: Hash) -> Block {
+        let coinbase = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0xffffffff,
+                },
+                script_sig: [bip34_height_script(height), vec![0x00]].concat(),
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 50_0000_0000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        let mut block = Block {
+            header: BlockHeader {
+                version: 4,
+                prev_block_hash,
+                merkle_root: [0u8; 32],
+                timestamp: 1_700_000_000 + height * 600,
+                bits: 0x207fffff,
+                nonce: 0,
+            },
+            transactions: vec![coinbase],
+        };
+        block.header.merkle_root = encoding::block_merkle_root(&block);
+        block
+    }
+
+    fn chain(len: u64) -> InMemoryChainState {
+        let mut state = InMemoryChainState::new();
+        let mut prev = [0u8; 32];
+        for height in 0..len {
+            prev = state.insert_block(regtest_block(height, prev));
+        }
+        state
+    }
+
+    fn process(
+        engine: &BitcoinProtocolEngine,
+        message: &NetworkMessage,
+        state: &InMemoryChainState,
+        height: Option<u64>,
+    ) -> NetworkResponse {
+        let mut peer_state = PeerState::new();
+        peer_state.handshake_complete = true;
+        let utxos = UtxoSet::new();
+        process_network_message(
+            engine,
+            message,
+            &mut peer_state,
+            Some(state),
+            Some(&utxos),
+            height,
+            &MessageLimits::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_inv_getdata_block_exchange() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let serving = chain(3);
+        let mut receiving = chain(2);
+        let new_hash = serving.block_hash_at(2).unwrap();
+
+        // Announce the tip: the receiver only asks for what it lacks
+        let inv = NetworkMessage::Inv(InvMessage {
+            inventory: (0..3)
+                .map(|height| InventoryVector {
+                    inv_type: InvType::Block,
+                    hash: serving.block_hash_at(height).unwrap(),
+                })
+                .collect(),
+        });
+        let getdata = match process(&engine, &inv, &receiving, None) {
+            NetworkResponse::SendMessage(NetworkMessage::GetData(getdata)) => getdata,
+            other => panic!("unexpected response: {other:?}"),
+        };
+        assert_eq!(getdata.inventory.len(), 1);
+        assert_eq!(getdata.inventory[0].hash, new_hash);
+
+        // The serving side answers with the block itself
+        let block = match process(&engine, &NetworkMessage::GetData(getdata), &serving, None) {
+            NetworkResponse::SendMessages(mut messages) => match messages.remove(0) {
+                NetworkMessage::Block(block) => block,
+                other => panic!("unexpected message: {other:?}"),
+            },
+            other => panic!("unexpected response: {other:?}"),
+        };
+        assert_eq!(encoding::block_hash(&block.header), new_hash);
+
+        // The receiver validates and stores it, after which the inv is satisfied
+        let response = process(
+            &engine,
+            &NetworkMessage::Block(block.clone()),
+            &receiving,
+            Some(2),
+        );
+        assert!(matches!(response, NetworkResponse::Ok), "{response:?}");
+        receiving.insert_block(block);
+        assert!(matches!(
+            process(&engine, &inv, &receiving, None),
+            NetworkResponse::Ok
+        ));
+    }
+
+    #[test]
+    fn test_getdata_for_unknown_object_sends_nothing() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let getdata = NetworkMessage::GetData(GetDataMessage {
+            inventory: vec![InventoryVector {
+                inv_type: InvType::Block,
+                hash: [0xee; 32],
+            }],
+        });
+
+        assert!(matches!(
+            process(&engine, &getdata, &chain(2), None),
+            NetworkResponse::Ok
+        ));
+    }
+
+    #[test]
+    fn test_headers_for_locator() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let state = chain(30);
+        let behind = chain(12);
+
+        let locator = behind.locator();
+        assert_eq!(locator[0], behind.block_hash_at(11).unwrap());
+        assert_eq!(locator.last(), behind.block_hash_at(0).as_ref());
+
+        let getheaders = NetworkMessage::GetHeaders(GetHeadersMessage {
+            version: PROTOCOL_VERSION,
+            block_locator_hashes: locator,
+            hash_stop: state.block_hash_at(20).unwrap(),
+        });
+        let headers = match process(&engine, &getheaders, &state, None) {
+            NetworkResponse::SendMessage(NetworkMessage::Headers(headers)) => headers.headers,
+            other => panic!("unexpected response: {other:?}"),
+        };
+        assert_eq!(headers.len(), 9);
+        assert_eq!(
+            encoding::block_hash(&headers[0]),
+            state.block_hash_at(12).unwrap()
+        );
+        assert_eq!(
+            encoding::block_hash(&headers[8]),
+            state.block_hash_at(20).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_mempool_tracks_confirmations() {
+        let block = regtest_block(0, [0u8; 32]);
+        let tx = block.transactions[0].clone();
+        let mut state = InMemoryChainState::new().with_mempool_tx(tx.clone(), Some(5));
+
+        assert_eq!(state.get_mempool_transactions(), vec![tx.clone()]);
+        assert_eq!(state.get_mempool_fee_rate(&tx), Some(5));
+
+        state.insert_block(block);
+        assert!(state.get_mempool_transactions().is_empty());
+        assert_eq!(state.get_mempool_fee_rate(&tx), None);
+        assert_eq!(state.tip_height(), Some(0));
+    }
+}
@@ -0,0 +1,94 @@
+//! Wall-Clock Time
+//!
+//! Time-dependent checks (future block timestamps, ping round trips, inv
+//! rate limiting, template timestamps) read the time from the engine's
+//! `Clock` instead of calling `SystemTime::now()` directly, so tests can pin
+//! it with a `MockClock`.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    /// Current time
+    fn now(&self) -> SystemTime;
+
+    /// Current time in seconds since the Unix epoch, 0 before it
+    fn unix_time(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// The operating system's clock, used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<SystemTime>,
+}
+
+impl MockClock {
+    /// Clock stopped at `now`
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Clock stopped at `secs` seconds after the Unix epoch
+    pub fn at_unix(secs: u64) -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Move the clock to `now`
+    pub fn set(&self, now: SystemTime) {
+        *self.lock() = now;
+    }
+
+    /// Move the clock forward by `elapsed`
+    pub fn advance(&self, elapsed: Duration) {
+        *self.lock() += elapsed;
+    }
+
+    /// The stored time; a panic while holding it cannot corrupt a `SystemTime`
+    fn lock(&self) -> MutexGuard<'_, SystemTime> {
+        self.now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_only_moves_when_told() {
+        let clock = MockClock::at_unix(1_700_000_000);
+        assert_eq!(clock.unix_time(), 1_700_000_000);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.unix_time(), 1_700_000_090);
+
+        clock.set(UNIX_EPOCH);
+        assert_eq!(clock.unix_time(), 0);
+        assert!(SystemClock.unix_time() > 1_700_000_000);
+    }
+}
@@ -0,0 +1,193 @@
+//! Network Configuration Files
+//!
+//! Loads `NetworkParameters` and `NetworkConstants` from TOML so operators can
+//! define custom networks (e.g. a private signet) without recompiling. Loaded
+//! values are checked with `validate` before they are returned.
+
+use crate::network_params::NetworkConstants;
+use crate::{encoding, Block, NetworkParameters, ProtocolError, Result};
+use serde::{Deserialize, Serialize};
+
+/// File layout of `NetworkParameters`
+#[derive(Serialize, Deserialize)]
+struct ParametersFile {
+    network_name: String,
+    magic_bytes: [u8; 4],
+    default_port: u16,
+    max_target: u32,
+    halving_interval: u64,
+    is_testnet: bool,
+    genesis_block: GenesisBlock,
+}
+
+/// Genesis block given either as a hex-serialized block or field by field
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum GenesisBlock {
+    Hex(String),
+    Block(Block),
+}
+
+impl NetworkParameters {
+    /// Parse and validate parameters from TOML
+    ///
+    /// `genesis_block` may be a hex string holding the serialized block.
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        let file: ParametersFile = toml::from_str(s).map_err(config_error)?;
+        let genesis_block = match file.genesis_block {
+            GenesisBlock::Hex(hex) => encoding::deserialize_block(&decode_hex(&hex)?)?.0,
+            GenesisBlock::Block(block) => block,
+        };
+        let params = NetworkParameters {
+            magic_bytes: file.magic_bytes,
+            default_port: file.default_port,
+            genesis_block,
+            max_target: file.max_target,
+            halving_interval: file.halving_interval,
+            network_name: file.network_name,
+            is_testnet: file.is_testnet,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Render as TOML, with the genesis block hex-serialized
+    pub fn to_toml_string(&self) -> Result<String> {
+        let file = ParametersFile {
+            network_name: self.network_name.clone(),
+            magic_bytes: self.magic_bytes,
+            default_port: self.default_port,
+            max_target: self.max_target,
+            halving_interval: self.halving_interval,
+            is_testnet: self.is_testnet,
+            genesis_block: GenesisBlock::Hex(encode_hex(&encoding::serialize_block(
+                &self.genesis_block,
+            ))),
+        };
+        toml::to_string(&file).map_err(config_error)
+    }
+}
+
+impl NetworkConstants {
+    /// Parse and validate constants from TOML
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        let constants: Self = toml::from_str(s).map_err(config_error)?;
+        constants.validate()?;
+        Ok(constants)
+    }
+
+    /// Render as TOML
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string(self).map_err(config_error)
+    }
+}
+
+fn config_error(err: impl std::fmt::Display) -> ProtocolError {
+    ProtocolError::InvalidConfig(err.to_string())
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    let hex = hex.trim();
+    if hex.len() % 2 != 0 {
+        return Err(config_error("hex string has odd length"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| config_error(format!("invalid hex at offset {i}")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{genesis, BitcoinProtocolEngine, ProtocolVersion};
+
+    fn classroom_network() -> NetworkParameters {
+        NetworkParameters {
+            magic_bytes: [0x0c, 0x1a, 0x55, 0x00],
+            default_port: 28444,
+            genesis_block: genesis::regtest_genesis(),
+            max_target: 0x207fffff,
+            halving_interval: 50,
+            network_name: "classnet".to_string(),
+            is_testnet: true,
+        }
+    }
+
+    #[test]
+    fn test_parameters_toml_round_trip() {
+        let params = classroom_network();
+        let toml = params.to_toml_string().unwrap();
+        let loaded = NetworkParameters::from_toml_str(&toml).unwrap();
+        assert_eq!(loaded, params);
+
+        let engine =
+            BitcoinProtocolEngine::with_network_params(ProtocolVersion::Regtest, loaded).unwrap();
+        assert_eq!(engine.get_network_params().network_name, "classnet");
+        assert_eq!(engine.get_network_params().default_port, 28444);
+    }
+
+    #[test]
+    fn test_parameters_from_hand_written_toml() {
+        let genesis_hex = encode_hex(&encoding::serialize_block(&genesis::regtest_genesis()));
+        let toml = format!(
+            r#"
+            network_name = "classnet"
+            magic_bytes = [12, 26, 85, 0]
+            default_port = 28444
+            max_target = 0x207fffff
+            halving_interval = 50
+            is_testnet = true
+            genesis_block = "{genesis_hex}"
+            "#
+        );
+        assert_eq!(
+            NetworkParameters::from_toml_str(&toml).unwrap(),
+            classroom_network()
+        );
+
+        let err = NetworkParameters::from_toml_str(&toml.replace("= 50", "= 0")).unwrap_err();
+        assert!(err.to_string().contains("halving interval"), "{err}");
+
+        let err =
+            NetworkParameters::from_toml_str(&toml.replace(&genesis_hex[..2], "zz")).unwrap_err();
+        assert!(err.to_string().contains("invalid hex"), "{err}");
+    }
+
+    #[test]
+    fn test_genesis_must_be_consistent() {
+        let mut params = classroom_network();
+        params.genesis_block.header.prev_block_hash = [1; 32];
+        let toml = params.to_toml_string().unwrap();
+        let err = NetworkParameters::from_toml_str(&toml).unwrap_err();
+        assert!(err.to_string().contains("has a parent"), "{err}");
+
+        let mut params = classroom_network();
+        params.genesis_block.transactions.clear();
+        let toml = params.to_toml_string().unwrap();
+        let err = NetworkParameters::from_toml_str(&toml).unwrap_err();
+        assert!(err.to_string().contains("no coinbase"), "{err}");
+    }
+
+    #[test]
+    fn test_constants_toml_round_trip() {
+        let mut constants = NetworkConstants::mainnet().unwrap();
+        constants.network_name = "classnet".to_string();
+        constants.mtp_window = 3;
+        let toml = constants.to_toml_string().unwrap();
+        assert_eq!(NetworkConstants::from_toml_str(&toml).unwrap(), constants);
+
+        constants.mtp_window = 0;
+        let toml = constants.to_toml_string().unwrap();
+        let err = NetworkConstants::from_toml_str(&toml).unwrap_err();
+        assert!(err.to_string().contains("mtp_window"), "{err}");
+    }
+}
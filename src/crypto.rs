@@ -0,0 +1,75 @@
+//! Hash Primitives
+//!
+//! Bitcoin's hash functions: double SHA256 (txids, block hashes, merkle
+//! nodes, checksums), HASH160 (key and script hashes in addresses) and the
+//! BIP340 tagged hashes used by Taproot.
+
+use crate::Hash;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// SHA256(SHA256(data))
+pub fn sha256d(data: &[u8]) -> Hash {
+    let first = Sha256::digest(data);
+    Sha256::digest(first).into()
+}
+
+/// RIPEMD160(SHA256(data))
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
+
+/// BIP340 tagged hash: SHA256(SHA256(tag) || SHA256(tag) || data)
+pub fn tagged_hash(tag: &str, data: &[u8]) -> Hash {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    Sha256::new()
+        .chain_update(tag_hash)
+        .chain_update(tag_hash)
+        .chain_update(data)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn test_sha256d_empty() {
+        assert_eq!(
+            hex(&sha256d(b"")),
+            "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456"
+        );
+    }
+
+    #[test]
+    fn test_hash160_of_generator_pubkey() {
+        // Compressed secp256k1 generator point, behind bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+        let pubkey = [
+            0x02, 0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce,
+            0x87, 0x0b, 0x07, 0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81,
+            0x5b, 0x16, 0xf8, 0x17, 0x98,
+        ];
+        assert_eq!(
+            hex(&hash160(&pubkey)),
+            "751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+    }
+
+    #[test]
+    fn test_tagged_hash_tapleaf() {
+        // Leaf hash of the tapscript OP_TRUE: leaf version 0xc0, script [OP_1]
+        assert_eq!(
+            hex(&tagged_hash("TapLeaf", &[0xc0, 0x01, 0x51])),
+            "a85b2107f791b26a84e7586c28cec7cb61202ed3d01944d832500f363782d675"
+        );
+        assert_ne!(
+            tagged_hash("TapLeaf", b"data"),
+            tagged_hash("TapBranch", b"data")
+        );
+    }
+}
@@ -0,0 +1,615 @@
+//! Difficulty Inspection and Retargeting
+//!
+//! Conversions between the compact `bits` encoding, 256-bit targets and the
+//! "difficulty" number shown by Bitcoin Core, without needing a chain, plus
+//! the work a block must carry given the headers before it and the 256-bit
+//! `ChainWork` accumulated by a chain.
+//!
+//! Unlike `pow::target_from_bits`, which rejects unusable encodings, these
+//! helpers mirror Core's `arith_uint256::SetCompact`/`GetCompact` exactly,
+//! reporting the negative and overflow flags rather than failing.
+
+use crate::{BitcoinProtocolEngine, BlockHeader, ConsensusError, ProtocolVersion, Result};
+use serde::{Deserialize, Serialize};
+
+/// Compact sign bit: set means a negative target
+const COMPACT_SIGN_BIT: u32 = 0x0080_0000;
+
+/// Compact `bits` value of the mainnet proof-of-work limit
+pub const MAINNET_MAX_TARGET_BITS: u32 = 0x1d00ffff;
+
+/// Blocks between difficulty adjustments (two weeks at ten minutes a block)
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u64 = 2016;
+
+/// Intended seconds between blocks
+pub const POW_TARGET_SPACING: u64 = 10 * 60;
+
+/// Intended seconds per adjustment interval
+pub const POW_TARGET_TIMESPAN: u64 = DIFFICULTY_ADJUSTMENT_INTERVAL * POW_TARGET_SPACING;
+
+/// A compact target decoded the way Bitcoin Core decodes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactTarget {
+    /// Big-endian 256-bit target, with any bits shifted past 256 dropped
+    pub target: [u8; 32],
+    /// Sign bit set on a nonzero mantissa
+    pub negative: bool,
+    /// Nonzero mantissa shifted beyond 256 bits
+    pub overflow: bool,
+}
+
+/// Decode compact `bits`, keeping Core's negative and overflow flags
+pub fn decode_compact(bits: u32) -> CompactTarget {
+    let size = bits >> 24;
+    let mut word = bits & 0x007f_ffff;
+    let mut target = [0u8; 32];
+
+    if size <= 3 {
+        word >>= 8 * (3 - size);
+        target[28..].copy_from_slice(&word.to_be_bytes());
+    } else {
+        for (i, byte) in word.to_be_bytes()[1..].iter().enumerate() {
+            // Byte i of the 3-byte mantissa lands at 256^(size - 1 - i)
+            let power = (size - 1) as usize - i;
+            if power < 32 {
+                target[31 - power] = *byte;
+            }
+        }
+    }
+
+    CompactTarget {
+        target,
+        negative: word != 0 && bits & COMPACT_SIGN_BIT != 0,
+        overflow: word != 0
+            && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32)),
+    }
+}
+
+/// Expand compact `bits` into a big-endian 256-bit target
+///
+/// Negative and overflowing encodings still produce Core's (meaningless)
+/// value; use `decode_compact` to detect them.
+pub fn bits_to_target(bits: u32) -> [u8; 32] {
+    decode_compact(bits).target
+}
+
+/// Encode a big-endian 256-bit target in compact form
+///
+/// The mantissa is shifted down a byte when its top bit would otherwise be
+/// read as the sign bit, so encoding never produces a negative value.
+pub fn target_to_bits(target: &[u8; 32]) -> u32 {
+    let Some(first) = target.iter().position(|byte| *byte != 0) else {
+        return 0;
+    };
+    let mut size = (32 - first) as u32;
+
+    let mut compact = if size <= 3 {
+        let low = target[29..]
+            .iter()
+            .fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+        low << (8 * (3 - size))
+    } else {
+        target[first..first + 3]
+            .iter()
+            .fold(0u32, |acc, byte| (acc << 8) | *byte as u32)
+    };
+
+    if compact & COMPACT_SIGN_BIT != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    compact | (size << 24)
+}
+
+/// Difficulty of `bits` relative to the `max_target` compact encoding
+///
+/// This is the ratio of the two targets, so `bits == max_target` gives 1.0.
+/// For mainnet pass `MAINNET_MAX_TARGET_BITS`, matching Core's
+/// `GetDifficulty`. A zero mantissa yields infinity.
+pub fn difficulty_from_bits(bits: u32, max_target: u32) -> f64 {
+    let mantissa = |compact: u32| (compact & 0x00ff_ffff) as f64;
+    let exponent = |compact: u32| (compact >> 24) as i32;
+
+    mantissa(max_target) / mantissa(bits) * 256f64.powi(exponent(max_target) - exponent(bits))
+}
+
+/// Expected number of hashes behind a block or chain, as a 256-bit integer
+///
+/// Stored big-endian like Core's `nChainWork`, so the derived ordering is
+/// numeric. Addition saturates at `2^256 - 1`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct ChainWork(pub [u8; 32]);
+
+impl ChainWork {
+    /// No work at all
+    pub const ZERO: ChainWork = ChainWork([0; 32]);
+
+    /// Work of one block at compact `bits`: `2^256 / (target + 1)`
+    ///
+    /// Mirrors Core's `GetBlockProof`: negative, overflowing and zero targets
+    /// carry no work.
+    pub fn from_bits(bits: u32) -> Self {
+        let compact = decode_compact(bits);
+        if compact.negative || compact.overflow || compact.target == [0; 32] {
+            return Self::ZERO;
+        }
+        // 2^256 / (target + 1) == ~target / (target + 1) + 1, which fits in 256 bits
+        let not_target = compact.target.map(|byte| !byte);
+        let divisor = add_saturating(&compact.target, &one());
+        ChainWork(add_saturating(&div_wide(&not_target, &divisor), &one()))
+    }
+
+    /// Sum of two amounts of work, saturating at `2^256 - 1`
+    pub fn add(&self, other: &ChainWork) -> ChainWork {
+        ChainWork(add_saturating(&self.0, &other.0))
+    }
+}
+
+impl From<u128> for ChainWork {
+    fn from(work: u128) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[16..].copy_from_slice(&work.to_be_bytes());
+        ChainWork(bytes)
+    }
+}
+
+fn one() -> [u8; 32] {
+    let mut value = [0u8; 32];
+    value[31] = 1;
+    value
+}
+
+/// Add two big-endian 256-bit values, saturating on overflow
+fn add_saturating(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut carry = 0u16;
+    for ((out_byte, x), y) in out.iter_mut().zip(a).zip(b).rev() {
+        let sum = *x as u16 + *y as u16 + carry;
+        *out_byte = sum as u8;
+        carry = sum >> 8;
+    }
+    if carry != 0 {
+        return [0xff; 32];
+    }
+    out
+}
+
+/// Divide big-endian 256-bit values by binary long division
+///
+/// `divisor` must be nonzero.
+fn div_wide(dividend: &[u8; 32], divisor: &[u8; 32]) -> [u8; 32] {
+    let mut quotient = [0u8; 32];
+    let mut remainder = [0u8; 32];
+    for bit in 0..256 {
+        // Shift the next dividend bit into the remainder, keeping the bit
+        // pushed out the top: with it set the remainder exceeds the divisor
+        let carried = remainder[0] & 0x80 != 0;
+        let mut incoming = (dividend[bit / 8] >> (7 - bit % 8)) & 1;
+        for byte in remainder.iter_mut().rev() {
+            let next = *byte >> 7;
+            *byte = (*byte << 1) | incoming;
+            incoming = next;
+        }
+        if carried || remainder >= *divisor {
+            // Wrapping subtraction is exact modulo 2^256
+            let mut borrow = 0i16;
+            for (r, d) in remainder.iter_mut().zip(divisor).rev() {
+                let diff = *r as i16 - *d as i16 - borrow;
+                *r = diff.rem_euclid(256) as u8;
+                borrow = (diff < 0) as i16;
+            }
+            quotient[bit / 8] |= 0x80 >> (bit % 8);
+        }
+    }
+    quotient
+}
+
+/// Multiply a big-endian 256-bit value, saturating on overflow
+fn mul_target(target: &[u8; 32], factor: u64) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut carry: u128 = 0;
+    for (out_byte, byte) in out.iter_mut().zip(target).rev() {
+        let value = *byte as u128 * factor as u128 + carry;
+        *out_byte = value as u8;
+        carry = value >> 8;
+    }
+    if carry != 0 {
+        return [0xff; 32];
+    }
+    out
+}
+
+/// Divide a big-endian 256-bit value, rounding down
+fn div_target(target: &[u8; 32], divisor: u64) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut remainder: u128 = 0;
+    for (out_byte, byte) in out.iter_mut().zip(target) {
+        let value = (remainder << 8) | *byte as u128;
+        *out_byte = (value / divisor as u128) as u8;
+        remainder = value % divisor as u128;
+    }
+    out
+}
+
+impl BitcoinProtocolEngine {
+    /// Compact `bits` required of the block at `height` with `timestamp`
+    ///
+    /// `prev_headers` are the headers before it, oldest first, ending with its
+    /// parent. At a retarget boundary they must cover the whole previous
+    /// interval; on testnet they must reach back to the last boundary or the
+    /// last block not mined at minimum difficulty. Mirrors Core's
+    /// `GetNextWorkRequired`:
+    ///
+    /// - At a retarget boundary the parent's target is scaled by the time the
+    ///   interval took, clamped to a factor of four and capped at the network's
+    ///   `max_target`. Regtest and simnet never retarget.
+    /// - Otherwise the parent's `bits` carry over, except on testnet (and
+    ///   regtest): a block more than `2 * POW_TARGET_SPACING` after its parent
+    ///   may be mined at `max_target`, and later blocks return to the last
+    ///   difficulty that wasn't such a reset.
+    pub fn calculate_next_work_required(
+        &self,
+        prev_headers: &[BlockHeader],
+        height: u64,
+        timestamp: u64,
+    ) -> Result<u32> {
+        let pow_limit = self.network_params.max_target;
+        let Some(parent) = prev_headers.last() else {
+            if height == 0 {
+                return Ok(pow_limit);
+            }
+            return Err(missing_headers("the parent"));
+        };
+        let allow_min_difficulty = match self.protocol_version {
+            ProtocolVersion::Testnet3
+            | ProtocolVersion::Regtest
+            | ProtocolVersion::Simnet
+            | ProtocolVersion::Educational => true,
+            ProtocolVersion::BitcoinV1 | ProtocolVersion::Signet => false,
+        };
+
+        if height % DIFFICULTY_ADJUSTMENT_INTERVAL != 0 {
+            if !allow_min_difficulty {
+                return Ok(parent.bits as u32);
+            }
+            // Testnet: twenty minutes without a block allows a difficulty 1 block
+            if timestamp > parent.timestamp + 2 * POW_TARGET_SPACING {
+                return Ok(pow_limit);
+            }
+            // Otherwise skip back over such blocks to the real difficulty
+            let first_height = height
+                .checked_sub(prev_headers.len() as u64)
+                .ok_or_else(|| missing_headers(&format!("at most {height} blocks")))?;
+            for (offset, header) in prev_headers.iter().enumerate().rev() {
+                let header_height = first_height + offset as u64;
+                if header_height % DIFFICULTY_ADJUSTMENT_INTERVAL == 0
+                    || header.bits as u32 != pow_limit
+                {
+                    return Ok(header.bits as u32);
+                }
+            }
+            return Err(missing_headers(
+                "the last retarget or non-minimum-difficulty block",
+            ));
+        }
+
+        if !self.retargets() {
+            return Ok(parent.bits as u32);
+        }
+        let interval = DIFFICULTY_ADJUSTMENT_INTERVAL as usize;
+        if prev_headers.len() < interval {
+            return Err(missing_headers("the whole previous interval"));
+        }
+        let first = &prev_headers[prev_headers.len() - interval];
+        let actual_timespan = parent
+            .timestamp
+            .saturating_sub(first.timestamp)
+            .clamp(POW_TARGET_TIMESPAN / 4, POW_TARGET_TIMESPAN * 4);
+
+        let scaled = div_target(
+            &mul_target(&bits_to_target(parent.bits as u32), actual_timespan),
+            POW_TARGET_TIMESPAN,
+        );
+        let limit = bits_to_target(pow_limit);
+        Ok(target_to_bits(if scaled > limit {
+            &limit
+        } else {
+            &scaled
+        }))
+    }
+
+    /// Check that `header` at `height` carries exactly the required `bits`
+    ///
+    /// See `calculate_next_work_required` for `prev_headers`. Rejects in
+    /// particular a testnet block claiming minimum difficulty without the
+    /// twenty-minute gap that allows it.
+    pub fn check_required_work(
+        &self,
+        prev_headers: &[BlockHeader],
+        header: &BlockHeader,
+        height: u64,
+    ) -> Result<()> {
+        let required = self.calculate_next_work_required(prev_headers, height, header.timestamp)?;
+        let bits = header.bits as u32;
+        if bits == required {
+            return Ok(());
+        }
+        let reason = if bits == self.network_params.max_target {
+            format!("minimum-difficulty block at height {height} is not allowed, requires {required:#010x}")
+        } else {
+            format!("bits {bits:#010x} at height {height} do not match required {required:#010x}")
+        };
+        Err(ConsensusError::BlockValidation(reason).into())
+    }
+
+    /// Whether the network adjusts difficulty at all (regtest, simnet and educational don't)
+    pub fn retargets(&self) -> bool {
+        !matches!(
+            self.protocol_version,
+            ProtocolVersion::Regtest | ProtocolVersion::Simnet | ProtocolVersion::Educational
+        )
+    }
+
+    /// Whether the block at `height` is the first of a new difficulty period
+    ///
+    /// Always false for the genesis block and on networks that never retarget.
+    pub fn is_retarget_boundary(&self, height: u64) -> bool {
+        self.retargets() && height > 0 && height % DIFFICULTY_ADJUSTMENT_INTERVAL == 0
+    }
+
+    /// Height of the next retarget boundary above `current_height`
+    ///
+    /// `None` on networks that never retarget.
+    pub fn next_retarget_height(&self, current_height: u64) -> Option<u64> {
+        self.retargets().then(|| {
+            (current_height / DIFFICULTY_ADJUSTMENT_INTERVAL + 1) * DIFFICULTY_ADJUSTMENT_INTERVAL
+        })
+    }
+
+    /// Blocks to mine on top of `current_height` until the next retarget
+    ///
+    /// `None` on networks that never retarget.
+    pub fn blocks_until_retarget(&self, current_height: u64) -> Option<u64> {
+        self.next_retarget_height(current_height)
+            .map(|next| next - current_height)
+    }
+}
+
+fn missing_headers(what: &str) -> crate::ProtocolError {
+    ConsensusError::BlockValidation(format!(
+        "previous headers must include {what} to compute the required work"
+    ))
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genesis_difficulty_is_one() {
+        assert_eq!(
+            difficulty_from_bits(0x1d00ffff, MAINNET_MAX_TARGET_BITS),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_block_100000_difficulty() {
+        let difficulty = difficulty_from_bits(0x1b04864c, MAINNET_MAX_TARGET_BITS);
+        assert!(
+            (difficulty - 14484.162361225399).abs() < 1e-6,
+            "{difficulty}"
+        );
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        for bits in [0x1d00ffff, 0x1b04864c, 0x207fffff, 0x17034219] {
+            assert_eq!(target_to_bits(&bits_to_target(bits)), bits, "{bits:#x}");
+        }
+        assert_eq!(target_to_bits(&[0u8; 32]), 0);
+    }
+
+    #[test]
+    fn test_encoding_avoids_sign_bit() {
+        let mut target = [0u8; 32];
+        target[31] = 0x80;
+        assert_eq!(target_to_bits(&target), 0x02008000);
+
+        target[31] = 0x12;
+        assert_eq!(target_to_bits(&target), 0x01120000);
+    }
+
+    #[test]
+    fn test_core_compact_quirks() {
+        // Vectors from Bitcoin Core's arith_uint256 tests
+        let decoded = decode_compact(0x01123456);
+        assert_eq!(decoded.target[31], 0x12);
+        assert!(!decoded.negative && !decoded.overflow);
+
+        let decoded = decode_compact(0x01fedcba);
+        assert_eq!(decoded.target[31], 0x7e);
+        assert!(decoded.negative);
+
+        let decoded = decode_compact(0x04923456);
+        assert_eq!(decoded.target[28..], [0x12, 0x34, 0x56, 0x00]);
+        assert!(decoded.negative);
+
+        assert!(!decode_compact(0x00123456).negative);
+        assert_eq!(decode_compact(0x00123456).target, [0u8; 32]);
+
+        assert!(decode_compact(0xff123456).overflow);
+        assert!(decode_compact(0x23000001).overflow);
+        assert!(!decode_compact(0x22000001).overflow);
+    }
+
+    /// `count` headers ending at `last_height`, `spacing` seconds apart
+    fn chain(count: u64, last_height: u64, bits: u32, spacing: u64) -> Vec<BlockHeader> {
+        (last_height + 1 - count..=last_height)
+            .map(|height| BlockHeader {
+                version: 4,
+                prev_block_hash: [0; 32],
+                merkle_root: [0; 32],
+                timestamp: 1_600_000_000 + height * spacing,
+                bits: bits as u64,
+                nonce: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_testnet_min_difficulty_reset() {
+        let testnet = BitcoinProtocolEngine::new(ProtocolVersion::Testnet3).unwrap();
+        let headers = chain(10, 1_000_009, 0x1c0fffff, POW_TARGET_SPACING);
+        let parent = headers.last().unwrap().clone();
+        let next = |timestamp: u64, bits: u32| BlockHeader {
+            timestamp,
+            bits: bits as u64,
+            ..parent.clone()
+        };
+
+        // 21 minutes without a block: difficulty 1 is allowed
+        let late = next(parent.timestamp + 21 * 60, 0x1d00ffff);
+        assert_eq!(
+            testnet
+                .calculate_next_work_required(&headers, 1_000_010, late.timestamp)
+                .unwrap(),
+            0x1d00ffff
+        );
+        testnet
+            .check_required_work(&headers, &late, 1_000_010)
+            .unwrap();
+
+        // A normal interval keeps the real difficulty
+        let on_time = next(parent.timestamp + POW_TARGET_SPACING, 0x1d00ffff);
+        let err = testnet
+            .check_required_work(&headers, &on_time, 1_000_010)
+            .unwrap_err();
+        assert!(err.to_string().contains("minimum-difficulty"), "{err}");
+        let on_time = next(on_time.timestamp, 0x1c0fffff);
+        testnet
+            .check_required_work(&headers, &on_time, 1_000_010)
+            .unwrap();
+
+        // After a reset block, the next on-time block returns to the real difficulty
+        let mut after_reset = headers.clone();
+        after_reset.push(late.clone());
+        assert_eq!(
+            testnet
+                .calculate_next_work_required(&after_reset, 1_000_011, late.timestamp + 60)
+                .unwrap(),
+            0x1c0fffff
+        );
+
+        // Mainnet has no such exception
+        let mainnet = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        assert!(mainnet
+            .check_required_work(&headers, &late, 1_000_010)
+            .is_err());
+
+        // More headers than blocks below the height is an error, not an underflow
+        let err = testnet
+            .calculate_next_work_required(&headers, 5, on_time.timestamp)
+            .unwrap_err();
+        assert!(err.to_string().contains("previous headers"), "{err}");
+    }
+
+    #[test]
+    fn test_retarget_matches_core_vectors() {
+        let mainnet = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        // (first block time, last block time, last bits, expected), from Core's pow_tests
+        let cases = [
+            (1261130161, 1262152739, 0x1d00ffff, 0x1d00d86a),
+            (1231006505, 1233061996, 0x1d00ffff, 0x1d00ffff),
+            (1279008237, 1279297671, 0x1c05a3f4, 0x1c0168fd),
+            (1263163443, 1269211443, 0x1c387f6f, 0x1d00e1fd),
+        ];
+        for (first_time, last_time, bits, expected) in cases {
+            let mut headers = chain(DIFFICULTY_ADJUSTMENT_INTERVAL, 32_255, bits, 0);
+            headers[0].timestamp = first_time;
+            headers.last_mut().unwrap().timestamp = last_time;
+            let required = mainnet
+                .calculate_next_work_required(&headers, 32_256, last_time + 600)
+                .unwrap();
+            assert_eq!(required, expected, "{required:#x}");
+        }
+
+        // Retargeting needs the whole interval
+        let short = chain(100, 32_255, 0x1d00ffff, POW_TARGET_SPACING);
+        assert!(mainnet
+            .calculate_next_work_required(&short, 32_256, 0)
+            .is_err());
+        // Between boundaries the parent's bits carry over
+        let short = chain(100, 32_249, 0x1d00ffff, POW_TARGET_SPACING);
+        assert_eq!(
+            mainnet
+                .calculate_next_work_required(&short, 32_250, 0)
+                .unwrap(),
+            0x1d00ffff
+        );
+    }
+
+    #[test]
+    fn test_retarget_schedule_around_boundary() {
+        let mainnet = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        // (height, boundary, next retarget, blocks until it)
+        let cases = [
+            (2015, false, 2016, 1),
+            (2016, true, 4032, 2016),
+            (2017, false, 4032, 2015),
+        ];
+        for (height, boundary, next, remaining) in cases {
+            assert_eq!(mainnet.is_retarget_boundary(height), boundary, "{height}");
+            assert_eq!(mainnet.next_retarget_height(height), Some(next), "{height}");
+            assert_eq!(
+                mainnet.blocks_until_retarget(height),
+                Some(remaining),
+                "{height}"
+            );
+        }
+        assert!(!mainnet.is_retarget_boundary(0));
+        assert_eq!(mainnet.next_retarget_height(0), Some(2016));
+
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        assert!(!regtest.is_retarget_boundary(2016));
+        assert_eq!(regtest.next_retarget_height(2015), None);
+        assert_eq!(regtest.blocks_until_retarget(2015), None);
+    }
+
+    #[test]
+    fn test_chain_work_from_bits() {
+        // Genesis difficulty: 0x0100010001 expected hashes
+        assert_eq!(
+            ChainWork::from_bits(0x1d00ffff),
+            ChainWork::from(0x0100010001)
+        );
+        assert_eq!(ChainWork::from_bits(0x207fffff), ChainWork::from(2));
+        assert_eq!(ChainWork::from_bits(0x1d000000), ChainWork::ZERO);
+        assert_eq!(ChainWork::from_bits(0x1d80ffff), ChainWork::ZERO);
+        // A target of 0xffff takes exactly 2^240 hashes, far beyond a u128
+        let mut expected = [0u8; 32];
+        expected[1] = 0x01;
+        assert_eq!(ChainWork::from_bits(0x0300ffff), ChainWork(expected));
+        assert!(ChainWork::from_bits(0x0300ffff) > ChainWork::from(u128::MAX));
+    }
+
+    #[test]
+    fn test_chain_work_sums_and_compares() {
+        let easy = ChainWork::from_bits(0x1d00ffff);
+        let period =
+            (0..DIFFICULTY_ADJUSTMENT_INTERVAL).fold(ChainWork::ZERO, |total, _| total.add(&easy));
+        assert_eq!(period, ChainWork::from(2016 * 0x0100010001u128));
+
+        // Block 100,000 alone outweighs a whole period at minimum difficulty
+        let hard = ChainWork::from_bits(0x1b04864c);
+        assert!(hard > period);
+        assert!(period.add(&easy) > period);
+        assert_eq!(ChainWork([0xff; 32]).add(&easy), ChainWork([0xff; 32]));
+
+        let json = serde_json::to_string(&hard).unwrap();
+        assert_eq!(serde_json::from_str::<ChainWork>(&json).unwrap(), hard);
+    }
+}
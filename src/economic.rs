@@ -3,9 +3,26 @@
 //! Expanded economic model abstraction beyond basic halving.
 //! Provides comprehensive economic parameters for protocol variants.
 
-use crate::ProtocolVersion;
+use crate::encoding::varint_len;
+use crate::validation::OP_RETURN;
+use crate::{ConsensusError, ProtocolVersion, Result};
 use serde::{Deserialize, Serialize};
 
+/// Default dust relay fee rate (satoshis per vbyte), as in Bitcoin Core
+pub const DUST_RELAY_FEE_RATE: u64 = 3;
+
+/// Default mempool expiry (hours), as in Bitcoin Core
+pub const DEFAULT_MEMPOOL_EXPIRY_HOURS: u64 = 336;
+
+/// Times the relay fee floor doubles between an empty and a full mempool
+pub const MEMPOOL_FEE_FLOOR_DOUBLINGS: u32 = 10;
+
+/// Size of a typical input spending a legacy output (outpoint, scriptSig, sequence)
+const LEGACY_SPEND_SIZE: u64 = 32 + 4 + 1 + 107 + 4;
+
+/// Size of a typical input spending a witness program, with the witness discounted
+const WITNESS_SPEND_SIZE: u64 = 32 + 4 + 1 + 107 / 4 + 4;
+
 /// Economic model parameters for a protocol version
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EconomicParameters {
@@ -23,19 +40,69 @@ pub struct EconomicParameters {
     pub min_fee_rate: u64,
     /// Maximum transaction fee rate (satoshis per vbyte)
     pub max_fee_rate: u64,
-    /// Minimum relay fee (satoshis per vbyte)
+    /// Minimum relay fee rate (satoshis per 1000 vbytes), as Core's `-minrelaytxfee`
     pub min_relay_fee: u64,
+    /// Hours a transaction may stay in the mempool (0 = never expires)
+    pub mempool_expiry_hours: u64,
     /// Block subsidy schedule (for custom schedules)
     pub subsidy_schedule: Vec<(u64, u64)>, // (height, subsidy)
 }
 
+/// Emission state at a given block height
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EconomicSnapshot {
+    /// Block height the snapshot describes
+    pub height: u64,
+    /// Subsidy paid by the block at `height` (in satoshis)
+    pub current_subsidy: u64,
+    /// Next height at which the subsidy changes, if it ever does
+    pub next_halving_height: Option<u64>,
+    /// Blocks from `height` until the next subsidy change
+    pub blocks_until_halving: Option<u64>,
+    /// Supply issued up to and including `height` (in satoshis)
+    pub total_supply: u64,
+    /// Supply left to reach `max_money_supply` (in satoshis)
+    pub remaining_supply: u64,
+    /// Share of `max_money_supply` already issued, in percent
+    pub percent_mined: f64,
+}
+
+/// How a block's coinbase value splits into subsidy and fees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinbaseBreakdown {
+    /// Block subsidy at the block's height (in satoshis)
+    pub subsidy: u64,
+    /// Inputs minus outputs of the non-coinbase transactions (in satoshis)
+    pub total_fees: u64,
+    /// Value claimed by the coinbase outputs (in satoshis)
+    pub total_coinbase_out: u64,
+    /// Allowed value the miner left unclaimed, destroying it (in satoshis)
+    pub burned: u64,
+}
+
+/// Mempool transactions whose fee rate falls in `[min_fee_rate, max_fee_rate)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeBucket {
+    /// Lowest fee rate in the bucket (sat/vbyte)
+    pub min_fee_rate: u64,
+    /// Fee rate where the next bucket starts (sat/vbyte, exclusive)
+    pub max_fee_rate: u64,
+    /// Number of transactions in the bucket
+    pub tx_count: usize,
+    /// Total vsize of the bucket's transactions
+    pub vsize: u64,
+    /// Total vsize paying at least `min_fee_rate`: this bucket and all above it
+    pub cumulative_vsize: u64,
+}
+
 impl EconomicParameters {
     /// Get economic parameters for a protocol version
     pub fn for_protocol(version: ProtocolVersion) -> Self {
         match version {
             ProtocolVersion::BitcoinV1 => Self::mainnet(),
-            ProtocolVersion::Testnet3 => Self::testnet(),
-            ProtocolVersion::Regtest => Self::regtest(),
+            ProtocolVersion::Testnet3 | ProtocolVersion::Signet => Self::testnet(),
+            ProtocolVersion::Regtest | ProtocolVersion::Simnet => Self::regtest(),
+            ProtocolVersion::Educational => Self::educational(),
         }
     }
 
@@ -44,13 +111,14 @@ impl EconomicParameters {
         Self {
             initial_subsidy: 50_0000_0000, // 50 BTC in satoshis
             halving_interval: 210_000,
-            max_money_supply: 21_0000_0000_0000_0000, // 21M BTC in satoshis
-            coinbase_maturity: 100,                   // 100 blocks
-            dust_limit: 546,                          // 546 satoshis
-            min_fee_rate: 1,                          // 1 sat/vbyte
-            max_fee_rate: 1_000_000,                  // 1M sat/vbyte (safety limit)
-            min_relay_fee: 1000,                      // 1000 satoshis per transaction (BIP125)
-            subsidy_schedule: Vec::new(),             // Use halving formula instead
+            max_money_supply: 2100_0000_0000_0000, // 21M BTC in satoshis
+            coinbase_maturity: 100,                // 100 blocks
+            dust_limit: 546,                       // 546 satoshis
+            min_fee_rate: 1,                       // 1 sat/vbyte
+            max_fee_rate: 1_000_000,               // 1M sat/vbyte (safety limit)
+            min_relay_fee: 1000,                   // 1000 sat/kvB (1 sat/vbyte)
+            mempool_expiry_hours: DEFAULT_MEMPOOL_EXPIRY_HOURS,
+            subsidy_schedule: Vec::new(), // Use halving formula instead
         }
     }
 
@@ -59,12 +127,13 @@ impl EconomicParameters {
         Self {
             initial_subsidy: 50_0000_0000,
             halving_interval: 210_000,
-            max_money_supply: 21_0000_0000_0000_0000,
+            max_money_supply: 2100_0000_0000_0000,
             coinbase_maturity: 100,
             dust_limit: 546,
             min_fee_rate: 1,
             max_fee_rate: 1_000_000,
             min_relay_fee: 1000,
+            mempool_expiry_hours: DEFAULT_MEMPOOL_EXPIRY_HOURS,
             subsidy_schedule: Vec::new(),
         }
     }
@@ -74,21 +143,50 @@ impl EconomicParameters {
         Self {
             initial_subsidy: 50_0000_0000,
             halving_interval: 150, // Faster halving for testing
-            max_money_supply: 21_0000_0000_0000_0000,
+            max_money_supply: 2100_0000_0000_0000,
             coinbase_maturity: 100,
             dust_limit: 546,
             min_fee_rate: 0, // No minimum fee for testing
             max_fee_rate: 1_000_000,
-            min_relay_fee: 0, // No minimum relay fee for testing
+            min_relay_fee: 0,        // No minimum relay fee for testing
+            mempool_expiry_hours: 0, // Transactions never expire
             subsidy_schedule: Vec::new(),
         }
     }
 
+    /// Educational economic parameters: regtest's, halving every 10 blocks
+    ///
+    /// The whole emission (1000 BTC) is issued within a few hundred blocks.
+    pub fn educational() -> Self {
+        Self {
+            halving_interval: 10,
+            ..Self::regtest()
+        }
+    }
+
+    /// The custom subsidy schedule in height order
+    ///
+    /// Of several entries at the same height, the last one applies. Every
+    /// schedule-based calculation goes through this, so an unsorted schedule
+    /// (which `validate` rejects) is still interpreted consistently.
+    fn sorted_subsidy_schedule(&self) -> Vec<(u64, u64)> {
+        let mut schedule = self.subsidy_schedule.clone();
+        schedule.sort_by_key(|(start, _)| *start);
+        schedule.dedup_by(|later, earlier| {
+            let duplicate = later.0 == earlier.0;
+            if duplicate {
+                *earlier = *later;
+            }
+            duplicate
+        });
+        schedule
+    }
+
     /// Calculate block subsidy for a given height
     pub fn get_block_subsidy(&self, height: u64) -> u64 {
         // If custom subsidy schedule exists, use it
         if !self.subsidy_schedule.is_empty() {
-            for (schedule_height, subsidy) in self.subsidy_schedule.iter().rev() {
+            for (schedule_height, subsidy) in self.sorted_subsidy_schedule().iter().rev() {
                 if height >= *schedule_height {
                     return *subsidy;
                 }
@@ -109,21 +207,182 @@ impl EconomicParameters {
     }
 
     /// Calculate total supply up to a given height
+    ///
+    /// Sums whole subsidy eras rather than individual blocks, so the cost is
+    /// bounded by the number of halvings (or schedule entries), not the height.
     pub fn total_supply_at_height(&self, height: u64) -> u64 {
         let mut total = 0u64;
 
-        for h in 0..=height {
-            total = total.saturating_add(self.get_block_subsidy(h));
+        if !self.subsidy_schedule.is_empty() {
+            let schedule = self.sorted_subsidy_schedule();
+            for (i, (start, subsidy)) in schedule.iter().enumerate() {
+                if *start > height {
+                    break;
+                }
+                let end = match schedule.get(i + 1) {
+                    Some((next_start, _)) => next_start.saturating_sub(1).min(height),
+                    None => height,
+                };
+                let blocks = end.saturating_sub(*start).saturating_add(1);
+                total = total.saturating_add(blocks.saturating_mul(*subsidy));
+            }
+            return total;
+        }
+
+        for period in 0..64u64 {
+            let start = period.saturating_mul(self.halving_interval);
+            if start > height {
+                break;
+            }
+            let end = start.saturating_add(self.halving_interval - 1).min(height);
+            let blocks = end - start + 1;
+            total = total.saturating_add(blocks.saturating_mul(self.initial_subsidy >> period));
         }
 
         total
     }
 
+    /// Sample the emission curve every `step` blocks up to `up_to_height`
+    ///
+    /// Returns `(height, block_subsidy, cumulative_supply)` tuples, suitable for
+    /// charting the subsidy schedule.
+    pub fn emission_schedule(&self, up_to_height: u64, step: u64) -> Vec<(u64, u64, u64)> {
+        (0..=up_to_height)
+            .step_by(step.max(1) as usize)
+            .map(|height| {
+                (
+                    height,
+                    self.get_block_subsidy(height),
+                    self.total_supply_at_height(height),
+                )
+            })
+            .collect()
+    }
+
+    /// Heights at which the block subsidy changes
+    pub fn halving_heights(&self) -> Vec<u64> {
+        let candidates: Vec<u64> = if self.subsidy_schedule.is_empty() {
+            (1..=64u64)
+                .map_while(|period| period.checked_mul(self.halving_interval))
+                .collect()
+        } else {
+            self.sorted_subsidy_schedule()
+                .iter()
+                .map(|(height, _)| *height)
+                .filter(|height| *height > 0)
+                .collect()
+        };
+
+        candidates
+            .into_iter()
+            .filter(|height| self.get_block_subsidy(*height) != self.get_block_subsidy(height - 1))
+            .collect()
+    }
+
+    /// Total amount that will ever be issued, or `None` if issuance never ends
+    ///
+    /// A custom schedule whose last entry pays a non-zero subsidy issues coins
+    /// forever. The result saturates at `u64::MAX`.
+    pub fn total_emission(&self) -> Option<u64> {
+        match self.sorted_subsidy_schedule().last() {
+            Some((_, subsidy)) if *subsidy > 0 => None,
+            Some((last_height, _)) => Some(self.total_supply_at_height(*last_height)),
+            None => {
+                let last_height = self.halving_interval.saturating_mul(64).saturating_sub(1);
+                Some(self.total_supply_at_height(last_height))
+            }
+        }
+    }
+
+    /// Check that the parameters are internally consistent
+    ///
+    /// Rejects a zero halving interval, emission above `max_money_supply`,
+    /// an unsorted subsidy schedule, an inverted fee-rate range and a zero
+    /// dust limit.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(ConsensusError::EconomicValidation(reason).into());
+
+        if self.subsidy_schedule.is_empty() && self.halving_interval == 0 {
+            return invalid("halving interval must be non-zero".to_string());
+        }
+        if self
+            .subsidy_schedule
+            .windows(2)
+            .any(|pair| pair[0].0 >= pair[1].0)
+        {
+            return invalid("subsidy schedule heights must be strictly increasing".to_string());
+        }
+        match self.total_emission() {
+            None => return invalid("subsidy schedule never reaches zero".to_string()),
+            Some(total) if total > self.max_money_supply => {
+                return invalid(format!(
+                    "total emission {total} exceeds max money supply {}",
+                    self.max_money_supply
+                ));
+            }
+            Some(_) => {}
+        }
+        if self.min_fee_rate > self.max_fee_rate {
+            return invalid(format!(
+                "min fee rate {} above max fee rate {}",
+                self.min_fee_rate, self.max_fee_rate
+            ));
+        }
+        if self.dust_limit == 0 {
+            return invalid("dust limit must be non-zero".to_string());
+        }
+        Ok(())
+    }
+
+    /// Summarise the emission state at `height`
+    pub fn snapshot(&self, height: u64) -> EconomicSnapshot {
+        let total_supply = self.total_supply_at_height(height);
+        let next_halving_height = self
+            .halving_heights()
+            .into_iter()
+            .find(|halving| *halving > height);
+        let percent_mined = if self.max_money_supply == 0 {
+            0.0
+        } else {
+            total_supply as f64 / self.max_money_supply as f64 * 100.0
+        };
+
+        EconomicSnapshot {
+            height,
+            current_subsidy: self.get_block_subsidy(height),
+            next_halving_height,
+            blocks_until_halving: next_halving_height.map(|halving| halving - height),
+            total_supply,
+            remaining_supply: self.max_money_supply.saturating_sub(total_supply),
+            percent_mined,
+        }
+    }
+
     /// Check if a value meets dust limit
     pub fn is_dust(&self, value: u64) -> bool {
         value < self.dust_limit
     }
 
+    /// Smallest economical value for an output paying to `script`
+    ///
+    /// Mirrors Core's `GetDustThreshold`: the cost at `dust_relay_rate`
+    /// (sat/vB) of the output itself plus a typical input spending it, so
+    /// witness programs get a lower threshold than legacy scripts.
+    /// Provably unspendable OP_RETURN outputs have no threshold. The flat
+    /// `dust_limit` used by `is_dust` is the P2PKH threshold at 3 sat/vB.
+    pub fn dust_threshold_for_script(&self, script: &[u8], dust_relay_rate: u64) -> u64 {
+        if script.first() == Some(&OP_RETURN) {
+            return 0;
+        }
+        let output_size = 8 + varint_len(script.len() as u64) + script.len() as u64;
+        let spend_size = if is_witness_program(script) {
+            WITNESS_SPEND_SIZE
+        } else {
+            LEGACY_SPEND_SIZE
+        };
+        (output_size + spend_size).saturating_mul(dust_relay_rate)
+    }
+
     /// Check if a fee rate is valid
     pub fn is_valid_fee_rate(&self, fee_rate: u64) -> bool {
         fee_rate >= self.min_fee_rate && fee_rate <= self.max_fee_rate
@@ -138,15 +397,125 @@ impl EconomicParameters {
         (size_vbytes as u64).saturating_mul(fee_rate_sat_per_vbyte)
     }
 
+    /// Virtual size of a transaction or block weight: `ceil(weight / 4)`
+    pub fn vsize_from_weight(weight: u64) -> u64 {
+        weight.div_ceil(4)
+    }
+
+    /// Fee rate in satoshis per vbyte for a fee paid at `weight`
+    pub fn fee_rate(fee: u64, weight: u64) -> f64 {
+        let vsize = Self::vsize_from_weight(weight);
+        if vsize == 0 {
+            return 0.0;
+        }
+        fee as f64 / vsize as f64
+    }
+
+    /// Minimum fee for a transaction of `weight`, at the higher of
+    /// `min_fee_rate` and the `min_relay_fee` rate
+    pub fn min_fee_for_weight(&self, weight: u64) -> u64 {
+        let vsize = Self::vsize_from_weight(weight);
+        let relay_fee = vsize.saturating_mul(self.min_relay_fee).div_ceil(1000);
+        vsize.saturating_mul(self.min_fee_rate).max(relay_fee)
+    }
+
+    /// Relay fee rate floor (sat/kvB) for a mempool holding `mempool_usage_bytes`
+    /// of `max_mempool_bytes`
+    ///
+    /// Starts at `min_relay_fee` when the mempool is empty and doubles
+    /// `MEMPOOL_FEE_FLOOR_DOUBLINGS` times as it fills, so a full mempool
+    /// demands 1024 times the base rate. Usage beyond capacity counts as full,
+    /// as does a mempool with no capacity. Never above `max_fee_rate`.
+    pub fn effective_min_relay_fee(&self, mempool_usage_bytes: u64, max_mempool_bytes: u64) -> u64 {
+        let fill = if max_mempool_bytes == 0 {
+            1.0
+        } else {
+            mempool_usage_bytes.min(max_mempool_bytes) as f64 / max_mempool_bytes as f64
+        };
+        let floor = self.min_relay_fee as f64 * (MEMPOOL_FEE_FLOOR_DOUBLINGS as f64 * fill).exp2();
+        (floor.round() as u64)
+            .max(self.min_relay_fee)
+            .min(self.max_fee_rate.saturating_mul(1000))
+    }
+
+    /// Group `(fee rate, vsize)` mempool entries into fee-rate buckets
+    ///
+    /// Buckets double in width (`[0, 1)`, `[1, 2)`, `[2, 4)`, ...) and only
+    /// non-empty ones are returned, ordered by rising fee rate. Entries below
+    /// `min_fee_rate` are ignored as they would not be relayed.
+    pub fn fee_buckets(&self, entries: &[(u64, u64)]) -> Vec<FeeBucket> {
+        let bucket_index = |fee_rate: u64| match fee_rate {
+            0 => 0,
+            rate => 1 + rate.ilog2() as usize,
+        };
+        let mut totals: Vec<(usize, u64)> = vec![(0, 0); bucket_index(u64::MAX) + 1];
+        for &(fee_rate, vsize) in entries {
+            if fee_rate < self.min_fee_rate {
+                continue;
+            }
+            let (count, total) = &mut totals[bucket_index(fee_rate)];
+            *count += 1;
+            *total = total.saturating_add(vsize);
+        }
+
+        let mut buckets = Vec::new();
+        let mut cumulative_vsize = 0u64;
+        for (index, &(tx_count, vsize)) in totals.iter().enumerate().rev() {
+            if tx_count == 0 {
+                continue;
+            }
+            cumulative_vsize = cumulative_vsize.saturating_add(vsize);
+            buckets.push(FeeBucket {
+                min_fee_rate: if index == 0 { 0 } else { 1 << (index - 1) },
+                max_fee_rate: 1u64.checked_shl(index as u32).unwrap_or(u64::MAX),
+                tx_count,
+                vsize,
+                cumulative_vsize,
+            });
+        }
+        buckets.reverse();
+        buckets
+    }
+
+    /// Lowest bucketed fee rate expected to confirm within `target_blocks`
+    ///
+    /// Picks the cheapest bucket whose `cumulative_vsize` fits in
+    /// `target_blocks` blocks of `max_block_weight`, ignoring new arrivals.
+    /// `None` when no bucket fits.
+    pub fn estimate_fee_rate(
+        buckets: &[FeeBucket],
+        target_blocks: u64,
+        max_block_weight: u64,
+    ) -> Option<u64> {
+        let capacity = Self::vsize_from_weight(max_block_weight).saturating_mul(target_blocks);
+        buckets
+            .iter()
+            .find(|bucket| bucket.cumulative_vsize <= capacity)
+            .map(|bucket| bucket.min_fee_rate)
+    }
+
     /// Check if total supply exceeds maximum
     pub fn exceeds_max_supply(&self, height: u64) -> bool {
         self.total_supply_at_height(height) > self.max_money_supply
     }
 }
 
+/// BIP141 witness program: a version opcode followed by one 2-40 byte push
+fn is_witness_program(script: &[u8]) -> bool {
+    match script {
+        [version, len, program @ ..] => {
+            (*version == 0x00 || (0x51..=0x60).contains(version))
+                && (2..=40).contains(len)
+                && program.len() == *len as usize
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scripts;
 
     #[test]
     fn test_mainnet_economic_parameters() {
@@ -154,7 +523,7 @@ mod tests {
 
         assert_eq!(params.initial_subsidy, 50_0000_0000);
         assert_eq!(params.halving_interval, 210_000);
-        assert_eq!(params.max_money_supply, 21_0000_0000_0000_0000);
+        assert_eq!(params.max_money_supply, 2100_0000_0000_0000);
         assert_eq!(params.coinbase_maturity, 100);
         assert_eq!(params.dust_limit, 546);
     }
@@ -196,6 +565,165 @@ mod tests {
         assert!(params.total_supply_at_height(first_halving_height) > 0);
     }
 
+    #[test]
+    fn test_total_supply_matches_per_block_sum() {
+        let params = EconomicParameters::regtest();
+
+        let mut expected = 0u64;
+        for height in 0..2_000 {
+            expected += params.get_block_subsidy(height);
+            assert_eq!(params.total_supply_at_height(height), expected);
+        }
+    }
+
+    #[test]
+    fn test_total_supply_final_mainnet() {
+        let params = EconomicParameters::mainnet();
+
+        // Sum of all subsidies ever issued: 20,999,999.9769 BTC
+        assert_eq!(
+            params.total_supply_at_height(13_440_000),
+            2_099_999_997_690_000
+        );
+        assert!(!params.exceeds_max_supply(20_000_000));
+    }
+
+    #[test]
+    fn test_emission_schedule() {
+        let params = EconomicParameters::mainnet();
+        let schedule = params.emission_schedule(13_440_000, 10_000);
+
+        assert_eq!(schedule.first(), Some(&(0, 50_0000_0000, 50_0000_0000)));
+        assert_eq!(schedule.len(), 1_345);
+
+        for window in schedule.windows(2) {
+            assert!(window[1].2 >= window[0].2);
+        }
+        for (height, subsidy, cumulative) in &schedule {
+            assert_eq!(*subsidy, params.get_block_subsidy(*height));
+            assert_eq!(*cumulative, params.total_supply_at_height(*height));
+        }
+    }
+
+    #[test]
+    fn test_halving_heights() {
+        let params = EconomicParameters::mainnet();
+        let heights = params.halving_heights();
+
+        // 50 BTC halves 33 times before rounding down to zero
+        assert_eq!(heights.len(), 33);
+        assert_eq!(heights[0], 210_000);
+        assert_eq!(heights[1], 420_000);
+        assert_eq!(params.get_block_subsidy(*heights.last().unwrap()), 0);
+
+        let mut custom = EconomicParameters::mainnet();
+        custom.subsidy_schedule = vec![(0, 100), (1000, 50), (2000, 50), (3000, 0)];
+        assert_eq!(custom.halving_heights(), vec![1000, 3000]);
+    }
+
+    #[test]
+    fn test_builtin_parameters_validate() {
+        for version in ProtocolVersion::all() {
+            let params = EconomicParameters::for_protocol(*version);
+            assert!(params.validate().is_ok(), "{version}");
+            assert!(params.total_emission().unwrap() <= params.max_money_supply);
+        }
+    }
+
+    #[test]
+    fn test_inflationary_parameters_fail_validation() {
+        let mut params = EconomicParameters::mainnet();
+        params.subsidy_schedule = vec![(0, 100_0000_0000), (1_000_000, 0)];
+        let err = params.validate().unwrap_err();
+        assert!(
+            err.to_string().contains("exceeds max money supply"),
+            "{err}"
+        );
+
+        params.subsidy_schedule = vec![(0, 50_0000_0000), (210_000, 1)];
+        let err = params.validate().unwrap_err();
+        assert!(err.to_string().contains("never reaches zero"), "{err}");
+
+        let mut params = EconomicParameters::mainnet();
+        params.initial_subsidy = 100_0000_0000;
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_inconsistent_policy_parameters_fail_validation() {
+        let mut params = EconomicParameters::mainnet();
+        params.min_fee_rate = params.max_fee_rate + 1;
+        assert!(params.validate().is_err());
+
+        let mut params = EconomicParameters::mainnet();
+        params.dust_limit = 0;
+        assert!(params.validate().is_err());
+
+        let mut params = EconomicParameters::mainnet();
+        params.halving_interval = 0;
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_at_genesis() {
+        let snapshot = EconomicParameters::mainnet().snapshot(0);
+
+        assert_eq!(snapshot.current_subsidy, 50_0000_0000);
+        assert_eq!(snapshot.next_halving_height, Some(210_000));
+        assert_eq!(snapshot.blocks_until_halving, Some(210_000));
+        assert_eq!(snapshot.total_supply, 50_0000_0000);
+        assert_eq!(
+            snapshot.remaining_supply,
+            2100_0000_0000_0000 - 50_0000_0000
+        );
+        assert!((snapshot.percent_mined - 50.0 / 21_000_000.0 * 100.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_snapshot_around_first_halving() {
+        let params = EconomicParameters::mainnet();
+
+        let before = params.snapshot(209_999);
+        assert_eq!(before.blocks_until_halving, Some(1));
+        assert_eq!(before.total_supply, 1050_0000_0000_0000);
+        assert!((before.percent_mined - 50.0).abs() < 1e-9);
+
+        let after = params.snapshot(1_000_000);
+        assert_eq!(after.current_subsidy, 3_1250_0000);
+        assert_eq!(after.next_halving_height, Some(1_050_000));
+        assert_eq!(after.blocks_until_halving, Some(50_000));
+        assert_eq!(after.total_supply, params.total_supply_at_height(1_000_000));
+        // 19,687,500 BTC from the first four eras plus 160,001 blocks at 3.125 BTC
+        assert_eq!(after.total_supply, 2018_7503_1250_0000);
+        assert!((after.percent_mined - 20_187_503.125 / 21_000_000.0 * 100.0).abs() < 1e-9);
+
+        // No further subsidy changes once it has reached zero
+        assert_eq!(params.snapshot(20_000_000).blocks_until_halving, None);
+    }
+
+    #[test]
+    fn test_dust_threshold_by_script_type() {
+        let params = EconomicParameters::mainnet();
+        let threshold =
+            |script: Vec<u8>| params.dust_threshold_for_script(&script, DUST_RELAY_FEE_RATE);
+
+        let p2pkh = threshold(scripts::p2pkh_script([1; 20]));
+        let p2wpkh = threshold(scripts::p2wpkh_script([1; 20]));
+        let p2tr = threshold(scripts::p2tr_script([1; 32]));
+        assert_eq!(p2pkh, 546);
+        assert_eq!(p2pkh, params.dust_limit);
+        assert_eq!(p2wpkh, 294);
+        assert_eq!(p2tr, 330);
+        assert!(p2wpkh < p2tr && p2tr < p2pkh);
+
+        assert_eq!(threshold(scripts::op_return_script(b"data")), 0);
+        // Scales with the relay rate
+        assert_eq!(
+            params.dust_threshold_for_script(&scripts::p2tr_script([1; 32]), 1),
+            110
+        );
+    }
+
     #[test]
     fn test_dust_limit() {
         let params = EconomicParameters::mainnet();
@@ -283,6 +811,133 @@ mod tests {
         assert_eq!(params.get_block_subsidy(999), 100_0000_0000);
         assert_eq!(params.get_block_subsidy(1000), 50_0000_0000);
         assert_eq!(params.get_block_subsidy(210_000), 25_0000_0000);
+
+        // Unsorted or duplicate heights behave like the sorted schedule
+        let sorted = params.clone();
+        params.subsidy_schedule = vec![
+            (210_000, 25_0000_0000),
+            (1000, 1),
+            (0, 100_0000_0000),
+            (1000, 50_0000_0000),
+        ];
+        assert_eq!(
+            params.emission_schedule(300_000, 500),
+            sorted.emission_schedule(300_000, 500)
+        );
+        assert_eq!(params.get_block_subsidy(300_000), 25_0000_0000);
+        for height in [1, 999, 1000, 1001, 210_000, 300_000] {
+            assert_eq!(
+                params.total_supply_at_height(height) - params.total_supply_at_height(height - 1),
+                params.get_block_subsidy(height),
+                "{height}"
+            );
+        }
+        assert_eq!(params.halving_heights(), sorted.halving_heights());
+        assert_eq!(params.total_emission(), sorted.total_emission());
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_vsize_from_weight_rounds_up() {
+        assert_eq!(EconomicParameters::vsize_from_weight(0), 0);
+        assert_eq!(EconomicParameters::vsize_from_weight(400), 100);
+        assert_eq!(EconomicParameters::vsize_from_weight(401), 101);
+        assert_eq!(EconomicParameters::vsize_from_weight(403), 101);
+
+        assert_eq!(EconomicParameters::fee_rate(1010, 401), 10.0);
+        assert_eq!(EconomicParameters::fee_rate(1000, 0), 0.0);
+    }
+
+    #[test]
+    fn test_min_fee_for_weight() {
+        let params = EconomicParameters::mainnet();
+
+        // 1000 sat/kvB matches the 1 sat/vbyte minimum fee rate
+        assert_eq!(params.min_fee_for_weight(400), 100);
+        assert_eq!(params.min_fee_for_weight(8_000), 2000);
+
+        // A higher relay rate wins, rounded up to whole satoshis
+        let mut params = params;
+        params.min_relay_fee = 2_500;
+        assert_eq!(params.min_fee_for_weight(400), 250);
+        assert_eq!(params.min_fee_for_weight(404), 253);
+
+        assert_eq!(EconomicParameters::regtest().min_fee_for_weight(8_000), 0);
+    }
+
+    #[test]
+    fn test_effective_min_relay_fee() {
+        let params = EconomicParameters::mainnet();
+        let max = 300_000_000;
+
+        assert_eq!(params.effective_min_relay_fee(0, max), params.min_relay_fee);
+        assert_eq!(
+            params.effective_min_relay_fee(max / 2, max),
+            32 * params.min_relay_fee
+        );
+        let near_full = params.effective_min_relay_fee(max - 1, max);
+        assert!(near_full > 1000 * params.min_relay_fee, "{near_full}");
+        assert_eq!(
+            params.effective_min_relay_fee(2 * max, max),
+            1024 * params.min_relay_fee
+        );
+
+        let floors: Vec<u64> = (0..=100)
+            .map(|percent| params.effective_min_relay_fee(max / 100 * percent, max))
+            .collect();
+        assert!(floors.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // No base fee to raise
+        assert_eq!(
+            EconomicParameters::regtest().effective_min_relay_fee(max, max),
+            0
+        );
+
+        // Never above the maximum fee rate
+        let mut capped = params.clone();
+        capped.max_fee_rate = 100;
+        assert_eq!(capped.effective_min_relay_fee(max, max), 100_000);
+        assert_eq!(capped.effective_min_relay_fee(0, max), 1000);
+    }
+
+    #[test]
+    fn test_fee_buckets() {
+        let params = EconomicParameters::mainnet();
+        let entries = [
+            (1, 200_000),
+            (3, 150_000),
+            (3, 50_000),
+            (12, 400_000),
+            (100, 250_000),
+            (0, 999_999), // below the minimum fee rate
+        ];
+        let buckets = params.fee_buckets(&entries);
+
+        let ranges: Vec<(u64, u64)> = buckets
+            .iter()
+            .map(|bucket| (bucket.min_fee_rate, bucket.max_fee_rate))
+            .collect();
+        assert_eq!(ranges, vec![(1, 2), (2, 4), (8, 16), (64, 128)]);
+        assert_eq!(buckets[1].tx_count, 2);
+        assert_eq!(buckets[1].vsize, 200_000);
+        assert_eq!(buckets[0].cumulative_vsize, 1_050_000);
+        assert!(buckets
+            .windows(2)
+            .all(|pair| pair[0].cumulative_vsize > pair[1].cumulative_vsize));
+
+        // One 1M-vbyte block clears everything from 2 sat/vB up
+        assert_eq!(
+            EconomicParameters::estimate_fee_rate(&buckets, 1, 4_000_000),
+            Some(2)
+        );
+        assert_eq!(
+            EconomicParameters::estimate_fee_rate(&buckets, 2, 4_000_000),
+            Some(1)
+        );
+        assert_eq!(
+            EconomicParameters::estimate_fee_rate(&buckets, 1, 400_000),
+            None
+        );
     }
 
     #[test]
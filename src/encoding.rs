@@ -0,0 +1,627 @@
+//! Wire Encoding
+//!
+//! Bitcoin wire-format serialization for headers and transactions, plus the
+//! identifiers derived from it (block hash, txid, wtxid, merkle root).
+//!
+//! Consensus transactions do not carry witness data, so witness-aware
+//! functions take the per-input witness stacks alongside the transaction.
+//!
+//! Hashes are returned in internal byte order (the raw double-SHA256 output),
+//! which is the reverse of the hex usually displayed by block explorers.
+
+use crate::crypto::sha256d;
+use crate::{
+    Block, BlockHeader, ConsensusError, Hash, OutPoint, Result, Transaction, TransactionInput,
+    TransactionOutput,
+};
+
+/// Append a Bitcoin CompactSize varint
+pub fn write_varint(buf: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => buf.push(n as u8),
+        0xfd..=0xffff => {
+            buf.push(0xfd);
+            buf.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            buf.push(0xfe);
+            buf.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            buf.push(0xff);
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+/// Encoded length of a CompactSize varint
+pub(crate) fn varint_len(n: u64) -> u64 {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Serialize a block header into its 80-byte wire form
+pub fn serialize_header(header: &BlockHeader) -> [u8; 80] {
+    let mut out = [0u8; 80];
+    out[0..4].copy_from_slice(&(header.version as i32).to_le_bytes());
+    out[4..36].copy_from_slice(&header.prev_block_hash);
+    out[36..68].copy_from_slice(&header.merkle_root);
+    out[68..72].copy_from_slice(&(header.timestamp as u32).to_le_bytes());
+    out[72..76].copy_from_slice(&(header.bits as u32).to_le_bytes());
+    out[76..80].copy_from_slice(&(header.nonce as u32).to_le_bytes());
+    out
+}
+
+/// Witness stack for a single transaction input
+pub type WitnessStack = Vec<Vec<u8>>;
+
+/// Whether any input carries witness data
+pub fn has_witness(witnesses: &[WitnessStack]) -> bool {
+    witnesses.iter().any(|stack| !stack.is_empty())
+}
+
+/// Serialize a transaction in the legacy (non-witness) format
+pub fn serialize_transaction(tx: &Transaction) -> Vec<u8> {
+    serialize_transaction_with_witness(tx, &[])
+}
+
+/// Serialize a transaction, using the BIP144 format when witness data is present
+///
+/// `witnesses` holds one stack per input; missing trailing stacks are empty.
+/// Without any witness data this is identical to `serialize_transaction`.
+pub fn serialize_transaction_with_witness(tx: &Transaction, witnesses: &[WitnessStack]) -> Vec<u8> {
+    let segwit = has_witness(witnesses);
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(tx.version as u32).to_le_bytes());
+    if segwit {
+        // Marker and flag
+        buf.extend_from_slice(&[0x00, 0x01]);
+    }
+
+    write_varint(&mut buf, tx.inputs.len() as u64);
+    for input in &tx.inputs {
+        buf.extend_from_slice(&input.prevout.hash);
+        buf.extend_from_slice(&(input.prevout.index as u32).to_le_bytes());
+        write_varint(&mut buf, input.script_sig.len() as u64);
+        buf.extend_from_slice(&input.script_sig);
+        buf.extend_from_slice(&(input.sequence as u32).to_le_bytes());
+    }
+
+    write_varint(&mut buf, tx.outputs.len() as u64);
+    for output in &tx.outputs {
+        buf.extend_from_slice(&output.value.to_le_bytes());
+        write_varint(&mut buf, output.script_pubkey.len() as u64);
+        buf.extend_from_slice(&output.script_pubkey);
+    }
+
+    if segwit {
+        for index in 0..tx.inputs.len() {
+            let stack = witnesses.get(index).map(Vec::as_slice).unwrap_or(&[]);
+            write_varint(&mut buf, stack.len() as u64);
+            for item in stack {
+                write_varint(&mut buf, item.len() as u64);
+                buf.extend_from_slice(item);
+            }
+        }
+    }
+
+    buf.extend_from_slice(&(tx.lock_time as u32).to_le_bytes());
+    buf
+}
+
+/// Cursor over wire-format bytes
+///
+/// Every read fails with a serialization error instead of panicking when the
+/// data runs out.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Bytes consumed so far
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    /// The next `n` bytes, without consuming them
+    pub(crate) fn peek(&self, n: usize) -> Option<&'a [u8]> {
+        self.data.get(self.pos..self.pos.checked_add(n)?)
+    }
+
+    pub(crate) fn read_bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .peek(n)
+            .ok_or_else(|| ConsensusError::Serialization("unexpected end of data".to_string()))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.read_bytes(N)?);
+        Ok(out)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Read a Bitcoin CompactSize varint
+    pub(crate) fn read_varint(&mut self) -> Result<u64> {
+        Ok(match self.read_u8()? {
+            0xfd => self.read_u16()? as u64,
+            0xfe => self.read_u32()? as u64,
+            0xff => self.read_u64()?,
+            n => n as u64,
+        })
+    }
+
+    /// Read a varint length followed by that many bytes
+    pub(crate) fn read_var_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_varint()?;
+        let len = usize::try_from(len)
+            .map_err(|_| ConsensusError::Serialization(format!("length {len} out of range")))?;
+        Ok(self.read_bytes(len)?.to_vec())
+    }
+}
+
+/// Parse an 80-byte block header
+pub fn deserialize_header(data: &[u8]) -> Result<BlockHeader> {
+    read_header(&mut Reader::new(data))
+}
+
+pub(crate) fn read_header(reader: &mut Reader) -> Result<BlockHeader> {
+    Ok(BlockHeader {
+        version: reader.read_u32()? as i32 as i64,
+        prev_block_hash: reader.read_array()?,
+        merkle_root: reader.read_array()?,
+        timestamp: reader.read_u32()? as u64,
+        bits: reader.read_u32()? as u64,
+        nonce: reader.read_u32()? as u64,
+    })
+}
+
+/// Parse a transaction in legacy or BIP144 format
+///
+/// Returns the transaction, one witness stack per input (all empty for a
+/// legacy serialization) and the number of bytes consumed.
+pub fn deserialize_transaction(data: &[u8]) -> Result<(Transaction, Vec<WitnessStack>, usize)> {
+    let mut reader = Reader::new(data);
+    let (tx, witnesses) = read_transaction(&mut reader)?;
+    Ok((tx, witnesses, reader.position()))
+}
+
+pub(crate) fn read_transaction(reader: &mut Reader) -> Result<(Transaction, Vec<WitnessStack>)> {
+    let version = reader.read_u32()? as u64;
+    let segwit = reader.peek(2) == Some(&[0x00, 0x01]);
+    if segwit {
+        reader.read_bytes(2)?;
+    }
+
+    let mut inputs = Vec::new();
+    for _ in 0..reader.read_varint()? {
+        inputs.push(TransactionInput {
+            prevout: OutPoint {
+                hash: reader.read_array()?,
+                index: reader.read_u32()? as u64,
+            },
+            script_sig: reader.read_var_bytes()?,
+            sequence: reader.read_u32()? as u64,
+        });
+    }
+
+    let mut outputs = Vec::new();
+    for _ in 0..reader.read_varint()? {
+        outputs.push(TransactionOutput {
+            value: reader.read_u64()? as i64,
+            script_pubkey: reader.read_var_bytes()?,
+        });
+    }
+
+    let mut witnesses = vec![WitnessStack::new(); inputs.len()];
+    if segwit {
+        for stack in &mut witnesses {
+            for _ in 0..reader.read_varint()? {
+                stack.push(reader.read_var_bytes()?);
+            }
+        }
+    }
+
+    let tx = Transaction {
+        version,
+        inputs,
+        outputs,
+        lock_time: reader.read_u32()? as u64,
+    };
+    Ok((tx, witnesses))
+}
+
+/// Serialize a block without witness data
+pub fn serialize_block(block: &Block) -> Vec<u8> {
+    serialize_block_with_witness(block, &[])
+}
+
+/// Serialize a block with its witness data, as relayed to witness peers
+///
+/// `witnesses` holds each transaction's per-input stacks, as returned by
+/// `deserialize_block`; transactions with witness data use the BIP144 format
+/// and the rest the legacy one.
+pub fn serialize_block_with_witness(block: &Block, witnesses: &[Vec<WitnessStack>]) -> Vec<u8> {
+    let mut buf = serialize_header(&block.header).to_vec();
+    write_varint(&mut buf, block.transactions.len() as u64);
+    for (index, tx) in block.transactions.iter().enumerate() {
+        let stacks = witnesses.get(index).map(Vec::as_slice).unwrap_or(&[]);
+        buf.extend_from_slice(&serialize_transaction_with_witness(tx, stacks));
+    }
+    buf
+}
+
+/// Parse a serialized block, rejecting trailing bytes
+///
+/// Returns the block and each transaction's witness stacks.
+pub fn deserialize_block(data: &[u8]) -> Result<(Block, Vec<Vec<WitnessStack>>)> {
+    let (block, witnesses, consumed) = deserialize_block_prefix(data)?;
+    if consumed != data.len() {
+        return Err(ConsensusError::Serialization("trailing bytes after block".to_string()).into());
+    }
+    Ok((block, witnesses))
+}
+
+/// Parse a block from the start of `data`, e.g. a stream of blocks
+///
+/// Like `deserialize_block`, but bytes after the block are allowed and the
+/// number of bytes consumed is returned as well.
+pub fn deserialize_block_prefix(data: &[u8]) -> Result<(Block, Vec<Vec<WitnessStack>>, usize)> {
+    let mut reader = Reader::new(data);
+    let header = read_header(&mut reader)?;
+    let mut transactions = Vec::new();
+    let mut witnesses = Vec::new();
+    for _ in 0..reader.read_varint()? {
+        let (tx, stacks) = read_transaction(&mut reader)?;
+        transactions.push(tx);
+        witnesses.push(stacks);
+    }
+    Ok((
+        Block {
+            header,
+            transactions,
+        },
+        witnesses,
+        reader.position(),
+    ))
+}
+
+/// Block hash (double SHA256 of the serialized header)
+pub fn block_hash(header: &BlockHeader) -> Hash {
+    sha256d(&serialize_header(header))
+}
+
+/// Transaction id (double SHA256 of the legacy serialization)
+pub fn txid(tx: &Transaction) -> Hash {
+    sha256d(&serialize_transaction(tx))
+}
+
+/// Witness transaction id (double SHA256 of the BIP144 serialization)
+///
+/// Equal to the txid when the transaction carries no witness data.
+pub fn wtxid(tx: &Transaction, witnesses: &[WitnessStack]) -> Hash {
+    sha256d(&serialize_transaction_with_witness(tx, witnesses))
+}
+
+/// Merkle root over a list of hashes
+///
+/// An odd hash at any level is paired with itself. Returns all zeros for an
+/// empty list.
+pub fn merkle_root(hashes: &[Hash]) -> Hash {
+    if hashes.is_empty() {
+        return [0u8; 32];
+    }
+
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut concat = [0u8; 64];
+                concat[..32].copy_from_slice(&pair[0]);
+                concat[32..].copy_from_slice(pair.get(1).unwrap_or(&pair[0]));
+                sha256d(&concat)
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// Merkle root of a block's transaction ids
+pub fn block_merkle_root(block: &Block) -> Hash {
+    let txids: Vec<Hash> = block.transactions.iter().map(txid).collect();
+    merkle_root(&txids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis;
+
+    fn reversed(mut hash: Hash) -> Hash {
+        hash.reverse();
+        hash
+    }
+
+    #[test]
+    fn test_varint_boundaries() {
+        let cases: [(u64, usize); 6] = [
+            (0, 1),
+            (0xfc, 1),
+            (0xfd, 3),
+            (0xffff, 3),
+            (0x1_0000, 5),
+            (0x1_0000_0000, 9),
+        ];
+        for (n, len) in cases {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, n);
+            assert_eq!(buf.len(), len, "varint length for {n}");
+        }
+    }
+
+    fn from_hex(hex: &str) -> Hash {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_genesis_coinbase_txid() {
+        let block = genesis::mainnet_genesis();
+        let coinbase_txid = txid(&block.transactions[0]);
+
+        assert_eq!(
+            reversed(coinbase_txid),
+            from_hex("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+        );
+        assert_eq!(block_merkle_root(&block), coinbase_txid);
+    }
+
+    #[test]
+    fn test_genesis_block_hash() {
+        let mut block = genesis::mainnet_genesis();
+        block.header.merkle_root = block_merkle_root(&block);
+
+        assert_eq!(
+            reversed(block_hash(&block.header)),
+            from_hex("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f")
+        );
+    }
+
+    fn p2wpkh_spend() -> Transaction {
+        use crate::{OutPoint, TransactionInput, TransactionOutput};
+
+        Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0xab; 32],
+                    index: 1,
+                },
+                script_sig: vec![],
+                sequence: 0xfffffffd,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 90_000,
+                script_pubkey: [vec![0x00, 0x14], vec![0xcd; 20]].concat(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_txid_equals_wtxid_without_witness() {
+        let tx = p2wpkh_spend();
+
+        assert_eq!(
+            serialize_transaction_with_witness(&tx, &[]),
+            serialize_transaction(&tx)
+        );
+        assert_eq!(wtxid(&tx, &[]), txid(&tx));
+        assert_eq!(wtxid(&tx, &[vec![]]), txid(&tx));
+    }
+
+    #[test]
+    fn test_bip144_witness_serialization() {
+        let tx = p2wpkh_spend();
+        let witness = vec![vec![vec![0x30; 71], vec![0x02; 33]]];
+        let legacy = serialize_transaction(&tx);
+        let segwit = serialize_transaction_with_witness(&tx, &witness);
+
+        // version | 00 01 | ... | witness | locktime
+        assert_eq!(segwit[..6], [0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(segwit[6..legacy.len() - 2], legacy[4..legacy.len() - 4]);
+        let witness_bytes = &segwit[legacy.len() - 2..segwit.len() - 4];
+        assert_eq!(witness_bytes[0], 2); // stack items
+        assert_eq!(witness_bytes[1], 71);
+        assert_eq!(witness_bytes[2 + 71], 33);
+        assert_eq!(segwit.len(), legacy.len() + 2 + 1 + 1 + 71 + 1 + 33);
+
+        // The witness changes the wtxid but never the txid
+        assert_ne!(wtxid(&tx, &witness), txid(&tx));
+        assert_eq!(sha256d(&legacy), txid(&tx));
+    }
+
+    #[test]
+    fn test_transaction_round_trip() {
+        let tx = p2wpkh_spend();
+        let witness = vec![vec![vec![0x30; 71], vec![0x02; 33]]];
+
+        let legacy = serialize_transaction(&tx);
+        let (decoded, witnesses, consumed) = deserialize_transaction(&legacy).unwrap();
+        assert_eq!(decoded, tx);
+        assert_eq!(witnesses, vec![WitnessStack::new()]);
+        assert_eq!(consumed, legacy.len());
+
+        let mut segwit = serialize_transaction_with_witness(&tx, &witness);
+        segwit.extend_from_slice(&[0xde, 0xad]);
+        let (decoded, witnesses, consumed) = deserialize_transaction(&segwit).unwrap();
+        assert_eq!(decoded, tx);
+        assert_eq!(witnesses, witness);
+        assert_eq!(consumed, segwit.len() - 2);
+    }
+
+    #[test]
+    fn test_truncated_transaction_rejected() {
+        let bytes = serialize_transaction(&p2wpkh_spend());
+        for len in [0, 4, 40, bytes.len() - 1] {
+            assert!(deserialize_transaction(&bytes[..len]).is_err(), "{len}");
+        }
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = genesis::mainnet_genesis().header;
+        let decoded = deserialize_header(&serialize_header(&header)).unwrap();
+        assert_eq!(decoded, header);
+        assert!(deserialize_header(&[0u8; 79]).is_err());
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let block = genesis::mainnet_genesis();
+        let bytes = serialize_block(&block);
+        assert_eq!(bytes.len(), 285);
+
+        let (decoded, witnesses) = deserialize_block(&bytes).unwrap();
+        assert_eq!(decoded, block);
+        assert_eq!(witnesses, vec![vec![WitnessStack::new()]]);
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(deserialize_block(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_segwit_block_round_trip() {
+        let mut block = genesis::mainnet_genesis();
+        block.transactions.push(p2wpkh_spend());
+        block.transactions.push(p2wpkh_spend());
+        block.transactions[2].lock_time = 1;
+        // Only the middle transaction carries a witness
+        let witnesses = vec![
+            vec![WitnessStack::new()],
+            vec![vec![vec![0x30; 71], vec![0x02; 33]]],
+            vec![WitnessStack::new()],
+        ];
+
+        let bytes = serialize_block_with_witness(&block, &witnesses);
+        let legacy = serialize_block(&block);
+        assert_eq!(bytes.len(), legacy.len() + 2 + 1 + 1 + 71 + 1 + 33);
+
+        let (decoded, decoded_witnesses) = deserialize_block(&bytes).unwrap();
+        assert_eq!(decoded, block);
+        assert_eq!(decoded_witnesses, witnesses);
+        assert_eq!(
+            serialize_block_with_witness(&decoded, &decoded_witnesses),
+            bytes
+        );
+
+        // Blocks back to back in a stream
+        let mut stream = bytes.clone();
+        stream.extend_from_slice(&legacy);
+        let (first, _, consumed) = deserialize_block_prefix(&stream).unwrap();
+        assert_eq!(first, block);
+        assert_eq!(consumed, bytes.len());
+        let (second, second_witnesses, consumed) =
+            deserialize_block_prefix(&stream[consumed..]).unwrap();
+        assert_eq!(second, block);
+        assert!(second_witnesses.iter().flatten().all(Vec::is_empty));
+        assert_eq!(consumed, legacy.len());
+    }
+
+    #[test]
+    fn test_decode_mainnet_header_125552() {
+        let hex = "0100000081cd02ab7e569e8bcd9317e2fe99f2de44d49ab2b8851ba4a30800000000\
+                   0000e320b6c2fffc8d750423db8b1eb942ae710e951ed797f7affc8892b0f1fc122b\
+                   c7f5d74df2b9441a42a14695";
+        let bytes = hex_bytes(hex);
+
+        let header = deserialize_header(&bytes).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.timestamp, 1305998791);
+        assert_eq!(header.bits, 0x1a44b9f2);
+        assert_eq!(header.nonce, 2504433986);
+        assert_eq!(serialize_header(&header)[..], bytes[..]);
+        assert_eq!(
+            reversed(block_hash(&header)),
+            from_hex("00000000000000001e8d6829a8a21adc5d38d0a473b144b6765798e61f98bd1d")
+        );
+    }
+
+    fn hex_bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_mainnet_segwit_txid_wtxid() {
+        // Mainnet P2WPKH spend f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206
+        let hex = "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93c\
+                   ac7c0100000000ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e3\
+                   70bd87cb5a078702483045022100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753\
+                   b88db122e70c79f9370220756e6633b17fd2710e626347d28d60b0a2d6cbb41de51740644b\
+                   9fb3ba7751040121028fa937ca8cba2197a37c007176ed8941055d3bcb8627d085e94553e6\
+                   2f057dcc00000000";
+        let bytes = hex_bytes(hex);
+
+        let (tx, witnesses, consumed) = deserialize_transaction(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(witnesses[0].len(), 2);
+        assert_eq!(serialize_transaction_with_witness(&tx, &witnesses), bytes);
+        assert_eq!(
+            reversed(txid(&tx)),
+            from_hex("f5864806e3565c34d1b41e716f72609d00b55ea5eac5b924c9719a842ef42206")
+        );
+        assert_eq!(
+            reversed(wtxid(&tx, &witnesses)),
+            from_hex("80b7d8a82d5d5bf92905b06f2014dd699e03837ca172e3a59d51426ebbe3e7f5")
+        );
+    }
+
+    #[test]
+    fn test_merkle_root_odd_count_duplicates_last() {
+        let a = [1u8; 32];
+        let b = [2u8; 32];
+        let c = [3u8; 32];
+
+        assert_eq!(merkle_root(&[a]), a);
+        assert_eq!(merkle_root(&[a, b, c]), merkle_root(&[a, b, c, c]));
+        assert_ne!(merkle_root(&[a, b]), merkle_root(&[b, a]));
+    }
+}
@@ -0,0 +1,56 @@
+//! Protocol Errors
+//!
+//! Consensus errors are re-exported from consensus-proof. `ProtocolError` wraps
+//! them and adds variants for protocol-policy failures, so callers can tell a
+//! policy rejection (size, sigops, fees, features) apart from a consensus one.
+
+pub use bllvm_consensus::error::*;
+
+use thiserror::Error;
+
+/// Errors returned by the protocol engine
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    /// Failure reported by the consensus layer
+    #[error(transparent)]
+    Consensus(#[from] ConsensusError),
+
+    /// Block size or weight above the protocol limit
+    #[error("Block size exceeded: {0}")]
+    BlockSizeExceeded(String),
+
+    /// Transaction or script size above the protocol limit
+    #[error("Transaction size exceeded: {0}")]
+    TransactionSizeExceeded(String),
+
+    /// Signature operations above the protocol limit
+    #[error("Sigop limit exceeded: {0}")]
+    SigopLimitExceeded(String),
+
+    /// Use of a feature that is not active on this network or at this height
+    #[error("Feature not active: {0}")]
+    FeatureNotActive(String),
+
+    /// Malformed, oversized or out-of-order P2P message
+    #[error("Invalid network message: {0}")]
+    InvalidNetworkMessage(String),
+
+    /// Fee or fee rate outside the accepted range
+    #[error("Fee out of range: {0}")]
+    FeeOutOfRange(String),
+
+    /// Relay policy rejection of a transaction that is consensus-valid
+    #[error("Policy violation: {0}")]
+    PolicyViolation(String),
+
+    /// Network name that matches no supported protocol version
+    #[error("Unknown network: {0}")]
+    UnknownNetwork(String),
+
+    /// Inconsistent network, economic or feature configuration
+    #[error("Invalid network configuration: {0}")]
+    InvalidConfig(String),
+}
+
+/// Result type for protocol engine operations
+pub type Result<T> = std::result::Result<T, ProtocolError>;
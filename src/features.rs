@@ -4,14 +4,20 @@
 //! This allows the protocol engine to determine if features are active
 //! at a specific block height, not just whether they're supported.
 
-use crate::ProtocolVersion;
+use crate::{ProtocolError, ProtocolVersion, Result};
 use serde::{Deserialize, Serialize};
+use std::ops::BitOr;
 
 /// Feature activation method
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ActivationMethod {
     /// BIP9 version bits activation
     BIP9,
+    /// BIP8 height-based version bits activation
+    ///
+    /// With `lockinontimeout` the deployment activates at its timeout height
+    /// even without miner signalling; without it, it fails like BIP9.
+    BIP8 { lockinontimeout: bool },
     /// Height-based activation (e.g., BIP34 blocks version)
     HeightBased,
     /// Timestamp-based activation
@@ -33,11 +39,129 @@ pub struct FeatureActivation {
     pub activation_timestamp: Option<u64>,
     /// Activation method
     pub activation_method: ActivationMethod,
+    /// BIPs specifying the feature, the defining one first
+    #[serde(default)]
+    pub bip_numbers: Vec<u32>,
+    /// Version bits deployment parameters (if signalled via BIP9)
+    pub deployment: Option<VersionBitsDeployment>,
+}
+
+/// Version bits deployment parameters, mirroring Bitcoin Core's chainparams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionBitsDeployment {
+    /// Version bit used for signalling
+    pub bit: u8,
+    /// Median time past at which signalling starts
+    pub start_time: u64,
+    /// Median time past at which the deployment times out
+    pub timeout: u64,
+    /// Minimum height at which the deployment may activate once locked in
+    pub min_activation_height: u64,
+    /// Height at which signalling starts (BIP8)
+    #[serde(default)]
+    pub start_height: Option<u64>,
+    /// Height at which signalling times out (BIP8)
+    #[serde(default)]
+    pub timeout_height: Option<u64>,
+}
+
+/// Row of a BIP9/BIP8 deployment table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentRow {
+    /// Feature name
+    pub feature_name: String,
+    /// BIP number (if applicable)
+    pub bip_number: Option<u32>,
+    /// Version bit (None for features not deployed via version bits)
+    pub bit: Option<u8>,
+    /// Signalling start time
+    pub start_time: Option<u64>,
+    /// Signalling timeout
+    pub timeout: Option<u64>,
+    /// Minimum activation height
+    pub min_activation_height: Option<u64>,
+    /// Activation method
+    pub activation_method: ActivationMethod,
+}
+
+/// Entry of a chronological feature activation timeline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivationEvent {
+    /// Feature name
+    pub feature_name: String,
     /// BIP number (if applicable)
     pub bip_number: Option<u32>,
+    /// Activation method
+    pub activation_method: ActivationMethod,
+    /// Activation height (0 for features active from genesis)
+    pub activation_height: Option<u64>,
+    /// Activation timestamp (if time-based)
+    pub activation_timestamp: Option<u64>,
+}
+
+/// Where a feature stands at a given height and time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeatureState {
+    /// Enforced
+    Active,
+    /// Scheduled to activate at a later height
+    Pending { at_height: u64 },
+    /// Registered without a scheduled activation height
+    Defined,
+    /// Deployment timed out without activating
+    Failed,
+}
+
+/// Human-oriented status of one feature, as listed by `status_table`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureStatus {
+    /// Feature name
+    pub feature_name: String,
+    /// BIPs specifying the feature, the defining one first
+    pub bip_numbers: Vec<u32>,
+    /// Activation method
+    pub activation_method: ActivationMethod,
+    /// State at the queried height and time
+    pub state: FeatureState,
+    /// Activation height (0 for features active from genesis)
+    pub activation_height: Option<u64>,
+}
+
+impl ActivationEvent {
+    /// Whether the feature has neither an activation height nor a timestamp
+    pub fn is_unactivated(&self) -> bool {
+        self.activation_height.is_none() && self.activation_timestamp.is_none()
+    }
 }
 
 impl FeatureActivation {
+    /// The BIP defining the feature, e.g. BIP141 for SegWit
+    pub fn primary_bip(&self) -> Option<u32> {
+        self.bip_numbers.first().copied()
+    }
+
+    /// State of the feature at the given height and timestamp
+    ///
+    /// A version bits deployment that is neither active nor scheduled has
+    /// failed once its timeout (by time for BIP9, by height for BIP8) passed.
+    pub fn state_at(&self, height: u64, timestamp: u64) -> FeatureState {
+        if self.is_active_at(height, timestamp) {
+            return FeatureState::Active;
+        }
+        if let Some(at_height) = self.activation_height.filter(|h| *h > height) {
+            return FeatureState::Pending { at_height };
+        }
+        let timed_out = self.deployment.is_some_and(|d| match d.timeout_height {
+            Some(timeout_height) => height >= timeout_height,
+            None => timestamp >= d.timeout,
+        });
+        if timed_out {
+            FeatureState::Failed
+        } else {
+            FeatureState::Defined
+        }
+    }
+
     /// Check if feature is active at given height and timestamp
     pub fn is_active_at(&self, height: u64, timestamp: u64) -> bool {
         match self.activation_method {
@@ -67,6 +191,21 @@ impl FeatureActivation {
                 let timestamp_active = self.activation_timestamp.is_some_and(|t| timestamp >= t);
                 height_active || timestamp_active
             }
+            ActivationMethod::BIP8 { lockinontimeout } => {
+                // Signalled activation, when known, takes effect at its height
+                if self.activation_height.is_some_and(|h| height >= h) {
+                    return true;
+                }
+
+                // Otherwise only a mandatory (LOT=true) deployment activates at timeout
+                let deployment = match self.deployment {
+                    Some(deployment) if lockinontimeout => deployment,
+                    _ => return false,
+                };
+                deployment
+                    .timeout_height
+                    .is_some_and(|timeout| height >= timeout.max(deployment.min_activation_height))
+            }
         }
     }
 }
@@ -87,6 +226,9 @@ impl FeatureRegistry {
             ProtocolVersion::BitcoinV1 => Self::mainnet(),
             ProtocolVersion::Testnet3 => Self::testnet(),
             ProtocolVersion::Regtest => Self::regtest(),
+            ProtocolVersion::Signet => Self::signet(),
+            ProtocolVersion::Simnet => Self::simnet(),
+            ProtocolVersion::Educational => Self::educational(),
         }
     }
 
@@ -101,7 +243,15 @@ impl FeatureRegistry {
                     activation_height: Some(481_824),
                     activation_timestamp: Some(1503539857), // Aug 24, 2017
                     activation_method: ActivationMethod::BIP9,
-                    bip_number: Some(141),
+                    bip_numbers: vec![141, 143, 144, 147],
+                    deployment: Some(VersionBitsDeployment {
+                        bit: 1,
+                        start_time: 1479168000, // Nov 15, 2016
+                        timeout: 1510704000,    // Nov 15, 2017
+                        min_activation_height: 0,
+                        start_height: None,
+                        timeout_height: None,
+                    }),
                 },
                 // Taproot activated via BIP9 at block 709,632 (November 14, 2021)
                 FeatureActivation {
@@ -109,7 +259,15 @@ impl FeatureRegistry {
                     activation_height: Some(709_632),
                     activation_timestamp: Some(1636934400), // Nov 14, 2021
                     activation_method: ActivationMethod::BIP9,
-                    bip_number: Some(341),
+                    bip_numbers: vec![341, 340, 342],
+                    deployment: Some(VersionBitsDeployment {
+                        bit: 2,
+                        start_time: 1619222400, // Apr 24, 2021
+                        timeout: 1628640000,    // Aug 11, 2021
+                        min_activation_height: 709_632,
+                        start_height: None,
+                        timeout_height: None,
+                    }),
                 },
                 // RBF (BIP125) - Always available (mempool policy)
                 FeatureActivation {
@@ -117,7 +275,8 @@ impl FeatureRegistry {
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(125),
+                    bip_numbers: vec![125],
+                    deployment: None,
                 },
                 // CTV (CheckTemplateVerify) - Not yet activated
                 FeatureActivation {
@@ -125,7 +284,8 @@ impl FeatureRegistry {
                     activation_height: None,
                     activation_timestamp: None,
                     activation_method: ActivationMethod::BIP9,
-                    bip_number: Some(119),
+                    bip_numbers: vec![119],
+                    deployment: None,
                 },
                 // CSV (CheckSequenceVerify) - Always active
                 FeatureActivation {
@@ -133,7 +293,8 @@ impl FeatureRegistry {
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(112),
+                    bip_numbers: vec![112, 68, 113],
+                    deployment: None,
                 },
                 // CLTV (CheckLockTimeVerify) - Always active
                 FeatureActivation {
@@ -141,7 +302,17 @@ impl FeatureRegistry {
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(65),
+                    bip_numbers: vec![65],
+                    deployment: None,
+                },
+                // BIP34 (height in coinbase) enforced from block 227,931
+                FeatureActivation {
+                    feature_name: "bip34".to_string(),
+                    activation_height: Some(227_931),
+                    activation_timestamp: None,
+                    activation_method: ActivationMethod::HeightBased,
+                    bip_numbers: vec![34],
+                    deployment: None,
                 },
             ],
         }
@@ -158,7 +329,15 @@ impl FeatureRegistry {
                     activation_height: Some(465_600), // Earlier on testnet
                     activation_timestamp: Some(1493596800), // May 1, 2017
                     activation_method: ActivationMethod::BIP9,
-                    bip_number: Some(141),
+                    bip_numbers: vec![141, 143, 144, 147],
+                    deployment: Some(VersionBitsDeployment {
+                        bit: 1,
+                        start_time: 1462060800, // May 1, 2016
+                        timeout: 1493596800,    // May 1, 2017
+                        min_activation_height: 0,
+                        start_height: None,
+                        timeout_height: None,
+                    }),
                 },
                 // Taproot activated earlier on testnet
                 FeatureActivation {
@@ -166,7 +345,15 @@ impl FeatureRegistry {
                     activation_height: Some(2_016_000), // Earlier on testnet
                     activation_timestamp: Some(1628640000), // Aug 11, 2021
                     activation_method: ActivationMethod::BIP9,
-                    bip_number: Some(341),
+                    bip_numbers: vec![341, 340, 342],
+                    deployment: Some(VersionBitsDeployment {
+                        bit: 2,
+                        start_time: 1619222400, // Apr 24, 2021
+                        timeout: 1628640000,    // Aug 11, 2021
+                        min_activation_height: 0,
+                        start_height: None,
+                        timeout_height: None,
+                    }),
                 },
                 // RBF - Always available
                 FeatureActivation {
@@ -174,7 +361,8 @@ impl FeatureRegistry {
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(125),
+                    bip_numbers: vec![125],
+                    deployment: None,
                 },
                 // CSV - Always active
                 FeatureActivation {
@@ -182,7 +370,8 @@ impl FeatureRegistry {
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(112),
+                    bip_numbers: vec![112, 68, 113],
+                    deployment: None,
                 },
                 // CLTV - Always active
                 FeatureActivation {
@@ -190,7 +379,17 @@ impl FeatureRegistry {
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(65),
+                    bip_numbers: vec![65],
+                    deployment: None,
+                },
+                // BIP34 enforced from block 21,111
+                FeatureActivation {
+                    feature_name: "bip34".to_string(),
+                    activation_height: Some(21_111),
+                    activation_timestamp: None,
+                    activation_method: ActivationMethod::HeightBased,
+                    bip_numbers: vec![34],
+                    deployment: None,
                 },
             ],
         }
@@ -207,47 +406,134 @@ impl FeatureRegistry {
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(141),
+                    bip_numbers: vec![141, 143, 144, 147],
+                    deployment: None,
                 },
                 FeatureActivation {
                     feature_name: "taproot".to_string(),
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(341),
+                    bip_numbers: vec![341, 340, 342],
+                    deployment: None,
                 },
                 FeatureActivation {
                     feature_name: "rbf".to_string(),
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(125),
+                    bip_numbers: vec![125],
+                    deployment: None,
                 },
                 FeatureActivation {
                     feature_name: "csv".to_string(),
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(112),
+                    bip_numbers: vec![112, 68, 113],
+                    deployment: None,
                 },
                 FeatureActivation {
                     feature_name: "cltv".to_string(),
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: Some(65),
+                    bip_numbers: vec![65],
+                    deployment: None,
+                },
+                // BIP34 from block 1 (the genesis coinbase predates it)
+                FeatureActivation {
+                    feature_name: "bip34".to_string(),
+                    activation_height: Some(1),
+                    activation_timestamp: None,
+                    activation_method: ActivationMethod::HeightBased,
+                    bip_numbers: vec![34],
+                    deployment: None,
                 },
                 FeatureActivation {
                     feature_name: "fast_mining".to_string(),
                     activation_height: Some(0),
                     activation_timestamp: None,
                     activation_method: ActivationMethod::AlwaysActive,
-                    bip_number: None,
+                    bip_numbers: vec![],
+                    deployment: None,
                 },
             ],
         }
     }
 
+    /// Signet feature activations: regtest's, without `fast_mining`
+    pub fn signet() -> Self {
+        let mut registry = Self::regtest();
+        registry.protocol_version = ProtocolVersion::Signet;
+        registry
+            .features
+            .retain(|feature| feature.feature_name != "fast_mining");
+        registry
+    }
+
+    /// Simnet feature activations: regtest's, plus skipping proof-of-work
+    ///
+    /// `skip_pow` makes block validation accept any nonce. Test-only.
+    pub fn simnet() -> Self {
+        let mut registry = Self::regtest();
+        registry.protocol_version = ProtocolVersion::Simnet;
+        registry.features.push(FeatureActivation {
+            feature_name: "skip_pow".to_string(),
+            activation_height: Some(0),
+            activation_timestamp: None,
+            activation_method: ActivationMethod::AlwaysActive,
+            bip_numbers: vec![],
+            deployment: None,
+        });
+        registry
+    }
+
+    /// Educational feature activations: regtest's, all active from genesis
+    ///
+    /// Except BIP34, which still starts at block 1: the genesis coinbase
+    /// does not encode its height.
+    pub fn educational() -> Self {
+        let mut registry = Self::regtest();
+        registry.protocol_version = ProtocolVersion::Educational;
+        for feature in &mut registry.features {
+            if feature.feature_name == "bip34" {
+                continue;
+            }
+            feature.activation_height = Some(0);
+            feature.activation_method = ActivationMethod::AlwaysActive;
+        }
+        registry
+    }
+
+    /// Registry with no features, for building custom activation scenarios
+    pub fn empty(version: ProtocolVersion) -> Self {
+        Self {
+            protocol_version: version,
+            features: Vec::new(),
+        }
+    }
+
+    /// Register a custom feature activation
+    ///
+    /// Fails if a feature with the same name is already registered.
+    pub fn register_feature(&mut self, activation: FeatureActivation) -> Result<()> {
+        if self.get_feature(&activation.feature_name).is_some() {
+            return Err(ProtocolError::InvalidConfig(format!(
+                "Feature {} is already registered",
+                activation.feature_name
+            )));
+        }
+        self.features.push(activation);
+        Ok(())
+    }
+
+    /// Remove a feature by name, returning its activation if it was registered
+    pub fn remove_feature(&mut self, name: &str) -> Option<FeatureActivation> {
+        let index = self.features.iter().position(|f| f.feature_name == name)?;
+        Some(self.features.remove(index))
+    }
+
     /// Check if a feature is active at a given height and timestamp
     pub fn is_feature_active(&self, feature_name: &str, height: u64, timestamp: u64) -> bool {
         self.features
@@ -257,6 +543,26 @@ impl FeatureRegistry {
             .unwrap_or(false)
     }
 
+    /// Features that are registered but not yet active
+    ///
+    /// Features without a scheduled activation (such as an undeployed soft
+    /// fork) are included; their `activation_height` is `None`.
+    pub fn pending_features(&self, height: u64, timestamp: u64) -> Vec<&FeatureActivation> {
+        self.features
+            .iter()
+            .filter(|f| !f.is_active_at(height, timestamp))
+            .collect()
+    }
+
+    /// Soonest height-based activation after `height`, with its activation height
+    pub fn next_activation(&self, height: u64) -> Option<(&FeatureActivation, u64)> {
+        self.features
+            .iter()
+            .filter_map(|f| Some((f, f.activation_height?)))
+            .filter(|(_, activation_height)| *activation_height > height)
+            .min_by_key(|(_, activation_height)| *activation_height)
+    }
+
     /// Get feature activation information
     pub fn get_feature(&self, feature_name: &str) -> Option<&FeatureActivation> {
         self.features
@@ -264,6 +570,11 @@ impl FeatureRegistry {
             .find(|f| f.feature_name == feature_name)
     }
 
+    /// Feature specified (in part) by a BIP, e.g. BIP143 gives segwit
+    pub fn feature_for_bip(&self, bip: u32) -> Option<&FeatureActivation> {
+        self.features.iter().find(|f| f.bip_numbers.contains(&bip))
+    }
+
     /// List all features
     pub fn list_features(&self) -> Vec<String> {
         self.features
@@ -272,6 +583,115 @@ impl FeatureRegistry {
             .collect()
     }
 
+    /// Export the registry as a deployment table in Bitcoin Core's chainparams layout
+    ///
+    /// Features without version bits parameters (always active or not yet
+    /// deployed) are listed with empty signalling columns.
+    pub fn to_deployment_table(&self) -> Vec<DeploymentRow> {
+        self.features
+            .iter()
+            .map(|f| DeploymentRow {
+                feature_name: f.feature_name.clone(),
+                bip_number: f.primary_bip(),
+                bit: f.deployment.map(|d| d.bit),
+                start_time: f.deployment.map(|d| d.start_time),
+                timeout: f.deployment.map(|d| d.timeout),
+                min_activation_height: f.deployment.map(|d| d.min_activation_height),
+                activation_method: f.activation_method,
+            })
+            .collect()
+    }
+
+    /// Status of every feature at `height` and `timestamp`, in registry order
+    pub fn status_table(&self, height: u64, timestamp: u64) -> Vec<FeatureStatus> {
+        self.features
+            .iter()
+            .map(|f| FeatureStatus {
+                feature_name: f.feature_name.clone(),
+                bip_numbers: f.bip_numbers.clone(),
+                activation_method: f.activation_method,
+                state: f.state_at(height, timestamp),
+                activation_height: match f.activation_method {
+                    ActivationMethod::AlwaysActive | ActivationMethod::HardFork => Some(0),
+                    _ => f.activation_height,
+                },
+            })
+            .collect()
+    }
+
+    /// Chronological timeline of feature activations
+    ///
+    /// Events are ordered by activation height, with always-active and hard
+    /// fork features at height 0. Features only scheduled by timestamp follow,
+    /// ordered by timestamp, and unactivated features come last.
+    pub fn activation_timeline(&self) -> Vec<ActivationEvent> {
+        let mut events: Vec<ActivationEvent> = self
+            .features
+            .iter()
+            .map(|f| ActivationEvent {
+                feature_name: f.feature_name.clone(),
+                bip_number: f.primary_bip(),
+                activation_method: f.activation_method,
+                activation_height: match f.activation_method {
+                    ActivationMethod::AlwaysActive | ActivationMethod::HardFork => Some(0),
+                    _ => f.activation_height,
+                },
+                activation_timestamp: f.activation_timestamp,
+            })
+            .collect();
+
+        events.sort_by_key(
+            |event| match (event.activation_height, event.activation_timestamp) {
+                (Some(height), _) => (0, height),
+                (None, Some(timestamp)) => (1, timestamp),
+                (None, None) => (2, 0),
+            },
+        );
+        events
+    }
+
+    /// Features that activated within `start_height..=end_height`, by height
+    ///
+    /// Each entry is the first height in the range at which the feature is
+    /// active, provided it was not already active the block before. Features
+    /// active from genesis are reported at 0 if the range starts there.
+    /// `timestamp_fn` maps a height to its block timestamp for time-based
+    /// activations and must not decrease with height, since activation is
+    /// located by binary search.
+    pub fn features_active_between(
+        &self,
+        start_height: u64,
+        end_height: u64,
+        timestamp_fn: impl Fn(u64) -> u64,
+    ) -> Vec<(u64, String)> {
+        if start_height > end_height {
+            return Vec::new();
+        }
+        let active =
+            |f: &FeatureActivation, height: u64| f.is_active_at(height, timestamp_fn(height));
+
+        let mut events: Vec<(u64, String)> = self
+            .features
+            .iter()
+            .filter(|f| active(f, end_height))
+            .filter(|f| start_height == 0 || !active(f, start_height - 1))
+            .map(|f| {
+                let (mut low, mut high) = (start_height, end_height);
+                while low < high {
+                    let mid = low + (high - low) / 2;
+                    if active(f, mid) {
+                        high = mid;
+                    } else {
+                        low = mid + 1;
+                    }
+                }
+                (low, f.feature_name.clone())
+            })
+            .collect();
+        events.sort();
+        events
+    }
+
     /// Create a FeatureContext for a specific height and timestamp
     /// This consolidates all feature activation checks into a single context
     pub fn create_context(&self, height: u64, timestamp: u64) -> FeatureContext {
@@ -282,6 +702,7 @@ impl FeatureRegistry {
             cltv: self.is_feature_active("cltv", height, timestamp),
             rbf: self.is_feature_active("rbf", height, timestamp),
             ctv: self.is_feature_active("ctv", height, timestamp),
+            skip_pow: self.is_feature_active("skip_pow", height, timestamp),
             height,
             timestamp,
         }
@@ -304,6 +725,8 @@ pub struct FeatureContext {
     pub rbf: bool,
     /// CTV (BIP119) activation state
     pub ctv: bool,
+    /// Proof-of-work checks skipped (simnet only, never in production)
+    pub skip_pow: bool,
     /// Block height at which this context is valid
     pub height: u64,
     /// Timestamp at which this context is valid
@@ -325,10 +748,41 @@ impl FeatureContext {
             "cltv" => self.cltv,
             "rbf" => self.rbf,
             "ctv" => self.ctv,
+            "skip_pow" => self.skip_pow,
             _ => false,
         }
     }
 
+    /// Copy of this context with one feature forced on or off
+    ///
+    /// Lets research scenarios model a soft fork's absence regardless of the
+    /// registry. Fails for an unknown feature, or if the result would have
+    /// taproot active without segwit.
+    pub fn with_override(&self, feature: &str, active: bool) -> Result<FeatureContext> {
+        let mut ctx = *self;
+        let flag = match feature {
+            "segwit" => &mut ctx.segwit,
+            "taproot" => &mut ctx.taproot,
+            "csv" => &mut ctx.csv,
+            "cltv" => &mut ctx.cltv,
+            "rbf" => &mut ctx.rbf,
+            "ctv" => &mut ctx.ctv,
+            "skip_pow" => &mut ctx.skip_pow,
+            _ => {
+                return Err(ProtocolError::InvalidConfig(format!(
+                    "Unknown feature {feature}"
+                )))
+            }
+        };
+        *flag = active;
+        if ctx.taproot && !ctx.segwit {
+            return Err(ProtocolError::InvalidConfig(
+                "taproot cannot be active without segwit".to_string(),
+            ));
+        }
+        Ok(ctx)
+    }
+
     /// Get list of all active features
     pub fn active_features(&self) -> Vec<&'static str> {
         let mut features = Vec::new();
@@ -350,14 +804,156 @@ impl FeatureContext {
         if self.ctv {
             features.push("ctv");
         }
+        if self.skip_pow {
+            features.push("skip_pow");
+        }
         features
     }
+
+    /// Script verification flags for blocks in this context
+    ///
+    /// Mirrors Core's `GetBlockScriptFlags`. P2SH is enforced from genesis;
+    /// the context has no separate BIP66 state, so DERSIG is tied to CLTV,
+    /// which every network buried after BIP66. NULLDUMMY shipped with SegWit.
+    pub fn script_flags(&self) -> ScriptFlags {
+        let mut flags = ScriptFlags::P2SH;
+        if self.cltv {
+            flags.insert(ScriptFlags::DERSIG | ScriptFlags::CHECKLOCKTIMEVERIFY);
+        }
+        if self.csv {
+            flags.insert(ScriptFlags::CHECKSEQUENCEVERIFY);
+        }
+        if self.segwit {
+            flags.insert(ScriptFlags::WITNESS | ScriptFlags::NULLDUMMY);
+        }
+        if self.taproot {
+            flags.insert(ScriptFlags::TAPROOT);
+        }
+        flags
+    }
+}
+
+/// Script verification flags passed to the script interpreter
+///
+/// Bit values match Bitcoin Core's `SCRIPT_VERIFY_*` constants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct ScriptFlags(u32);
+
+impl ScriptFlags {
+    /// No flags
+    pub const NONE: ScriptFlags = ScriptFlags(0);
+    /// BIP16 pay-to-script-hash evaluation
+    pub const P2SH: ScriptFlags = ScriptFlags(1 << 0);
+    /// BIP66 strict DER signatures
+    pub const DERSIG: ScriptFlags = ScriptFlags(1 << 2);
+    /// BIP147 CHECKMULTISIG dummy must be empty
+    pub const NULLDUMMY: ScriptFlags = ScriptFlags(1 << 4);
+    /// BIP65 OP_CHECKLOCKTIMEVERIFY
+    pub const CHECKLOCKTIMEVERIFY: ScriptFlags = ScriptFlags(1 << 9);
+    /// BIP112 OP_CHECKSEQUENCEVERIFY
+    pub const CHECKSEQUENCEVERIFY: ScriptFlags = ScriptFlags(1 << 10);
+    /// BIP141 witness program evaluation
+    pub const WITNESS: ScriptFlags = ScriptFlags(1 << 11);
+    /// BIP341/342 Taproot and Tapscript evaluation
+    pub const TAPROOT: ScriptFlags = ScriptFlags(1 << 17);
+
+    /// Check if every bit in `flags` is set
+    pub fn contains(&self, flags: ScriptFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Set the bits in `flags`
+    pub fn insert(&mut self, flags: ScriptFlags) {
+        self.0 |= flags.0;
+    }
+
+    /// Raw flag value
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for ScriptFlags {
+    type Output = ScriptFlags;
+
+    fn bitor(self, rhs: ScriptFlags) -> ScriptFlags {
+        ScriptFlags(self.0 | rhs.0)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_script_flags_follow_activation() {
+        let registry = FeatureRegistry::mainnet();
+        let pre_taproot = registry
+            .create_context(700_000, 1_630_000_000)
+            .script_flags();
+        let post_taproot = registry
+            .create_context(709_632, 1_637_000_000)
+            .script_flags();
+
+        let earlier = ScriptFlags::P2SH
+            | ScriptFlags::DERSIG
+            | ScriptFlags::CHECKLOCKTIMEVERIFY
+            | ScriptFlags::CHECKSEQUENCEVERIFY
+            | ScriptFlags::WITNESS
+            | ScriptFlags::NULLDUMMY;
+        assert!(pre_taproot.contains(earlier));
+        assert!(!pre_taproot.contains(ScriptFlags::TAPROOT));
+        assert!(post_taproot.contains(earlier | ScriptFlags::TAPROOT));
+        assert_eq!(post_taproot, earlier | ScriptFlags::TAPROOT);
+
+        let pre_segwit = registry
+            .create_context(400_000, 1_450_000_000)
+            .script_flags();
+        assert!(pre_segwit.contains(ScriptFlags::P2SH | ScriptFlags::CHECKSEQUENCEVERIFY));
+        assert!(!pre_segwit.contains(ScriptFlags::WITNESS));
+        assert!(!pre_segwit.contains(ScriptFlags::NULLDUMMY));
+    }
+
+    #[test]
+    fn test_pending_features_at_genesis() {
+        let registry = FeatureRegistry::mainnet();
+        let pending = registry.pending_features(0, 0);
+        let height_of = |name: &str| {
+            pending
+                .iter()
+                .find(|f| f.feature_name == name)
+                .map(|f| f.activation_height)
+        };
+
+        assert_eq!(height_of("segwit"), Some(Some(481_824)));
+        assert_eq!(height_of("taproot"), Some(Some(709_632)));
+        // Undeployed: pending with no ETA
+        assert_eq!(height_of("ctv"), Some(None));
+        // Already active from genesis
+        assert_eq!(height_of("csv"), None);
+
+        // Only CTV is still pending at the tip
+        let pending = registry.pending_features(900_000, 1_750_000_000);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].feature_name, "ctv");
+    }
+
+    #[test]
+    fn test_next_activation() {
+        let registry = FeatureRegistry::mainnet();
+
+        let (feature, height) = registry.next_activation(0).unwrap();
+        assert_eq!((feature.feature_name.as_str(), height), ("bip34", 227_931));
+
+        let (feature, height) = registry.next_activation(481_824).unwrap();
+        assert_eq!(
+            (feature.feature_name.as_str(), height),
+            ("taproot", 709_632)
+        );
+
+        assert!(registry.next_activation(709_632).is_none());
+    }
+
     #[test]
     fn test_segwit_activation_mainnet() {
         let registry = FeatureRegistry::mainnet();
@@ -432,12 +1028,66 @@ mod tests {
 
         let segwit = registry.get_feature("segwit").unwrap();
         assert_eq!(segwit.feature_name, "segwit");
-        assert_eq!(segwit.bip_number, Some(141));
+        assert_eq!(segwit.primary_bip(), Some(141));
+        assert_eq!(segwit.bip_numbers, vec![141, 143, 144, 147]);
         assert_eq!(segwit.activation_method, ActivationMethod::BIP9);
 
         assert!(registry.get_feature("nonexistent").is_none());
     }
 
+    #[test]
+    fn test_feature_for_bip() {
+        for registry in [FeatureRegistry::mainnet(), FeatureRegistry::testnet()] {
+            let feature_for = |bip| {
+                registry
+                    .feature_for_bip(bip)
+                    .map(|f| f.feature_name.as_str())
+            };
+            assert_eq!(feature_for(143), Some("segwit"));
+            assert_eq!(feature_for(141), Some("segwit"));
+            assert_eq!(feature_for(342), Some("taproot"));
+            assert_eq!(feature_for(68), Some("csv"));
+            assert_eq!(feature_for(9), None);
+        }
+        let mainnet = FeatureRegistry::mainnet();
+        let taproot = mainnet.feature_for_bip(340).unwrap();
+        assert_eq!(taproot.primary_bip(), Some(341));
+    }
+
+    #[test]
+    fn test_features_active_between() {
+        let registry = FeatureRegistry::mainnet();
+        // Ten-minute blocks anchored at the SegWit activation block's time
+        let timestamp = |height: u64| 1_503_539_857 + height * 600 - 481_824 * 600;
+
+        assert_eq!(
+            registry.features_active_between(480_000, 500_000, timestamp),
+            vec![(481_824, "segwit".to_string())]
+        );
+        // Ranges ending just before, or starting just after, miss it
+        assert!(registry
+            .features_active_between(480_000, 481_823, timestamp)
+            .is_empty());
+        assert!(registry
+            .features_active_between(481_825, 500_000, timestamp)
+            .is_empty());
+        assert_eq!(
+            registry.features_active_between(481_824, 481_824, timestamp),
+            vec![(481_824, "segwit".to_string())]
+        );
+
+        // From genesis: the always-active features first, then in height order
+        let names: Vec<String> = registry
+            .features_active_between(0, 700_000, timestamp)
+            .into_iter()
+            .map(|(height, name)| format!("{name}@{height}"))
+            .collect();
+        assert_eq!(
+            names,
+            ["cltv@0", "csv@0", "rbf@0", "bip34@227931", "segwit@481824"]
+        );
+    }
+
     #[test]
     fn test_list_features() {
         let mainnet = FeatureRegistry::mainnet();
@@ -473,6 +1123,73 @@ mod tests {
         assert!(registry.is_feature_active("segwit", 481_000, 1503539857));
     }
 
+    #[test]
+    fn test_timed_out_deployment_failed() {
+        let mut failed = FeatureRegistry::mainnet()
+            .get_feature("taproot")
+            .unwrap()
+            .clone();
+        failed.feature_name = "speedy".to_string();
+        failed.activation_height = None;
+        failed.activation_timestamp = None;
+        let timeout = failed.deployment.unwrap().timeout;
+
+        assert_eq!(failed.state_at(700_000, timeout - 1), FeatureState::Defined);
+        assert_eq!(failed.state_at(700_000, timeout), FeatureState::Failed);
+    }
+
+    #[test]
+    fn test_deployment_table_mainnet() {
+        let table = FeatureRegistry::mainnet().to_deployment_table();
+        assert_eq!(table.len(), FeatureRegistry::mainnet().features.len());
+
+        let segwit = table.iter().find(|r| r.feature_name == "segwit").unwrap();
+        assert_eq!(segwit.bip_number, Some(141));
+        assert_eq!(segwit.bit, Some(1));
+        assert_eq!(segwit.start_time, Some(1479168000));
+        assert_eq!(segwit.timeout, Some(1510704000));
+        assert_eq!(segwit.min_activation_height, Some(0));
+        assert_eq!(segwit.activation_method, ActivationMethod::BIP9);
+
+        let taproot = table.iter().find(|r| r.feature_name == "taproot").unwrap();
+        assert_eq!(taproot.bit, Some(2));
+        assert_eq!(taproot.min_activation_height, Some(709_632));
+
+        // Always-active features have no signalling parameters
+        let csv = table.iter().find(|r| r.feature_name == "csv").unwrap();
+        assert_eq!(csv.bit, None);
+        assert_eq!(csv.activation_method, ActivationMethod::AlwaysActive);
+    }
+
+    #[test]
+    fn test_activation_timeline_mainnet() {
+        let timeline = FeatureRegistry::mainnet().activation_timeline();
+        let position = |name: &str| {
+            timeline
+                .iter()
+                .position(|event| event.feature_name == name)
+                .unwrap()
+        };
+
+        for genesis_feature in ["csv", "cltv", "rbf"] {
+            assert_eq!(
+                timeline[position(genesis_feature)].activation_height,
+                Some(0)
+            );
+            assert!(position(genesis_feature) < position("segwit"));
+        }
+        assert!(position("segwit") < position("taproot"));
+
+        // Not yet activated soft forks close the timeline
+        let ctv = timeline.last().unwrap();
+        assert_eq!(ctv.feature_name, "ctv");
+        assert!(ctv.is_unactivated());
+
+        let json = serde_json::to_string(&timeline).unwrap();
+        let decoded: Vec<ActivationEvent> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, timeline);
+    }
+
     #[test]
     fn test_feature_context_creation() {
         let registry = FeatureRegistry::mainnet();
@@ -561,4 +1278,105 @@ mod tests {
         assert_eq!(ctx.height, 800_000);
         assert_eq!(ctx.timestamp, 1640000000);
     }
+
+    #[test]
+    fn test_register_custom_feature() {
+        let mut registry = FeatureRegistry::mainnet();
+        registry.remove_feature("ctv").unwrap();
+        registry
+            .register_feature(FeatureActivation {
+                feature_name: "ctv".to_string(),
+                activation_height: Some(900_000),
+                activation_timestamp: None,
+                activation_method: ActivationMethod::HeightBased,
+                bip_numbers: vec![119],
+                deployment: None,
+            })
+            .unwrap();
+        registry
+            .register_feature(FeatureActivation {
+                feature_name: "op_cat".to_string(),
+                activation_height: Some(950_000),
+                activation_timestamp: None,
+                activation_method: ActivationMethod::HeightBased,
+                bip_numbers: vec![347],
+                deployment: None,
+            })
+            .unwrap();
+
+        assert!(!registry.is_feature_active("ctv", 899_999, 0));
+        assert!(registry.is_feature_active("ctv", 900_000, 0));
+        assert!(registry.is_feature_active("op_cat", 950_000, 0));
+
+        // Contexts only carry the known flags, but reflect the custom CTV height
+        let ctx = registry.create_context(900_000, 0);
+        assert!(ctx.ctv);
+        assert!(!ctx.is_active("op_cat"));
+    }
+
+    #[test]
+    fn test_register_duplicate_feature_rejected() {
+        let mut registry = FeatureRegistry::empty(ProtocolVersion::Regtest);
+        assert!(registry.list_features().is_empty());
+
+        let activation = FeatureActivation {
+            feature_name: "custom".to_string(),
+            activation_height: Some(10),
+            activation_timestamp: None,
+            activation_method: ActivationMethod::HeightBased,
+            bip_numbers: vec![],
+            deployment: None,
+        };
+        registry.register_feature(activation.clone()).unwrap();
+        assert!(matches!(
+            registry.register_feature(activation),
+            Err(ProtocolError::InvalidConfig(_))
+        ));
+
+        assert!(registry.remove_feature("custom").is_some());
+        assert!(registry.remove_feature("custom").is_none());
+        assert!(!registry.is_feature_active("custom", 100, 0));
+    }
+
+    fn bip8_feature(lockinontimeout: bool) -> FeatureActivation {
+        FeatureActivation {
+            feature_name: "taproot".to_string(),
+            activation_height: None, // never reached the signalling threshold
+            activation_timestamp: None,
+            activation_method: ActivationMethod::BIP8 { lockinontimeout },
+            bip_numbers: vec![341, 340, 342],
+            deployment: Some(VersionBitsDeployment {
+                bit: 2,
+                start_time: 0,
+                timeout: 0,
+                min_activation_height: 0,
+                start_height: Some(681_408),
+                timeout_height: Some(760_032),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_bip8_lockinontimeout_true_activates_at_timeout() {
+        let feature = bip8_feature(true);
+
+        assert!(!feature.is_active_at(700_000, 0));
+        assert!(!feature.is_active_at(760_031, 0));
+        assert!(feature.is_active_at(760_032, 0));
+        assert!(feature.is_active_at(800_000, 0));
+    }
+
+    #[test]
+    fn test_bip8_lockinontimeout_false_fails_at_timeout() {
+        let feature = bip8_feature(false);
+
+        assert!(!feature.is_active_at(760_031, 0));
+        assert!(!feature.is_active_at(760_032, 0));
+        assert!(!feature.is_active_at(800_000, 0));
+
+        // Signalled lock-in still activates regardless of LOT
+        let mut signalled = bip8_feature(false);
+        signalled.activation_height = Some(709_632);
+        assert!(signalled.is_active_at(709_632, 0));
+    }
 }
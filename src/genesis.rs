@@ -1,6 +1,6 @@
 //! Genesis Blocks
 //!
-//! Provides actual Bitcoin genesis blocks for mainnet, testnet, and regtest networks.
+//! Provides actual Bitcoin genesis blocks for mainnet, testnet, regtest and signet networks.
 
 use bllvm_consensus::types::*;
 
@@ -104,6 +104,19 @@ pub fn testnet_genesis() -> Block {
     }
 }
 
+/// Create Bitcoin signet genesis block
+///
+/// Shared by every signet, whatever its challenge: mainnet's coinbase under a
+/// new header.
+pub fn signet_genesis() -> Block {
+    // Hash: 0x00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6
+    let mut block = mainnet_genesis();
+    block.header.timestamp = 1598918400; // Sep 1, 2020
+    block.header.bits = 0x1e0377ae;
+    block.header.nonce = 52613770;
+    block
+}
+
 /// Create Bitcoin regtest genesis block
 pub fn regtest_genesis() -> Block {
     // Bitcoin regtest genesis block
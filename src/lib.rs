@@ -15,13 +15,15 @@
 //! 5. developer-sdk (ergonomic API)
 
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
 // Re-export commonly used types from consensus-proof for convenience
 // This allows upper layers (like reference-node) to depend only on protocol-engine
 pub use bllvm_consensus::{
     Block, BlockHeader, ByteString, ConsensusError, ConsensusProof, Hash, Integer, Natural,
-    OutPoint, Result, Transaction, TransactionInput, TransactionOutput, UtxoSet, ValidationResult,
-    UTXO,
+    OutPoint, Transaction, TransactionInput, TransactionOutput, UtxoSet, ValidationResult, UTXO,
 };
 
 // Re-export commonly used modules
@@ -37,9 +39,7 @@ pub mod block {
 pub mod mining {
     pub use bllvm_consensus::mining::*;
 }
-pub mod pow {
-    pub use bllvm_consensus::pow::*;
-}
+pub mod pow;
 
 pub mod sigop {
     pub use bllvm_consensus::sigop::*;
@@ -56,18 +56,33 @@ pub mod network;
 pub mod types {
     pub use bllvm_consensus::types::*;
 }
-pub mod error {
-    pub use bllvm_consensus::error::*;
-}
+pub mod error;
+pub use error::{ProtocolError, Result};
 
 // Re-export feature and economic modules for convenience
-pub use economic::EconomicParameters;
-pub use features::{ActivationMethod, FeatureActivation, FeatureContext, FeatureRegistry};
+pub use economic::{CoinbaseBreakdown, EconomicParameters, EconomicSnapshot, FeeBucket};
+pub use features::{
+    ActivationMethod, FeatureActivation, FeatureContext, FeatureRegistry, FeatureState,
+    FeatureStatus, ScriptFlags,
+};
 
+#[cfg(any(test, feature = "test-util"))]
+pub mod chain_state;
+pub mod clock;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod crypto;
+pub mod difficulty;
 pub mod economic;
+pub mod encoding;
 pub mod features;
 pub mod genesis;
 pub mod network_params;
+pub mod scripts;
+pub mod signet;
+pub mod template;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testkit;
 pub mod validation;
 pub mod variants;
 
@@ -75,6 +90,7 @@ pub mod variants;
 pub mod address; // BIP173/350/351: Bech32/Bech32m address encoding
 pub mod bip157; // BIP157: Client-side block filtering network protocol
 pub mod bip158; // BIP158: Compact block filters
+pub mod bip37; // BIP37: Connection bloom filtering
 pub mod payment; // BIP70: Payment protocol (P2P variant)
 
 /// Bitcoin Protocol Engine
@@ -85,6 +101,9 @@ pub struct BitcoinProtocolEngine {
     consensus: ConsensusProof,
     protocol_version: ProtocolVersion,
     network_params: NetworkParameters,
+    variant: variants::ProtocolVariant,
+    signet: Option<signet::SignetParams>,
+    clock: Arc<dyn clock::Clock>,
 }
 
 /// Bitcoin protocol versions
@@ -96,6 +115,96 @@ pub enum ProtocolVersion {
     Testnet3,
     /// Regression test network protocol
     Regtest,
+    /// Signet: testnet rules with blocks signed by the network's challenge
+    ///
+    /// `BitcoinProtocolEngine::new` gives the default signet; custom signets
+    /// are created with `BitcoinProtocolEngine::new_signet`.
+    Signet,
+    /// Simulation network: regtest rules with proof-of-work checks skipped
+    ///
+    /// Lets test harnesses produce valid blocks instantly with any nonce.
+    /// Test-only; never use it for anything holding real value.
+    Simnet,
+    /// Minimal Bitcoin for learning: every feature active from genesis,
+    /// trivial proof-of-work and a subsidy halving every 10 blocks
+    ///
+    /// The parameters are deliberately unrealistic so a whole emission
+    /// schedule fits in a classroom exercise. Never use it for real value.
+    Educational,
+}
+
+impl ProtocolVersion {
+    /// Every supported protocol version
+    pub fn all() -> &'static [ProtocolVersion] {
+        &[
+            ProtocolVersion::BitcoinV1,
+            ProtocolVersion::Testnet3,
+            ProtocolVersion::Regtest,
+            ProtocolVersion::Signet,
+            ProtocolVersion::Simnet,
+            ProtocolVersion::Educational,
+        ]
+    }
+
+    /// Canonical network name
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtocolVersion::BitcoinV1 => "mainnet",
+            ProtocolVersion::Testnet3 => "testnet",
+            ProtocolVersion::Regtest => "regtest",
+            ProtocolVersion::Signet => "signet",
+            ProtocolVersion::Simnet => "simnet",
+            ProtocolVersion::Educational => "educational",
+        }
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = ProtocolError;
+
+    /// Parse a network name, case-insensitively
+    ///
+    /// Accepts the canonical names plus `bitcoin`/`main` for mainnet and
+    /// `testnet3`/`test` for testnet.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mainnet" | "main" | "bitcoin" => Ok(ProtocolVersion::BitcoinV1),
+            "testnet" | "testnet3" | "test" => Ok(ProtocolVersion::Testnet3),
+            "regtest" => Ok(ProtocolVersion::Regtest),
+            "signet" => Ok(ProtocolVersion::Signet),
+            "simnet" => Ok(ProtocolVersion::Simnet),
+            "educational" | "edu" => Ok(ProtocolVersion::Educational),
+            _ => Err(ProtocolError::UnknownNetwork(format!(
+                "'{s}' (expected mainnet, testnet, regtest, signet, simnet or educational)"
+            ))),
+        }
+    }
+}
+
+/// Every height-dependent rule for judging a block, captured at one height
+///
+/// Produced by `BitcoinProtocolEngine::consensus_params_at`; serializable so
+/// a historical validation can be reproduced exactly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsensusParams {
+    /// Network the parameters belong to
+    pub protocol_version: ProtocolVersion,
+    /// Feature activation state at this height and timestamp
+    pub features: FeatureContext,
+    /// Subsidy and supply at this height
+    pub economics: EconomicSnapshot,
+    /// Size, weight and policy limits
+    pub validation_rules: validation::ProtocolValidationRules,
+    /// Script verification flags derived from `features`
+    pub script_flags: ScriptFlags,
+    /// Whether the height starts a new difficulty adjustment period
+    pub retarget_boundary: bool,
 }
 
 /// Network parameters for different Bitcoin variants
@@ -122,14 +231,64 @@ impl BitcoinProtocolEngine {
     pub fn new(version: ProtocolVersion) -> Result<Self> {
         let consensus = ConsensusProof::new();
         let network_params = NetworkParameters::for_version(version)?;
+        let variant = variants::ProtocolVariant::for_version(version).ok_or_else(|| {
+            ConsensusError::ConsensusRuleViolation(format!("No variant for {version:?}"))
+        })?;
+
+        let signet = (version == ProtocolVersion::Signet).then(signet::SignetParams::default);
 
         Ok(BitcoinProtocolEngine {
             consensus,
             protocol_version: version,
             network_params,
+            variant,
+            signet,
+            clock: Arc::new(clock::SystemClock),
         })
     }
 
+    /// Create a protocol engine for a custom signet
+    ///
+    /// The challenge sets the network magic; the genesis block is shared by
+    /// every signet.
+    pub fn new_signet(params: signet::SignetParams) -> Result<Self> {
+        let network_params = params.network_params()?;
+        let mut engine = Self::new(ProtocolVersion::Signet)?;
+        engine.network_params = network_params;
+        engine.signet = Some(params);
+        Ok(engine)
+    }
+
+    /// Create a protocol engine for a custom network, e.g. one loaded from a
+    /// config file, using `version`'s rules
+    pub fn with_network_params(
+        version: ProtocolVersion,
+        params: NetworkParameters,
+    ) -> Result<Self> {
+        params.validate()?;
+        let mut engine = Self::new(version)?;
+        engine.network_params = params;
+        Ok(engine)
+    }
+
+    /// Create a protocol engine gated by a specific variant's capabilities
+    pub fn with_variant(variant: variants::ProtocolVariant) -> Result<Self> {
+        let mut engine = Self::new(variant.version)?;
+        engine.variant = variant;
+        Ok(engine)
+    }
+
+    /// Read the time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn clock::Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Clock used by time-dependent checks
+    pub fn clock(&self) -> &dyn clock::Clock {
+        self.clock.as_ref()
+    }
+
     /// Get the current protocol version
     pub fn get_protocol_version(&self) -> ProtocolVersion {
         self.protocol_version
@@ -140,6 +299,38 @@ impl BitcoinProtocolEngine {
         &self.network_params
     }
 
+    /// Signet parameters, for signet engines only
+    pub fn signet_params(&self) -> Option<&signet::SignetParams> {
+        self.signet.as_ref()
+    }
+
+    /// Get the protocol variant this engine was created for
+    pub fn get_variant(&self) -> &variants::ProtocolVariant {
+        &self.variant
+    }
+
+    /// Ensure this engine's variant allows mining operations
+    pub fn assert_mining_supported(&self) -> Result<()> {
+        if !self.variant.supports_mining {
+            return Err(ProtocolError::FeatureNotActive(format!(
+                "{} does not support mining",
+                self.variant.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Ensure this engine's variant allows wallet operations
+    pub fn assert_wallet_supported(&self) -> Result<()> {
+        if !self.variant.supports_wallet {
+            return Err(ProtocolError::FeatureNotActive(format!(
+                "{} does not support wallet operations",
+                self.variant.name
+            )));
+        }
+        Ok(())
+    }
+
     /// Validate a block using this protocol's rules
     pub fn validate_block(
         &self,
@@ -158,7 +349,45 @@ impl BitcoinProtocolEngine {
 
     /// Validate a transaction using this protocol's rules
     pub fn validate_transaction(&self, tx: &Transaction) -> Result<ValidationResult> {
-        self.consensus.validate_transaction(tx)
+        Ok(self.consensus.validate_transaction(tx)?)
+    }
+
+    /// Validate a transaction with only the features active in `ctx`
+    ///
+    /// `witnesses` holds the transaction's per-input witness stacks and
+    /// `utxos` the outputs it spends. Witness data needs `ctx.segwit` and
+    /// spending a P2TR output needs `ctx.taproot`. Creating witness-program
+    /// outputs is always allowed: before the soft fork they are merely
+    /// anyone-can-spend.
+    pub fn validate_transaction_at(
+        &self,
+        tx: &Transaction,
+        witnesses: &[encoding::WitnessStack],
+        utxos: &std::collections::HashMap<OutPoint, UTXO>,
+        ctx: &FeatureContext,
+    ) -> Result<ValidationResult> {
+        if !ctx.segwit && encoding::has_witness(witnesses) {
+            return Ok(ValidationResult::Invalid(format!(
+                "witness data requires segwit, not active at height {}",
+                ctx.height
+            )));
+        }
+        if !ctx.taproot && !validation::is_coinbase(tx) {
+            for (index, input) in tx.inputs.iter().enumerate() {
+                let Some(spent) = utxos.get(&input.prevout) else {
+                    return Ok(ValidationResult::Invalid(format!("missing input {index}")));
+                };
+                if validation::classify_script(&spent.script_pubkey) == validation::ScriptType::P2TR
+                {
+                    return Ok(ValidationResult::Invalid(format!(
+                        "input {index} spends a P2TR output, which requires taproot, \
+                         not active at height {}",
+                        ctx.height
+                    )));
+                }
+            }
+        }
+        self.validate_transaction(tx)
     }
 
     /// Check if this protocol supports a specific feature
@@ -176,6 +405,21 @@ impl BitcoinProtocolEngine {
                     "segwit" | "taproot" | "rbf" | "ctv" | "fast_mining"
                 )
             }
+            ProtocolVersion::Signet => {
+                matches!(feature, "segwit" | "taproot" | "rbf" | "ctv")
+            }
+            ProtocolVersion::Simnet => {
+                matches!(
+                    feature,
+                    "segwit" | "taproot" | "rbf" | "ctv" | "fast_mining" | "skip_pow"
+                )
+            }
+            ProtocolVersion::Educational => {
+                matches!(
+                    feature,
+                    "segwit" | "taproot" | "rbf" | "ctv" | "fast_mining"
+                )
+            }
         }
     }
 
@@ -190,6 +434,22 @@ impl BitcoinProtocolEngine {
         economic::EconomicParameters::for_protocol(self.protocol_version)
     }
 
+    /// Emission state (subsidy, supply, next halving) at a block height
+    pub fn economic_snapshot(&self, height: u64) -> economic::EconomicSnapshot {
+        self.get_economic_parameters().snapshot(height)
+    }
+
+    /// Whether a mempool entry added at `entry_time` has expired by `now`
+    ///
+    /// Both times are Unix seconds. Entries never expire when the network's
+    /// `mempool_expiry_hours` is 0.
+    pub fn is_expired(&self, entry_time: u64, now: u64) -> bool {
+        match self.get_economic_parameters().mempool_expiry_hours {
+            0 => false,
+            hours => now.saturating_sub(entry_time) > hours.saturating_mul(60 * 60),
+        }
+    }
+
     /// Get feature activation registry for this protocol
     pub fn get_feature_registry(&self) -> features::FeatureRegistry {
         features::FeatureRegistry::for_protocol(self.protocol_version)
@@ -201,6 +461,72 @@ impl BitcoinProtocolEngine {
         let registry = features::FeatureRegistry::for_protocol(self.protocol_version);
         registry.create_context(height, timestamp)
     }
+
+    /// Status of every feature at `height` and `timestamp`, for display
+    pub fn feature_status_table(&self, height: u64, timestamp: u64) -> Vec<FeatureStatus> {
+        self.get_feature_registry().status_table(height, timestamp)
+    }
+
+    /// Snapshot every rule that applies to a block at `height` and `timestamp`
+    pub fn consensus_params_at(&self, height: u64, timestamp: u64) -> ConsensusParams {
+        let features = self.feature_context(height, timestamp);
+        ConsensusParams {
+            protocol_version: self.protocol_version,
+            script_flags: features.script_flags(),
+            features,
+            economics: self.economic_snapshot(height),
+            validation_rules: validation::ProtocolValidationRules::for_protocol(
+                self.protocol_version,
+            ),
+            retarget_boundary: self.is_retarget_boundary(height),
+        }
+    }
+
+    /// Split a block's coinbase into subsidy and fees
+    ///
+    /// `total_input_value` is the summed value of every input spent by the
+    /// block's non-coinbase transactions. Negative output values count as
+    /// zero, and a coinbase claiming more than allowed reports `burned == 0`.
+    pub fn coinbase_breakdown(
+        &self,
+        block: &Block,
+        height: u64,
+        total_input_value: u64,
+    ) -> CoinbaseBreakdown {
+        let output_total = |tx: &Transaction| -> u64 {
+            tx.outputs
+                .iter()
+                .map(|output| output.value.max(0) as u64)
+                .fold(0u64, u64::saturating_add)
+        };
+        let (coinbase, rest) = match block.transactions.split_first() {
+            Some((coinbase, rest)) => (Some(coinbase), rest),
+            None => (None, &[][..]),
+        };
+
+        let subsidy = self.get_economic_parameters().get_block_subsidy(height);
+        let total_fees = total_input_value
+            .saturating_sub(rest.iter().map(output_total).fold(0, u64::saturating_add));
+        let total_coinbase_out = coinbase.map_or(0, output_total);
+
+        CoinbaseBreakdown {
+            subsidy,
+            total_fees,
+            total_coinbase_out,
+            burned: subsidy
+                .saturating_add(total_fees)
+                .saturating_sub(total_coinbase_out),
+        }
+    }
+
+    /// Whether a chain tip has at least this network's minimum chain work
+    ///
+    /// A syncing node should not trust a chain below this, however long.
+    pub fn meets_min_chain_work(&self, tip_chain_work: &difficulty::ChainWork) -> bool {
+        network_params::NetworkConstants::for_version(self.protocol_version).is_ok_and(
+            |constants| *tip_chain_work >= difficulty::ChainWork(constants.min_chain_work),
+        )
+    }
 }
 
 impl NetworkParameters {
@@ -210,6 +536,9 @@ impl NetworkParameters {
             ProtocolVersion::BitcoinV1 => Self::mainnet(),
             ProtocolVersion::Testnet3 => Self::testnet(),
             ProtocolVersion::Regtest => Self::regtest(),
+            ProtocolVersion::Signet => signet::SignetParams::default().network_params(),
+            ProtocolVersion::Simnet => Self::simnet(),
+            ProtocolVersion::Educational => Self::educational(),
         }
     }
 
@@ -251,6 +580,53 @@ impl NetworkParameters {
             is_testnet: true,
         })
     }
+
+    /// Simnet parameters: regtest's genesis and target, without proof-of-work
+    pub fn simnet() -> Result<Self> {
+        Ok(NetworkParameters {
+            magic_bytes: [0x16, 0x1c, 0x14, 0x12], // btcd simnet magic
+            default_port: 18555,
+            genesis_block: genesis::regtest_genesis(),
+            max_target: 0x207fffff,
+            halving_interval: 150,
+            network_name: "simnet".to_string(),
+            is_testnet: true,
+        })
+    }
+
+    /// Educational parameters: regtest's genesis and target, halving every 10 blocks
+    pub fn educational() -> Result<Self> {
+        Ok(NetworkParameters {
+            magic_bytes: [0xed, 0x0c, 0xa7, 0x10],
+            default_port: 18777,
+            genesis_block: genesis::regtest_genesis(),
+            max_target: 0x207fffff,
+            halving_interval: 10,
+            network_name: "educational".to_string(),
+            is_testnet: true,
+        })
+    }
+
+    /// Check that the parameters are usable, e.g. after loading a config file
+    ///
+    /// Besides the name, halving and target checks of
+    /// `NetworkConstants::validate`, the genesis block must start with a
+    /// coinbase and have no parent.
+    pub fn validate(&self) -> Result<()> {
+        network_params::check_common(&self.network_name, self.halving_interval, self.max_target)?;
+        let genesis = &self.genesis_block;
+        if !genesis
+            .transactions
+            .first()
+            .is_some_and(validation::is_coinbase)
+        {
+            return Err(network_params::invalid("genesis block has no coinbase"));
+        }
+        if genesis.header.prev_block_hash != [0u8; 32] {
+            return Err(network_params::invalid("genesis block has a parent"));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -266,6 +642,21 @@ mod tests {
         assert_eq!(engine.get_network_params().network_name, "mainnet");
     }
 
+    #[test]
+    fn test_meets_min_chain_work() {
+        let mainnet = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let min_work = 0x52b2559353df4117b7348b64u128;
+        let work = |value: u128| difficulty::ChainWork::from(value);
+        assert!(mainnet.meets_min_chain_work(&work(min_work)));
+        assert!(mainnet.meets_min_chain_work(&work(min_work + 1)));
+        assert!(!mainnet.meets_min_chain_work(&work(min_work - 1)));
+        assert!(!mainnet.meets_min_chain_work(&difficulty::ChainWork::ZERO));
+
+        // Regtest requires no work at all
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        assert!(regtest.meets_min_chain_work(&difficulty::ChainWork::ZERO));
+    }
+
     #[test]
     fn test_bllvm_protocol_creation_all_variants() {
         // Test mainnet
@@ -287,6 +678,82 @@ mod tests {
         assert!(regtest.get_network_params().is_testnet);
     }
 
+    #[test]
+    fn test_educational_network() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Educational).unwrap();
+        assert_eq!(engine.get_network_params().network_name, "educational");
+        assert_eq!(engine.get_network_params().max_target, 0x207fffff);
+        assert!(!engine.get_variant().is_production_ready());
+        assert_eq!(
+            "edu".parse::<ProtocolVersion>().unwrap(),
+            ProtocolVersion::Educational
+        );
+
+        // Halves at block 10
+        let economics = engine.get_economic_parameters();
+        assert_eq!(economics.get_block_subsidy(9), 50_0000_0000);
+        assert_eq!(economics.get_block_subsidy(10), 25_0000_0000);
+        assert_eq!(engine.economic_snapshot(0).next_halving_height, Some(10));
+
+        // Every feature but BIP34 is active at genesis
+        let registry = engine.get_feature_registry();
+        for feature in &registry.features {
+            let active = registry.is_feature_active(&feature.feature_name, 0, 0);
+            assert_eq!(
+                active,
+                feature.feature_name != "bip34",
+                "{}",
+                feature.feature_name
+            );
+        }
+        for feature in ["segwit", "taproot", "csv", "cltv", "rbf"] {
+            assert!(engine.is_feature_active(feature, 0, 0), "{feature}");
+        }
+    }
+
+    #[test]
+    fn test_protocol_version_from_str() {
+        for name in ["mainnet", "main", "bitcoin", "MainNet", " BITCOIN "] {
+            assert_eq!(
+                name.parse::<ProtocolVersion>().unwrap(),
+                ProtocolVersion::BitcoinV1
+            );
+        }
+        for name in ["testnet", "testnet3", "test", "TESTNET"] {
+            assert_eq!(
+                name.parse::<ProtocolVersion>().unwrap(),
+                ProtocolVersion::Testnet3
+            );
+        }
+        assert_eq!(
+            "RegTest".parse::<ProtocolVersion>().unwrap(),
+            ProtocolVersion::Regtest
+        );
+
+        let err = "litecoin".parse::<ProtocolVersion>().unwrap_err();
+        assert!(matches!(err, ProtocolError::UnknownNetwork(_)));
+        assert!(err.to_string().contains("litecoin"), "{err}");
+        assert_eq!(
+            "signet".parse::<ProtocolVersion>().unwrap(),
+            ProtocolVersion::Signet
+        );
+    }
+
+    #[test]
+    fn test_protocol_version_display_round_trips() {
+        assert_eq!(ProtocolVersion::all().len(), 6);
+        for version in ProtocolVersion::all() {
+            let name = version.to_string();
+            assert_eq!(name.parse::<ProtocolVersion>().unwrap(), *version);
+            assert_eq!(
+                name,
+                NetworkParameters::for_version(*version)
+                    .unwrap()
+                    .network_name
+            );
+        }
+    }
+
     #[test]
     fn test_network_parameters() {
         let mainnet = NetworkParameters::mainnet().unwrap();
@@ -415,6 +882,151 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_consensus_params_snapshot() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+
+        // Segwit era, before taproot: after the second halving (12.5 BTC)
+        let params = engine.consensus_params_at(600_000, 1_570_000_000);
+        assert!(params.features.segwit);
+        assert!(!params.features.taproot);
+        assert!(!params.script_flags.contains(ScriptFlags::TAPROOT));
+        assert!(params.script_flags.contains(ScriptFlags::WITNESS));
+        assert_eq!(params.economics.current_subsidy, 12_5000_0000);
+        assert_eq!(params.economics.height, 600_000);
+        assert_eq!(params.validation_rules.max_block_weight, 4_000_000);
+        assert!(!params.retarget_boundary);
+
+        let params = engine.consensus_params_at(709_632, 1_637_000_000);
+        assert!(params.script_flags.contains(ScriptFlags::TAPROOT));
+        assert_eq!(params.economics.current_subsidy, 6_2500_0000);
+        assert!(params.retarget_boundary);
+
+        let json = serde_json::to_string(&params).unwrap();
+        let decoded: ConsensusParams = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, params);
+
+        // Genesis and non-retargeting networks are never boundaries
+        assert!(
+            !engine
+                .consensus_params_at(0, 1_231_006_505)
+                .retarget_boundary
+        );
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        assert!(
+            !regtest
+                .consensus_params_at(2016, 1_300_000_000)
+                .retarget_boundary
+        );
+    }
+
+    /// Spends [1; 32]:0, paying to a P2WPKH output
+    fn witness_spend() -> Transaction {
+        Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [1u8; 32],
+                    index: 0,
+                },
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 10_000,
+                script_pubkey: scripts::p2wpkh_script([7; 20]),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    fn spending_from(script_pubkey: Vec<u8>) -> HashMap<OutPoint, UTXO> {
+        HashMap::from([(
+            witness_spend().inputs[0].prevout.clone(),
+            UTXO {
+                value: 20_000,
+                script_pubkey,
+                height: 1,
+            },
+        )])
+    }
+
+    #[test]
+    fn test_validate_transaction_at_feature_context() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let tx = witness_spend();
+        let witness = vec![vec![vec![0x30; 71], vec![0x02; 33]]];
+        let from_p2wpkh = spending_from(scripts::p2wpkh_script([9; 20]));
+        let from_p2tr = spending_from(scripts::p2tr_script([9; 32]));
+        let is_invalid_for = |result: ValidationResult, feature: &str| match result {
+            ValidationResult::Invalid(reason) => reason.contains(&format!("requires {feature}")),
+            ValidationResult::Valid => false,
+        };
+
+        let pre_segwit = engine.feature_context(400_000, 1_450_000_000);
+        let result = engine
+            .validate_transaction_at(&tx, &witness, &from_p2wpkh, &pre_segwit)
+            .unwrap();
+        assert!(is_invalid_for(result, "segwit"));
+        // Creating a witness output needs no soft fork
+        assert_eq!(
+            engine
+                .validate_transaction_at(&tx, &[], &from_p2wpkh, &pre_segwit)
+                .unwrap(),
+            ValidationResult::Valid
+        );
+
+        let post_segwit = engine.feature_context(500_000, 1_510_000_000);
+        assert_eq!(
+            engine
+                .validate_transaction_at(&tx, &witness, &from_p2wpkh, &post_segwit)
+                .unwrap(),
+            ValidationResult::Valid
+        );
+        let result = engine
+            .validate_transaction_at(&tx, &[vec![vec![0x30; 64]]], &from_p2tr, &post_segwit)
+            .unwrap();
+        assert!(is_invalid_for(result, "taproot"));
+
+        let post_taproot = engine.feature_context(710_000, 1_640_000_000);
+        assert_eq!(
+            engine
+                .validate_transaction_at(&tx, &[vec![vec![0x30; 64]]], &from_p2tr, &post_taproot)
+                .unwrap(),
+            ValidationResult::Valid
+        );
+    }
+
+    #[test]
+    fn test_feature_override_disables_segwit() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let post_segwit = engine.feature_context(500_000, 1_510_000_000);
+        let witness = vec![vec![vec![0x30; 71], vec![0x02; 33]]];
+        let utxos = spending_from(scripts::p2wpkh_script([9; 20]));
+
+        let no_segwit = post_segwit.with_override("segwit", false).unwrap();
+        assert!(!no_segwit.segwit);
+        assert_eq!(
+            no_segwit.with_override("segwit", true).unwrap(),
+            post_segwit
+        );
+        let result = engine
+            .validate_transaction_at(&witness_spend(), &witness, &utxos, &no_segwit)
+            .unwrap();
+        assert!(
+            matches!(&result, ValidationResult::Invalid(reason) if reason.contains("requires segwit")),
+            "{result:?}"
+        );
+
+        // Taproot depends on segwit, whichever flag is overridden
+        let err = no_segwit.with_override("taproot", true).unwrap_err();
+        assert!(matches!(err, ProtocolError::InvalidConfig(_)), "{err}");
+        assert!(err.to_string().contains("without segwit"), "{err}");
+        let post_taproot = engine.feature_context(710_000, 1_640_000_000);
+        assert!(post_taproot.with_override("segwit", false).is_err());
+        assert!(post_taproot.with_override("mimblewimble", true).is_err());
+    }
+
     #[test]
     fn test_cross_protocol_validation() {
         let mainnet_engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
@@ -540,6 +1152,60 @@ mod tests {
         assert_eq!(params.get_block_subsidy(210_000), 25_0000_0000);
     }
 
+    #[test]
+    fn test_economic_snapshot() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let snapshot = engine.economic_snapshot(149);
+
+        assert_eq!(snapshot.current_subsidy, 50_0000_0000);
+        assert_eq!(snapshot.next_halving_height, Some(150));
+        assert_eq!(snapshot.blocks_until_halving, Some(1));
+    }
+
+    #[test]
+    fn test_mempool_expiry() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let now = 1_700_000_000;
+        let hours_ago = |hours: u64| now - hours * 60 * 60;
+
+        assert!(engine.is_expired(hours_ago(337), now));
+        assert!(!engine.is_expired(hours_ago(336), now));
+        assert!(!engine.is_expired(hours_ago(100), now));
+
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        assert!(!regtest.is_expired(hours_ago(337), now));
+        assert!(!regtest.is_expired(0, now));
+    }
+
+    #[test]
+    fn test_coinbase_breakdown_detects_underclaim() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let tx = |value: i64| Transaction {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![TransactionOutput {
+                value,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        let block = Block {
+            header: genesis::mainnet_genesis().header,
+            // Coinbase claims 6.25 BTC; the spend pays 10_000 sat in fees
+            transactions: vec![tx(6_2500_0000), tx(90_000)],
+        };
+
+        let breakdown = engine.coinbase_breakdown(&block, 840_000, 100_000);
+        assert_eq!(breakdown.subsidy, 3_1250_0000);
+        assert_eq!(breakdown.total_fees, 10_000);
+        assert_eq!(breakdown.total_coinbase_out, 6_2500_0000);
+        assert_eq!(breakdown.burned, 0);
+
+        let breakdown = engine.coinbase_breakdown(&block, 630_000, 100_000);
+        assert_eq!(breakdown.subsidy, 6_2500_0000);
+        assert_eq!(breakdown.burned, 10_000);
+    }
+
     #[test]
     fn test_feature_registry_access() {
         let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
@@ -553,4 +1219,29 @@ mod tests {
         assert!(features.contains(&"segwit".to_string()));
         assert!(features.contains(&"taproot".to_string()));
     }
+
+    #[test]
+    fn test_feature_status_table() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        // Block 600,000, October 2019
+        let table = engine.feature_status_table(600_000, 1_571_443_461);
+        let status = |name: &str| table.iter().find(|s| s.feature_name == name).unwrap();
+
+        assert_eq!(status("segwit").state, FeatureState::Active);
+        assert_eq!(status("segwit").bip_numbers, [141, 143, 144, 147]);
+        assert_eq!(
+            status("taproot").state,
+            FeatureState::Pending { at_height: 709_632 }
+        );
+        assert_eq!(status("ctv").state, FeatureState::Defined);
+        assert_eq!(status("ctv").activation_height, None);
+        assert_eq!(status("csv").activation_height, Some(0));
+        assert_eq!(table.len(), engine.get_feature_registry().features.len());
+
+        let json = serde_json::to_string(&table).unwrap();
+        assert!(
+            json.contains("\"Pending\":{\"at_height\":709632}"),
+            "{json}"
+        );
+    }
 }
@@ -4,10 +4,167 @@
 //! Protocol-specific limits and validation are handled here, with consensus
 //! validation delegated to the consensus layer.
 
+use crate::bip157::{
+    self, CFilter, Cfcheckpt, Cfheaders, FilterType, GetCfcheckpt, GetCfheaders, GetCfilters,
+};
+use crate::bip158::CompactBlockFilter;
+use crate::bip37::{self, BloomFilter};
+use crate::encoding::{self, WitnessStack};
 use crate::validation::ProtocolValidationContext;
-use crate::{BitcoinProtocolEngine, Result};
+use crate::{BitcoinProtocolEngine, ProtocolError, Result};
 use bllvm_consensus::types::UtxoSet;
 use bllvm_consensus::{Block, BlockHeader, Hash, Transaction, ValidationResult};
+use std::ops::BitOr;
+use std::time::{Duration, SystemTime};
+
+pub mod wire;
+pub use wire::MessageFramer;
+
+/// Highest P2P protocol version spoken by this crate
+pub const PROTOCOL_VERSION: u32 = 70016;
+
+/// Default minimum peer protocol version accepted during the handshake
+pub const MIN_SUPPORTED_VERSION: u32 = 70001;
+
+/// Maximum number of block hashes returned for a single getblocks request
+pub const MAX_GETBLOCKS_RESULTS: usize = 500;
+
+/// Maximum number of addresses carried by a single addr message
+pub const MAX_ADDR_TO_SEND: usize = 1000;
+
+/// Maximum number of items in a single inv or getdata message
+pub const MAX_INV_SIZE: usize = 50_000;
+
+/// Maximum number of headers carried by a single headers message
+pub const MAX_HEADERS_RESULTS: usize = 2000;
+
+/// Per-message-type size limits applied by `process_network_message`
+///
+/// `Default` matches Bitcoin Core; custom networks may tighten or relax them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageLimits {
+    /// Addresses in one addr message, and in our answer to getaddr
+    pub max_addr: usize,
+    /// Items in one inv message
+    pub max_inv: usize,
+    /// Items in one getdata message
+    pub max_getdata: usize,
+    /// Headers in one headers message, and in our answer to getheaders
+    pub max_headers: usize,
+    /// Block hashes in our answer to getblocks
+    pub max_getblocks_results: usize,
+    /// Bytes of one filteradd element (BIP37)
+    pub max_filter_add: usize,
+    /// Blocks covered by one getcfilters request (BIP157)
+    pub max_getcfilters: u64,
+    /// Blocks covered by one getcfheaders request (BIP157)
+    pub max_getcfheaders: u64,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_addr: MAX_ADDR_TO_SEND,
+            max_inv: MAX_INV_SIZE,
+            max_getdata: MAX_INV_SIZE,
+            max_headers: MAX_HEADERS_RESULTS,
+            max_getblocks_results: MAX_GETBLOCKS_RESULTS,
+            max_filter_add: bip37::MAX_FILTER_ADD_SIZE,
+            max_getcfilters: bip157::MAX_GETCFILTERS_SIZE,
+            max_getcfheaders: bip157::MAX_GETCFHEADERS_SIZE,
+        }
+    }
+}
+
+/// Sustained inventory items per second accepted from one peer
+pub const DEFAULT_INV_RATE: f64 = 1_000.0;
+
+/// Inventory items a peer may send at once; one full inv message fits
+pub const DEFAULT_INV_BURST: f64 = MAX_INV_SIZE as f64;
+
+/// Service bits advertised in version messages and network addresses
+///
+/// On the wire this is the plain `u64` carried by `services` fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ServiceFlags(u64);
+
+impl ServiceFlags {
+    /// No services
+    pub const NONE: ServiceFlags = ServiceFlags(0);
+    /// Serves the full block chain
+    pub const NODE_NETWORK: ServiceFlags = ServiceFlags(1);
+    /// BIP111 bloom-filtered connections
+    pub const NODE_BLOOM: ServiceFlags = ServiceFlags(1 << 2);
+    /// BIP144 witness data
+    pub const NODE_WITNESS: ServiceFlags = ServiceFlags(1 << 3);
+    /// BIP157 compact block filters
+    pub const NODE_COMPACT_FILTERS: ServiceFlags =
+        ServiceFlags(crate::bip157::NODE_COMPACT_FILTERS);
+    /// BIP159 recent blocks only
+    pub const NODE_NETWORK_LIMITED: ServiceFlags = ServiceFlags(1 << 10);
+
+    /// Check if every bit in `flags` is set
+    pub fn has(&self, flags: ServiceFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Set the bits in `flags`
+    pub fn insert(&mut self, flags: ServiceFlags) {
+        self.0 |= flags.0;
+    }
+
+    /// Clear the bits in `flags`
+    pub fn remove(&mut self, flags: ServiceFlags) {
+        self.0 &= !flags.0;
+    }
+
+    /// Raw wire value
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+}
+
+impl BitOr for ServiceFlags {
+    type Output = ServiceFlags;
+
+    fn bitor(self, rhs: ServiceFlags) -> ServiceFlags {
+        ServiceFlags(self.0 | rhs.0)
+    }
+}
+
+impl From<u64> for ServiceFlags {
+    fn from(bits: u64) -> Self {
+        ServiceFlags(bits)
+    }
+}
+
+impl From<ServiceFlags> for u64 {
+    fn from(flags: ServiceFlags) -> Self {
+        flags.0
+    }
+}
+
+/// Optional P2P protocol features gated on the negotiated version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolFeature {
+    /// BIP130 sendheaders announcements
+    SendHeaders,
+    /// BIP133 feefilter messages
+    FeeFilter,
+    /// BIP155 addrv2 messages
+    AddrV2,
+}
+
+impl ProtocolFeature {
+    /// Minimum negotiated protocol version required for this feature
+    pub fn min_version(self) -> u32 {
+        match self {
+            ProtocolFeature::SendHeaders => 70012,
+            ProtocolFeature::FeeFilter => 70013,
+            ProtocolFeature::AddrV2 => 70016,
+        }
+    }
+}
 
 /// NetworkMessage: Bitcoin P2P protocol message types
 ///
@@ -17,16 +174,47 @@ pub enum NetworkMessage {
     Version(VersionMessage),
     VerAck,
     Addr(AddrMessage),
+    GetAddr,
     Inv(InvMessage),
     GetData(GetDataMessage),
+    GetBlocks(GetBlocksMessage),
     GetHeaders(GetHeadersMessage),
     Headers(HeadersMessage),
     Block(Block),
     Tx(Transaction),
+    /// Block with each transaction's witness stacks (BIP144), sent as `block`
+    WitnessBlock {
+        block: Block,
+        witnesses: Vec<Vec<WitnessStack>>,
+    },
+    /// Transaction with its per-input witness stacks (BIP144), sent as `tx`
+    WitnessTx {
+        tx: Transaction,
+        witnesses: Vec<WitnessStack>,
+    },
     Ping(PingMessage),
     Pong(PongMessage),
     MemPool,
     FeeFilter(FeeFilterMessage),
+    /// BIP130: announce new blocks with headers instead of inv
+    SendHeaders,
+    /// BIP155: the sender understands addrv2 (sent before verack)
+    SendAddrV2,
+    FilterLoad(BloomFilter),
+    FilterAdd(FilterAddMessage),
+    FilterClear,
+    GetCFilters(GetCfilters),
+    CFilter(CFilter),
+    GetCFHeaders(GetCfheaders),
+    CFHeaders(Cfheaders),
+    GetCFCheckpt(GetCfcheckpt),
+    CFCheckpt(Cfcheckpt),
+    /// Command this crate does not understand, kept undecoded so it can be
+    /// ignored (peers may speak newer protocol versions)
+    Unknown {
+        command: String,
+        payload: Vec<u8>,
+    },
 }
 
 /// Version message for initial handshake
@@ -61,6 +249,14 @@ pub struct GetDataMessage {
     pub inventory: Vec<InventoryVector>,
 }
 
+/// GetBlocks message requesting an inventory of block hashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetBlocksMessage {
+    pub version: u32,
+    pub block_locator_hashes: Vec<Hash>,
+    pub hash_stop: Hash,
+}
+
 /// GetHeaders message requesting block headers
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GetHeadersMessage {
@@ -93,6 +289,12 @@ pub struct FeeFilterMessage {
     pub feerate: u64,
 }
 
+/// BIP37 filteradd message: add one element to the loaded filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterAddMessage {
+    pub data: Vec<u8>,
+}
+
 /// Network address structure
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NetworkAddress {
@@ -101,10 +303,71 @@ pub struct NetworkAddress {
     pub port: u16,
 }
 
+/// Flag set on inventory types asking for witness serialization (BIP144)
+pub const MSG_WITNESS_FLAG: u32 = 1 << 30;
+
+/// Type of object named by an inventory vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InvType {
+    /// Entry to be ignored
+    Error,
+    /// Transaction, served without witness data
+    Tx,
+    /// Block, served without witness data
+    Block,
+    /// BIP37 merkle block
+    FilteredBlock,
+    /// BIP152 compact block
+    CmpctBlock,
+    /// BIP144 transaction with witness data
+    WitnessTx,
+    /// BIP144 block with witness data
+    WitnessBlock,
+    /// Type number this crate does not know
+    Unknown(u32),
+}
+
+impl InvType {
+    /// Whether the object should be sent with its witness data
+    pub fn is_witness(self) -> bool {
+        matches!(self, InvType::WitnessTx | InvType::WitnessBlock)
+    }
+}
+
+impl From<u32> for InvType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => InvType::Error,
+            1 => InvType::Tx,
+            2 => InvType::Block,
+            3 => InvType::FilteredBlock,
+            4 => InvType::CmpctBlock,
+            v if v == MSG_WITNESS_FLAG | 1 => InvType::WitnessTx,
+            v if v == MSG_WITNESS_FLAG | 2 => InvType::WitnessBlock,
+            other => InvType::Unknown(other),
+        }
+    }
+}
+
+impl From<InvType> for u32 {
+    fn from(inv_type: InvType) -> Self {
+        match inv_type {
+            InvType::Error => 0,
+            InvType::Tx => 1,
+            InvType::Block => 2,
+            InvType::FilteredBlock => 3,
+            InvType::CmpctBlock => 4,
+            InvType::WitnessTx => MSG_WITNESS_FLAG | 1,
+            InvType::WitnessBlock => MSG_WITNESS_FLAG | 2,
+            InvType::Unknown(value) => value,
+        }
+    }
+}
+
 /// Inventory vector identifying objects
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InventoryVector {
-    pub inv_type: u32,
+    pub inv_type: InvType,
     pub hash: Hash,
 }
 
@@ -115,34 +378,123 @@ pub enum NetworkResponse {
     SendMessage(NetworkMessage),
     SendMessages(Vec<NetworkMessage>),
     Reject(String),
+    /// The block is an orphan: fetch this parent (its `prev_block_hash`) first
+    RequestParent(Hash),
 }
 
 /// Peer connection state
 #[derive(Debug, Clone)]
 pub struct PeerState {
     pub version: u32,
+    pub negotiated_version: u32,
+    pub min_supported_version: u32,
     pub services: u64,
     pub user_agent: String,
     pub start_height: i32,
     pub handshake_complete: bool,
     pub known_addresses: Vec<NetworkAddress>,
     pub ping_nonce: Option<u64>,
-    pub last_pong: Option<std::time::SystemTime>,
+    pub ping_sent: Option<SystemTime>,
+    pub last_pong: Option<SystemTime>,
+    pub last_rtt: Option<Duration>,
+    /// BIP133 feefilter sent by the peer, in sat/kvB
     pub min_fee_rate: Option<u64>,
+    /// BIP37 filter loaded by the peer, restricting which transactions we relay
+    pub bloom_filter: Option<BloomFilter>,
+    /// Inventory items the peer may still announce (token bucket, starts full)
+    pub inv_tokens: f64,
+    /// When `inv_tokens` was last refilled
+    pub last_refill: SystemTime,
+    /// Peer asked for block announcements via headers (BIP130)
+    pub prefers_headers: bool,
+    /// Peer sent sendaddrv2 during the handshake (BIP155)
+    pub addrv2: bool,
+    /// Fee rate we announce in a feefilter once the handshake completes
+    pub local_fee_filter: Option<u64>,
 }
 
 impl PeerState {
     pub fn new() -> Self {
         Self {
             version: 0,
+            negotiated_version: 0,
+            min_supported_version: MIN_SUPPORTED_VERSION,
             services: 0,
             user_agent: String::new(),
             start_height: 0,
             handshake_complete: false,
             known_addresses: Vec::new(),
             ping_nonce: None,
+            ping_sent: None,
             last_pong: None,
+            last_rtt: None,
             min_fee_rate: None,
+            bloom_filter: None,
+            inv_tokens: DEFAULT_INV_BURST,
+            last_refill: SystemTime::UNIX_EPOCH,
+            prefers_headers: false,
+            addrv2: false,
+            local_fee_filter: None,
+        }
+    }
+
+    /// Check if a protocol feature may be used with this peer
+    ///
+    /// Features are gated on the negotiated version, so this is always
+    /// false before a version message has been accepted.
+    pub fn supports(&self, feature: ProtocolFeature) -> bool {
+        self.negotiated_version >= feature.min_version()
+    }
+
+    /// Services advertised by the peer in its version message
+    pub fn service_flags(&self) -> ServiceFlags {
+        ServiceFlags::from(self.services)
+    }
+
+    /// Record an outgoing ping so the matching pong can be timed
+    pub fn send_ping(&mut self, nonce: u64, now: SystemTime) {
+        self.ping_nonce = Some(nonce);
+        self.ping_sent = Some(now);
+    }
+
+    /// Check if a ping has been outstanding for longer than `timeout`
+    ///
+    /// Returns false when no ping is outstanding. A node uses this to
+    /// disconnect peers that stop answering pings.
+    pub fn is_stale(&self, now: SystemTime, timeout: Duration) -> bool {
+        match (self.ping_nonce, self.ping_sent) {
+            (Some(_), Some(sent)) => now
+                .duration_since(sent)
+                .map(|elapsed| elapsed > timeout)
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Take `count` inventory tokens, refilling at `rate` per second up to `burst`
+    ///
+    /// Returns false, leaving the bucket untouched, when the peer has
+    /// announced more than the limit allows.
+    pub fn allow_inv(&mut self, count: usize, now: SystemTime, rate: f64, burst: f64) -> bool {
+        if let Ok(elapsed) = now.duration_since(self.last_refill) {
+            self.inv_tokens = (self.inv_tokens + elapsed.as_secs_f64() * rate).min(burst);
+            self.last_refill = now;
+        }
+        if count as f64 > self.inv_tokens {
+            return false;
+        }
+        self.inv_tokens -= count as f64;
+        true
+    }
+
+    /// Check if a transaction at this fee rate may be relayed to the peer
+    ///
+    /// False when the peer sent a feefilter above `fee_rate_sat_per_vbyte`.
+    /// The feefilter is in sat/kvB, so the fee rate is scaled to match.
+    pub fn should_relay_tx(&self, fee_rate_sat_per_vbyte: u64) -> bool {
+        match self.min_fee_rate {
+            Some(min_fee_rate) => fee_rate_sat_per_vbyte.saturating_mul(1000) >= min_fee_rate,
+            None => true,
         }
     }
 }
@@ -194,12 +546,58 @@ pub trait ChainStateAccess {
 
     /// Get all mempool transactions
     fn get_mempool_transactions(&self) -> Vec<Transaction>;
+
+    /// Fee rate (sat/vbyte) of a mempool transaction, if known
+    ///
+    /// Used to honour peers' feefilter. Transactions with an unknown fee rate
+    /// are always relayed.
+    fn get_mempool_fee_rate(&self, _tx: &Transaction) -> Option<u64> {
+        None
+    }
+
+    /// Height of a block in the active chain
+    fn get_block_height(&self, _hash: &Hash) -> Option<u64> {
+        None
+    }
+
+    /// Hash of the active-chain block at `height`
+    fn get_block_hash(&self, _height: u64) -> Option<Hash> {
+        None
+    }
+
+    /// Witness stacks of each transaction of a block, for witness getdata
+    ///
+    /// Without them, witness requests are answered with the stripped block.
+    fn get_block_witnesses(&self, _hash: &Hash) -> Option<Vec<Vec<WitnessStack>>> {
+        None
+    }
+
+    /// Per-input witness stacks of a transaction, for witness getdata
+    fn get_transaction_witnesses(&self, _txid: &Hash) -> Option<Vec<WitnessStack>> {
+        None
+    }
+
+    /// Precomputed BIP158 filter of a block, for serving BIP157 requests
+    fn get_block_filter(
+        &self,
+        _filter_type: FilterType,
+        _block_hash: &Hash,
+    ) -> Option<CompactBlockFilter> {
+        None
+    }
+
+    /// BIP157 filter header of a block
+    fn get_filter_header(&self, _filter_type: FilterType, _block_hash: &Hash) -> Option<Hash> {
+        None
+    }
 }
 
 /// Process incoming network message
 ///
 /// This function handles Bitcoin P2P protocol messages, applying protocol-specific
 /// limits and delegating consensus validation to the protocol engine.
+/// Until the version/verack handshake completes, every other message is
+/// rejected, as is a second version message.
 ///
 /// # Arguments
 ///
@@ -209,6 +607,7 @@ pub trait ChainStateAccess {
 /// * `chain_access` - Optional chain state access (node layer provides this)
 /// * `utxo_set` - Optional UTXO set for block validation
 /// * `height` - Optional block height for validation context
+/// * `limits` - Per-message-type size limits
 ///
 /// # Returns
 ///
@@ -220,56 +619,176 @@ pub fn process_network_message(
     chain_access: Option<&dyn ChainStateAccess>,
     utxo_set: Option<&UtxoSet>,
     height: Option<u64>,
+    limits: &MessageLimits,
 ) -> Result<NetworkResponse> {
+    // The handshake, and anything arriving before it completes, follows the
+    // same rules as `handshake_step`
+    if !peer_state.handshake_complete
+        || matches!(message, NetworkMessage::Version(_) | NetworkMessage::VerAck)
+    {
+        return match handshake_step(engine, peer_state, message) {
+            Ok(mut replies) => Ok(match replies.len() {
+                0 => NetworkResponse::Ok,
+                1 => NetworkResponse::SendMessage(replies.remove(0)),
+                _ => NetworkResponse::SendMessages(replies),
+            }),
+            Err(ProtocolError::InvalidNetworkMessage(reason)) => {
+                Ok(NetworkResponse::Reject(reason))
+            }
+            Err(err) => Err(err),
+        };
+    }
+
     match message {
-        NetworkMessage::Version(version) => process_version_message(version, peer_state),
-        NetworkMessage::VerAck => process_verack_message(peer_state),
-        NetworkMessage::Addr(addr) => process_addr_message(addr, peer_state),
-        NetworkMessage::Inv(inv) => process_inv_message(inv, chain_access),
-        NetworkMessage::GetData(getdata) => process_getdata_message(getdata, chain_access),
+        // Handled by `handshake_step` above
+        NetworkMessage::Version(_) | NetworkMessage::VerAck => Ok(NetworkResponse::Ok),
+        NetworkMessage::Addr(addr) => process_addr_message(addr, peer_state, limits),
+        NetworkMessage::GetAddr => process_getaddr_message(peer_state, limits),
+        NetworkMessage::Inv(inv) => {
+            process_inv_message(inv, peer_state, chain_access, limits, engine.clock().now())
+        }
+        NetworkMessage::GetData(getdata) => process_getdata_message(getdata, chain_access, limits),
+        NetworkMessage::GetBlocks(getblocks) => {
+            process_getblocks_message(getblocks, chain_access, limits)
+        }
         NetworkMessage::GetHeaders(getheaders) => {
-            process_getheaders_message(getheaders, chain_access)
+            process_getheaders_message(getheaders, chain_access, limits)
+        }
+        NetworkMessage::Headers(headers) => {
+            process_headers_message(engine, headers, height, limits)
+        }
+        NetworkMessage::Block(block) => {
+            process_block_message(engine, block, &[], chain_access, utxo_set, height)
+        }
+        NetworkMessage::WitnessBlock { block, witnesses } => {
+            process_block_message(engine, block, witnesses, chain_access, utxo_set, height)
+        }
+        NetworkMessage::Tx(tx) | NetworkMessage::WitnessTx { tx, .. } => {
+            process_tx_message(engine, tx, height)
         }
-        NetworkMessage::Headers(headers) => process_headers_message(headers),
-        NetworkMessage::Block(block) => process_block_message(engine, block, utxo_set, height),
-        NetworkMessage::Tx(tx) => process_tx_message(engine, tx, height),
         NetworkMessage::Ping(ping) => process_ping_message(ping, peer_state),
-        NetworkMessage::Pong(pong) => process_pong_message(pong, peer_state),
-        NetworkMessage::MemPool => process_mempool_message(chain_access),
+        NetworkMessage::Pong(pong) => process_pong_message(pong, peer_state, engine.clock().now()),
+        NetworkMessage::MemPool => process_mempool_message(chain_access, peer_state),
         NetworkMessage::FeeFilter(feefilter) => process_feefilter_message(feefilter, peer_state),
+        NetworkMessage::SendHeaders => {
+            peer_state.prefers_headers = true;
+            Ok(NetworkResponse::Ok)
+        }
+        // BIP155: only meaningful before verack (see `handshake_step`), ignored afterwards
+        NetworkMessage::SendAddrV2 => Ok(NetworkResponse::Ok),
+        NetworkMessage::FilterLoad(filter) => process_filterload_message(filter, peer_state),
+        NetworkMessage::FilterAdd(filteradd) => {
+            process_filteradd_message(filteradd, peer_state, limits)
+        }
+        NetworkMessage::FilterClear => {
+            peer_state.bloom_filter = None;
+            Ok(NetworkResponse::Ok)
+        }
+        NetworkMessage::GetCFilters(request) => {
+            process_getcfilters_message(request, chain_access, limits)
+        }
+        NetworkMessage::GetCFHeaders(request) => {
+            process_getcfheaders_message(request, chain_access, limits)
+        }
+        NetworkMessage::GetCFCheckpt(request) => {
+            process_getcfcheckpt_message(request, chain_access)
+        }
+        // Filter responses are consumed by the light-client layer
+        NetworkMessage::CFilter(_)
+        | NetworkMessage::CFHeaders(_)
+        | NetworkMessage::CFCheckpt(_) => Ok(NetworkResponse::Ok),
+        // Forward compatibility: unknown commands are ignored
+        NetworkMessage::Unknown { .. } => Ok(NetworkResponse::Ok),
     }
 }
 
-/// Process version message
-fn process_version_message(
-    version: &VersionMessage,
+/// Start a handshake by sending our version message
+///
+/// Feed every message the peer sends back through `handshake_step` and send
+/// whatever it returns, until `peer_state.handshake_complete` is set.
+pub fn initiate_handshake(our_version: VersionMessage) -> NetworkMessage {
+    NetworkMessage::Version(our_version)
+}
+
+/// Advance the version/verack handshake with one message from the peer
+///
+/// Returns the messages to send in reply: sendaddrv2 and verack for the
+/// peer's version, then sendheaders and feefilter (when
+/// `local_fee_filter` is set) once its verack completes the handshake,
+/// each only if the negotiated version supports it. Once SegWit is
+/// supported, peers without `NODE_WITNESS` are refused. After completion
+/// every message but version and verack is left to
+/// `process_network_message`, which delegates the handshake here.
+pub fn handshake_step(
+    engine: &BitcoinProtocolEngine,
     peer_state: &mut PeerState,
-) -> Result<NetworkResponse> {
-    // Validate version message
-    if version.version < 70001 {
-        return Ok(NetworkResponse::Reject("Version too old".to_string()));
+    incoming: &NetworkMessage,
+) -> Result<Vec<NetworkMessage>> {
+    let handshake_error = |reason: &str| Err(ProtocolError::InvalidNetworkMessage(reason.into()));
+
+    match incoming {
+        NetworkMessage::Version(_) if peer_state.version != 0 => {
+            handshake_error("duplicate version")
+        }
+        NetworkMessage::Version(version) => {
+            if version.version < peer_state.min_supported_version {
+                return handshake_error("Version too old");
+            }
+            // Peers that cannot serve witness data are useless once SegWit is required
+            if engine.supports_feature("segwit")
+                && !ServiceFlags::from(version.services).has(ServiceFlags::NODE_WITNESS)
+            {
+                return handshake_error("Peer does not support witness");
+            }
+            record_version(version, peer_state);
+            let mut replies = Vec::new();
+            if peer_state.supports(ProtocolFeature::AddrV2) {
+                replies.push(NetworkMessage::SendAddrV2);
+            }
+            replies.push(NetworkMessage::VerAck);
+            Ok(replies)
+        }
+        NetworkMessage::VerAck if peer_state.version != 0 && !peer_state.handshake_complete => {
+            peer_state.handshake_complete = true;
+            let mut replies = Vec::new();
+            if peer_state.supports(ProtocolFeature::SendHeaders) {
+                replies.push(NetworkMessage::SendHeaders);
+            }
+            if let Some(feerate) = peer_state.local_fee_filter {
+                if peer_state.supports(ProtocolFeature::FeeFilter) {
+                    replies.push(NetworkMessage::FeeFilter(FeeFilterMessage { feerate }));
+                }
+            }
+            Ok(replies)
+        }
+        NetworkMessage::SendAddrV2 if !peer_state.handshake_complete => {
+            peer_state.addrv2 = true;
+            Ok(Vec::new())
+        }
+        // Newer peers send their own negotiation messages before verack
+        NetworkMessage::Unknown { .. } => Ok(Vec::new()),
+        _ if !peer_state.handshake_complete => handshake_error("message before handshake"),
+        _ => Ok(Vec::new()),
     }
+}
 
-    // Update peer state
+/// Store the peer's version, downgrading to the highest version both sides speak
+fn record_version(version: &VersionMessage, peer_state: &mut PeerState) {
     peer_state.version = version.version;
+    peer_state.negotiated_version = PROTOCOL_VERSION.min(version.version);
     peer_state.services = version.services;
     peer_state.user_agent = version.user_agent.clone();
     peer_state.start_height = version.start_height;
-
-    // Send verack response
-    Ok(NetworkResponse::SendMessage(NetworkMessage::VerAck))
-}
-
-/// Process verack message
-fn process_verack_message(peer_state: &mut PeerState) -> Result<NetworkResponse> {
-    peer_state.handshake_complete = true;
-    Ok(NetworkResponse::Ok)
 }
 
 /// Process addr message
-fn process_addr_message(addr: &AddrMessage, peer_state: &mut PeerState) -> Result<NetworkResponse> {
+fn process_addr_message(
+    addr: &AddrMessage,
+    peer_state: &mut PeerState,
+    limits: &MessageLimits,
+) -> Result<NetworkResponse> {
     // Validate address count (protocol limit)
-    if addr.addresses.len() > 1000 {
+    if addr.addresses.len() > limits.max_addr {
         return Ok(NetworkResponse::Reject("Too many addresses".to_string()));
     }
 
@@ -279,18 +798,52 @@ fn process_addr_message(addr: &AddrMessage, peer_state: &mut PeerState) -> Resul
     Ok(NetworkResponse::Ok)
 }
 
-/// Process inv message
+/// Process getaddr message
+///
+/// Answers with up to `limits.max_addr` of the addresses we know.
+fn process_getaddr_message(
+    peer_state: &PeerState,
+    limits: &MessageLimits,
+) -> Result<NetworkResponse> {
+    if peer_state.known_addresses.is_empty() {
+        return Ok(NetworkResponse::Ok);
+    }
+
+    let addresses = peer_state
+        .known_addresses
+        .iter()
+        .take(limits.max_addr)
+        .cloned()
+        .collect();
+    Ok(NetworkResponse::SendMessage(NetworkMessage::Addr(
+        AddrMessage { addresses },
+    )))
+}
+
+/// Process inv message received at `now`
 fn process_inv_message(
     inv: &InvMessage,
+    peer_state: &mut PeerState,
     chain_access: Option<&dyn ChainStateAccess>,
+    limits: &MessageLimits,
+    now: SystemTime,
 ) -> Result<NetworkResponse> {
     // Validate inventory count (protocol limit)
-    if inv.inventory.len() > 50000 {
+    if inv.inventory.len() > limits.max_inv {
         return Ok(NetworkResponse::Reject(
             "Too many inventory items".to_string(),
         ));
     }
 
+    if !peer_state.allow_inv(
+        inv.inventory.len(),
+        now,
+        DEFAULT_INV_RATE,
+        DEFAULT_INV_BURST,
+    ) {
+        return Ok(NetworkResponse::Reject("inv rate exceeded".to_string()));
+    }
+
     // Check which items we need (if chain access provided)
     if let Some(chain) = chain_access {
         let mut needed_items = Vec::new();
@@ -316,9 +869,10 @@ fn process_inv_message(
 fn process_getdata_message(
     getdata: &GetDataMessage,
     chain_access: Option<&dyn ChainStateAccess>,
+    limits: &MessageLimits,
 ) -> Result<NetworkResponse> {
     // Validate request count (protocol limit)
-    if getdata.inventory.len() > 50000 {
+    if getdata.inventory.len() > limits.max_getdata {
         return Ok(NetworkResponse::Reject(
             "Too many getdata items".to_string(),
         ));
@@ -329,22 +883,33 @@ fn process_getdata_message(
         let mut responses = Vec::new();
         for item in &getdata.inventory {
             if let Some(obj) = chain.get_object(&item.hash) {
-                match item.inv_type {
-                    1 => {
-                        // MSG_TX
-                        if let Some(tx) = obj.as_transaction() {
-                            responses.push(NetworkMessage::Tx(tx.clone()));
-                        }
-                    }
-                    2 => {
-                        // MSG_BLOCK
-                        if let Some(block) = obj.as_block() {
-                            responses.push(NetworkMessage::Block(block.clone()));
-                        }
+                match (item.inv_type, obj) {
+                    (InvType::Tx | InvType::WitnessTx, ChainObject::Transaction(tx)) => {
+                        let witnesses = item
+                            .inv_type
+                            .is_witness()
+                            .then(|| chain.get_transaction_witnesses(&item.hash))
+                            .flatten()
+                            .filter(|witnesses| encoding::has_witness(witnesses));
+                        responses.push(match witnesses {
+                            Some(witnesses) => NetworkMessage::WitnessTx { tx, witnesses },
+                            None => NetworkMessage::Tx(tx),
+                        });
                     }
-                    _ => {
-                        // Unknown inventory type - skip
+                    (InvType::Block | InvType::WitnessBlock, ChainObject::Block(block)) => {
+                        let witnesses = item
+                            .inv_type
+                            .is_witness()
+                            .then(|| chain.get_block_witnesses(&item.hash))
+                            .flatten()
+                            .filter(|witnesses| witnesses.iter().any(|w| encoding::has_witness(w)));
+                        responses.push(match witnesses {
+                            Some(witnesses) => NetworkMessage::WitnessBlock { block, witnesses },
+                            None => NetworkMessage::Block(block),
+                        });
                     }
+                    // Filtered and compact blocks are not served
+                    _ => {}
                 }
             }
         }
@@ -357,15 +922,51 @@ fn process_getdata_message(
     Ok(NetworkResponse::Ok)
 }
 
+/// Process getblocks message
+///
+/// Answers with an inv of the blocks following the locator's fork point, up to
+/// (but not including) `hash_stop` and at most `limits.max_getblocks_results`
+/// entries.
+fn process_getblocks_message(
+    getblocks: &GetBlocksMessage,
+    chain_access: Option<&dyn ChainStateAccess>,
+    limits: &MessageLimits,
+) -> Result<NetworkResponse> {
+    if let Some(chain) = chain_access {
+        let inventory = chain
+            .get_headers_for_locator(&getblocks.block_locator_hashes, &getblocks.hash_stop)
+            .iter()
+            .map(encoding::block_hash)
+            .take_while(|hash| *hash != getblocks.hash_stop)
+            .take(limits.max_getblocks_results)
+            .map(|hash| InventoryVector {
+                inv_type: InvType::Block,
+                hash,
+            })
+            .collect();
+        return Ok(NetworkResponse::SendMessage(NetworkMessage::Inv(
+            InvMessage { inventory },
+        )));
+    }
+
+    Ok(NetworkResponse::Reject(
+        "Chain access not available".to_string(),
+    ))
+}
+
 /// Process getheaders message
+///
+/// Answers with at most `limits.max_headers` headers.
 fn process_getheaders_message(
     getheaders: &GetHeadersMessage,
     chain_access: Option<&dyn ChainStateAccess>,
+    limits: &MessageLimits,
 ) -> Result<NetworkResponse> {
     // Use chain access to find headers (if provided)
     if let Some(chain) = chain_access {
-        let headers =
+        let mut headers =
             chain.get_headers_for_locator(&getheaders.block_locator_hashes, &getheaders.hash_stop);
+        headers.truncate(limits.max_headers);
         return Ok(NetworkResponse::SendMessage(NetworkMessage::Headers(
             HeadersMessage { headers },
         )));
@@ -377,21 +978,36 @@ fn process_getheaders_message(
 }
 
 /// Process headers message
-fn process_headers_message(headers: &HeadersMessage) -> Result<NetworkResponse> {
+///
+/// `height` is taken as the height of the first header, or 0 when unknown.
+fn process_headers_message(
+    engine: &BitcoinProtocolEngine,
+    headers: &HeadersMessage,
+    height: Option<u64>,
+    limits: &MessageLimits,
+) -> Result<NetworkResponse> {
     // Validate header count (protocol limit)
-    if headers.headers.len() > 2000 {
+    if headers.headers.len() > limits.max_headers {
         return Ok(NetworkResponse::Reject("Too many headers".to_string()));
     }
 
-    // Header validation is consensus logic, not protocol
-    // Node layer will validate headers using consensus layer
+    // Cheap structural checks catch junk early; contextual header validation
+    // (difficulty, timestamps) is left to the node layer
+    if let Err(err) = engine.validate_header_chain(&headers.headers, height.unwrap_or(0)) {
+        return Ok(NetworkResponse::Reject(err.to_string()));
+    }
     Ok(NetworkResponse::Ok)
 }
 
 /// Process block message
+///
+/// `witnesses` holds each transaction's witness stacks, empty for a block
+/// received without them.
 fn process_block_message(
     engine: &BitcoinProtocolEngine,
     block: &Block,
+    witnesses: &[Vec<WitnessStack>],
+    chain_access: Option<&dyn ChainStateAccess>,
     utxo_set: Option<&UtxoSet>,
     height: Option<u64>,
 ) -> Result<NetworkResponse> {
@@ -400,10 +1016,18 @@ fn process_block_message(
         return Ok(NetworkResponse::Reject("Too many transactions".to_string()));
     }
 
+    // An orphan cannot be validated until its parent arrives
+    let parent = block.header.prev_block_hash;
+    if let Some(chain) = chain_access {
+        if parent != [0u8; 32] && !chain.has_object(&parent) {
+            return Ok(NetworkResponse::RequestParent(parent));
+        }
+    }
+
     // Delegate to consensus via protocol engine (requires utxo_set and height)
     if let (Some(utxos), Some(h)) = (utxo_set, height) {
         let context = ProtocolValidationContext::new(engine.get_protocol_version(), h)?;
-        let result = engine.validate_block_with_protocol(block, utxos, h, &context)?;
+        let result = engine.validate_block_with_witnesses(block, witnesses, utxos, h, &context)?;
 
         match result {
             ValidationResult::Valid => Ok(NetworkResponse::Ok),
@@ -446,26 +1070,64 @@ fn process_ping_message(
     Ok(NetworkResponse::SendMessage(pong))
 }
 
-/// Process pong message
-fn process_pong_message(pong: &PongMessage, peer_state: &mut PeerState) -> Result<NetworkResponse> {
+/// Process pong message received at `now`
+fn process_pong_message(
+    pong: &PongMessage,
+    peer_state: &mut PeerState,
+    now: SystemTime,
+) -> Result<NetworkResponse> {
     // Validate pong nonce matches our ping
     if peer_state.ping_nonce == Some(pong.nonce) {
+        peer_state.last_rtt = peer_state
+            .ping_sent
+            .and_then(|sent| now.duration_since(sent).ok());
         peer_state.ping_nonce = None;
-        peer_state.last_pong = Some(std::time::SystemTime::now());
+        peer_state.ping_sent = None;
+        peer_state.last_pong = Some(now);
     }
 
     Ok(NetworkResponse::Ok)
 }
 
+/// Build the inv announcing new transactions to a peer
+///
+/// `txs` holds txids with their fee rates in sat/vB. Transactions below the
+/// peer's feefilter (in sat/kvB) are left out, and the announcement is capped at
+/// `MAX_INV_SIZE` items; the rest wait for the next announcement.
+pub fn build_tx_inv(txs: &[(Hash, u64)], peer: &PeerState) -> InvMessage {
+    let inventory = txs
+        .iter()
+        .filter(|(_, fee_rate)| peer.should_relay_tx(*fee_rate))
+        .take(MAX_INV_SIZE)
+        .map(|(txid, _)| InventoryVector {
+            inv_type: InvType::Tx,
+            hash: *txid,
+        })
+        .collect();
+    InvMessage { inventory }
+}
+
 /// Process mempool message
-fn process_mempool_message(chain_access: Option<&dyn ChainStateAccess>) -> Result<NetworkResponse> {
-    // Send all mempool transactions (if chain access provided)
+fn process_mempool_message(
+    chain_access: Option<&dyn ChainStateAccess>,
+    peer_state: &PeerState,
+) -> Result<NetworkResponse> {
+    // Send mempool transactions that pass the peer's feefilter (if chain access provided)
     if let Some(chain) = chain_access {
         let mempool_txs = chain.get_mempool_transactions();
         let mut responses = Vec::new();
 
         for tx in mempool_txs {
-            responses.push(NetworkMessage::Tx(tx));
+            let relay = chain
+                .get_mempool_fee_rate(&tx)
+                .is_none_or(|fee_rate| peer_state.should_relay_tx(fee_rate))
+                && peer_state
+                    .bloom_filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches_tx(&tx));
+            if relay {
+                responses.push(NetworkMessage::Tx(tx));
+            }
         }
 
         if !responses.is_empty() {
@@ -484,3 +1146,1398 @@ fn process_feefilter_message(
     peer_state.min_fee_rate = Some(feefilter.feerate);
     Ok(NetworkResponse::Ok)
 }
+
+/// Process filterload message (BIP37)
+fn process_filterload_message(
+    filter: &BloomFilter,
+    peer_state: &mut PeerState,
+) -> Result<NetworkResponse> {
+    if !filter.is_within_limits() {
+        return Ok(NetworkResponse::Reject(
+            "Bloom filter too large".to_string(),
+        ));
+    }
+    peer_state.bloom_filter = Some(filter.clone());
+    Ok(NetworkResponse::Ok)
+}
+
+/// Process filteradd message (BIP37)
+fn process_filteradd_message(
+    filteradd: &FilterAddMessage,
+    peer_state: &mut PeerState,
+    limits: &MessageLimits,
+) -> Result<NetworkResponse> {
+    if filteradd.data.len() > limits.max_filter_add {
+        return Ok(NetworkResponse::Reject(
+            "Filter element too large".to_string(),
+        ));
+    }
+    match peer_state.bloom_filter.as_mut() {
+        Some(filter) => {
+            filter.insert(&filteradd.data);
+            Ok(NetworkResponse::Ok)
+        }
+        None => Ok(NetworkResponse::Reject("No filter loaded".to_string())),
+    }
+}
+
+/// Active-chain block hashes from `start_height` up to `stop_hash`
+///
+/// Fails with a reject reason if the stop block is unknown, the range is
+/// empty or spans more than `max_blocks` blocks.
+fn filter_request_range(
+    chain: &dyn ChainStateAccess,
+    start_height: u64,
+    stop_hash: &Hash,
+    max_blocks: u64,
+) -> std::result::Result<Vec<Hash>, String> {
+    let stop_height = chain
+        .get_block_height(stop_hash)
+        .ok_or("Unknown stop hash")?;
+    if start_height > stop_height {
+        return Err("Start height after stop hash".to_string());
+    }
+    if stop_height - start_height >= max_blocks {
+        return Err(format!("Request spans more than {max_blocks} blocks"));
+    }
+    (start_height..=stop_height)
+        .map(|height| {
+            chain
+                .get_block_hash(height)
+                .ok_or_else(|| format!("No block at height {height}"))
+        })
+        .collect()
+}
+
+/// Process getcfilters message: one cfilter per block in the range
+fn process_getcfilters_message(
+    request: &GetCfilters,
+    chain_access: Option<&dyn ChainStateAccess>,
+    limits: &MessageLimits,
+) -> Result<NetworkResponse> {
+    let Some(chain) = chain_access else {
+        return Ok(NetworkResponse::Reject(
+            "Chain access not available".to_string(),
+        ));
+    };
+    let block_hashes = match filter_request_range(
+        chain,
+        request.start_height as u64,
+        &request.stop_hash,
+        limits.max_getcfilters,
+    ) {
+        Ok(block_hashes) => block_hashes,
+        Err(reason) => return Ok(NetworkResponse::Reject(reason)),
+    };
+
+    let mut messages = Vec::with_capacity(block_hashes.len());
+    for block_hash in block_hashes {
+        let Some(filter) = chain.get_block_filter(request.filter_type, &block_hash) else {
+            return Ok(NetworkResponse::Reject("Filter not available".to_string()));
+        };
+        messages.push(NetworkMessage::CFilter(CFilter {
+            filter_type: request.filter_type,
+            block_hash,
+            filter,
+        }));
+    }
+    Ok(NetworkResponse::SendMessages(messages))
+}
+
+/// Process getcfheaders message: the filter hashes of the range, anchored to
+/// the filter header of the block before it
+fn process_getcfheaders_message(
+    request: &GetCfheaders,
+    chain_access: Option<&dyn ChainStateAccess>,
+    limits: &MessageLimits,
+) -> Result<NetworkResponse> {
+    let Some(chain) = chain_access else {
+        return Ok(NetworkResponse::Reject(
+            "Chain access not available".to_string(),
+        ));
+    };
+    let start_height = request.start_height as u64;
+    let block_hashes = match filter_request_range(
+        chain,
+        start_height,
+        &request.stop_hash,
+        limits.max_getcfheaders,
+    ) {
+        Ok(block_hashes) => block_hashes,
+        Err(reason) => return Ok(NetworkResponse::Reject(reason)),
+    };
+
+    let prev_filter_header = match start_height.checked_sub(1) {
+        None => Some([0u8; 32]),
+        Some(prev_height) => chain
+            .get_block_hash(prev_height)
+            .and_then(|hash| chain.get_filter_header(request.filter_type, &hash)),
+    };
+    let filter_hashes: Option<Vec<Hash>> = block_hashes
+        .iter()
+        .map(|hash| {
+            chain
+                .get_block_filter(request.filter_type, hash)
+                .map(|filter| bip157::filter_hash(&filter))
+        })
+        .collect();
+    match (prev_filter_header, filter_hashes) {
+        (Some(prev_filter_header), Some(filter_hashes)) => Ok(NetworkResponse::SendMessage(
+            NetworkMessage::CFHeaders(Cfheaders {
+                filter_type: request.filter_type,
+                stop_hash: request.stop_hash,
+                prev_filter_header,
+                filter_hashes,
+            }),
+        )),
+        _ => Ok(NetworkResponse::Reject("Filter not available".to_string())),
+    }
+}
+
+/// Process getcfcheckpt message: filter headers every `CFCHECKPT_INTERVAL`
+/// blocks up to the stop block
+fn process_getcfcheckpt_message(
+    request: &GetCfcheckpt,
+    chain_access: Option<&dyn ChainStateAccess>,
+) -> Result<NetworkResponse> {
+    let Some(chain) = chain_access else {
+        return Ok(NetworkResponse::Reject(
+            "Chain access not available".to_string(),
+        ));
+    };
+    let Some(stop_height) = chain.get_block_height(&request.stop_hash) else {
+        return Ok(NetworkResponse::Reject("Unknown stop hash".to_string()));
+    };
+
+    let filter_header_hashes: Option<Vec<Hash>> = (1..=stop_height / bip157::CFCHECKPT_INTERVAL)
+        .map(|i| {
+            chain
+                .get_block_hash(i * bip157::CFCHECKPT_INTERVAL)
+                .and_then(|hash| chain.get_filter_header(request.filter_type, &hash))
+        })
+        .collect();
+    let Some(filter_header_hashes) = filter_header_hashes else {
+        return Ok(NetworkResponse::Reject("Filter not available".to_string()));
+    };
+    Ok(NetworkResponse::SendMessage(NetworkMessage::CFCheckpt(
+        Cfcheckpt {
+            filter_type: request.filter_type,
+            stop_hash: request.stop_hash,
+            filter_header_hashes,
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use crate::ProtocolVersion;
+    use std::sync::Arc;
+
+    fn version_message(version: u32) -> VersionMessage {
+        let addr = NetworkAddress {
+            services: 0,
+            ip: [0u8; 16],
+            port: 8333,
+        };
+        VersionMessage {
+            version,
+            services: (ServiceFlags::NODE_NETWORK | ServiceFlags::NODE_WITNESS).into(),
+            timestamp: 1_700_000_000,
+            addr_recv: addr.clone(),
+            addr_from: addr,
+            nonce: 1,
+            user_agent: "/test:0.1.0/".to_string(),
+            start_height: 0,
+            relay: true,
+        }
+    }
+
+    /// Peer state after a completed version/verack exchange
+    fn connected_peer() -> PeerState {
+        let mut peer_state = PeerState::new();
+        peer_state.handshake_complete = true;
+        peer_state
+    }
+
+    #[test]
+    fn test_old_peer_version_rejected() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = PeerState::new();
+        let message = NetworkMessage::Version(version_message(60002));
+
+        let response = process_network_message(
+            &engine,
+            &message,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Reject(_)));
+        assert_eq!(peer_state.negotiated_version, 0);
+        assert!(!peer_state.supports(ProtocolFeature::SendHeaders));
+    }
+
+    #[test]
+    fn test_mid_version_peer_negotiation() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = PeerState::new();
+        let message = NetworkMessage::Version(version_message(70012));
+
+        let response = process_network_message(
+            &engine,
+            &message,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            response,
+            NetworkResponse::SendMessage(NetworkMessage::VerAck)
+        ));
+        assert_eq!(peer_state.negotiated_version, 70012);
+        assert!(peer_state.supports(ProtocolFeature::SendHeaders));
+        assert!(!peer_state.supports(ProtocolFeature::FeeFilter));
+        assert!(!peer_state.supports(ProtocolFeature::AddrV2));
+    }
+
+    #[test]
+    fn test_newer_peer_downgrades_to_our_version() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = PeerState::new();
+        let message = NetworkMessage::Version(version_message(PROTOCOL_VERSION + 100));
+
+        process_network_message(
+            &engine,
+            &message,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(peer_state.negotiated_version, PROTOCOL_VERSION);
+        assert!(peer_state.supports(ProtocolFeature::FeeFilter));
+    }
+
+    #[test]
+    fn test_timely_pong_records_rtt() {
+        let clock = Arc::new(MockClock::at_unix(1_700_000_000));
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest)
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut peer_state = connected_peer();
+        peer_state.send_ping(42, clock.now());
+        clock.advance(Duration::from_millis(250));
+
+        let pong = NetworkMessage::Pong(PongMessage { nonce: 42 });
+        let response = process_network_message(
+            &engine,
+            &pong,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Ok));
+
+        assert!(peer_state.ping_nonce.is_none());
+        assert_eq!(peer_state.last_pong, Some(clock.now()));
+        assert_eq!(peer_state.last_rtt, Some(Duration::from_millis(250)));
+        assert!(!peer_state.is_stale(clock.now(), Duration::from_secs(20 * 60)));
+    }
+
+    #[test]
+    fn test_overdue_ping_is_stale() {
+        let mut peer_state = PeerState::new();
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        peer_state.send_ping(7, sent);
+
+        let timeout = Duration::from_secs(20 * 60);
+        assert!(!peer_state.is_stale(sent + Duration::from_secs(60), timeout));
+        assert!(peer_state.is_stale(sent + timeout + Duration::from_secs(1), timeout));
+        assert!(peer_state.last_rtt.is_none());
+    }
+
+    #[test]
+    fn test_inv_burst_within_bucket_passes() {
+        let mut peer_state = PeerState::new();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert!(peer_state.allow_inv(60, start, 10.0, 100.0));
+        assert!(peer_state.allow_inv(40, start, 10.0, 100.0));
+        assert!(!peer_state.allow_inv(1, start, 10.0, 100.0));
+
+        // Refills at the configured rate, capped at the burst size
+        assert!(peer_state.allow_inv(10, start + Duration::from_secs(1), 10.0, 100.0));
+        let later = start + Duration::from_secs(3600);
+        assert!(!peer_state.allow_inv(101, later, 10.0, 100.0));
+        assert!(peer_state.allow_inv(100, later, 10.0, 100.0));
+    }
+
+    #[test]
+    fn test_disconnected_headers_rejected() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut peer_state = connected_peer();
+        let block = crate::genesis::mainnet_genesis();
+        let mut genesis = block.header.clone();
+        genesis.merkle_root = encoding::block_merkle_root(&block);
+
+        let single = NetworkMessage::Headers(HeadersMessage {
+            headers: vec![genesis.clone()],
+        });
+        let response = process_network_message(
+            &engine,
+            &single,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Ok));
+
+        let repeated = NetworkMessage::Headers(HeadersMessage {
+            headers: vec![genesis.clone(), genesis],
+        });
+        let response = process_network_message(
+            &engine,
+            &repeated,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Reject(reason) if reason.contains("header 1")));
+    }
+
+    #[test]
+    fn test_sustained_inv_flood_is_throttled() {
+        let mut peer_state = PeerState::new();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        // 100 items every 100ms against a 10/s rate: only the burst gets through
+        let allowed = (0..100)
+            .filter(|i| {
+                let now = start + Duration::from_millis(100 * i);
+                peer_state.allow_inv(100, now, 10.0, 500.0)
+            })
+            .count();
+        assert!(allowed <= 7, "{allowed} messages allowed");
+
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut peer_state = connected_peer();
+        let inv = NetworkMessage::Inv(InvMessage {
+            inventory: vec![
+                InventoryVector {
+                    inv_type: InvType::Tx,
+                    hash: [1; 32],
+                };
+                MAX_INV_SIZE
+            ],
+        });
+        let response = process_network_message(
+            &engine,
+            &inv,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Ok));
+        let response = process_network_message(
+            &engine,
+            &inv,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Reject(reason) if reason.contains("rate")));
+    }
+
+    #[test]
+    fn test_service_flag_composition() {
+        let mut flags = ServiceFlags::NODE_NETWORK | ServiceFlags::NODE_WITNESS;
+        assert_eq!(flags.bits(), 9);
+        assert!(flags.has(ServiceFlags::NODE_WITNESS));
+        assert!(flags.has(ServiceFlags::NODE_NETWORK | ServiceFlags::NODE_WITNESS));
+        assert!(!flags.has(ServiceFlags::NODE_BLOOM));
+
+        flags.insert(ServiceFlags::NODE_COMPACT_FILTERS);
+        flags.remove(ServiceFlags::NODE_NETWORK);
+        assert_eq!(u64::from(flags), 8 | 64);
+        assert_eq!(ServiceFlags::from(1024), ServiceFlags::NODE_NETWORK_LIMITED);
+        assert!(ServiceFlags::NONE.has(ServiceFlags::NONE));
+    }
+
+    #[test]
+    fn test_peer_without_witness_rejected() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut peer_state = PeerState::new();
+        let mut version = version_message(PROTOCOL_VERSION);
+        version.services = ServiceFlags::NODE_NETWORK.into();
+
+        let response = process_network_message(
+            &engine,
+            &NetworkMessage::Version(version),
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Reject(_)));
+        assert_eq!(peer_state.negotiated_version, 0);
+    }
+
+    #[test]
+    fn test_peer_with_witness_accepted() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut peer_state = PeerState::new();
+        let message = NetworkMessage::Version(version_message(PROTOCOL_VERSION));
+
+        process_network_message(
+            &engine,
+            &message,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(peer_state.service_flags().has(ServiceFlags::NODE_WITNESS));
+    }
+
+    /// Chain of `len` headers that answers every locator with the whole chain
+    struct HeaderChain {
+        headers: Vec<BlockHeader>,
+    }
+
+    impl HeaderChain {
+        fn new(len: u64) -> Self {
+            let headers = (0..len)
+                .map(|nonce| BlockHeader {
+                    version: 1,
+                    prev_block_hash: [0u8; 32],
+                    merkle_root: [0u8; 32],
+                    timestamp: 1_700_000_000,
+                    bits: 0x207fffff,
+                    nonce,
+                })
+                .collect();
+            Self { headers }
+        }
+    }
+
+    impl ChainStateAccess for HeaderChain {
+        fn has_object(&self, _hash: &Hash) -> bool {
+            false
+        }
+
+        fn get_object(&self, _hash: &Hash) -> Option<ChainObject> {
+            None
+        }
+
+        fn get_headers_for_locator(&self, _locator: &[Hash], _stop: &Hash) -> Vec<BlockHeader> {
+            self.headers.clone()
+        }
+
+        fn get_mempool_transactions(&self) -> Vec<Transaction> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_orphan_block_requests_parent() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = connected_peer();
+        let chain = HeaderChain::new(1);
+        let mut block = crate::genesis::regtest_genesis();
+        block.header.prev_block_hash = [0x5a; 32];
+
+        let message = NetworkMessage::Block(block);
+        let response = process_network_message(
+            &engine,
+            &message,
+            &mut peer_state,
+            Some(&chain),
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::RequestParent(parent) if parent == [0x5a; 32]));
+    }
+
+    fn getblocks_inventory(chain: &HeaderChain, hash_stop: Hash) -> Vec<InventoryVector> {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = connected_peer();
+        let message = NetworkMessage::GetBlocks(GetBlocksMessage {
+            version: PROTOCOL_VERSION,
+            block_locator_hashes: vec![[0u8; 32]],
+            hash_stop,
+        });
+
+        match process_network_message(
+            &engine,
+            &message,
+            &mut peer_state,
+            Some(chain),
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap()
+        {
+            NetworkResponse::SendMessage(NetworkMessage::Inv(inv)) => inv.inventory,
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_getblocks_stops_at_hash_stop() {
+        let chain = HeaderChain::new(20);
+        let stop = encoding::block_hash(&chain.headers[10]);
+
+        let inventory = getblocks_inventory(&chain, stop);
+        assert_eq!(inventory.len(), 10);
+        assert!(inventory.iter().all(|item| item.inv_type == InvType::Block));
+        assert_eq!(inventory[0].hash, encoding::block_hash(&chain.headers[0]));
+        assert_eq!(inventory[9].hash, encoding::block_hash(&chain.headers[9]));
+    }
+
+    #[test]
+    fn test_getblocks_caps_at_500() {
+        let chain = HeaderChain::new(600);
+
+        let inventory = getblocks_inventory(&chain, [0u8; 32]);
+        assert_eq!(inventory.len(), MAX_GETBLOCKS_RESULTS);
+    }
+
+    /// Mempool holding transactions tagged with their fee rate (in `lock_time`)
+    struct FeeRatedMempool {
+        transactions: Vec<Transaction>,
+    }
+
+    impl ChainStateAccess for FeeRatedMempool {
+        fn has_object(&self, _hash: &Hash) -> bool {
+            false
+        }
+
+        fn get_object(&self, _hash: &Hash) -> Option<ChainObject> {
+            None
+        }
+
+        fn get_headers_for_locator(&self, _locator: &[Hash], _stop: &Hash) -> Vec<BlockHeader> {
+            Vec::new()
+        }
+
+        fn get_mempool_transactions(&self) -> Vec<Transaction> {
+            self.transactions.clone()
+        }
+
+        fn get_mempool_fee_rate(&self, tx: &Transaction) -> Option<u64> {
+            Some(tx.lock_time)
+        }
+    }
+
+    #[test]
+    fn test_build_tx_inv_respects_feefilter() {
+        let txs: Vec<(Hash, u64)> = [1u64, 9, 10, 25]
+            .into_iter()
+            .map(|fee_rate| ([fee_rate as u8; 32], fee_rate))
+            .collect();
+
+        let mut peer_state = connected_peer();
+        assert_eq!(build_tx_inv(&txs, &peer_state).inventory.len(), 4);
+
+        peer_state.min_fee_rate = Some(10_000);
+        let inv = build_tx_inv(&txs, &peer_state);
+        let hashes: Vec<Hash> = inv.inventory.iter().map(|item| item.hash).collect();
+        assert_eq!(hashes, vec![[10; 32], [25; 32]]);
+
+        // Just above 9 sat/vB, so 9 sat/vB is filtered out
+        peer_state.min_fee_rate = Some(9_001);
+        assert_eq!(build_tx_inv(&txs, &peer_state).inventory.len(), 2);
+        peer_state.min_fee_rate = Some(10_000);
+        let inv = build_tx_inv(&txs, &peer_state);
+        assert!(inv
+            .inventory
+            .iter()
+            .all(|item| item.inv_type == InvType::Tx));
+
+        // Capped at the protocol limit
+        let many = vec![([7; 32], 50); MAX_INV_SIZE + 10];
+        assert_eq!(
+            build_tx_inv(&many, &peer_state).inventory.len(),
+            MAX_INV_SIZE
+        );
+    }
+
+    #[test]
+    fn test_mempool_respects_feefilter() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let fee_rated_tx = |fee_rate: u64| Transaction {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![],
+            lock_time: fee_rate,
+        };
+        let mempool = FeeRatedMempool {
+            transactions: vec![fee_rated_tx(2), fee_rated_tx(10)],
+        };
+
+        let mut peer_state = connected_peer();
+        let feefilter = NetworkMessage::FeeFilter(FeeFilterMessage { feerate: 5_000 });
+        process_network_message(
+            &engine,
+            &feefilter,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(!peer_state.should_relay_tx(2));
+        assert!(peer_state.should_relay_tx(5));
+
+        let response = process_network_message(
+            &engine,
+            &NetworkMessage::MemPool,
+            &mut peer_state,
+            Some(&mempool),
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        match response {
+            NetworkResponse::SendMessages(messages) => {
+                assert_eq!(messages, vec![NetworkMessage::Tx(fee_rated_tx(10))]);
+            }
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_mempool_respects_bloom_filter() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let paying_to = |byte: u8| Transaction {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![bllvm_consensus::types::TransactionOutput {
+                value: 1000,
+                script_pubkey: [vec![0x00, 0x14], vec![byte; 20]].concat(),
+            }],
+            lock_time: 0,
+        };
+        let mempool = FeeRatedMempool {
+            transactions: vec![paying_to(0xaa), paying_to(0xbb)],
+        };
+        let mut peer_state = connected_peer();
+        let mut run = |message: NetworkMessage| {
+            process_network_message(
+                &engine,
+                &message,
+                &mut peer_state,
+                Some(&mempool),
+                None,
+                None,
+                &MessageLimits::default(),
+            )
+            .unwrap()
+        };
+
+        let filter = BloomFilter::new(10, 0.000001, 0, bip37::BLOOM_UPDATE_NONE);
+        assert!(matches!(
+            run(NetworkMessage::FilterLoad(filter)),
+            NetworkResponse::Ok
+        ));
+        assert!(matches!(run(NetworkMessage::MemPool), NetworkResponse::Ok));
+
+        let filteradd = FilterAddMessage {
+            data: vec![0xbb; 20],
+        };
+        run(NetworkMessage::FilterAdd(filteradd));
+        match run(NetworkMessage::MemPool) {
+            NetworkResponse::SendMessages(messages) => {
+                assert_eq!(messages, vec![NetworkMessage::Tx(paying_to(0xbb))]);
+            }
+            other => panic!("unexpected response: {other:?}"),
+        }
+
+        // Clearing the filter relays everything again
+        run(NetworkMessage::FilterClear);
+        match run(NetworkMessage::MemPool) {
+            NetworkResponse::SendMessages(messages) => assert_eq!(messages.len(), 2),
+            other => panic!("unexpected response: {other:?}"),
+        }
+        assert!(matches!(
+            run(NetworkMessage::FilterAdd(FilterAddMessage {
+                data: vec![1]
+            })),
+            NetworkResponse::Reject(_)
+        ));
+    }
+
+    #[test]
+    fn test_oversized_bloom_filter_rejected() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = connected_peer();
+        let filter = BloomFilter {
+            data: vec![0; bip37::MAX_BLOOM_FILTER_SIZE + 1],
+            hash_funcs: 1,
+            tweak: 0,
+            flags: 0,
+        };
+
+        let response = process_network_message(
+            &engine,
+            &NetworkMessage::FilterLoad(filter),
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Reject(_)));
+        assert!(peer_state.bloom_filter.is_none());
+    }
+
+    #[test]
+    fn test_unknown_message_ignored() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let unknown = NetworkMessage::Unknown {
+            command: "wtxidrelay".to_string(),
+            payload: vec![],
+        };
+
+        // Tolerated mid-handshake, where newer peers negotiate extras
+        let mut peer_state = PeerState::new();
+        let version = NetworkMessage::Version(version_message(PROTOCOL_VERSION));
+        handshake_step(&engine, &mut peer_state, &version).unwrap();
+        assert!(handshake_step(&engine, &mut peer_state, &unknown)
+            .unwrap()
+            .is_empty());
+
+        let mut peer_state = connected_peer();
+        let response = process_network_message(
+            &engine,
+            &unknown,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Ok), "{response:?}");
+    }
+
+    #[test]
+    fn test_message_before_handshake_rejected() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = PeerState::new();
+        let ping = NetworkMessage::Ping(PingMessage { nonce: 1 });
+
+        let response = process_network_message(
+            &engine,
+            &ping,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        match response {
+            NetworkResponse::Reject(reason) => assert_eq!(reason, "message before handshake"),
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_verack_before_version_rejected() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = PeerState::new();
+        let mut process = |message: NetworkMessage| {
+            process_network_message(
+                &engine,
+                &message,
+                &mut peer_state,
+                None,
+                None,
+                None,
+                &MessageLimits::default(),
+            )
+            .unwrap()
+        };
+
+        for message in [
+            NetworkMessage::VerAck,
+            NetworkMessage::Ping(PingMessage { nonce: 1 }),
+        ] {
+            match process(message) {
+                NetworkResponse::Reject(reason) => assert_eq!(reason, "message before handshake"),
+                other => panic!("unexpected response: {other:?}"),
+            }
+        }
+        assert!(!peer_state.handshake_complete);
+    }
+
+    #[test]
+    fn test_handshake_then_ping_accepted() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = PeerState::new();
+        let mut process = |message: NetworkMessage| {
+            process_network_message(
+                &engine,
+                &message,
+                &mut peer_state,
+                None,
+                None,
+                None,
+                &MessageLimits::default(),
+            )
+            .unwrap()
+        };
+
+        // The same replies as `handshake_step`
+        let version = NetworkMessage::Version(version_message(PROTOCOL_VERSION));
+        match process(version.clone()) {
+            NetworkResponse::SendMessages(messages) => assert_eq!(
+                messages,
+                [NetworkMessage::SendAddrV2, NetworkMessage::VerAck]
+            ),
+            other => panic!("unexpected response: {other:?}"),
+        }
+        assert!(matches!(
+            process(NetworkMessage::VerAck),
+            NetworkResponse::SendMessage(NetworkMessage::SendHeaders)
+        ));
+        assert!(matches!(
+            process(NetworkMessage::Ping(PingMessage { nonce: 9 })),
+            NetworkResponse::SendMessage(NetworkMessage::Pong(PongMessage { nonce: 9 }))
+        ));
+        match process(version) {
+            NetworkResponse::Reject(reason) => assert_eq!(reason, "duplicate version"),
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_handshake_state_machine() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut alice = PeerState::new();
+        alice.local_fee_filter = Some(3);
+        let mut bob = PeerState::new();
+
+        // Each side's outbox starts with its version; deliver until both drain
+        let mut to_bob = vec![initiate_handshake(version_message(PROTOCOL_VERSION))];
+        let mut to_alice = vec![initiate_handshake(version_message(PROTOCOL_VERSION))];
+        let mut alice_sent = to_bob.clone();
+        while !to_bob.is_empty() || !to_alice.is_empty() {
+            for message in std::mem::take(&mut to_bob) {
+                to_alice.extend(handshake_step(&engine, &mut bob, &message).unwrap());
+            }
+            for message in std::mem::take(&mut to_alice) {
+                let replies = handshake_step(&engine, &mut alice, &message).unwrap();
+                alice_sent.extend(replies.clone());
+                to_bob.extend(replies);
+            }
+        }
+
+        assert!(alice.handshake_complete && bob.handshake_complete);
+        assert!(alice.addrv2 && bob.addrv2);
+        assert_eq!(
+            alice_sent[1..],
+            [
+                NetworkMessage::SendAddrV2,
+                NetworkMessage::VerAck,
+                NetworkMessage::SendHeaders,
+                NetworkMessage::FeeFilter(FeeFilterMessage { feerate: 3 }),
+            ]
+        );
+
+        // Below BIP155 there is no sendaddrv2, and a second version is refused
+        let mut peer_state = PeerState::new();
+        let version = NetworkMessage::Version(version_message(70015));
+        assert_eq!(
+            handshake_step(&engine, &mut peer_state, &version).unwrap(),
+            [NetworkMessage::VerAck]
+        );
+        assert!(handshake_step(&engine, &mut peer_state, &version).is_err());
+    }
+
+    #[test]
+    fn test_handshake_step_rejects_out_of_order() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = PeerState::new();
+        let err = handshake_step(&engine, &mut peer_state, &NetworkMessage::VerAck).unwrap_err();
+        assert!(err.to_string().contains("before handshake"), "{err}");
+        let err = handshake_step(&engine, &mut peer_state, &NetworkMessage::MemPool).unwrap_err();
+        assert!(err.to_string().contains("before handshake"), "{err}");
+        let old = NetworkMessage::Version(version_message(60000));
+        assert!(handshake_step(&engine, &mut peer_state, &old).is_err());
+        assert_eq!(peer_state.version, 0);
+    }
+
+    #[test]
+    fn test_peer_without_witness_refused() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut version = version_message(PROTOCOL_VERSION);
+        version.services = ServiceFlags::NODE_NETWORK.into();
+        let version = NetworkMessage::Version(version);
+
+        let mut peer_state = PeerState::new();
+        let err = handshake_step(&engine, &mut peer_state, &version).unwrap_err();
+        assert!(
+            err.to_string().contains("does not support witness"),
+            "{err}"
+        );
+
+        let response = process_network_message(
+            &engine,
+            &version,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        match response {
+            NetworkResponse::Reject(reason) => assert_eq!(reason, "Peer does not support witness"),
+            other => panic!("unexpected response: {other:?}"),
+        }
+        assert_eq!(peer_state.version, 0);
+    }
+
+    fn address(index: u16) -> NetworkAddress {
+        NetworkAddress {
+            services: ServiceFlags::NODE_NETWORK.into(),
+            ip: [0; 16],
+            port: index,
+        }
+    }
+
+    fn getaddr(peer_state: &mut PeerState) -> Vec<NetworkAddress> {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        match process_network_message(
+            &engine,
+            &NetworkMessage::GetAddr,
+            peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap()
+        {
+            NetworkResponse::SendMessage(NetworkMessage::Addr(addr)) => addr.addresses,
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_getaddr_returns_learned_addresses() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = connected_peer();
+        let learned: Vec<NetworkAddress> = (0..5).map(address).collect();
+        let addr = NetworkMessage::Addr(AddrMessage {
+            addresses: learned.clone(),
+        });
+        process_network_message(
+            &engine,
+            &addr,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+
+        assert_eq!(getaddr(&mut peer_state), learned);
+    }
+
+    #[test]
+    fn test_getaddr_caps_response() {
+        let mut peer_state = connected_peer();
+        peer_state.known_addresses = (0..1500).map(address).collect();
+
+        let addresses = getaddr(&mut peer_state);
+        assert_eq!(addresses.len(), MAX_ADDR_TO_SEND);
+        assert_eq!(addresses[0], address(0));
+    }
+
+    #[test]
+    fn test_message_limits_tighten_addr() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = connected_peer();
+        let limits = MessageLimits {
+            max_addr: 2,
+            ..MessageLimits::default()
+        };
+        let addr = NetworkMessage::Addr(AddrMessage {
+            addresses: (0..3).map(address).collect(),
+        });
+
+        let response =
+            process_network_message(&engine, &addr, &mut peer_state, None, None, None, &limits)
+                .unwrap();
+        assert!(
+            matches!(response, NetworkResponse::Reject(reason) if reason == "Too many addresses")
+        );
+        assert!(peer_state.known_addresses.is_empty());
+
+        // The default Bitcoin limits accept the same message
+        let response = process_network_message(
+            &engine,
+            &addr,
+            &mut peer_state,
+            None,
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        assert!(matches!(response, NetworkResponse::Ok));
+    }
+
+    /// Chain of `len` blocks, each with a one-byte filter
+    struct FilterChain {
+        len: u64,
+    }
+
+    impl FilterChain {
+        fn block_hash(height: u64) -> Hash {
+            let mut hash = [0xbb; 32];
+            hash[..8].copy_from_slice(&height.to_le_bytes());
+            hash
+        }
+
+        fn height_of(hash: &Hash) -> u64 {
+            u64::from_le_bytes(hash[..8].try_into().unwrap())
+        }
+    }
+
+    impl ChainStateAccess for FilterChain {
+        fn has_object(&self, _hash: &Hash) -> bool {
+            false
+        }
+
+        fn get_object(&self, _hash: &Hash) -> Option<ChainObject> {
+            None
+        }
+
+        fn get_headers_for_locator(&self, _locator: &[Hash], _stop: &Hash) -> Vec<BlockHeader> {
+            Vec::new()
+        }
+
+        fn get_mempool_transactions(&self) -> Vec<Transaction> {
+            Vec::new()
+        }
+
+        fn get_block_height(&self, hash: &Hash) -> Option<u64> {
+            let height = Self::height_of(hash);
+            (height < self.len && *hash == Self::block_hash(height)).then_some(height)
+        }
+
+        fn get_block_hash(&self, height: u64) -> Option<Hash> {
+            (height < self.len).then(|| Self::block_hash(height))
+        }
+
+        fn get_block_filter(
+            &self,
+            _filter_type: FilterType,
+            block_hash: &Hash,
+        ) -> Option<CompactBlockFilter> {
+            self.get_block_height(block_hash)
+                .map(|height| CompactBlockFilter {
+                    filter_data: vec![height as u8],
+                    num_elements: 1,
+                })
+        }
+
+        fn get_filter_header(&self, filter_type: FilterType, block_hash: &Hash) -> Option<Hash> {
+            let mut header = [0u8; 32];
+            for height in 0..=self.get_block_height(block_hash)? {
+                let filter = self.get_block_filter(filter_type, &Self::block_hash(height))?;
+                header = bip157::chain_filter_header(&bip157::filter_hash(&filter), &header);
+            }
+            Some(header)
+        }
+    }
+
+    fn serve(message: NetworkMessage, chain: &FilterChain) -> NetworkResponse {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = connected_peer();
+        process_network_message(
+            &engine,
+            &message,
+            &mut peer_state,
+            Some(chain),
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_serve_filter_range() {
+        let chain = FilterChain { len: 10 };
+        let response = serve(
+            NetworkMessage::GetCFilters(GetCfilters {
+                filter_type: FilterType::Basic,
+                start_height: 2,
+                stop_hash: FilterChain::block_hash(4),
+            }),
+            &chain,
+        );
+        let NetworkResponse::SendMessages(messages) = response else {
+            panic!("expected filters, got {response:?}");
+        };
+        let served: Vec<(Hash, Vec<u8>)> = messages
+            .into_iter()
+            .map(|message| match message {
+                NetworkMessage::CFilter(cfilter) => {
+                    (cfilter.block_hash, cfilter.filter.filter_data)
+                }
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            served,
+            (2..=4)
+                .map(|height| (FilterChain::block_hash(height), vec![height as u8]))
+                .collect::<Vec<_>>()
+        );
+
+        // The served hashes extend the previous header to the stop block's
+        let response = serve(
+            NetworkMessage::GetCFHeaders(GetCfheaders {
+                filter_type: FilterType::Basic,
+                start_height: 2,
+                stop_hash: FilterChain::block_hash(4),
+            }),
+            &chain,
+        );
+        let NetworkResponse::SendMessage(NetworkMessage::CFHeaders(cfheaders)) = response else {
+            panic!("expected cfheaders, got {response:?}");
+        };
+        assert_eq!(cfheaders.filter_hashes.len(), 3);
+        let tip = cfheaders
+            .filter_hashes
+            .iter()
+            .fold(cfheaders.prev_filter_header, |prev, hash| {
+                bip157::chain_filter_header(hash, &prev)
+            });
+        assert_eq!(
+            Some(tip),
+            chain.get_filter_header(FilterType::Basic, &FilterChain::block_hash(4))
+        );
+    }
+
+    #[test]
+    fn test_filter_requests_limited() {
+        let chain = FilterChain { len: 3000 };
+        let request = |start_height: u32, stop_height: u64| {
+            NetworkMessage::GetCFilters(GetCfilters {
+                filter_type: FilterType::Basic,
+                start_height,
+                stop_hash: FilterChain::block_hash(stop_height),
+            })
+        };
+
+        let response = serve(request(0, 1000), &chain);
+        assert!(
+            matches!(&response, NetworkResponse::Reject(reason) if reason.contains("more than 1000")),
+            "{response:?}"
+        );
+        let response = serve(request(1, 1000), &chain);
+        assert!(
+            matches!(&response, NetworkResponse::SendMessages(messages) if messages.len() == 1000)
+        );
+        let response = serve(request(5, 4), &chain);
+        assert!(matches!(response, NetworkResponse::Reject(_)));
+        let response = serve(request(0, 5000), &chain);
+        assert!(
+            matches!(&response, NetworkResponse::Reject(reason) if reason.contains("Unknown stop")),
+            "{response:?}"
+        );
+    }
+
+    #[test]
+    fn test_serve_filter_checkpoints() {
+        let chain = FilterChain { len: 2600 };
+        let response = serve(
+            NetworkMessage::GetCFCheckpt(GetCfcheckpt {
+                filter_type: FilterType::Basic,
+                stop_hash: FilterChain::block_hash(2500),
+            }),
+            &chain,
+        );
+        let NetworkResponse::SendMessage(NetworkMessage::CFCheckpt(cfcheckpt)) = response else {
+            panic!("expected cfcheckpt, got {response:?}");
+        };
+        let expected: Vec<Hash> = [1000, 2000]
+            .iter()
+            .map(|height| {
+                chain
+                    .get_filter_header(FilterType::Basic, &FilterChain::block_hash(*height))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(cfcheckpt.filter_header_hashes, expected);
+    }
+
+    /// Chain holding one block and one transaction, both with witness data
+    struct WitnessChain {
+        block: Block,
+        block_witnesses: Vec<Vec<WitnessStack>>,
+        tx: Transaction,
+        tx_witnesses: Vec<WitnessStack>,
+    }
+
+    impl WitnessChain {
+        fn new() -> Self {
+            let block = crate::genesis::regtest_genesis();
+            let tx = block.transactions[0].clone();
+            Self {
+                block,
+                block_witnesses: vec![vec![vec![vec![0; 32]]]],
+                tx,
+                tx_witnesses: vec![vec![vec![0x30; 71], vec![0x02; 33]]],
+            }
+        }
+    }
+
+    impl ChainStateAccess for WitnessChain {
+        fn has_object(&self, hash: &Hash) -> bool {
+            self.get_object(hash).is_some()
+        }
+
+        fn get_object(&self, hash: &Hash) -> Option<ChainObject> {
+            if *hash == encoding::block_hash(&self.block.header) {
+                Some(ChainObject::Block(self.block.clone()))
+            } else if *hash == encoding::txid(&self.tx) {
+                Some(ChainObject::Transaction(self.tx.clone()))
+            } else {
+                None
+            }
+        }
+
+        fn get_headers_for_locator(&self, _locator: &[Hash], _stop: &Hash) -> Vec<BlockHeader> {
+            Vec::new()
+        }
+
+        fn get_mempool_transactions(&self) -> Vec<Transaction> {
+            Vec::new()
+        }
+
+        fn get_block_witnesses(&self, _hash: &Hash) -> Option<Vec<Vec<WitnessStack>>> {
+            Some(self.block_witnesses.clone())
+        }
+
+        fn get_transaction_witnesses(&self, _txid: &Hash) -> Option<Vec<WitnessStack>> {
+            Some(self.tx_witnesses.clone())
+        }
+    }
+
+    #[test]
+    fn test_inv_type_numbers() {
+        for (inv_type, number) in [
+            (InvType::Error, 0),
+            (InvType::Tx, 1),
+            (InvType::Block, 2),
+            (InvType::FilteredBlock, 3),
+            (InvType::CmpctBlock, 4),
+            (InvType::WitnessTx, 0x4000_0001),
+            (InvType::WitnessBlock, 0x4000_0002),
+            (InvType::Unknown(5), 5),
+        ] {
+            assert_eq!(InvType::from(number), inv_type);
+            assert_eq!(u32::from(inv_type), number);
+        }
+    }
+
+    #[test]
+    fn test_getdata_serves_witness_by_inv_type() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut peer_state = connected_peer();
+        let chain = WitnessChain::new();
+        let block_hash = encoding::block_hash(&chain.block.header);
+        let txid = encoding::txid(&chain.tx);
+        let getdata = NetworkMessage::GetData(GetDataMessage {
+            inventory: [
+                (InvType::Block, block_hash),
+                (InvType::WitnessBlock, block_hash),
+                (InvType::Tx, txid),
+                (InvType::WitnessTx, txid),
+                (InvType::CmpctBlock, block_hash),
+            ]
+            .into_iter()
+            .map(|(inv_type, hash)| InventoryVector { inv_type, hash })
+            .collect(),
+        });
+
+        let response = process_network_message(
+            &engine,
+            &getdata,
+            &mut peer_state,
+            Some(&chain),
+            None,
+            None,
+            &MessageLimits::default(),
+        )
+        .unwrap();
+        let NetworkResponse::SendMessages(messages) = response else {
+            panic!("unexpected response: {response:?}");
+        };
+        assert_eq!(
+            messages,
+            vec![
+                NetworkMessage::Block(chain.block.clone()),
+                NetworkMessage::WitnessBlock {
+                    block: chain.block.clone(),
+                    witnesses: chain.block_witnesses.clone(),
+                },
+                NetworkMessage::Tx(chain.tx.clone()),
+                NetworkMessage::WitnessTx {
+                    tx: chain.tx.clone(),
+                    witnesses: chain.tx_witnesses.clone(),
+                },
+            ]
+        );
+
+        // Only the witness block carries the BIP144 marker on the wire
+        let stripped = wire::serialize_payload(&messages[0]);
+        let witness = wire::serialize_payload(&messages[1]);
+        assert_eq!(stripped, encoding::serialize_block(&chain.block));
+        assert!(witness.len() > stripped.len());
+    }
+}
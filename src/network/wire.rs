@@ -0,0 +1,754 @@
+//! P2P wire format
+//!
+//! Encoding of `NetworkMessage` payloads and the 24-byte message header
+//! (magic, command, length, checksum), plus `MessageFramer` for pulling
+//! complete messages out of a byte stream.
+//!
+//! `block` and `tx` payloads carrying witness data decode to
+//! `NetworkMessage::WitnessBlock` and `NetworkMessage::WitnessTx`, and those
+//! encode in the BIP144 format; payloads without any decode to the plain
+//! variants.
+
+use super::{
+    AddrMessage, FeeFilterMessage, FilterAddMessage, GetBlocksMessage, GetDataMessage,
+    GetHeadersMessage, HeadersMessage, InvMessage, InvType, InventoryVector, NetworkAddress,
+    NetworkMessage, PingMessage, PongMessage, VersionMessage,
+};
+use crate::bip157::{
+    CFilter, Cfcheckpt, Cfheaders, FilterType, GetCfcheckpt, GetCfheaders, GetCfilters,
+};
+use crate::bip158::CompactBlockFilter;
+use crate::bip37::BloomFilter;
+use crate::crypto;
+use crate::encoding::{self, Reader};
+use crate::{Block, Hash, ProtocolError, Result};
+
+/// Size of the message header: magic, command, payload length, checksum
+pub const MESSAGE_HEADER_SIZE: usize = 24;
+
+/// Largest payload accepted from a peer (32 MiB)
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+impl NetworkMessage {
+    /// Command string identifying this message on the wire
+    pub fn command(&self) -> &str {
+        match self {
+            NetworkMessage::Version(_) => "version",
+            NetworkMessage::VerAck => "verack",
+            NetworkMessage::Addr(_) => "addr",
+            NetworkMessage::GetAddr => "getaddr",
+            NetworkMessage::Inv(_) => "inv",
+            NetworkMessage::GetData(_) => "getdata",
+            NetworkMessage::GetBlocks(_) => "getblocks",
+            NetworkMessage::GetHeaders(_) => "getheaders",
+            NetworkMessage::Headers(_) => "headers",
+            NetworkMessage::Block(_) | NetworkMessage::WitnessBlock { .. } => "block",
+            NetworkMessage::Tx(_) | NetworkMessage::WitnessTx { .. } => "tx",
+            NetworkMessage::Ping(_) => "ping",
+            NetworkMessage::Pong(_) => "pong",
+            NetworkMessage::MemPool => "mempool",
+            NetworkMessage::FeeFilter(_) => "feefilter",
+            NetworkMessage::SendHeaders => "sendheaders",
+            NetworkMessage::SendAddrV2 => "sendaddrv2",
+            NetworkMessage::FilterLoad(_) => "filterload",
+            NetworkMessage::FilterAdd(_) => "filteradd",
+            NetworkMessage::FilterClear => "filterclear",
+            NetworkMessage::GetCFilters(_) => "getcfilters",
+            NetworkMessage::CFilter(_) => "cfilter",
+            NetworkMessage::GetCFHeaders(_) => "getcfheaders",
+            NetworkMessage::CFHeaders(_) => "cfheaders",
+            NetworkMessage::GetCFCheckpt(_) => "getcfcheckpt",
+            NetworkMessage::CFCheckpt(_) => "cfcheckpt",
+            NetworkMessage::Unknown { command, .. } => command,
+        }
+    }
+}
+
+/// First four bytes of the payload's double SHA256
+pub fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = crypto::sha256d(payload);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Serialize a complete message: header followed by payload
+///
+/// Only the first 12 bytes of an `Unknown` command fit in the header.
+pub fn serialize_message(message: &NetworkMessage, magic: [u8; 4]) -> Vec<u8> {
+    let payload = serialize_payload(message);
+    let mut command = [0u8; 12];
+    let name = message.command().as_bytes();
+    let name = &name[..name.len().min(command.len())];
+    command[..name.len()].copy_from_slice(name);
+
+    let mut buf = Vec::with_capacity(MESSAGE_HEADER_SIZE + payload.len());
+    buf.extend_from_slice(&magic);
+    buf.extend_from_slice(&command);
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&checksum(&payload));
+    buf.extend_from_slice(&payload);
+    buf
+}
+
+/// Serialize a message payload
+pub fn serialize_payload(message: &NetworkMessage) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match message {
+        NetworkMessage::Version(version) => {
+            buf.extend_from_slice(&version.version.to_le_bytes());
+            buf.extend_from_slice(&version.services.to_le_bytes());
+            buf.extend_from_slice(&version.timestamp.to_le_bytes());
+            write_address(&mut buf, &version.addr_recv);
+            write_address(&mut buf, &version.addr_from);
+            buf.extend_from_slice(&version.nonce.to_le_bytes());
+            write_var_bytes(&mut buf, version.user_agent.as_bytes());
+            buf.extend_from_slice(&version.start_height.to_le_bytes());
+            buf.push(version.relay as u8);
+        }
+        NetworkMessage::Addr(addr) => {
+            encoding::write_varint(&mut buf, addr.addresses.len() as u64);
+            for address in &addr.addresses {
+                // Last-seen time is not tracked
+                buf.extend_from_slice(&0u32.to_le_bytes());
+                write_address(&mut buf, address);
+            }
+        }
+        NetworkMessage::Inv(InvMessage { inventory })
+        | NetworkMessage::GetData(GetDataMessage { inventory }) => {
+            encoding::write_varint(&mut buf, inventory.len() as u64);
+            for item in inventory {
+                buf.extend_from_slice(&u32::from(item.inv_type).to_le_bytes());
+                buf.extend_from_slice(&item.hash);
+            }
+        }
+        NetworkMessage::GetBlocks(GetBlocksMessage {
+            version,
+            block_locator_hashes,
+            hash_stop,
+        })
+        | NetworkMessage::GetHeaders(GetHeadersMessage {
+            version,
+            block_locator_hashes,
+            hash_stop,
+        }) => {
+            buf.extend_from_slice(&version.to_le_bytes());
+            encoding::write_varint(&mut buf, block_locator_hashes.len() as u64);
+            for hash in block_locator_hashes {
+                buf.extend_from_slice(hash);
+            }
+            buf.extend_from_slice(hash_stop);
+        }
+        NetworkMessage::Headers(headers) => {
+            encoding::write_varint(&mut buf, headers.headers.len() as u64);
+            for header in &headers.headers {
+                buf.extend_from_slice(&encoding::serialize_header(header));
+                // Transaction count, always zero in headers messages
+                buf.push(0);
+            }
+        }
+        NetworkMessage::Block(block) => {
+            buf.extend_from_slice(&encoding::serialize_header(&block.header));
+            encoding::write_varint(&mut buf, block.transactions.len() as u64);
+            for tx in &block.transactions {
+                buf.extend_from_slice(&encoding::serialize_transaction(tx));
+            }
+        }
+        NetworkMessage::WitnessBlock { block, witnesses } => {
+            buf = encoding::serialize_block_with_witness(block, witnesses);
+        }
+        NetworkMessage::Tx(tx) => buf = encoding::serialize_transaction(tx),
+        NetworkMessage::WitnessTx { tx, witnesses } => {
+            buf = encoding::serialize_transaction_with_witness(tx, witnesses);
+        }
+        NetworkMessage::Ping(PingMessage { nonce })
+        | NetworkMessage::Pong(PongMessage { nonce }) => {
+            buf.extend_from_slice(&nonce.to_le_bytes());
+        }
+        NetworkMessage::FeeFilter(feefilter) => {
+            buf.extend_from_slice(&feefilter.feerate.to_le_bytes());
+        }
+        NetworkMessage::FilterLoad(filter) => buf = filter.serialize(),
+        NetworkMessage::FilterAdd(filteradd) => write_var_bytes(&mut buf, &filteradd.data),
+        NetworkMessage::GetCFilters(GetCfilters {
+            filter_type,
+            start_height,
+            stop_hash,
+        })
+        | NetworkMessage::GetCFHeaders(GetCfheaders {
+            filter_type,
+            start_height,
+            stop_hash,
+        }) => {
+            buf.push(filter_type.to_u8());
+            buf.extend_from_slice(&start_height.to_le_bytes());
+            buf.extend_from_slice(stop_hash);
+        }
+        NetworkMessage::CFilter(cfilter) => {
+            buf.push(cfilter.filter_type.to_u8());
+            buf.extend_from_slice(&cfilter.block_hash);
+            write_var_bytes(&mut buf, &cfilter.filter.serialize());
+        }
+        NetworkMessage::CFHeaders(cfheaders) => {
+            buf.push(cfheaders.filter_type.to_u8());
+            buf.extend_from_slice(&cfheaders.stop_hash);
+            buf.extend_from_slice(&cfheaders.prev_filter_header);
+            write_hashes(&mut buf, &cfheaders.filter_hashes);
+        }
+        NetworkMessage::GetCFCheckpt(getcfcheckpt) => {
+            buf.push(getcfcheckpt.filter_type.to_u8());
+            buf.extend_from_slice(&getcfcheckpt.stop_hash);
+        }
+        NetworkMessage::CFCheckpt(cfcheckpt) => {
+            buf.push(cfcheckpt.filter_type.to_u8());
+            buf.extend_from_slice(&cfcheckpt.stop_hash);
+            write_hashes(&mut buf, &cfcheckpt.filter_header_hashes);
+        }
+        NetworkMessage::Unknown { payload, .. } => buf.extend_from_slice(payload),
+        NetworkMessage::VerAck
+        | NetworkMessage::GetAddr
+        | NetworkMessage::MemPool
+        | NetworkMessage::SendHeaders
+        | NetworkMessage::SendAddrV2
+        | NetworkMessage::FilterClear => {}
+    }
+    buf
+}
+
+/// Parse a message payload for `command`
+///
+/// The whole payload must be consumed, except that the trailing `relay`
+/// flag of a version message is optional. Unrecognised commands decode as
+/// `NetworkMessage::Unknown` with the raw payload.
+pub fn deserialize_payload(command: &str, payload: &[u8]) -> Result<NetworkMessage> {
+    let mut reader = Reader::new(payload);
+    let message = match command {
+        "version" => NetworkMessage::Version(VersionMessage {
+            version: reader.read_u32()?,
+            services: reader.read_u64()?,
+            timestamp: reader.read_u64()? as i64,
+            addr_recv: read_address(&mut reader)?,
+            addr_from: read_address(&mut reader)?,
+            nonce: reader.read_u64()?,
+            user_agent: String::from_utf8(reader.read_var_bytes()?).map_err(|_| {
+                ProtocolError::InvalidNetworkMessage("user agent is not UTF-8".to_string())
+            })?,
+            start_height: reader.read_u32()? as i32,
+            relay: reader.is_empty() || reader.read_u8()? != 0,
+        }),
+        "verack" => NetworkMessage::VerAck,
+        "addr" => {
+            let mut addresses = Vec::new();
+            for _ in 0..reader.read_varint()? {
+                reader.read_u32()?; // last-seen time
+                addresses.push(read_address(&mut reader)?);
+            }
+            NetworkMessage::Addr(AddrMessage { addresses })
+        }
+        "getaddr" => NetworkMessage::GetAddr,
+        "inv" => NetworkMessage::Inv(InvMessage {
+            inventory: read_inventory(&mut reader)?,
+        }),
+        "getdata" => NetworkMessage::GetData(GetDataMessage {
+            inventory: read_inventory(&mut reader)?,
+        }),
+        "getblocks" => {
+            let (version, block_locator_hashes, hash_stop) = read_locator(&mut reader)?;
+            NetworkMessage::GetBlocks(GetBlocksMessage {
+                version,
+                block_locator_hashes,
+                hash_stop,
+            })
+        }
+        "getheaders" => {
+            let (version, block_locator_hashes, hash_stop) = read_locator(&mut reader)?;
+            NetworkMessage::GetHeaders(GetHeadersMessage {
+                version,
+                block_locator_hashes,
+                hash_stop,
+            })
+        }
+        "headers" => {
+            let mut headers = Vec::new();
+            for _ in 0..reader.read_varint()? {
+                headers.push(encoding::read_header(&mut reader)?);
+                reader.read_varint()?; // transaction count
+            }
+            NetworkMessage::Headers(HeadersMessage { headers })
+        }
+        "block" => {
+            let header = encoding::read_header(&mut reader)?;
+            let mut transactions = Vec::new();
+            let mut witnesses = Vec::new();
+            for _ in 0..reader.read_varint()? {
+                let (tx, tx_witnesses) = encoding::read_transaction(&mut reader)?;
+                transactions.push(tx);
+                witnesses.push(tx_witnesses);
+            }
+            let block = Block {
+                header,
+                transactions,
+            };
+            if witnesses.iter().any(|stacks| encoding::has_witness(stacks)) {
+                NetworkMessage::WitnessBlock { block, witnesses }
+            } else {
+                NetworkMessage::Block(block)
+            }
+        }
+        "tx" => {
+            let (tx, witnesses) = encoding::read_transaction(&mut reader)?;
+            if encoding::has_witness(&witnesses) {
+                NetworkMessage::WitnessTx { tx, witnesses }
+            } else {
+                NetworkMessage::Tx(tx)
+            }
+        }
+        "ping" => NetworkMessage::Ping(PingMessage {
+            nonce: reader.read_u64()?,
+        }),
+        "pong" => NetworkMessage::Pong(PongMessage {
+            nonce: reader.read_u64()?,
+        }),
+        "mempool" => NetworkMessage::MemPool,
+        "feefilter" => NetworkMessage::FeeFilter(FeeFilterMessage {
+            feerate: reader.read_u64()?,
+        }),
+        "filterload" => NetworkMessage::FilterLoad(BloomFilter {
+            data: reader.read_var_bytes()?,
+            hash_funcs: reader.read_u32()?,
+            tweak: reader.read_u32()?,
+            flags: reader.read_u8()?,
+        }),
+        "filteradd" => NetworkMessage::FilterAdd(FilterAddMessage {
+            data: reader.read_var_bytes()?,
+        }),
+        "filterclear" => NetworkMessage::FilterClear,
+        "sendheaders" => NetworkMessage::SendHeaders,
+        "sendaddrv2" => NetworkMessage::SendAddrV2,
+        "getcfilters" => NetworkMessage::GetCFilters(GetCfilters {
+            filter_type: read_filter_type(&mut reader)?,
+            start_height: reader.read_u32()?,
+            stop_hash: reader.read_array()?,
+        }),
+        "cfilter" => NetworkMessage::CFilter(CFilter {
+            filter_type: read_filter_type(&mut reader)?,
+            block_hash: reader.read_array()?,
+            filter: read_filter(&reader.read_var_bytes()?)?,
+        }),
+        "getcfheaders" => NetworkMessage::GetCFHeaders(GetCfheaders {
+            filter_type: read_filter_type(&mut reader)?,
+            start_height: reader.read_u32()?,
+            stop_hash: reader.read_array()?,
+        }),
+        "cfheaders" => NetworkMessage::CFHeaders(Cfheaders {
+            filter_type: read_filter_type(&mut reader)?,
+            stop_hash: reader.read_array()?,
+            prev_filter_header: reader.read_array()?,
+            filter_hashes: read_hashes(&mut reader)?,
+        }),
+        "getcfcheckpt" => NetworkMessage::GetCFCheckpt(GetCfcheckpt {
+            filter_type: read_filter_type(&mut reader)?,
+            stop_hash: reader.read_array()?,
+        }),
+        "cfcheckpt" => NetworkMessage::CFCheckpt(Cfcheckpt {
+            filter_type: read_filter_type(&mut reader)?,
+            stop_hash: reader.read_array()?,
+            filter_header_hashes: read_hashes(&mut reader)?,
+        }),
+        _ => {
+            return Ok(NetworkMessage::Unknown {
+                command: command.to_string(),
+                payload: payload.to_vec(),
+            })
+        }
+    };
+
+    if !reader.is_empty() {
+        return Err(ProtocolError::InvalidNetworkMessage(format!(
+            "trailing bytes in {command} payload"
+        )));
+    }
+    Ok(message)
+}
+
+/// Buffers a byte stream and splits it into complete messages
+///
+/// A bad magic or an oversized payload leaves the stream unusable and the
+/// caller should disconnect. A frame with a bad checksum or an undecodable
+/// payload is discarded, so later messages can still be read.
+#[derive(Debug, Clone, Default)]
+pub struct MessageFramer {
+    buffer: Vec<u8>,
+}
+
+impl MessageFramer {
+    /// Create an empty framer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append bytes received from the peer
+    pub fn push_bytes(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Number of bytes buffered but not yet returned as messages
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Take the next complete message, or `Ok(None)` until one has arrived
+    pub fn next_message(&mut self, magic: [u8; 4]) -> Result<Option<NetworkMessage>> {
+        if self.buffer.len() < MESSAGE_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let header = &self.buffer[..MESSAGE_HEADER_SIZE];
+        if header[..4] != magic {
+            return Err(ProtocolError::InvalidNetworkMessage(format!(
+                "bad magic {:02x?}",
+                &header[..4]
+            )));
+        }
+        let length = u32::from_le_bytes([header[16], header[17], header[18], header[19]]) as usize;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(ProtocolError::InvalidNetworkMessage(format!(
+                "payload of {length} bytes exceeds maximum"
+            )));
+        }
+        if self.buffer.len() < MESSAGE_HEADER_SIZE + length {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buffer.drain(..MESSAGE_HEADER_SIZE + length).collect();
+        let (header, payload) = frame.split_at(MESSAGE_HEADER_SIZE);
+        if header[20..24] != checksum(payload) {
+            return Err(ProtocolError::InvalidNetworkMessage(
+                "checksum mismatch".to_string(),
+            ));
+        }
+        let command = &header[4..16];
+        let name_len = command
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(command.len());
+        let command = std::str::from_utf8(&command[..name_len]).map_err(|_| {
+            ProtocolError::InvalidNetworkMessage("command is not ASCII".to_string())
+        })?;
+
+        deserialize_payload(command, payload).map(Some)
+    }
+}
+
+fn write_var_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    encoding::write_varint(buf, data.len() as u64);
+    buf.extend_from_slice(data);
+}
+
+/// Address without timestamp, as in version messages (port is big-endian)
+fn write_address(buf: &mut Vec<u8>, address: &NetworkAddress) {
+    buf.extend_from_slice(&address.services.to_le_bytes());
+    buf.extend_from_slice(&address.ip);
+    buf.extend_from_slice(&address.port.to_be_bytes());
+}
+
+fn read_address(reader: &mut Reader) -> Result<NetworkAddress> {
+    Ok(NetworkAddress {
+        services: reader.read_u64()?,
+        ip: reader.read_array()?,
+        port: u16::from_be_bytes(reader.read_array()?),
+    })
+}
+
+fn read_inventory(reader: &mut Reader) -> Result<Vec<InventoryVector>> {
+    let mut inventory = Vec::new();
+    for _ in 0..reader.read_varint()? {
+        inventory.push(InventoryVector {
+            inv_type: InvType::from(reader.read_u32()?),
+            hash: reader.read_array()?,
+        });
+    }
+    Ok(inventory)
+}
+
+fn write_hashes(buf: &mut Vec<u8>, hashes: &[Hash]) {
+    encoding::write_varint(buf, hashes.len() as u64);
+    for hash in hashes {
+        buf.extend_from_slice(hash);
+    }
+}
+
+fn read_hashes(reader: &mut Reader) -> Result<Vec<Hash>> {
+    let mut hashes = Vec::new();
+    for _ in 0..reader.read_varint()? {
+        hashes.push(reader.read_array()?);
+    }
+    Ok(hashes)
+}
+
+fn read_filter_type(reader: &mut Reader) -> Result<FilterType> {
+    let filter_type = reader.read_u8()?;
+    FilterType::from_u8(filter_type).ok_or_else(|| {
+        ProtocolError::InvalidNetworkMessage(format!("unknown filter type {filter_type}"))
+    })
+}
+
+/// Split a BIP158 filter into its element count and Golomb-Rice data
+fn read_filter(data: &[u8]) -> Result<CompactBlockFilter> {
+    let mut reader = Reader::new(data);
+    let num_elements = u32::try_from(reader.read_varint()?).map_err(|_| {
+        ProtocolError::InvalidNetworkMessage("filter element count out of range".to_string())
+    })?;
+    Ok(CompactBlockFilter {
+        num_elements,
+        filter_data: data[reader.position()..].to_vec(),
+    })
+}
+
+fn read_locator(reader: &mut Reader) -> Result<(u32, Vec<Hash>, Hash)> {
+    let version = reader.read_u32()?;
+    let hashes = read_hashes(reader)?;
+    Ok((version, hashes, reader.read_array()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::PROTOCOL_VERSION;
+    use crate::{genesis, BlockHeader};
+
+    const REGTEST_MAGIC: [u8; 4] = [0xfa, 0xbf, 0xb5, 0xda];
+
+    fn sample_messages() -> Vec<NetworkMessage> {
+        let address = NetworkAddress {
+            services: 9,
+            ip: [0xff; 16],
+            port: 18444,
+        };
+        let block = genesis::mainnet_genesis();
+        let header: BlockHeader = block.header.clone();
+        vec![
+            NetworkMessage::Version(VersionMessage {
+                version: PROTOCOL_VERSION,
+                services: 9,
+                timestamp: 1_700_000_000,
+                addr_recv: address.clone(),
+                addr_from: address.clone(),
+                nonce: 42,
+                user_agent: "/test:0.1.0/".to_string(),
+                start_height: 100,
+                relay: false,
+            }),
+            NetworkMessage::VerAck,
+            NetworkMessage::Addr(AddrMessage {
+                addresses: vec![address],
+            }),
+            NetworkMessage::GetAddr,
+            NetworkMessage::Inv(InvMessage {
+                inventory: vec![InventoryVector {
+                    inv_type: InvType::Block,
+                    hash: [7; 32],
+                }],
+            }),
+            NetworkMessage::GetData(GetDataMessage {
+                inventory: vec![InventoryVector {
+                    inv_type: InvType::Tx,
+                    hash: [8; 32],
+                }],
+            }),
+            NetworkMessage::GetBlocks(GetBlocksMessage {
+                version: PROTOCOL_VERSION,
+                block_locator_hashes: vec![[1; 32], [2; 32]],
+                hash_stop: [0; 32],
+            }),
+            NetworkMessage::GetHeaders(GetHeadersMessage {
+                version: PROTOCOL_VERSION,
+                block_locator_hashes: vec![[3; 32]],
+                hash_stop: [4; 32],
+            }),
+            NetworkMessage::Headers(HeadersMessage {
+                headers: vec![header.clone(), header],
+            }),
+            NetworkMessage::Tx(block.transactions[0].clone()),
+            NetworkMessage::WitnessTx {
+                tx: block.transactions[0].clone(),
+                witnesses: vec![vec![vec![0x30; 71], vec![0x02; 33]]],
+            },
+            NetworkMessage::WitnessBlock {
+                block: block.clone(),
+                witnesses: vec![vec![vec![vec![0; 32]]]],
+            },
+            NetworkMessage::Block(block),
+            NetworkMessage::Ping(PingMessage { nonce: 5 }),
+            NetworkMessage::Pong(PongMessage { nonce: 6 }),
+            NetworkMessage::MemPool,
+            NetworkMessage::FeeFilter(FeeFilterMessage { feerate: 1000 }),
+            NetworkMessage::SendHeaders,
+            NetworkMessage::SendAddrV2,
+            NetworkMessage::FilterLoad(BloomFilter {
+                data: vec![1, 2, 3],
+                hash_funcs: 5,
+                tweak: 7,
+                flags: 1,
+            }),
+            NetworkMessage::FilterAdd(FilterAddMessage { data: vec![9; 20] }),
+            NetworkMessage::FilterClear,
+            NetworkMessage::GetCFilters(GetCfilters {
+                filter_type: FilterType::Basic,
+                start_height: 10,
+                stop_hash: [5; 32],
+            }),
+            NetworkMessage::CFilter(CFilter {
+                filter_type: FilterType::Basic,
+                block_hash: [6; 32],
+                filter: CompactBlockFilter {
+                    filter_data: vec![0x1c, 0x2d, 0x3e],
+                    num_elements: 2,
+                },
+            }),
+            NetworkMessage::GetCFHeaders(GetCfheaders {
+                filter_type: FilterType::Basic,
+                start_height: 0,
+                stop_hash: [7; 32],
+            }),
+            NetworkMessage::CFHeaders(Cfheaders {
+                filter_type: FilterType::Basic,
+                stop_hash: [7; 32],
+                prev_filter_header: [0; 32],
+                filter_hashes: vec![[1; 32], [2; 32]],
+            }),
+            NetworkMessage::GetCFCheckpt(GetCfcheckpt {
+                filter_type: FilterType::Basic,
+                stop_hash: [8; 32],
+            }),
+            NetworkMessage::CFCheckpt(Cfcheckpt {
+                filter_type: FilterType::Basic,
+                stop_hash: [8; 32],
+                filter_header_hashes: vec![[3; 32]],
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        for message in sample_messages() {
+            let payload = serialize_payload(&message);
+            let decoded = deserialize_payload(message.command(), &payload).unwrap();
+            assert_eq!(decoded, message, "{}", message.command());
+        }
+    }
+
+    #[test]
+    fn test_cfilter_wire_layout() {
+        let message = NetworkMessage::CFilter(CFilter {
+            filter_type: FilterType::Basic,
+            block_hash: [6; 32],
+            filter: CompactBlockFilter {
+                filter_data: vec![0xaa, 0xbb],
+                num_elements: 3,
+            },
+        });
+        let payload = serialize_payload(&message);
+        // Type, block hash, then the length-prefixed count and data
+        assert_eq!(payload.len(), 1 + 32 + 1 + 3);
+        assert_eq!(&payload[33..], [0x03, 0x03, 0xaa, 0xbb]);
+
+        let mut bad_type = payload.clone();
+        bad_type[0] = 7;
+        let err = deserialize_payload("cfilter", &bad_type).unwrap_err();
+        assert!(err.to_string().contains("unknown filter type 7"), "{err}");
+    }
+
+    #[test]
+    fn test_unknown_command_round_trip() {
+        let frame = serialize_message(
+            &NetworkMessage::Unknown {
+                command: "frobnicate".to_string(),
+                payload: vec![1, 2, 3],
+            },
+            REGTEST_MAGIC,
+        );
+        assert_eq!(&frame[4..16], b"frobnicate\0\0");
+
+        let mut framer = MessageFramer::new();
+        framer.push_bytes(&frame);
+        let decoded = framer.next_message(REGTEST_MAGIC).unwrap().unwrap();
+        assert_eq!(
+            decoded,
+            NetworkMessage::Unknown {
+                command: "frobnicate".to_string(),
+                payload: vec![1, 2, 3],
+            }
+        );
+        assert_eq!(serialize_message(&decoded, REGTEST_MAGIC), frame);
+    }
+
+    #[test]
+    fn test_verack_frame_bytes() {
+        let frame = serialize_message(&NetworkMessage::VerAck, [0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_eq!(frame.len(), MESSAGE_HEADER_SIZE);
+        assert_eq!(&frame[4..10], b"verack");
+        // Checksum of the empty payload
+        assert_eq!(frame[20..], [0x5d, 0xf6, 0xe0, 0xe2]);
+    }
+
+    #[test]
+    fn test_framer_byte_at_a_time() {
+        let message = NetworkMessage::Ping(PingMessage { nonce: 0xdead_beef });
+        let frame = serialize_message(&message, REGTEST_MAGIC);
+        let mut framer = MessageFramer::new();
+
+        for byte in &frame[..frame.len() - 1] {
+            framer.push_bytes(&[*byte]);
+            assert_eq!(framer.next_message(REGTEST_MAGIC).unwrap(), None);
+        }
+        framer.push_bytes(&frame[frame.len() - 1..]);
+        assert_eq!(framer.next_message(REGTEST_MAGIC).unwrap(), Some(message));
+        assert_eq!(framer.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_framer_two_messages_in_one_chunk() {
+        let first = NetworkMessage::VerAck;
+        let second = NetworkMessage::Tx(genesis::mainnet_genesis().transactions[0].clone());
+        let mut stream = serialize_message(&first, REGTEST_MAGIC);
+        stream.extend(serialize_message(&second, REGTEST_MAGIC));
+
+        let mut framer = MessageFramer::new();
+        framer.push_bytes(&stream);
+        assert_eq!(framer.next_message(REGTEST_MAGIC).unwrap(), Some(first));
+        assert_eq!(framer.next_message(REGTEST_MAGIC).unwrap(), Some(second));
+        assert_eq!(framer.next_message(REGTEST_MAGIC).unwrap(), None);
+    }
+
+    #[test]
+    fn test_framer_rejects_bad_frames() {
+        let frame = serialize_message(&NetworkMessage::MemPool, REGTEST_MAGIC);
+
+        let mut framer = MessageFramer::new();
+        framer.push_bytes(&frame);
+        let err = framer.next_message([0xf9, 0xbe, 0xb4, 0xd9]).unwrap_err();
+        assert!(err.to_string().contains("bad magic"), "{err}");
+
+        let mut oversized = frame.clone();
+        oversized[16..20].copy_from_slice(&(MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes());
+        let mut framer = MessageFramer::new();
+        framer.push_bytes(&oversized);
+        let err = framer.next_message(REGTEST_MAGIC).unwrap_err();
+        assert!(err.to_string().contains("exceeds maximum"), "{err}");
+
+        // A corrupt frame is dropped and the next one still decodes
+        let mut corrupt = serialize_message(
+            &NetworkMessage::Ping(PingMessage { nonce: 1 }),
+            REGTEST_MAGIC,
+        );
+        corrupt[MESSAGE_HEADER_SIZE] ^= 0x01;
+        let mut framer = MessageFramer::new();
+        framer.push_bytes(&corrupt);
+        framer.push_bytes(&frame);
+        let err = framer.next_message(REGTEST_MAGIC).unwrap_err();
+        assert!(err.to_string().contains("checksum"), "{err}");
+        assert_eq!(
+            framer.next_message(REGTEST_MAGIC).unwrap(),
+            Some(NetworkMessage::MemPool)
+        );
+    }
+}
@@ -4,7 +4,8 @@
 //! Bitcoin protocol variants, including magic bytes, ports, genesis blocks,
 //! and other network-specific constants.
 
-use crate::{NetworkParameters, ProtocolVersion, Result};
+use crate::validation::{MAX_FUTURE_BLOCK_TIME, MEDIAN_TIME_SPAN};
+use crate::{difficulty, NetworkParameters, ProtocolError, ProtocolVersion, Result};
 use serde::{Deserialize, Serialize};
 
 /// Network-specific constants
@@ -28,6 +29,39 @@ pub struct NetworkConstants {
     pub dns_seeds: Vec<String>,
     /// Checkpoint blocks for fast sync
     pub checkpoints: Vec<Checkpoint>,
+    /// Minimum cumulative chain work (big-endian) a chain must have to be synced
+    ///
+    /// Protects against low-work chains fed by an eclipsing attacker.
+    pub min_chain_work: [u8; 32],
+    /// Block whose ancestors skip script checks by default (`assumevalid`)
+    pub default_assume_valid: [u8; 32],
+    /// Seconds a block timestamp may be ahead of local time
+    pub max_future_block_time: u64,
+    /// Number of previous blocks whose median timestamp a block must exceed
+    ///
+    /// A window of 1 requires strictly increasing timestamps.
+    pub mtp_window: usize,
+    /// Blocks (height, hash in internal byte order) allowed to violate BIP30
+    ///
+    /// Their coinbases duplicated earlier ones before BIP30 was enforced.
+    pub bip30_exceptions: Vec<(u64, [u8; 32])>,
+    /// Human-readable part of segwit addresses (BIP173)
+    pub bech32_hrp: String,
+    /// Base58 version byte of P2PKH addresses
+    pub pubkey_address_prefix: u8,
+    /// Base58 version byte of P2SH addresses
+    pub script_address_prefix: u8,
+}
+
+/// One field that differs between two `NetworkConstants`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldDiff {
+    /// Field name, as in the struct and its TOML/JSON form
+    pub field: String,
+    /// Value in the constants `diff` was called on
+    pub self_value: serde_json::Value,
+    /// Value in the constants compared against
+    pub other_value: serde_json::Value,
 }
 
 /// Checkpoint block for fast synchronization
@@ -48,6 +82,9 @@ impl NetworkConstants {
             ProtocolVersion::BitcoinV1 => Self::mainnet(),
             ProtocolVersion::Testnet3 => Self::testnet(),
             ProtocolVersion::Regtest => Self::regtest(),
+            ProtocolVersion::Signet => Self::signet(),
+            ProtocolVersion::Simnet => Self::simnet(),
+            ProtocolVersion::Educational => Self::educational(),
         }
     }
 
@@ -74,6 +111,23 @@ impl NetworkConstants {
                 "seed.btc.petertodd.org".to_string(),
             ],
             checkpoints: Self::mainnet_checkpoints(),
+            min_chain_work: [
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x52, 0xb2, 0x55, 0x93, 0x53, 0xdf, 0x41, 0x17,
+                0xb7, 0x34, 0x8b, 0x64,
+            ],
+            // Block 804,000
+            default_assume_valid: [
+                0x15, 0x68, 0x26, 0x91, 0x64, 0x8c, 0xb1, 0xb2, 0x30, 0xc0, 0x9c, 0x38, 0x01, 0x68,
+                0xb0, 0x46, 0x25, 0xcf, 0xd6, 0x48, 0xa4, 0xa0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00,
+            ],
+            max_future_block_time: MAX_FUTURE_BLOCK_TIME,
+            mtp_window: MEDIAN_TIME_SPAN,
+            bip30_exceptions: Self::mainnet_bip30_exceptions(),
+            bech32_hrp: "bc".to_string(),
+            pubkey_address_prefix: 0x00,
+            script_address_prefix: 0x05,
         })
     }
 
@@ -98,6 +152,23 @@ impl NetworkConstants {
                 "testnet-seed.bluematt.me".to_string(),
             ],
             checkpoints: Self::testnet_checkpoints(),
+            min_chain_work: [
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x59, 0xb1, 0x4e, 0x26, 0x4b,
+                0xa6, 0xc1, 0x5d, 0xb9,
+            ],
+            // Block 2,500,000
+            default_assume_valid: [
+                0x17, 0x89, 0x96, 0x2d, 0xcc, 0x9b, 0xa5, 0x06, 0xe5, 0xcc, 0xac, 0xad, 0x1e, 0x49,
+                0xce, 0x9e, 0x52, 0xae, 0x5a, 0xea, 0x21, 0x8f, 0xf3, 0x71, 0x30, 0x32, 0x01, 0x00,
+                0x00, 0x00, 0x00, 0x00,
+            ],
+            max_future_block_time: MAX_FUTURE_BLOCK_TIME,
+            mtp_window: MEDIAN_TIME_SPAN,
+            bip30_exceptions: vec![],
+            bech32_hrp: "tb".to_string(),
+            pubkey_address_prefix: 0x6f,
+            script_address_prefix: 0xc4,
         })
     }
 
@@ -117,9 +188,167 @@ impl NetworkConstants {
             is_testnet: true,
             dns_seeds: vec![],   // No DNS seeds for regtest
             checkpoints: vec![], // No checkpoints for regtest
+            min_chain_work: [0u8; 32],
+            default_assume_valid: [0u8; 32],
+            max_future_block_time: MAX_FUTURE_BLOCK_TIME,
+            mtp_window: MEDIAN_TIME_SPAN,
+            bip30_exceptions: vec![],
+            bech32_hrp: "bcrt".to_string(),
+            pubkey_address_prefix: 0x6f,
+            script_address_prefix: 0xc4,
+        })
+    }
+
+    /// Default signet constants
+    pub fn signet() -> Result<Self> {
+        Ok(Self {
+            magic_bytes: [0x0a, 0x03, 0xcf, 0x40], // Default signet magic
+            default_port: 38333,
+            genesis_hash: [
+                0xf6, 0x1e, 0xee, 0x3b, 0x63, 0xa3, 0x80, 0xa4, 0x77, 0xa0, 0x63, 0xaf, 0x32, 0xb2,
+                0xbb, 0xc9, 0x7c, 0x9f, 0xf9, 0xf0, 0x1f, 0x2c, 0x42, 0x25, 0xe9, 0x73, 0x98, 0x81,
+                0x08, 0x00, 0x00, 0x00,
+            ],
+            max_target: 0x1e0377ae,
+            halving_interval: 210000,
+            network_name: "signet".to_string(),
+            is_testnet: true,
+            dns_seeds: vec!["seed.signet.bitcoin.sprovoost.nl".to_string()],
+            checkpoints: vec![],
+            min_chain_work: [0u8; 32],
+            default_assume_valid: [0u8; 32],
+            max_future_block_time: MAX_FUTURE_BLOCK_TIME,
+            mtp_window: MEDIAN_TIME_SPAN,
+            bip30_exceptions: vec![],
+            bech32_hrp: "tb".to_string(),
+            pubkey_address_prefix: 0x6f,
+            script_address_prefix: 0xc4,
         })
     }
 
+    /// Simnet constants: regtest's genesis and schedule on separate ports
+    pub fn simnet() -> Result<Self> {
+        let regtest = Self::regtest()?;
+        Ok(Self {
+            magic_bytes: [0x16, 0x1c, 0x14, 0x12], // btcd simnet magic
+            default_port: 18555,
+            network_name: "simnet".to_string(),
+            bech32_hrp: "sb".to_string(),
+            pubkey_address_prefix: 0x3f,
+            script_address_prefix: 0x7b,
+            ..regtest
+        })
+    }
+
+    /// Educational constants: regtest's genesis and addresses, halving every 10 blocks
+    pub fn educational() -> Result<Self> {
+        let regtest = Self::regtest()?;
+        Ok(Self {
+            magic_bytes: [0xed, 0x0c, 0xa7, 0x10],
+            default_port: 18777,
+            halving_interval: 10,
+            network_name: "educational".to_string(),
+            ..regtest
+        })
+    }
+
+    /// Check that the constants are usable, e.g. after loading a config file
+    ///
+    /// Rejects an empty name or address HRP, a zero halving interval or MTP
+    /// window, an unusable proof-of-work limit and checkpoints out of height
+    /// order.
+    pub fn validate(&self) -> Result<()> {
+        check_common(&self.network_name, self.halving_interval, self.max_target)?;
+        if self.mtp_window == 0 {
+            return Err(invalid("mtp_window must be non-zero"));
+        }
+        if self.bech32_hrp.is_empty() {
+            return Err(invalid("bech32_hrp must be non-empty"));
+        }
+        if self
+            .checkpoints
+            .windows(2)
+            .any(|pair| pair[0].height >= pair[1].height)
+        {
+            return Err(invalid("checkpoint heights must be strictly increasing"));
+        }
+        Ok(())
+    }
+
+    /// Fields whose values differ from `other`, in declaration order
+    ///
+    /// Handy for auditing a custom network against the canonical one it
+    /// was derived from.
+    pub fn diff(&self, other: &NetworkConstants) -> Vec<FieldDiff> {
+        // Destructure so a new field cannot be forgotten here
+        let NetworkConstants {
+            magic_bytes,
+            default_port,
+            genesis_hash,
+            max_target,
+            halving_interval,
+            network_name,
+            is_testnet,
+            dns_seeds,
+            checkpoints,
+            min_chain_work,
+            default_assume_valid,
+            max_future_block_time,
+            mtp_window,
+            bip30_exceptions,
+            bech32_hrp,
+            pubkey_address_prefix,
+            script_address_prefix,
+        } = self;
+
+        [
+            field_diff("magic_bytes", magic_bytes, &other.magic_bytes),
+            field_diff("default_port", default_port, &other.default_port),
+            field_diff("genesis_hash", genesis_hash, &other.genesis_hash),
+            field_diff("max_target", max_target, &other.max_target),
+            field_diff(
+                "halving_interval",
+                halving_interval,
+                &other.halving_interval,
+            ),
+            field_diff("network_name", network_name, &other.network_name),
+            field_diff("is_testnet", is_testnet, &other.is_testnet),
+            field_diff("dns_seeds", dns_seeds, &other.dns_seeds),
+            field_diff("checkpoints", checkpoints, &other.checkpoints),
+            field_diff("min_chain_work", min_chain_work, &other.min_chain_work),
+            field_diff(
+                "default_assume_valid",
+                default_assume_valid,
+                &other.default_assume_valid,
+            ),
+            field_diff(
+                "max_future_block_time",
+                max_future_block_time,
+                &other.max_future_block_time,
+            ),
+            field_diff("mtp_window", mtp_window, &other.mtp_window),
+            field_diff(
+                "bip30_exceptions",
+                bip30_exceptions,
+                &other.bip30_exceptions,
+            ),
+            field_diff("bech32_hrp", bech32_hrp, &other.bech32_hrp),
+            field_diff(
+                "pubkey_address_prefix",
+                pubkey_address_prefix,
+                &other.pubkey_address_prefix,
+            ),
+            field_diff(
+                "script_address_prefix",
+                script_address_prefix,
+                &other.script_address_prefix,
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
     /// Mainnet checkpoints for fast sync
     fn mainnet_checkpoints() -> Vec<Checkpoint> {
         vec![
@@ -136,6 +365,28 @@ impl NetworkConstants {
         ]
     }
 
+    /// Mainnet blocks 91,842 and 91,880, whose coinbases duplicate earlier ones
+    fn mainnet_bip30_exceptions() -> Vec<(u64, [u8; 32])> {
+        vec![
+            (
+                91842,
+                [
+                    0xec, 0xca, 0xe0, 0x00, 0xe3, 0xc8, 0xe4, 0xe0, 0x93, 0x93, 0x63, 0x60, 0x43,
+                    0x1f, 0x3b, 0x76, 0x03, 0xc5, 0x63, 0xc1, 0xff, 0x61, 0x81, 0x39, 0x0a, 0x4d,
+                    0x0a, 0x00, 0x00, 0x00, 0x00, 0x00,
+                ],
+            ),
+            (
+                91880,
+                [
+                    0x21, 0xd7, 0x7c, 0xcb, 0x4c, 0x08, 0x38, 0x6a, 0x04, 0xac, 0x01, 0x96, 0xae,
+                    0x10, 0xf6, 0xa1, 0xd2, 0xc2, 0xa3, 0x77, 0x55, 0x8c, 0xa1, 0x90, 0xf1, 0x43,
+                    0x07, 0x00, 0x00, 0x00, 0x00, 0x00,
+                ],
+            ),
+        ]
+    }
+
     /// Testnet checkpoints for fast sync
     fn testnet_checkpoints() -> Vec<Checkpoint> {
         vec![
@@ -153,6 +404,39 @@ impl NetworkConstants {
     }
 }
 
+fn field_diff<T: PartialEq + Serialize>(field: &str, ours: &T, theirs: &T) -> Option<FieldDiff> {
+    (ours != theirs).then(|| FieldDiff {
+        field: field.to_string(),
+        self_value: serde_json::json!(ours),
+        other_value: serde_json::json!(theirs),
+    })
+}
+
+/// Checks shared by `NetworkConstants` and `NetworkParameters` validation
+pub(crate) fn check_common(
+    network_name: &str,
+    halving_interval: u64,
+    max_target: u32,
+) -> Result<()> {
+    if network_name.is_empty() {
+        return Err(invalid("network name must not be empty"));
+    }
+    if halving_interval == 0 {
+        return Err(invalid("halving interval must be non-zero"));
+    }
+    let target = difficulty::decode_compact(max_target);
+    if target.negative || target.overflow || target.target == [0u8; 32] {
+        return Err(ProtocolError::InvalidConfig(format!(
+            "max target {max_target:#010x} is not a usable compact target"
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn invalid(reason: &str) -> ProtocolError {
+    ProtocolError::InvalidConfig(reason.to_string())
+}
+
 impl NetworkParameters {
     /// Create network parameters from constants
     pub fn from_constants(constants: &NetworkConstants) -> Result<Self> {
@@ -172,6 +456,36 @@ impl NetworkParameters {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_diff_mainnet_testnet() {
+        let mainnet = NetworkConstants::mainnet().unwrap();
+        let testnet = NetworkConstants::testnet().unwrap();
+        assert!(mainnet.diff(&mainnet).is_empty());
+
+        let diffs = mainnet.diff(&testnet);
+        let fields: Vec<&str> = diffs.iter().map(|diff| diff.field.as_str()).collect();
+        for field in ["magic_bytes", "default_port", "genesis_hash", "dns_seeds"] {
+            assert!(fields.contains(&field), "{field} missing from {fields:?}");
+        }
+        assert!(!fields.contains(&"halving_interval"));
+
+        let port = diffs
+            .iter()
+            .find(|diff| diff.field == "default_port")
+            .unwrap();
+        assert_eq!(port.self_value, serde_json::json!(8333));
+        assert_eq!(port.other_value, serde_json::json!(18333));
+
+        // Reversing the comparison swaps the sides
+        let reversed = testnet.diff(&mainnet);
+        assert_eq!(reversed.len(), diffs.len());
+        assert_eq!(reversed[0].self_value, diffs[0].other_value);
+
+        let json = serde_json::to_string(&diffs).unwrap();
+        let decoded: Vec<FieldDiff> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, diffs);
+    }
+
     #[test]
     fn test_network_constants() {
         let mainnet = NetworkConstants::mainnet().unwrap();
@@ -282,6 +596,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_min_chain_work_and_assume_valid() {
+        let mainnet = NetworkConstants::mainnet().unwrap();
+        let testnet = NetworkConstants::testnet().unwrap();
+        let regtest = NetworkConstants::regtest().unwrap();
+
+        assert!(mainnet.min_chain_work > testnet.min_chain_work);
+        assert_ne!(mainnet.default_assume_valid, [0u8; 32]);
+        assert_ne!(testnet.default_assume_valid, [0u8; 32]);
+        // Hashes are stored in internal byte order: trailing zeros
+        assert_eq!(mainnet.default_assume_valid[24..], [0u8; 8]);
+
+        assert_eq!(regtest.min_chain_work, [0u8; 32]);
+        assert_eq!(regtest.default_assume_valid, [0u8; 32]);
+    }
+
     #[test]
     fn test_max_targets() {
         let mainnet = NetworkConstants::mainnet().unwrap();
@@ -332,6 +662,11 @@ mod tests {
             assert_eq!(constants.is_testnet, deserialized.is_testnet);
             assert_eq!(constants.dns_seeds, deserialized.dns_seeds);
             assert_eq!(constants.checkpoints, deserialized.checkpoints);
+            assert_eq!(constants.min_chain_work, deserialized.min_chain_work);
+            assert_eq!(
+                constants.default_assume_valid,
+                deserialized.default_assume_valid
+            );
         }
     }
 
@@ -0,0 +1,137 @@
+//! Proof of Work
+//!
+//! Re-exports the consensus proof-of-work functions and adds protocol-level
+//! helpers for inspecting compact difficulty targets.
+
+pub use bllvm_consensus::pow::*;
+
+use crate::encoding;
+use crate::BlockHeader;
+
+/// Approximate number of leading zero bits a valid block hash needs
+///
+/// Computes `256 - log2(target + 1)` for the target encoded by the compact
+/// `bits` value, i.e. the bit length of the target subtracted from 256.
+/// A zero or negative target can never be met and reports all 256 bits.
+pub fn required_leading_zero_bits(bits: u32) -> u32 {
+    let exponent = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+
+    // Negative or zero targets are unsatisfiable
+    if mantissa == 0 || bits & 0x0080_0000 != 0 {
+        return 256;
+    }
+
+    // Bit length of target = mantissa * 256^(exponent - 3)
+    let mantissa_bits = 32 - mantissa.leading_zeros();
+    let target_bits = if exponent <= 3 {
+        mantissa_bits.saturating_sub(8 * (3 - exponent))
+    } else {
+        mantissa_bits + 8 * (exponent - 3)
+    };
+
+    256u32.saturating_sub(target_bits)
+}
+
+/// Expand compact `bits` into a 256-bit big-endian target
+///
+/// Returns `None` for negative, zero or overflowing encodings.
+pub fn target_from_bits(bits: u32) -> Option<[u8; 32]> {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = bits & 0x007f_ffff;
+
+    if mantissa == 0 || bits & 0x0080_0000 != 0 {
+        return None;
+    }
+
+    let mantissa_bytes = mantissa.to_be_bytes();
+    let mut target = [0u8; 32];
+    for (i, byte) in mantissa_bytes[1..].iter().enumerate() {
+        // Byte i of the 3-byte mantissa lands at 256^(exponent - 1 - i)
+        let power = exponent as isize - 1 - i as isize;
+        if power < 0 {
+            continue;
+        }
+        if power >= 32 {
+            if *byte != 0 {
+                return None;
+            }
+            continue;
+        }
+        target[31 - power as usize] = *byte;
+    }
+
+    if target == [0u8; 32] {
+        None
+    } else {
+        Some(target)
+    }
+}
+
+/// Check that a header's hash is at or below the target encoded in its `bits`
+pub fn header_meets_target(header: &BlockHeader) -> bool {
+    let target = match target_from_bits(header.bits as u32) {
+        Some(target) => target,
+        None => return false,
+    };
+
+    // Block hashes compare as little-endian 256-bit integers
+    let mut hash = encoding::block_hash(header);
+    hash.reverse();
+    hash <= target
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mainnet_genesis_leading_zero_bits() {
+        // 0x1d00ffff: target = 0xffff * 2^208, a hash needs 32 leading zero bits
+        assert_eq!(required_leading_zero_bits(0x1d00ffff), 32);
+    }
+
+    #[test]
+    fn test_regtest_leading_zero_bits() {
+        // 0x207fffff is the easiest regtest target: only the top bit must be zero
+        assert_eq!(required_leading_zero_bits(0x207fffff), 1);
+    }
+
+    #[test]
+    fn test_harder_target_needs_more_zeros() {
+        // Block 100,000 on mainnet
+        let easy = required_leading_zero_bits(0x1d00ffff);
+        let hard = required_leading_zero_bits(0x1b04864c);
+        assert!(hard > easy);
+        assert_eq!(hard, 45);
+    }
+
+    #[test]
+    fn test_target_from_bits() {
+        let target = target_from_bits(0x1d00ffff).unwrap();
+        assert_eq!(target[..6], [0x00, 0x00, 0x00, 0x00, 0xff, 0xff]);
+        assert!(target[6..].iter().all(|b| *b == 0));
+
+        let regtest = target_from_bits(0x207fffff).unwrap();
+        assert_eq!(regtest[..3], [0x7f, 0xff, 0xff]);
+
+        assert_eq!(target_from_bits(0x1d000000), None);
+        assert_eq!(target_from_bits(0xff123456), None);
+    }
+
+    #[test]
+    fn test_genesis_header_meets_target() {
+        let mut block = crate::genesis::mainnet_genesis();
+        block.header.merkle_root = crate::encoding::block_merkle_root(&block);
+        assert!(header_meets_target(&block.header));
+
+        block.header.nonce += 1;
+        assert!(!header_meets_target(&block.header));
+    }
+
+    #[test]
+    fn test_unsatisfiable_targets() {
+        assert_eq!(required_leading_zero_bits(0x1d000000), 256);
+        assert_eq!(required_leading_zero_bits(0x1d800001), 256);
+    }
+}
@@ -0,0 +1,89 @@
+//! Standard Script Templates
+//!
+//! Builders for the canonical output scripts. `validation::classify_script`
+//! recognises exactly these templates.
+
+/// OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+pub fn p2pkh_script(pubkey_hash: [u8; 20]) -> Vec<u8> {
+    let mut script = vec![0x76, 0xa9, 0x14];
+    script.extend_from_slice(&pubkey_hash);
+    script.extend_from_slice(&[0x88, 0xac]);
+    script
+}
+
+/// OP_HASH160 <20 bytes> OP_EQUAL
+pub fn p2sh_script(script_hash: [u8; 20]) -> Vec<u8> {
+    let mut script = vec![0xa9, 0x14];
+    script.extend_from_slice(&script_hash);
+    script.push(0x87);
+    script
+}
+
+/// OP_0 <20 bytes>
+pub fn p2wpkh_script(pubkey_hash: [u8; 20]) -> Vec<u8> {
+    let mut script = vec![0x00, 0x14];
+    script.extend_from_slice(&pubkey_hash);
+    script
+}
+
+/// OP_0 <32 bytes>
+pub fn p2wsh_script(script_hash: [u8; 32]) -> Vec<u8> {
+    let mut script = vec![0x00, 0x20];
+    script.extend_from_slice(&script_hash);
+    script
+}
+
+/// OP_1 <32-byte x-only output key>
+pub fn p2tr_script(output_key: [u8; 32]) -> Vec<u8> {
+    let mut script = vec![0x51, 0x20];
+    script.extend_from_slice(&output_key);
+    script
+}
+
+/// `OP_n <program>`: any witness version, with OP_0 for version 0
+///
+/// The program is a single direct push, so it must be at most 75 bytes;
+/// BIP141 programs are at most 40.
+pub fn witness_program_script(witness_version: u8, program: &[u8]) -> Vec<u8> {
+    let version_opcode = if witness_version == 0 {
+        0x00
+    } else {
+        0x50 + witness_version
+    };
+    let mut script = vec![version_opcode, program.len() as u8];
+    script.extend_from_slice(program);
+    script
+}
+
+/// `OP_m <pubkeys...> OP_n OP_CHECKMULTISIG`
+///
+/// `required` and the number of keys must both be between 1 and 16, and
+/// each key must be a 33- or 65-byte public key for the result to classify
+/// as multisig.
+pub fn multisig_script(required: u8, pubkeys: &[Vec<u8>]) -> Vec<u8> {
+    let mut script = vec![0x50 + required];
+    for pubkey in pubkeys {
+        script.push(pubkey.len() as u8);
+        script.extend_from_slice(pubkey);
+    }
+    script.extend_from_slice(&[0x50 + pubkeys.len() as u8, 0xae]);
+    script
+}
+
+/// `OP_RETURN <data>`, using the smallest push for `data`
+///
+/// `data` longer than 255 bytes is pushed with OP_PUSHDATA2.
+pub fn op_return_script(data: &[u8]) -> Vec<u8> {
+    let mut script = vec![0x6a];
+    match data.len() {
+        0 => return script,
+        len @ 1..=0x4b => script.push(len as u8),
+        len @ 0x4c..=0xff => script.extend_from_slice(&[0x4c, len as u8]),
+        len => {
+            script.push(0x4d);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+    script
+}
@@ -0,0 +1,132 @@
+//! Signet (BIP325)
+//!
+//! Signets are test networks whose blocks must carry a solution to the
+//! network's challenge script. Each challenge defines its own network, with
+//! magic bytes derived from the challenge; every signet shares the same
+//! genesis block.
+
+use crate::{crypto, encoding, genesis, NetworkParameters, ProtocolError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Default signet challenge: a 1-of-2 multisig
+pub const DEFAULT_SIGNET_CHALLENGE: [u8; 71] = [
+    0x51, 0x21, 0x03, 0xad, 0x5e, 0x0e, 0xda, 0xd1, 0x8c, 0xb1, 0xf0, 0xfc, 0x0d, 0x28, 0xa3, 0xd4,
+    0xf1, 0xf3, 0xe4, 0x45, 0x64, 0x03, 0x37, 0x48, 0x9a, 0xbb, 0x10, 0x40, 0x4f, 0x2d, 0x1e, 0x08,
+    0x6b, 0xe4, 0x30, 0x21, 0x03, 0x59, 0xef, 0x50, 0x21, 0x96, 0x4f, 0xe2, 0x2d, 0x6f, 0x8e, 0x05,
+    0xb2, 0x46, 0x3c, 0x95, 0x40, 0xce, 0x96, 0x88, 0x3f, 0xe3, 0xb2, 0x78, 0x76, 0x0f, 0x04, 0x8f,
+    0x51, 0x89, 0xf2, 0xe6, 0xc4, 0x52, 0xae,
+];
+
+/// Default signet P2P port, also used by custom signets
+pub const SIGNET_DEFAULT_PORT: u16 = 38333;
+
+/// Parameters selecting a signet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignetParams {
+    /// Script every block's signet solution must satisfy
+    pub challenge: Vec<u8>,
+}
+
+impl Default for SignetParams {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIGNET_CHALLENGE.to_vec())
+    }
+}
+
+impl SignetParams {
+    /// Signet defined by `challenge`
+    pub fn new(challenge: Vec<u8>) -> Self {
+        Self { challenge }
+    }
+
+    /// Whether this is the default (public) signet
+    pub fn is_default(&self) -> bool {
+        self.challenge == DEFAULT_SIGNET_CHALLENGE
+    }
+
+    /// Network magic: the first four bytes of the double SHA256 of the
+    /// length-prefixed challenge
+    pub fn magic_bytes(&self) -> [u8; 4] {
+        let mut data = Vec::with_capacity(self.challenge.len() + 9);
+        encoding::write_varint(&mut data, self.challenge.len() as u64);
+        data.extend_from_slice(&self.challenge);
+        let hash = crypto::sha256d(&data);
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Network parameters for this signet
+    pub fn network_params(&self) -> Result<NetworkParameters> {
+        if self.challenge.is_empty() {
+            return Err(ProtocolError::InvalidConfig(
+                "signet challenge is empty".to_string(),
+            ));
+        }
+        Ok(NetworkParameters {
+            magic_bytes: self.magic_bytes(),
+            default_port: SIGNET_DEFAULT_PORT,
+            genesis_block: genesis::signet_genesis(),
+            max_target: 0x1e0377ae,
+            halving_interval: 210000,
+            network_name: "signet".to_string(),
+            is_testnet: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitcoinProtocolEngine, ProtocolVersion};
+
+    #[test]
+    fn test_default_signet_magic() {
+        let params = SignetParams::default();
+        assert!(params.is_default());
+        assert_eq!(params.magic_bytes(), [0x0a, 0x03, 0xcf, 0x40]);
+
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Signet).unwrap();
+        assert_eq!(engine.signet_params(), Some(&params));
+        assert_eq!(
+            engine.get_network_params().magic_bytes,
+            crate::network_params::NetworkConstants::signet()
+                .unwrap()
+                .magic_bytes
+        );
+    }
+
+    #[test]
+    fn test_custom_signet_engine() {
+        // OP_TRUE: anyone can sign blocks
+        let params = SignetParams::new(vec![0x51]);
+        let engine = BitcoinProtocolEngine::new_signet(params.clone()).unwrap();
+
+        assert_eq!(engine.get_protocol_version(), ProtocolVersion::Signet);
+        assert_eq!(engine.signet_params().unwrap().challenge, vec![0x51]);
+        assert!(!engine.signet_params().unwrap().is_default());
+
+        let network = engine.get_network_params();
+        assert_eq!(network.magic_bytes, params.magic_bytes());
+        assert_ne!(network.magic_bytes, SignetParams::default().magic_bytes());
+        assert_eq!(network.genesis_block, genesis::signet_genesis());
+
+        // The challenge survives a serialization round trip
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(serde_json::from_str::<SignetParams>(&json).unwrap(), params);
+    }
+
+    #[test]
+    fn test_empty_challenge_rejected() {
+        let err = SignetParams::new(vec![]).network_params().unwrap_err();
+        assert!(err.to_string().contains("challenge is empty"), "{err}");
+        let Err(err) = BitcoinProtocolEngine::new_signet(SignetParams::new(vec![])) else {
+            panic!("engine accepted an empty challenge");
+        };
+        assert!(err.to_string().contains("challenge is empty"), "{err}");
+    }
+
+    #[test]
+    fn test_non_signet_engines_have_no_signet_params() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        assert!(engine.signet_params().is_none());
+    }
+}
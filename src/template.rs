@@ -0,0 +1,254 @@
+//! Block Templates
+//!
+//! Assembles candidate blocks for miners from a set of mempool transactions.
+//! The template is complete except for proof of work: the nonce is left at
+//! zero for the miner to grind.
+
+use crate::validation::{
+    bip34_height_script, ProtocolValidationRules, MAX_COINBASE_SCRIPT_SIG_SIZE,
+    MIN_COINBASE_SCRIPT_SIG_SIZE, WITNESS_SCALE_FACTOR,
+};
+use crate::{encoding, BitcoinProtocolEngine, Block, BlockHeader, ConsensusError, Result};
+use bllvm_consensus::types::{OutPoint, TransactionInput, TransactionOutput};
+use bllvm_consensus::Transaction;
+use std::collections::HashSet;
+
+/// Version of assembled blocks: BIP9 top bits with no deployment signalled
+pub const TEMPLATE_BLOCK_VERSION: i64 = 0x2000_0000;
+
+impl BitcoinProtocolEngine {
+    /// Assemble a block template on top of `prev_hash` at `height`
+    ///
+    /// `mempool` holds candidate transactions with their fees. They are
+    /// picked greedily by fee rate until the network's block weight limit;
+    /// a transaction spending another mempool transaction is only picked
+    /// after its parent, so the template stays correctly ordered. The
+    /// coinbase scriptSig is the BIP34 height followed by `coinbase_script`,
+    /// and its single output pays the subsidy plus the selected fees to
+    /// `payout_script`.
+    ///
+    /// Mempool transactions carry no witness data, so no witness commitment
+    /// is added. `bits` is the network's proof-of-work limit; on networks that
+    /// retarget, the caller replaces it with the required difficulty.
+    pub fn build_block_template(
+        &self,
+        prev_hash: [u8; 32],
+        height: u64,
+        mempool: &[(Transaction, u64)],
+        coinbase_script: Vec<u8>,
+        payout_script: Vec<u8>,
+    ) -> Result<Block> {
+        self.assert_mining_supported()?;
+
+        let mut script_sig = bip34_height_script(height);
+        script_sig.extend_from_slice(&coinbase_script);
+        if script_sig.len() < MIN_COINBASE_SCRIPT_SIG_SIZE {
+            script_sig.push(0x00);
+        }
+        if script_sig.len() > MAX_COINBASE_SCRIPT_SIG_SIZE {
+            return Err(ConsensusError::BlockValidation(format!(
+                "coinbase scriptSig of {} bytes exceeds {MAX_COINBASE_SCRIPT_SIG_SIZE}",
+                script_sig.len()
+            ))
+            .into());
+        }
+        let mut coinbase = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0xffffffff,
+                },
+                script_sig,
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 0,
+                script_pubkey: payout_script,
+            }],
+            lock_time: 0,
+        };
+
+        // Header, the largest transaction count varint and the coinbase
+        let rules = ProtocolValidationRules::for_protocol(self.protocol_version);
+        let reserved = (80 + 9 + encoding::serialize_transaction(&coinbase).len() as u64)
+            * WITNESS_SCALE_FACTOR;
+        let budget = (rules.max_block_weight as u64).saturating_sub(reserved);
+        let (selected, fees) = select_transactions(mempool, budget);
+
+        let subsidy = self.get_economic_parameters().get_block_subsidy(height);
+        coinbase.outputs[0].value = subsidy.saturating_add(fees) as i64;
+
+        let mut transactions = Vec::with_capacity(selected.len() + 1);
+        transactions.push(coinbase);
+        transactions.extend(selected.into_iter().map(|index| mempool[index].0.clone()));
+
+        let mut block = Block {
+            header: BlockHeader {
+                version: TEMPLATE_BLOCK_VERSION,
+                prev_block_hash: prev_hash,
+                merkle_root: [0u8; 32],
+                timestamp: self.clock.unix_time(),
+                bits: self.network_params.max_target as u64,
+                nonce: 0,
+            },
+            transactions,
+        };
+        block.header.merkle_root = encoding::block_merkle_root(&block);
+        Ok(block)
+    }
+}
+
+/// Greedily pick mempool indices by fee rate within `budget` weight units
+///
+/// Returns the picks in block order with their total fee. A transaction
+/// whose mempool parent has not been picked yet waits for a later pass.
+fn select_transactions(mempool: &[(Transaction, u64)], budget: u64) -> (Vec<usize>, u64) {
+    let txids: Vec<[u8; 32]> = mempool.iter().map(|(tx, _)| encoding::txid(tx)).collect();
+    let weights: Vec<u64> = mempool
+        .iter()
+        .map(|(tx, _)| encoding::serialize_transaction(tx).len() as u64 * WITNESS_SCALE_FACTOR)
+        .collect();
+    let in_mempool: HashSet<[u8; 32]> = txids.iter().copied().collect();
+
+    // Highest fee per weight unit first, mempool order among equals
+    let mut candidates: Vec<usize> = (0..mempool.len()).collect();
+    candidates.sort_by(|&a, &b| {
+        let rate_a = mempool[a].1 as u128 * weights[b] as u128;
+        let rate_b = mempool[b].1 as u128 * weights[a] as u128;
+        rate_b.cmp(&rate_a)
+    });
+
+    let mut selected = Vec::new();
+    let mut included: HashSet<[u8; 32]> = HashSet::new();
+    let mut used = 0u64;
+    let mut fees = 0u64;
+    loop {
+        let picked_before = selected.len();
+        candidates.retain(|&index| {
+            if used + weights[index] > budget {
+                return true;
+            }
+            let parents_included = mempool[index].0.inputs.iter().all(|input| {
+                !in_mempool.contains(&input.prevout.hash) || included.contains(&input.prevout.hash)
+            });
+            if !parents_included {
+                return true;
+            }
+            used += weights[index];
+            fees = fees.saturating_add(mempool[index].1);
+            included.insert(txids[index]);
+            selected.push(index);
+            false
+        });
+        if selected.len() == picked_before {
+            return (selected, fees);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::TransactionBuilder;
+    use crate::validation::{check_coinbase_placement, extract_bip34_height};
+    use crate::ProtocolVersion;
+
+    /// ~360k weight units: about eleven fit in a 4M weight block
+    fn filler(seed: u8) -> Transaction {
+        TransactionBuilder::new()
+            .add_input(
+                OutPoint {
+                    hash: [seed; 32],
+                    index: 0,
+                },
+                vec![],
+                0xffffffff,
+            )
+            .add_output(1000, vec![0x51; 90_000])
+            .build()
+    }
+
+    #[test]
+    fn test_block_template_respects_weight_and_pays_fees() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        // Fees rise with the seed; the cheapest don't fit
+        let mempool: Vec<(Transaction, u64)> = (1..=15u8)
+            .map(|seed| (filler(seed), seed as u64 * 10_000))
+            .collect();
+
+        let block = engine
+            .build_block_template([7; 32], 200, &mempool, b"pool".to_vec(), vec![0x51])
+            .unwrap();
+
+        let weight = encoding::serialize_block(&block).len() as u64 * WITNESS_SCALE_FACTOR;
+        let rules = ProtocolValidationRules::for_protocol(ProtocolVersion::Regtest);
+        assert!(weight <= rules.max_block_weight as u64, "weight {weight}");
+        assert!(block.transactions.len() > 1 && block.transactions.len() < 16);
+
+        // The most valuable transactions were picked
+        let selected = &block.transactions[1..];
+        assert!(selected.contains(&mempool[14].0));
+        assert!(!selected.contains(&mempool[0].0));
+
+        let fees: u64 = mempool
+            .iter()
+            .filter(|(tx, _)| selected.contains(tx))
+            .map(|(_, fee)| fee)
+            .sum();
+        let subsidy = engine.get_economic_parameters().get_block_subsidy(200);
+        assert_eq!(
+            block.transactions[0].outputs[0].value as u64,
+            subsidy + fees
+        );
+
+        check_coinbase_placement(&block).unwrap();
+        assert_eq!(extract_bip34_height(&block.transactions[0]).unwrap(), 200);
+        assert!(block.transactions[0].inputs[0]
+            .script_sig
+            .ends_with(b"pool"));
+        assert_eq!(
+            block.header.merkle_root,
+            encoding::block_merkle_root(&block)
+        );
+        assert_eq!(block.header.prev_block_hash, [7; 32]);
+        assert_eq!(block.header.nonce, 0);
+        assert_eq!(
+            block.header.bits,
+            engine.get_network_params().max_target as u64
+        );
+    }
+
+    #[test]
+    fn test_block_template_orders_parents_first() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let parent = filler(1);
+        let child = TransactionBuilder::new()
+            .add_input(
+                OutPoint {
+                    hash: encoding::txid(&parent),
+                    index: 0,
+                },
+                vec![],
+                0xffffffff,
+            )
+            .add_output(500, vec![0x51])
+            .build();
+        // The child pays far more per weight unit than its parent
+        let mempool = vec![(child.clone(), 50_000), (parent.clone(), 100)];
+
+        let block = engine
+            .build_block_template([0; 32], 1, &mempool, vec![], vec![0x51])
+            .unwrap();
+        assert_eq!(block.transactions[1..], [parent, child]);
+    }
+
+    #[test]
+    fn test_block_template_rejects_long_coinbase_script() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let err = engine
+            .build_block_template([0; 32], 1, &[], vec![0; 100], vec![0x51])
+            .unwrap_err();
+        assert!(err.to_string().contains("coinbase scriptSig"), "{err}");
+    }
+}
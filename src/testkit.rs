@@ -0,0 +1,256 @@
+//! Test Kit
+//!
+//! Deterministic builders for transactions, blocks and whole chains, for
+//! consensus tests here and in downstream crates. Available with the
+//! `test-util` feature.
+//!
+//! Mining grinds nonces, so it is only practical on networks with a trivial
+//! target such as regtest and simnet.
+
+use crate::validation::bip34_height_script;
+use crate::{encoding, pow, BitcoinProtocolEngine, Block, BlockHeader, Hash, Transaction};
+use bllvm_consensus::types::{OutPoint, TransactionInput, TransactionOutput, UtxoSet};
+
+/// Anyone-can-spend script (OP_TRUE) paid by generated coinbases
+pub const OP_TRUE_SCRIPT: [u8; 1] = [0x51];
+
+/// Seconds between the timestamps of blocks produced by `mine_chain`
+pub const BLOCK_SPACING: u64 = 600;
+
+/// Block builder with regtest defaults
+#[derive(Debug, Clone)]
+pub struct BlockBuilder {
+    header: BlockHeader,
+    transactions: Vec<Transaction>,
+}
+
+impl BlockBuilder {
+    /// Empty version 4 block on top of `prev_block_hash`, at regtest difficulty
+    pub fn new(prev_block_hash: Hash) -> Self {
+        Self {
+            header: BlockHeader {
+                version: 4,
+                prev_block_hash,
+                merkle_root: [0u8; 32],
+                timestamp: 0,
+                bits: 0x207fffff,
+                nonce: 0,
+            },
+            transactions: Vec::new(),
+        }
+    }
+
+    pub fn with_version(mut self, version: i64) -> Self {
+        self.header.version = version;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.header.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_bits(mut self, bits: u64) -> Self {
+        self.header.bits = bits;
+        self
+    }
+
+    /// Append a BIP34 coinbase for `height` paying `value` to `OP_TRUE_SCRIPT`
+    pub fn add_coinbase(self, height: u64, value: i64) -> Self {
+        self.add_transaction(coinbase(height, value))
+    }
+
+    pub fn add_transaction(mut self, tx: Transaction) -> Self {
+        self.transactions.push(tx);
+        self
+    }
+
+    /// Finish the block with its merkle root filled in, without mining it
+    pub fn build(self) -> Block {
+        let mut block = Block {
+            header: self.header,
+            transactions: self.transactions,
+        };
+        block.header.merkle_root = encoding::block_merkle_root(&block);
+        block
+    }
+
+    /// Finish the block and grind the nonce until it meets its own target
+    pub fn mine(self) -> Block {
+        let mut block = self.build();
+        while !pow::header_meets_target(&block.header) {
+            block.header.nonce += 1;
+        }
+        block
+    }
+}
+
+/// Transaction builder; version 1 with no inputs or outputs by default
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    tx: Transaction,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransactionBuilder {
+    pub fn new() -> Self {
+        Self {
+            tx: Transaction {
+                version: 1,
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                lock_time: 0,
+            },
+        }
+    }
+
+    pub fn with_version(mut self, version: u64) -> Self {
+        self.tx.version = version;
+        self
+    }
+
+    pub fn with_lock_time(mut self, lock_time: u64) -> Self {
+        self.tx.lock_time = lock_time;
+        self
+    }
+
+    pub fn add_input(mut self, prevout: OutPoint, script_sig: Vec<u8>, sequence: u64) -> Self {
+        self.tx.inputs.push(TransactionInput {
+            prevout,
+            script_sig,
+            sequence,
+        });
+        self
+    }
+
+    pub fn add_output(mut self, value: i64, script_pubkey: Vec<u8>) -> Self {
+        self.tx.outputs.push(TransactionOutput {
+            value,
+            script_pubkey,
+        });
+        self
+    }
+
+    pub fn build(self) -> Transaction {
+        self.tx
+    }
+}
+
+/// Coinbase for `height` paying `value` to `OP_TRUE_SCRIPT`
+///
+/// The scriptSig is the BIP34 height padded to the two-byte minimum.
+pub fn coinbase(height: u64, value: i64) -> Transaction {
+    let mut script_sig = bip34_height_script(height);
+    if script_sig.len() < 2 {
+        script_sig.push(0x00);
+    }
+    TransactionBuilder::new()
+        .add_input(
+            OutPoint {
+                hash: [0u8; 32],
+                index: 0xffffffff,
+            },
+            script_sig,
+            0xffffffff,
+        )
+        .add_output(value, OP_TRUE_SCRIPT.to_vec())
+        .build()
+}
+
+/// Mine `count` coinbase-only blocks on top of the engine's genesis block
+///
+/// Block `n` is at height `n`, claims the full subsidy, is timestamped
+/// `n * BLOCK_SPACING` seconds after genesis and uses the network's maximum
+/// target. Each block's outputs are added to `start_utxos` at its height, so
+/// earlier coinbases mature as the chain grows. Returns the blocks and the
+/// resulting UTXO set.
+pub fn mine_chain(
+    engine: &BitcoinProtocolEngine,
+    count: u64,
+    start_utxos: UtxoSet,
+) -> (Vec<Block>, UtxoSet) {
+    let network = engine.get_network_params();
+    let economics = engine.get_economic_parameters();
+    let genesis = &network.genesis_block.header;
+
+    let mut utxos = start_utxos;
+    let mut blocks: Vec<Block> = Vec::with_capacity(count as usize);
+    let mut prev_hash = encoding::block_hash(genesis);
+    for height in 1..=count {
+        let block = BlockBuilder::new(prev_hash)
+            .with_timestamp(genesis.timestamp + height * BLOCK_SPACING)
+            .with_bits(network.max_target as u64)
+            .add_coinbase(height, economics.get_block_subsidy(height) as i64)
+            .mine();
+
+        let delta = engine.utxo_delta(&block, height);
+        for outpoint in &delta.spent {
+            utxos.remove(outpoint);
+        }
+        utxos.extend(
+            delta
+                .created
+                .into_iter()
+                .map(|created| (created.outpoint, created.utxo)),
+        );
+        prev_hash = encoding::block_hash(&block.header);
+        blocks.push(block);
+    }
+    (blocks, utxos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProtocolVersion;
+
+    #[test]
+    fn test_mine_regtest_chain() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let (blocks, mined_utxos) = mine_chain(&engine, 10, UtxoSet::new());
+        assert_eq!(blocks.len(), 10);
+
+        let genesis = &engine.get_network_params().genesis_block.header;
+        let mut prev_hash = encoding::block_hash(genesis);
+        let mut timestamps = vec![genesis.timestamp as u32];
+        let mut utxos = UtxoSet::new();
+        for (block, height) in blocks.iter().zip(1..) {
+            engine
+                .fully_validate_block(
+                    block,
+                    &prev_hash,
+                    height,
+                    &mut utxos,
+                    block.header.timestamp,
+                    &timestamps,
+                )
+                .unwrap_or_else(|err| panic!("block {height}: {err}"));
+            prev_hash = encoding::block_hash(&block.header);
+            timestamps.push(block.header.timestamp as u32);
+        }
+        assert_eq!(utxos, mined_utxos);
+        assert_eq!(utxos.len(), 10);
+    }
+
+    #[test]
+    fn test_mine_chain_is_deterministic() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let (first, _) = mine_chain(&engine, 3, UtxoSet::new());
+        let (second, _) = mine_chain(&engine, 3, UtxoSet::new());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_coinbase_pads_small_heights() {
+        assert_eq!(coinbase(1, 0).inputs[0].script_sig, vec![0x51, 0x00]);
+        assert_eq!(
+            coinbase(1000, 0).inputs[0].script_sig,
+            vec![0x02, 0xe8, 0x03]
+        );
+    }
+}
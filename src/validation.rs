@@ -4,17 +4,102 @@
 //! the pure mathematical consensus rules with network-specific
 //! and protocol-specific validation logic.
 
-use crate::{BitcoinProtocolEngine, NetworkParameters, ProtocolVersion, Result};
-use bllvm_consensus::types::{OutPoint, UTXO};
-use bllvm_consensus::{Block, Transaction, ValidationResult};
+use crate::difficulty::ChainWork;
+use crate::encoding::{varint_len, Reader, WitnessStack};
+use crate::features::FeatureContext;
+use crate::network_params::NetworkConstants;
+use crate::{
+    crypto, encoding, pow, BitcoinProtocolEngine, EconomicParameters, Hash, NetworkParameters,
+    ProtocolError, ProtocolVersion, Result,
+};
+use bllvm_consensus::error::ConsensusError;
+use bllvm_consensus::types::{OutPoint, TransactionInput, UTXO};
+use bllvm_consensus::{Block, BlockHeader, Transaction, ValidationResult};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+pub mod sighash;
+pub mod standard;
+pub mod taproot;
+
+pub use sighash::{SighashBase, SighashType};
+pub use standard::{check_standard_tx, classify_script, is_standard, ScriptType};
+
+/// Maximum number of seconds a block timestamp may be ahead of local time
+pub const MAX_FUTURE_BLOCK_TIME: u64 = 2 * 60 * 60;
+
+/// Minimum coinbase scriptSig size
+pub const MIN_COINBASE_SCRIPT_SIG_SIZE: usize = 2;
+
+/// Maximum coinbase scriptSig size, including the BIP34 height
+pub const MAX_COINBASE_SCRIPT_SIG_SIZE: usize = 100;
+
+/// Number of previous blocks used for the median-time-past
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Maximum signature-operation cost per block
+pub const MAX_BLOCK_SIGOPS_COST: u64 = 80_000;
+
+/// Weight multiplier applied to non-witness bytes and legacy sigops
+pub const WITNESS_SCALE_FACTOR: u64 = 4;
+
+/// BIP125 rule 4: minimum extra fee rate (sat/vbyte) a replacement must pay
+pub const INCREMENTAL_RELAY_FEE: u64 = 1;
+
+/// Opcode marking a provably unspendable data-carrier output
+pub const OP_RETURN: u8 = 0x6a;
+
+/// Maximum number of non-push opcodes in a script
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+
+/// Maximum size of a single pushed stack element
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+/// Opcodes that fail a script wherever they appear (OP_CAT, OP_MUL, ...)
+pub const DISABLED_OPCODES: [u8; 15] = [
+    0x7e, 0x7f, 0x80, 0x81, 0x83, 0x84, 0x85, 0x86, 0x8d, 0x8e, 0x95, 0x96, 0x97, 0x98, 0x99,
+];
+
+/// BIP141 witness commitment output prefix: OP_RETURN, push 36, 0xaa21a9ed
+pub const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// BIP325: marker opening the signet solution push in the witness commitment
+pub const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// Lock times below this value are block heights, above it Unix timestamps
+pub const LOCKTIME_THRESHOLD: u64 = 500_000_000;
+
+/// Sequence number of an input with no relative lock, RBF or lock time
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// BIP68: sequence bit disabling the relative lock time
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+
+/// BIP68: sequence bit selecting a time-based relative lock
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+/// BIP68: mask for the relative lock value
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+
+/// Default mempool ancestor count limit, including the transaction itself
+pub const DEFAULT_ANCESTOR_LIMIT: usize = 25;
+
+/// Default mempool descendant count limit, including the transaction itself
+pub const DEFAULT_DESCENDANT_LIMIT: usize = 25;
+
+/// Default ancestor and descendant package vsize limit (101 kvB)
+pub const DEFAULT_PACKAGE_VSIZE_LIMIT: u64 = 101_000;
+
+/// Smallest `max_block_weight` accepted by `with_max_block_weight`
+pub const MIN_BLOCK_WEIGHT: u32 = 4_000;
 
 /// Protocol-specific validation rules
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProtocolValidationRules {
     /// Maximum block size for this protocol
     pub max_block_size: u32,
+    /// Maximum block weight (BIP141 weight units) for this protocol
+    pub max_block_weight: u32,
     /// Maximum transaction size for this protocol
     pub max_tx_size: u32,
     /// Maximum script size for this protocol
@@ -29,6 +114,28 @@ pub struct ProtocolValidationRules {
     pub min_fee_rate: u64,
     /// Maximum transaction fee rate
     pub max_fee_rate: u64,
+    /// Maximum OP_RETURN scriptPubKey size, including the opcode and pushes
+    pub max_op_return_size: usize,
+    /// Maximum number of OP_RETURN outputs per transaction
+    pub max_op_return_count: usize,
+    /// Whether transactions must pass relay standardness (`check_standard_tx`)
+    pub require_standard: bool,
+    /// Orphan blocks the node should buffer while fetching their parents
+    pub max_orphan_blocks: usize,
+    /// Orphan transactions the node should buffer while fetching their inputs
+    pub max_orphan_tx: usize,
+    /// Lowest transaction version accepted
+    pub min_tx_version: i32,
+    /// Highest transaction version accepted
+    pub max_tx_version: i32,
+    /// Unconfirmed ancestors a mempool transaction may have, counting itself
+    pub max_ancestors: usize,
+    /// Total vsize of a transaction and its unconfirmed ancestors
+    pub max_ancestor_vsize: u64,
+    /// Unconfirmed descendants a mempool transaction may have, counting itself
+    pub max_descendants: usize,
+    /// Total vsize of a transaction and its unconfirmed descendants
+    pub max_descendant_vsize: u64,
 }
 
 impl ProtocolValidationRules {
@@ -36,8 +143,10 @@ impl ProtocolValidationRules {
     pub fn for_protocol(version: ProtocolVersion) -> Self {
         match version {
             ProtocolVersion::BitcoinV1 => Self::mainnet(),
-            ProtocolVersion::Testnet3 => Self::testnet(),
-            ProtocolVersion::Regtest => Self::regtest(),
+            ProtocolVersion::Testnet3 | ProtocolVersion::Signet => Self::testnet(),
+            ProtocolVersion::Regtest | ProtocolVersion::Simnet | ProtocolVersion::Educational => {
+                Self::regtest()
+            }
         }
     }
 
@@ -45,13 +154,25 @@ impl ProtocolValidationRules {
     pub fn mainnet() -> Self {
         Self {
             max_block_size: 4_000_000, // 4MB block size limit
-            max_tx_size: 1_000_000,    // 1MB transaction size limit
-            max_script_size: 10_000,   // 10KB script size limit
+            max_block_weight: 4_000_000,
+            max_tx_size: 1_000_000,  // 1MB transaction size limit
+            max_script_size: 10_000, // 10KB script size limit
             segwit_enabled: true,
             taproot_enabled: true,
             rbf_enabled: true,
             min_fee_rate: 1,         // 1 sat/vB minimum
             max_fee_rate: 1_000_000, // 1M sat/vB maximum
+            max_op_return_size: 83,  // 80 bytes of data
+            max_op_return_count: 1,
+            require_standard: true,
+            max_orphan_blocks: 100,
+            max_orphan_tx: 100,
+            min_tx_version: 1,
+            max_tx_version: 2,
+            max_ancestors: DEFAULT_ANCESTOR_LIMIT,
+            max_ancestor_vsize: DEFAULT_PACKAGE_VSIZE_LIMIT,
+            max_descendants: DEFAULT_DESCENDANT_LIMIT,
+            max_descendant_vsize: DEFAULT_PACKAGE_VSIZE_LIMIT,
         }
     }
 
@@ -59,6 +180,7 @@ impl ProtocolValidationRules {
     pub fn testnet() -> Self {
         Self {
             max_block_size: 4_000_000,
+            max_block_weight: 4_000_000,
             max_tx_size: 1_000_000,
             max_script_size: 10_000,
             segwit_enabled: true,
@@ -66,6 +188,17 @@ impl ProtocolValidationRules {
             rbf_enabled: true,
             min_fee_rate: 1,
             max_fee_rate: 1_000_000,
+            max_op_return_size: 83,
+            max_op_return_count: 1,
+            require_standard: true,
+            max_orphan_blocks: 100,
+            max_orphan_tx: 100,
+            min_tx_version: 1,
+            max_tx_version: 2,
+            max_ancestors: DEFAULT_ANCESTOR_LIMIT,
+            max_ancestor_vsize: DEFAULT_PACKAGE_VSIZE_LIMIT,
+            max_descendants: DEFAULT_DESCENDANT_LIMIT,
+            max_descendant_vsize: DEFAULT_PACKAGE_VSIZE_LIMIT,
         }
     }
 
@@ -73,6 +206,7 @@ impl ProtocolValidationRules {
     pub fn regtest() -> Self {
         Self {
             max_block_size: 4_000_000,
+            max_block_weight: 4_000_000,
             max_tx_size: 1_000_000,
             max_script_size: 10_000,
             segwit_enabled: true,
@@ -80,8 +214,199 @@ impl ProtocolValidationRules {
             rbf_enabled: true,
             min_fee_rate: 0, // No minimum fee for testing
             max_fee_rate: 1_000_000,
+            max_op_return_size: 10_000, // Any script within max_script_size
+            max_op_return_count: 100,
+            require_standard: false, // Allow arbitrary scripts in tests
+            max_orphan_blocks: 100,
+            max_orphan_tx: 100,
+            min_tx_version: 1,
+            max_tx_version: 3, // Includes v3 (TRUC) for policy experiments
+            max_ancestors: DEFAULT_ANCESTOR_LIMIT,
+            max_ancestor_vsize: DEFAULT_PACKAGE_VSIZE_LIMIT,
+            max_descendants: DEFAULT_DESCENDANT_LIMIT,
+            max_descendant_vsize: DEFAULT_PACKAGE_VSIZE_LIMIT,
+        }
+    }
+
+    /// Override the maximum block weight, e.g. for block-size research networks
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is below `MIN_BLOCK_WEIGHT`, which could not fit a
+    /// header and coinbase.
+    pub fn with_max_block_weight(mut self, weight: u32) -> Self {
+        assert!(
+            weight >= MIN_BLOCK_WEIGHT,
+            "max block weight {weight} is below the minimum of {MIN_BLOCK_WEIGHT}"
+        );
+        self.max_block_weight = weight;
+        self
+    }
+}
+
+/// Outcome of a successful mempool acceptance check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MempoolAcceptance {
+    /// Fee paid (inputs minus outputs, in satoshis)
+    pub fee: u64,
+    /// Virtual size in vbytes
+    pub vsize: u64,
+    /// Fee rate in satoshis per vbyte
+    pub fee_rate: f64,
+    /// Whether any input opts in to BIP125 replacement
+    pub signals_rbf: bool,
+}
+
+/// Output created by a block, as recorded in a `UtxoDelta`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedUtxo {
+    /// Where the output lives
+    pub outpoint: OutPoint,
+    /// The new UTXO entry
+    pub utxo: UTXO,
+    /// Created by the coinbase, so unspendable until it reaches maturity
+    pub is_coinbase: bool,
+}
+
+/// UTXO-set changes made by connecting a block
+///
+/// Connecting inserts `created` and removes `spent`; disconnecting does the
+/// reverse (restoring the spent entries needs their original data, kept in a
+/// `BlockUndo`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoDelta {
+    /// Every output the block creates, in block order
+    pub created: Vec<CreatedUtxo>,
+    /// Prevouts of every non-coinbase input, in block order
+    pub spent: Vec<OutPoint>,
+}
+
+impl UtxoDelta {
+    /// Whether `outpoint` is one of the block's coinbase outputs
+    pub fn is_coinbase_output(&self, outpoint: &OutPoint) -> bool {
+        self.created
+            .iter()
+            .any(|created| created.is_coinbase && created.outpoint == *outpoint)
+    }
+}
+
+/// Previous UTXO entries a block spent, enough to disconnect it
+///
+/// The counterpart of Bitcoin Core's `undo.dat` records: the UTXO set no
+/// longer holds the spent outputs once the block is connected, so reverting
+/// the block needs them saved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockUndo {
+    /// Spent outpoints with their entries before the block, in block order
+    pub spent: Vec<(OutPoint, UTXO)>,
+}
+
+/// Disconnect `block`, reverting the UTXO changes it made
+///
+/// Removes every output the block created, then restores the entries
+/// recorded in `undo`, leaving `utxos` as it was before the block.
+pub fn apply_undo(utxos: &mut HashMap<OutPoint, UTXO>, undo: &BlockUndo, block: &Block) {
+    for tx in &block.transactions {
+        let txid = encoding::txid(tx);
+        for index in 0..tx.outputs.len() {
+            utxos.remove(&OutPoint {
+                hash: txid,
+                index: index as u64,
+            });
         }
     }
+    utxos.extend(undo.spent.iter().cloned());
+}
+
+/// Which part of a block a `ValidationFinding` concerns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FindingCategory {
+    /// Coinbase placement, BIP34 height or witness commitment
+    Structure,
+    /// Block size or weight
+    Size,
+    /// Block signature operations
+    Sigops,
+    /// Merkle root
+    Merkle,
+    /// Proof of work
+    ProofOfWork,
+    /// Coinbase paying more than subsidy plus fees
+    CoinbaseValue,
+    /// Duplicate transaction ids
+    DuplicateTransaction,
+    /// A single transaction's rules, inputs or standardness
+    Transaction,
+    /// Script and other checks delegated to the consensus layer
+    Consensus,
+}
+
+/// How serious a `ValidationFinding` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FindingSeverity {
+    /// Makes the block invalid
+    Error,
+    /// Relay policy only; the block is still valid
+    Warning,
+}
+
+/// One problem found by `validate_block_verbose`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationFinding {
+    pub category: FindingCategory,
+    pub severity: FindingSeverity,
+    /// Position in the block of the offending transaction, if any
+    pub tx_index: Option<usize>,
+    pub message: String,
+}
+
+/// Every problem found in a block, rather than just the first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockValidationReport {
+    /// No finding has `FindingSeverity::Error`
+    pub valid: bool,
+    /// Findings in the order the checks ran
+    pub findings: Vec<ValidationFinding>,
+}
+
+impl BlockValidationReport {
+    /// Findings that make the block invalid
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationFinding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == FindingSeverity::Error)
+    }
+
+    /// Findings in a given category
+    pub fn in_category(
+        &self,
+        category: FindingCategory,
+    ) -> impl Iterator<Item = &ValidationFinding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.category == category)
+    }
+}
+
+/// Size and fee-rate measurements of one transaction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TxMetrics {
+    /// BIP141 weight: base size times three plus total size
+    pub weight: u64,
+    /// Virtual size, `ceil(weight / 4)`
+    pub vsize: u64,
+    /// Serialized size without witness data
+    pub base_size: u64,
+    /// Serialized size including witness data
+    pub total_size: u64,
+    /// Fee paid, in satoshis
+    pub fee: u64,
+    /// `fee / vsize`
+    pub fee_rate_sat_per_vbyte: f64,
+    /// Whether any input carries witness data
+    pub is_segwit: bool,
+    /// Whether the transaction opts in to BIP125 replacement
+    pub signals_rbf: bool,
 }
 
 /// Protocol-specific validation context
@@ -95,6 +420,12 @@ pub struct ProtocolValidationContext {
     pub validation_rules: ProtocolValidationRules,
     /// Additional context data
     pub context_data: HashMap<String, String>,
+    /// Trusted height below which script validation is skipped (`assumevalid`)
+    ///
+    /// Blocks below this height only get structural, proof-of-work and merkle
+    /// root checks. This trades safety for sync speed and must only be set to
+    /// a block the operator already trusts.
+    pub assume_valid_height: Option<u64>,
 }
 
 impl ProtocolValidationContext {
@@ -108,6 +439,7 @@ impl ProtocolValidationContext {
             network_params,
             validation_rules,
             context_data: HashMap::new(),
+            assume_valid_height: None,
         })
     }
 
@@ -121,6 +453,25 @@ impl ProtocolValidationContext {
         }
     }
 
+    /// Orphan buffer limits as `(max_orphan_blocks, max_orphan_tx)`
+    pub fn orphan_limits(&self) -> (usize, usize) {
+        (
+            self.validation_rules.max_orphan_blocks,
+            self.validation_rules.max_orphan_tx,
+        )
+    }
+
+    /// Whether script checks are skipped at the current block height
+    pub fn is_assumed_valid(&self) -> bool {
+        self.is_assumed_valid_at(self.block_height)
+    }
+
+    /// Whether script checks are skipped for a block at `height`
+    pub fn is_assumed_valid_at(&self, height: u64) -> bool {
+        self.assume_valid_height
+            .is_some_and(|trusted| height < trusted)
+    }
+
     /// Get maximum allowed size for a component
     pub fn get_max_size(&self, component: &str) -> u32 {
         match component {
@@ -141,17 +492,244 @@ impl BitcoinProtocolEngine {
         height: u64,
         context: &ProtocolValidationContext,
     ) -> Result<ValidationResult> {
-        // First, run consensus validation
-        let (consensus_result, _) = self
-            .consensus
-            .validate_block(block, utxos.clone(), height)?;
+        self.validate_block_with_witnesses(block, &[], utxos, height, context)
+    }
+
+    /// Validate a block with protocol-specific rules, including its witness data
+    ///
+    /// `witnesses` holds the per-input witness stacks of each transaction, in
+    /// block order. The witness commitment is only checked once SegWit is active.
+    ///
+    /// Below the context's `assume_valid_height`, script validation is
+    /// skipped: the block structure, proof of work, merkle root, input
+    /// existence, amounts and coinbase value are still checked.
+    pub fn validate_block_with_witnesses(
+        &self,
+        block: &Block,
+        witnesses: &[Vec<WitnessStack>],
+        utxos: &HashMap<OutPoint, UTXO>,
+        height: u64,
+        context: &ProtocolValidationContext,
+    ) -> Result<ValidationResult> {
+        // Structural rules hold regardless of assume-valid
+        check_coinbase_placement(block)?;
+        check_coinbase_script_sig(block)?;
+
+        // Consensus validation, or the cheap subset when scripts are assumed valid
+        let consensus_result = if context.is_assumed_valid_at(height) {
+            check_assumed_valid_block(block)?;
+            self.check_proof_of_work(&block.header, height)?;
+            ValidationResult::Valid
+        } else {
+            self.consensus
+                .validate_block(block, utxos.clone(), height)?
+                .0
+        };
 
         // Then, apply protocol-specific validation
         self.apply_protocol_validation(block, context)?;
 
+        // Spends and amounts are checked whether or not scripts are assumed valid
+        self.connect_block(block, height, utxos)?;
+        if block_weight(block, witnesses) > context.validation_rules.max_block_weight as u64 {
+            return Err(ProtocolError::BlockSizeExceeded(
+                "block weight exceeds maximum".to_string(),
+            ));
+        }
+        self.check_bip34_height(block, height)?;
+
+        if self.feature_context(height, block.header.timestamp).segwit {
+            verify_witness_commitment(block, witnesses)?;
+        }
+
         Ok(consensus_result)
     }
 
+    /// Validate a block like `validate_block_with_protocol`, reporting every
+    /// problem instead of stopping at the first
+    ///
+    /// Each check runs independently and its failure becomes an error
+    /// finding. Non-coinbase transactions are also checked against relay
+    /// policy (version range, standardness, data carriers), which only
+    /// yields warnings. Inputs are spent and the coinbase value checked by
+    /// the same code as `fully_validate_block`; the coinbase value is only
+    /// checked when every transaction connected, since the fees are unknown
+    /// otherwise. `witnesses` holds the per-input witness stacks of each
+    /// transaction, as for `validate_block_with_witnesses`.
+    pub fn validate_block_verbose(
+        &self,
+        block: &Block,
+        witnesses: &[Vec<WitnessStack>],
+        utxos: &HashMap<OutPoint, UTXO>,
+        height: u64,
+        context: &ProtocolValidationContext,
+    ) -> BlockValidationReport {
+        let mut findings = Vec::new();
+        let mut record = |category, severity, tx_index, result: Result<()>| {
+            if let Err(err) = result {
+                findings.push(ValidationFinding {
+                    category,
+                    severity,
+                    tx_index,
+                    message: err.to_string(),
+                });
+            }
+        };
+        let error = FindingSeverity::Error;
+
+        // Block structure
+        record(
+            FindingCategory::Structure,
+            error,
+            None,
+            check_coinbase_placement(block),
+        );
+        record(
+            FindingCategory::Structure,
+            error,
+            None,
+            check_coinbase_script_sig(block),
+        );
+        record(
+            FindingCategory::Structure,
+            error,
+            None,
+            check_intra_block_ordering(block),
+        );
+        record(
+            FindingCategory::Structure,
+            error,
+            None,
+            self.check_bip34_height(block, height),
+        );
+        if self.feature_context(height, block.header.timestamp).segwit {
+            record(
+                FindingCategory::Structure,
+                error,
+                None,
+                verify_witness_commitment(block, witnesses),
+            );
+        }
+        let txids: Vec<Hash> = block.transactions.iter().map(encoding::txid).collect();
+        let merkle = if encoding::merkle_root(&txids) == block.header.merkle_root {
+            Ok(())
+        } else {
+            Err(ConsensusError::BlockValidation("merkle root mismatch".to_string()).into())
+        };
+        record(FindingCategory::Merkle, error, None, merkle);
+        record(
+            FindingCategory::ProofOfWork,
+            error,
+            None,
+            self.check_proof_of_work(&block.header, height),
+        );
+        record(
+            FindingCategory::DuplicateTransaction,
+            error,
+            None,
+            check_duplicate_txids(&txids),
+        );
+
+        // Limits
+        let rules = &context.validation_rules;
+        if self.calculate_block_size(block) > rules.max_block_size {
+            record(
+                FindingCategory::Size,
+                error,
+                None,
+                Err(ProtocolError::BlockSizeExceeded(
+                    "Block size exceeds maximum".to_string(),
+                )),
+            );
+        }
+        if block_weight(block, &[]) > rules.max_block_weight as u64 {
+            record(
+                FindingCategory::Size,
+                error,
+                None,
+                Err(ProtocolError::BlockSizeExceeded(
+                    "block weight exceeds maximum".to_string(),
+                )),
+            );
+        }
+        let sigops: u64 = block.transactions.iter().map(legacy_sigop_count).sum();
+        if sigops * WITNESS_SCALE_FACTOR > MAX_BLOCK_SIGOPS_COST {
+            record(
+                FindingCategory::Sigops,
+                error,
+                None,
+                Err(ProtocolError::SigopLimitExceeded(
+                    "block sigops exceed maximum".to_string(),
+                )),
+            );
+        }
+
+        // Transactions, spending from the UTXO set and earlier outputs
+        let max_money = self.get_economic_parameters().max_money_supply;
+        let mut available = utxos.clone();
+        let mut total_fees: u64 = 0;
+        let mut fees_known = true;
+        for (index, (tx, txid)) in block.transactions.iter().zip(&txids).enumerate() {
+            record(
+                FindingCategory::Transaction,
+                error,
+                Some(index),
+                self.apply_transaction_protocol_validation(tx, context),
+            );
+            // The coinbase never goes through the mempool
+            if !is_coinbase(tx) {
+                let warning = FindingSeverity::Warning;
+                record(
+                    FindingCategory::Transaction,
+                    warning,
+                    Some(index),
+                    self.apply_transaction_policy(tx, context),
+                );
+                if !rules.require_standard {
+                    record(
+                        FindingCategory::Transaction,
+                        warning,
+                        Some(index),
+                        check_standard_tx(tx),
+                    );
+                }
+            }
+
+            match connect_transaction(tx, txid, height, max_money, &mut available) {
+                Ok(fee) => total_fees = total_fees.saturating_add(fee),
+                Err(err) => {
+                    fees_known = false;
+                    record(FindingCategory::Transaction, error, Some(index), Err(err));
+                }
+            }
+        }
+        if fees_known && !block.transactions.is_empty() {
+            record(
+                FindingCategory::CoinbaseValue,
+                error,
+                Some(0),
+                self.check_coinbase_value(block, height, total_fees),
+            );
+        }
+
+        // Scripts and anything else the consensus layer checks
+        let consensus = match self.consensus.validate_block(block, utxos.clone(), height) {
+            Ok((ValidationResult::Invalid(reason), _)) => {
+                Err(ConsensusError::BlockValidation(reason).into())
+            }
+            Ok((ValidationResult::Valid, _)) => Ok(()),
+            Err(err) => Err(err.into()),
+        };
+        record(FindingCategory::Consensus, error, None, consensus);
+
+        BlockValidationReport {
+            valid: !findings
+                .iter()
+                .any(|finding| finding.severity == FindingSeverity::Error),
+            findings,
+        }
+    }
+
     /// Validate a transaction with protocol-specific rules
     pub fn validate_transaction_with_protocol(
         &self,
@@ -161,397 +739,3615 @@ impl BitcoinProtocolEngine {
         // First, run consensus validation
         let consensus_result = self.consensus.validate_transaction(tx)?;
 
-        // Then, apply protocol-specific validation
+        // Then, apply protocol-specific validation and relay policy
         self.apply_transaction_protocol_validation(tx, context)?;
+        self.apply_transaction_policy(tx, context)?;
 
         Ok(consensus_result)
     }
 
-    /// Apply protocol-specific validation rules
-    fn apply_protocol_validation(
+    /// Check that a header's hash meets the target encoded in its `bits`
+    ///
+    /// Always passes when `skip_pow` is active, which only simnet enables.
+    pub fn check_proof_of_work(&self, header: &BlockHeader, height: u64) -> Result<()> {
+        if self.feature_context(height, header.timestamp).skip_pow {
+            return Ok(());
+        }
+        if !pow::header_meets_target(header) {
+            return Err(
+                ConsensusError::BlockValidation("block hash above target".to_string()).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Check that headers form a connected chain and each meets its own target
+    ///
+    /// Each header's `prev_block_hash` must be the hash of the header before
+    /// it; the first header's parent is not checked. `first_height` is the
+    /// height of the first header. As in `check_proof_of_work`, targets are
+    /// not checked while `skip_pow` is active. The error names the index of
+    /// the first offending header.
+    pub fn validate_header_chain(&self, headers: &[BlockHeader], first_height: u64) -> Result<()> {
+        for (index, header) in headers.iter().enumerate() {
+            if index > 0 && header.prev_block_hash != encoding::block_hash(&headers[index - 1]) {
+                return Err(ConsensusError::BlockValidation(format!(
+                    "header {index} does not connect to the previous header"
+                ))
+                .into());
+            }
+            let height = first_height.saturating_add(index as u64);
+            if !self.feature_context(height, header.timestamp).skip_pow
+                && !pow::header_meets_target(header)
+            {
+                return Err(ConsensusError::BlockValidation(format!(
+                    "header {index} hash above target"
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a block timestamp against the median time past and local time
+    ///
+    /// Uses this network's `max_future_block_time` and `mtp_window`; see
+    /// `check_block_timestamp_with`.
+    pub fn check_block_timestamp(
+        &self,
+        header: &BlockHeader,
+        prev_timestamps: &[u32],
+        now: u64,
+    ) -> Result<()> {
+        let constants = NetworkConstants::for_version(self.protocol_version)?;
+        check_block_timestamp_with(header, prev_timestamps, now, &constants)
+    }
+
+    /// Check a block timestamp like `check_block_timestamp`, taking the local
+    /// time from the engine's clock
+    pub fn check_block_timestamp_now(
+        &self,
+        header: &BlockHeader,
+        prev_timestamps: &[u32],
+    ) -> Result<()> {
+        self.check_block_timestamp(header, prev_timestamps, self.clock.unix_time())
+    }
+
+    /// Fully validate a block against its parent and UTXO set, then connect it
+    ///
+    /// Runs every check in order: header context (parent, proof of work,
+    /// timestamp), block structure (coinbase placement, merkle root, duplicate
+    /// transactions, weight, sigops), per-transaction checks, and finally input
+    /// spending and the coinbase value limit. `utxos` is only updated when all
+    /// checks pass.
+    pub fn fully_validate_block(
         &self,
         block: &Block,
-        context: &ProtocolValidationContext,
+        prev_hash: &[u8; 32],
+        height: u64,
+        utxos: &mut HashMap<OutPoint, UTXO>,
+        current_time: u64,
+        recent_timestamps: &[u32],
     ) -> Result<()> {
-        // Check block size limits
-        let block_size = self.calculate_block_size(block);
-        if block_size > context.validation_rules.max_block_size {
-            return Err(bllvm_consensus::error::ConsensusError::BlockValidation(
-                "Block size exceeds maximum".to_string(),
+        let header = &block.header;
+
+        // Header context
+        if &header.prev_block_hash != prev_hash {
+            return Err(ConsensusError::BlockValidation(
+                "previous block hash mismatch".to_string(),
+            )
+            .into());
+        }
+        self.check_proof_of_work(header, height)?;
+        self.check_block_timestamp(header, recent_timestamps, current_time)?;
+
+        // Block structure
+        check_coinbase_placement(block)?;
+        check_coinbase_script_sig(block)?;
+        self.check_bip34_height(block, height)?;
+
+        let txids: Vec<[u8; 32]> = block.transactions.iter().map(encoding::txid).collect();
+        if encoding::merkle_root(&txids) != header.merkle_root {
+            return Err(ConsensusError::BlockValidation("merkle root mismatch".to_string()).into());
+        }
+        check_duplicate_txids(&txids)?;
+        check_intra_block_ordering(block)?;
+        self.check_bip30(block, utxos)?;
+
+        let rules = ProtocolValidationRules::for_protocol(self.protocol_version);
+        let base_size = self.calculate_block_size(block) as u64;
+        if base_size * WITNESS_SCALE_FACTOR > rules.max_block_weight as u64 {
+            return Err(ProtocolError::BlockSizeExceeded(
+                "block weight exceeds maximum".to_string(),
             ));
         }
 
-        // Check transaction count limits
-        if block.transactions.len() > 10000 {
-            // Reasonable limit
-            return Err(bllvm_consensus::error::ConsensusError::BlockValidation(
-                "Too many transactions in block".to_string(),
+        let sigops: u64 = block.transactions.iter().map(legacy_sigop_count).sum();
+        if sigops * WITNESS_SCALE_FACTOR > MAX_BLOCK_SIGOPS_COST {
+            return Err(ProtocolError::SigopLimitExceeded(
+                "block sigops exceed maximum".to_string(),
             ));
         }
 
-        // Validate each transaction with protocol rules
+        // Transactions
+        let context = ProtocolValidationContext::new(self.protocol_version, height)?;
         for tx in &block.transactions {
-            self.apply_transaction_protocol_validation(tx, context)?;
+            if let ValidationResult::Invalid(reason) = self.consensus.validate_transaction(tx)? {
+                return Err(ConsensusError::TransactionValidation(reason).into());
+            }
+            self.apply_transaction_protocol_validation(tx, &context)?;
+        }
+        let (consensus_result, _) = self
+            .consensus
+            .validate_block(block, utxos.clone(), height)?;
+        if let ValidationResult::Invalid(reason) = consensus_result {
+            return Err(ConsensusError::BlockValidation(reason).into());
         }
 
+        // UTXO application on a scratch copy, committed only on success
+        *utxos = self.connect_block(block, height, utxos)?;
         Ok(())
     }
 
-    /// Apply protocol-specific transaction validation
-    fn apply_transaction_protocol_validation(
+    /// Apply a block's transactions to a copy of `utxos` and check the coinbase value
+    ///
+    /// The non-script consensus checks: every input exists in `utxos` or
+    /// earlier in the block, no transaction spends more than its inputs, and
+    /// the coinbase claims at most the subsidy plus fees. Returns the updated
+    /// UTXO set.
+    fn connect_block(
         &self,
-        tx: &Transaction,
-        context: &ProtocolValidationContext,
-    ) -> Result<()> {
-        // Check transaction size limits
-        let tx_size = self.calculate_transaction_size(tx);
-        if tx_size > context.validation_rules.max_tx_size {
-            return Err(
-                bllvm_consensus::error::ConsensusError::TransactionValidation(
-                    "Transaction size exceeds maximum".to_string(),
-                ),
-            );
+        block: &Block,
+        height: u64,
+        utxos: &HashMap<OutPoint, UTXO>,
+    ) -> Result<HashMap<OutPoint, UTXO>> {
+        let max_money = self.get_economic_parameters().max_money_supply;
+        let mut working = utxos.clone();
+        let mut total_fees: u64 = 0;
+        for tx in &block.transactions {
+            let txid = encoding::txid(tx);
+            let fee = connect_transaction(tx, &txid, height, max_money, &mut working)?;
+            total_fees = total_fees.saturating_add(fee);
         }
+        self.check_coinbase_value(block, height, total_fees)?;
+        Ok(working)
+    }
 
-        // Check script size limits
-        for input in &tx.inputs {
-            if input.script_sig.len() > context.validation_rules.max_script_size as usize {
-                return Err(
-                    bllvm_consensus::error::ConsensusError::TransactionValidation(
-                        "Script size exceeds maximum".to_string(),
-                    ),
-                );
-            }
+    /// Check the BIP34 coinbase height once BIP34 is active at `height`
+    fn check_bip34_height(&self, block: &Block, height: u64) -> Result<()> {
+        if !self.is_feature_active("bip34", height, block.header.timestamp) {
+            return Ok(());
         }
+        let coinbase = block
+            .transactions
+            .first()
+            .ok_or_else(|| ConsensusError::BlockValidation("no coinbase".to_string()))?;
+        let encoded = extract_bip34_height(coinbase)?;
+        if encoded != height {
+            return Err(ConsensusError::BlockValidation(format!(
+                "coinbase height {encoded} does not match block height {height}"
+            ))
+            .into());
+        }
+        Ok(())
+    }
 
-        for output in &tx.outputs {
-            if output.script_pubkey.len() > context.validation_rules.max_script_size as usize {
-                return Err(
-                    bllvm_consensus::error::ConsensusError::TransactionValidation(
-                        "Script size exceeds maximum".to_string(),
-                    ),
-                );
-            }
+    /// BIP30: a block may not create an output that is already unspent
+    ///
+    /// Stops a coinbase with a reused txid from overwriting an earlier
+    /// coinbase's outputs. The two mainnet blocks that did so before the rule
+    /// existed (`NetworkConstants::bip30_exceptions`) are exempt.
+    pub fn check_bip30(&self, block: &Block, utxos: &HashMap<OutPoint, UTXO>) -> Result<()> {
+        let constants = NetworkConstants::for_version(self.protocol_version)?;
+        let hash = encoding::block_hash(&block.header);
+        if constants
+            .bip30_exceptions
+            .iter()
+            .any(|(_, exception)| *exception == hash)
+        {
+            return Ok(());
         }
 
+        for tx in &block.transactions {
+            let txid = encoding::txid(tx);
+            let overwrites = (0..tx.outputs.len()).any(|index| {
+                utxos.contains_key(&OutPoint {
+                    hash: txid,
+                    index: index as u64,
+                })
+            });
+            if overwrites {
+                return Err(ConsensusError::BlockValidation(
+                    "transaction overwrites an unspent output (BIP30)".to_string(),
+                )
+                .into());
+            }
+        }
         Ok(())
     }
 
-    /// Calculate block size in bytes
-    fn calculate_block_size(&self, block: &Block) -> u32 {
-        // Simplified size calculation
-        // In reality, this would include proper serialization
-        let header_size = 80; // Block header is always 80 bytes
-        let tx_count_size = 4; // Varint for transaction count
-        let tx_sizes: u32 = block
+    /// Whether `candidate` carries more cumulative work than `current`
+    ///
+    /// Both slices should start after their common ancestor. Ties keep the
+    /// current chain, as a node sticks with the first chain it saw.
+    pub fn is_better_chain(&self, candidate: &[BlockHeader], current: &[BlockHeader]) -> bool {
+        cumulative_work(candidate) > cumulative_work(current)
+    }
+
+    /// Check that the coinbase claims no more than the block subsidy plus fees
+    ///
+    /// Claiming less than the maximum is allowed; the difference is burned.
+    pub fn check_coinbase_value(&self, block: &Block, height: u64, total_fees: u64) -> Result<()> {
+        let coinbase = block
             .transactions
-            .iter()
-            .map(|tx| self.calculate_transaction_size(tx))
-            .sum();
+            .first()
+            .ok_or_else(|| ConsensusError::BlockValidation("no coinbase".to_string()))?;
+        let economics = self.get_economic_parameters();
+        let max_value = economics
+            .get_block_subsidy(height)
+            .saturating_add(total_fees);
 
-        header_size + tx_count_size + tx_sizes
+        if sum_output_values(coinbase, economics.max_money_supply)? as u64 > max_value {
+            return Err(ConsensusError::BlockValidation(
+                "coinbase pays more than subsidy plus fees".to_string(),
+            )
+            .into());
+        }
+        Ok(())
     }
 
-    /// Calculate transaction size in bytes
-    fn calculate_transaction_size(&self, tx: &Transaction) -> u32 {
-        // Simplified size calculation
-        let version_size = 4;
-        let input_count_size = 4;
-        let output_count_size = 4;
-        let locktime_size = 4;
+    /// Run every mempool policy check for a transaction
+    ///
+    /// Covers consensus validation, protocol size limits, input availability,
+    /// finality, BIP68 relative lock times (height-based), fee rate bounds and
+    /// dust outputs. Time-based relative locks need the median time of the
+    /// spent coin's block, which the UTXO set does not carry, so they are left
+    /// to the node layer.
+    pub fn accept_to_mempool(
+        &self,
+        tx: &Transaction,
+        utxos: &HashMap<OutPoint, UTXO>,
+        context: &ProtocolValidationContext,
+        feature_ctx: &FeatureContext,
+    ) -> Result<MempoolAcceptance> {
+        if let ValidationResult::Invalid(reason) = self.consensus.validate_transaction(tx)? {
+            return Err(ConsensusError::TransactionValidation(reason).into());
+        }
+        self.apply_transaction_protocol_validation(tx, context)?;
+        self.apply_transaction_policy(tx, context)?;
+
+        if is_coinbase(tx) {
+            return Err(ConsensusError::TransactionValidation(
+                "coinbase not allowed in mempool".to_string(),
+            )
+            .into());
+        }
+
+        // Inputs and fee
+        let mut input_value: i64 = 0;
+        for input in &tx.inputs {
+            let utxo = utxos.get(&input.prevout).ok_or_else(|| {
+                ConsensusError::TransactionValidation("missing inputs".to_string())
+            })?;
+            input_value = input_value.checked_add(utxo.value).ok_or_else(|| {
+                ConsensusError::TransactionValidation("input value overflow".to_string())
+            })?;
+        }
+        let output_value = sum_output_values(tx, self.get_economic_parameters().max_money_supply)?;
+        if input_value < output_value {
+            return Err(ConsensusError::TransactionValidation(
+                "inputs less than outputs".to_string(),
+            )
+            .into());
+        }
+        let fee = (input_value - output_value) as u64;
 
-        let input_sizes: u32 = tx
+        // Finality (nLockTime) against the next block
+        let all_final = tx
             .inputs
             .iter()
-            .map(|input| {
-                32 + // prevout hash
-                4 +  // prevout index
-                input.script_sig.len() as u32 +
-                4 // sequence
-            })
-            .sum();
+            .all(|input| SequenceInfo::parse(input.sequence as u32).is_final);
+        if tx.lock_time != 0 && !all_final {
+            let limit = if tx.lock_time < LOCKTIME_THRESHOLD {
+                feature_ctx.height
+            } else {
+                feature_ctx.timestamp
+            };
+            if tx.lock_time >= limit {
+                return Err(ConsensusError::TransactionValidation(
+                    "non-final transaction".to_string(),
+                )
+                .into());
+            }
+        }
 
-        let output_sizes: u32 = tx
-            .outputs
-            .iter()
-            .map(|output| {
-                8 + // value
-                output.script_pubkey.len() as u32
-            })
-            .sum();
+        // BIP68 relative lock times
+        if tx.version >= 2 && feature_ctx.csv {
+            for input in &tx.inputs {
+                let sequence = SequenceInfo::parse(input.sequence as u32);
+                if sequence.relative_lock_disabled || sequence.is_time_based {
+                    continue;
+                }
+                let coin_height = utxos[&input.prevout].height;
+                let required = coin_height + sequence.locktime_value as u64;
+                if feature_ctx.height < required {
+                    return Err(ConsensusError::TransactionValidation(
+                        "sequence lock not satisfied".to_string(),
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // Fee rate
+        let vsize = encoding::serialize_transaction(tx).len() as u64;
+        let rules = &context.validation_rules;
+        if fee < rules.min_fee_rate.saturating_mul(vsize) {
+            return Err(ProtocolError::FeeOutOfRange(
+                "fee rate below minimum".to_string(),
+            ));
+        }
+        if fee > rules.max_fee_rate.saturating_mul(vsize) {
+            return Err(ProtocolError::FeeOutOfRange(
+                "fee rate above maximum".to_string(),
+            ));
+        }
+
+        // Dust (provably unspendable OP_RETURN outputs are exempt)
+        let economics = self.get_economic_parameters();
+        for output in &tx.outputs {
+            let unspendable = output.script_pubkey.first() == Some(&OP_RETURN);
+            if !unspendable && economics.is_dust(output.value as u64) {
+                return Err(ProtocolError::PolicyViolation("dust output".to_string()));
+            }
+        }
 
-        version_size
-            + input_count_size
-            + input_sizes
-            + output_count_size
-            + output_sizes
-            + locktime_size
+        Ok(MempoolAcceptance {
+            fee,
+            vsize,
+            fee_rate: fee as f64 / vsize as f64,
+            signals_rbf: signals_rbf(tx),
+        })
     }
-}
 
-#[cfg(test)]
+    /// Check a BIP125 replacement against the transactions it conflicts with
+    ///
+    /// `new_fee` is the replacement's absolute fee and `old_total_fee` the sum
+    /// of fees paid by all `conflicting` transactions (including descendants).
+    pub fn check_rbf_replacement(
+        &self,
+        new: &Transaction,
+        conflicting: &[Transaction],
+        new_fee: u64,
+        old_total_fee: u64,
+    ) -> Result<()> {
+        if !self.supports_feature("rbf") {
+            return Err(ProtocolError::FeatureNotActive(
+                "replacement not supported".to_string(),
+            ));
+        }
+
+        for old in conflicting {
+            if !replaces(new, old) {
+                return Err(ConsensusError::TransactionValidation(
+                    "replacement does not conflict with original".to_string(),
+                )
+                .into());
+            }
+            // Rule 1: originals must opt in
+            if !signals_rbf(old) {
+                return Err(ConsensusError::TransactionValidation(
+                    "original does not signal replaceability".to_string(),
+                )
+                .into());
+            }
+        }
+
+        // Rule 3: pay at least the absolute fees of the originals
+        if new_fee < old_total_fee {
+            return Err(ProtocolError::FeeOutOfRange(
+                "replacement fee lower than original fees".to_string(),
+            ));
+        }
+
+        // Rule 4: pay for its own relay bandwidth on top
+        let vsize = encoding::serialize_transaction(new).len() as u64;
+        if new_fee - old_total_fee < INCREMENTAL_RELAY_FEE * vsize {
+            return Err(ProtocolError::FeeOutOfRange(
+                "replacement does not pay for its own bandwidth".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check mempool package limits for `new_tx` and its unconfirmed ancestors
+    ///
+    /// `ancestors` must hold every unconfirmed transaction `new_tx` depends
+    /// on, directly or indirectly. The ancestor count and vsize include
+    /// `new_tx` itself. Descendant limits are checked for each ancestor over
+    /// the supplied package only; descendants outside it are not known here.
+    pub fn check_package_limits(
+        &self,
+        new_tx: &Transaction,
+        ancestors: &[Transaction],
+    ) -> Result<()> {
+        let rules = ProtocolValidationRules::for_protocol(self.protocol_version);
+        let too_long = |reason: String| Err(ProtocolError::PolicyViolation(reason));
+        let vsize = |tx: &Transaction| encoding::serialize_transaction(tx).len() as u64;
+
+        let package: Vec<&Transaction> = ancestors.iter().chain([new_tx]).collect();
+        if package.len() > rules.max_ancestors {
+            return too_long(format!(
+                "too many unconfirmed ancestors: {} > {}",
+                package.len(),
+                rules.max_ancestors
+            ));
+        }
+        let ancestor_vsize: u64 = package.iter().map(|tx| vsize(tx)).sum();
+        if ancestor_vsize > rules.max_ancestor_vsize {
+            return too_long(format!(
+                "ancestor package of {ancestor_vsize} vB exceeds {}",
+                rules.max_ancestor_vsize
+            ));
+        }
+
+        // Each ancestor gains new_tx, and any package members between them,
+        // as descendants
+        let txids: Vec<[u8; 32]> = package.iter().map(|tx| encoding::txid(tx)).collect();
+        for (index, txid) in txids.iter().enumerate().take(ancestors.len()) {
+            let mut descendants: HashSet<[u8; 32]> = HashSet::from([*txid]);
+            let mut descendant_vsize = vsize(package[index]);
+            let mut grew = true;
+            while grew {
+                grew = false;
+                for (tx, child_txid) in package.iter().zip(&txids) {
+                    if !descendants.contains(child_txid)
+                        && tx
+                            .inputs
+                            .iter()
+                            .any(|input| descendants.contains(&input.prevout.hash))
+                    {
+                        descendants.insert(*child_txid);
+                        descendant_vsize += vsize(tx);
+                        grew = true;
+                    }
+                }
+            }
+            if descendants.len() > rules.max_descendants {
+                return too_long(format!(
+                    "ancestor {index} would have {} descendants, limit {}",
+                    descendants.len(),
+                    rules.max_descendants
+                ));
+            }
+            if descendant_vsize > rules.max_descendant_vsize {
+                return too_long(format!(
+                    "ancestor {index} descendant package of {descendant_vsize} vB exceeds {}",
+                    rules.max_descendant_vsize
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Combined fee rate (sat/vB) of a package, e.g. a parent and its CPFP child
+    ///
+    /// Fees and vsizes are summed across the package, so a low-fee parent is
+    /// judged together with the child paying for it. `fees[i]` is the fee of
+    /// `txs[i]`.
+    pub fn package_fee_rate(&self, txs: &[Transaction], fees: &[u64]) -> Result<f64> {
+        if txs.len() != fees.len() {
+            return Err(ConsensusError::TransactionValidation(format!(
+                "package has {} transactions but {} fees",
+                txs.len(),
+                fees.len()
+            ))
+            .into());
+        }
+        let weight: u64 = txs
+            .iter()
+            .map(|tx| encoding::serialize_transaction(tx).len() as u64 * WITNESS_SCALE_FACTOR)
+            .sum();
+        let fee = fees
+            .iter()
+            .try_fold(0u64, |total, fee| total.checked_add(*fee))
+            .ok_or_else(|| ProtocolError::FeeOutOfRange("package fee overflow".to_string()))?;
+        Ok(EconomicParameters::fee_rate(fee, weight))
+    }
+
+    /// Weight, sizes and fee rate of a transaction paying `fee`, in one pass
+    ///
+    /// `witnesses` holds the transaction's per-input witness stacks, empty
+    /// for a legacy transaction.
+    pub fn tx_metrics(&self, tx: &Transaction, witnesses: &[WitnessStack], fee: u64) -> TxMetrics {
+        let base_size = encoding::serialize_transaction(tx).len() as u64;
+        let total_size = encoding::serialize_transaction_with_witness(tx, witnesses).len() as u64;
+        let weight = base_size * (WITNESS_SCALE_FACTOR - 1) + total_size;
+        TxMetrics {
+            weight,
+            vsize: EconomicParameters::vsize_from_weight(weight),
+            base_size,
+            total_size,
+            fee,
+            fee_rate_sat_per_vbyte: EconomicParameters::fee_rate(fee, weight),
+            is_segwit: encoding::has_witness(witnesses),
+            signals_rbf: signals_rbf(tx),
+        }
+    }
+
+    /// Apply protocol-specific validation rules
+    fn apply_protocol_validation(
+        &self,
+        block: &Block,
+        context: &ProtocolValidationContext,
+    ) -> Result<()> {
+        // Check block size limits
+        let block_size = self.calculate_block_size(block);
+        if block_size > context.validation_rules.max_block_size {
+            return Err(ProtocolError::BlockSizeExceeded(
+                "Block size exceeds maximum".to_string(),
+            ));
+        }
+
+        // Check transaction count limits
+        if block.transactions.len() > 10000 {
+            // Reasonable limit
+            return Err(ProtocolError::BlockSizeExceeded(
+                "Too many transactions in block".to_string(),
+            ));
+        }
+
+        let txids: Vec<Hash> = block.transactions.iter().map(encoding::txid).collect();
+        check_duplicate_txids(&txids)?;
+        check_intra_block_ordering(block)?;
+
+        // Validate each transaction with protocol rules
+        for tx in &block.transactions {
+            self.apply_transaction_protocol_validation(tx, context)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply protocol-specific transaction validation
+    fn apply_transaction_protocol_validation(
+        &self,
+        tx: &Transaction,
+        context: &ProtocolValidationContext,
+    ) -> Result<()> {
+        // Check transaction size limits
+        let tx_size = self.calculate_transaction_size(tx);
+        if tx_size > context.validation_rules.max_tx_size {
+            return Err(ProtocolError::TransactionSizeExceeded(
+                "Transaction size exceeds maximum".to_string(),
+            ));
+        }
+
+        // Every output and their sum must stay within the money supply
+        sum_output_values(tx, self.get_economic_parameters().max_money_supply)?;
+
+        // Check script size limits
+        for input in &tx.inputs {
+            if input.script_sig.len() > context.validation_rules.max_script_size as usize {
+                return Err(ProtocolError::TransactionSizeExceeded(
+                    "Script size exceeds maximum".to_string(),
+                ));
+            }
+        }
+
+        for output in &tx.outputs {
+            if output.script_pubkey.len() > context.validation_rules.max_script_size as usize {
+                return Err(ProtocolError::TransactionSizeExceeded(
+                    "Script size exceeds maximum".to_string(),
+                ));
+            }
+        }
+
+        // Opcode-level limits on executed scripts: outputs only run when
+        // spent, and the coinbase scriptSig never does
+        if !is_coinbase(tx) {
+            for input in &tx.inputs {
+                check_script_limits(&input.script_sig)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Relay policy for a transaction entering the mempool
+    ///
+    /// Never applied to block transactions: a block may contain anything
+    /// consensus allows, however unusual.
+    fn apply_transaction_policy(
+        &self,
+        tx: &Transaction,
+        context: &ProtocolValidationContext,
+    ) -> Result<()> {
+        let rules = &context.validation_rules;
+        let supported = i32::try_from(tx.version)
+            .is_ok_and(|version| (rules.min_tx_version..=rules.max_tx_version).contains(&version));
+        if !supported {
+            return Err(ProtocolError::PolicyViolation(
+                "unsupported tx version".to_string(),
+            ));
+        }
+
+        if rules.require_standard {
+            check_standard_tx(tx)?;
+        }
+
+        // Outputs that could never be spent within the opcode limits
+        for (index, output) in tx.outputs.iter().enumerate() {
+            check_script_limits(&output.script_pubkey).map_err(|err| {
+                ProtocolError::PolicyViolation(format!("output {index} script: {err}"))
+            })?;
+        }
+
+        // Data-carrier (OP_RETURN) outputs
+        let data_outputs: Vec<&[u8]> = tx
+            .outputs
+            .iter()
+            .map(|output| output.script_pubkey.as_slice())
+            .filter(|script| script.first() == Some(&OP_RETURN))
+            .collect();
+        if data_outputs.len() > context.validation_rules.max_op_return_count {
+            return Err(ProtocolError::PolicyViolation(
+                "too many OP_RETURN outputs".to_string(),
+            ));
+        }
+        if data_outputs
+            .iter()
+            .any(|script| script.len() > context.validation_rules.max_op_return_size)
+        {
+            return Err(ProtocolError::PolicyViolation(
+                "OP_RETURN output exceeds maximum size".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Outputs created and prevouts spent by a block at `height`
+    ///
+    /// Purely structural: no inputs are looked up and nothing is validated.
+    pub fn utxo_delta(&self, block: &Block, height: u64) -> UtxoDelta {
+        let mut delta = UtxoDelta {
+            created: Vec::new(),
+            spent: Vec::new(),
+        };
+        for (position, tx) in block.transactions.iter().enumerate() {
+            let txid = encoding::txid(tx);
+            let is_coinbase = position == 0 && is_coinbase(tx);
+            if !is_coinbase {
+                delta
+                    .spent
+                    .extend(tx.inputs.iter().map(|input| input.prevout.clone()));
+            }
+            delta
+                .created
+                .extend(
+                    tx.outputs
+                        .iter()
+                        .enumerate()
+                        .map(|(index, output)| CreatedUtxo {
+                            outpoint: OutPoint {
+                                hash: txid,
+                                index: index as u64,
+                            },
+                            utxo: UTXO {
+                                value: output.value,
+                                script_pubkey: output.script_pubkey.clone(),
+                                height,
+                            },
+                            is_coinbase,
+                        }),
+                );
+        }
+        delta
+    }
+
+    /// Undo record for connecting `block` on top of `spent_utxos`
+    ///
+    /// `spent_utxos` is the UTXO set before the block, or any part of it
+    /// holding the block's inputs. Inputs spending outputs created earlier in
+    /// the same block are skipped, as disconnecting removes rather than
+    /// restores them; any other input missing from `spent_utxos` is an error.
+    pub fn compute_undo(
+        &self,
+        block: &Block,
+        spent_utxos: &HashMap<OutPoint, UTXO>,
+    ) -> Result<BlockUndo> {
+        let mut created: HashMap<Hash, usize> = HashMap::new();
+        let mut spent = Vec::new();
+        for (position, tx) in block.transactions.iter().enumerate() {
+            if !(position == 0 && is_coinbase(tx)) {
+                for (index, input) in tx.inputs.iter().enumerate() {
+                    if let Some(utxo) = spent_utxos.get(&input.prevout) {
+                        spent.push((input.prevout.clone(), utxo.clone()));
+                    } else if created
+                        .get(&input.prevout.hash)
+                        .is_none_or(|&outputs| input.prevout.index >= outputs as u64)
+                    {
+                        return Err(ConsensusError::TransactionValidation(format!(
+                            "transaction {position} input {index} is not in the spent UTXOs"
+                        ))
+                        .into());
+                    }
+                }
+            }
+            created.insert(encoding::txid(tx), tx.outputs.len());
+        }
+        Ok(BlockUndo { spent })
+    }
+
+    /// Deterministic commitment to a UTXO set, for comparing snapshots
+    ///
+    /// Entries are sorted by outpoint, so the result doesn't depend on map
+    /// iteration order. Each is serialized as the txid, the output index and
+    /// creation height (both 4 bytes little-endian), the 8-byte value and the
+    /// length-prefixed script; the concatenation is double-SHA256 hashed.
+    pub fn utxo_set_hash(utxos: &HashMap<OutPoint, UTXO>) -> [u8; 32] {
+        let mut entries: Vec<(&OutPoint, &UTXO)> = utxos.iter().collect();
+        entries.sort_unstable_by_key(|(outpoint, _)| (outpoint.hash, outpoint.index));
+
+        let mut buf = Vec::new();
+        for (outpoint, utxo) in entries {
+            buf.extend_from_slice(&outpoint.hash);
+            buf.extend_from_slice(&(outpoint.index as u32).to_le_bytes());
+            buf.extend_from_slice(&(utxo.height as u32).to_le_bytes());
+            buf.extend_from_slice(&utxo.value.to_le_bytes());
+            encoding::write_varint(&mut buf, utxo.script_pubkey.len() as u64);
+            buf.extend_from_slice(&utxo.script_pubkey);
+        }
+        crypto::sha256d(&buf)
+    }
+
+    /// Calculate block size in bytes, as serialized without witness data
+    fn calculate_block_size(&self, block: &Block) -> u32 {
+        let tx_sizes: u32 = block
+            .transactions
+            .iter()
+            .map(|tx| self.calculate_transaction_size(tx))
+            .sum();
+
+        80 + varint_len(block.transactions.len() as u64) as u32 + tx_sizes
+    }
+
+    /// Calculate transaction size in bytes, as serialized without witness data
+    fn calculate_transaction_size(&self, tx: &Transaction) -> u32 {
+        encoding::serialize_transaction(tx).len() as u32
+    }
+}
+
+/// Verify the BIP141 witness commitment in a block's coinbase
+///
+/// Only enforced when some transaction carries witness data. The last coinbase
+/// output starting with `WITNESS_COMMITMENT_PREFIX` must commit to the witness
+/// merkle root (coinbase wtxid taken as zero) hashed with the coinbase's witness
+/// reserved value, read with `coinbase_witness_reserved`.
+pub fn verify_witness_commitment(block: &Block, witnesses: &[Vec<WitnessStack>]) -> Result<()> {
+    if !witnesses.iter().any(|stacks| encoding::has_witness(stacks)) {
+        return Ok(());
+    }
+
+    let coinbase = block
+        .transactions
+        .first()
+        .ok_or_else(|| ConsensusError::BlockValidation("no coinbase".to_string()))?;
+    let commitment = coinbase
+        .outputs
+        .iter()
+        .rev()
+        .find(|output| {
+            output.script_pubkey.len() >= 38
+                && output.script_pubkey.starts_with(&WITNESS_COMMITMENT_PREFIX)
+        })
+        .map(|output| &output.script_pubkey[6..38])
+        .ok_or_else(|| ConsensusError::BlockValidation("missing witness commitment".to_string()))?;
+
+    let wtxids: Vec<[u8; 32]> = block
+        .transactions
+        .iter()
+        .enumerate()
+        .map(|(i, tx)| match i {
+            0 => [0u8; 32],
+            _ => encoding::wtxid(tx, witnesses.get(i).map(Vec::as_slice).unwrap_or(&[])),
+        })
+        .collect();
+
+    let reserved = coinbase_witness_reserved(block, witnesses)?;
+
+    let mut preimage = encoding::merkle_root(&wtxids).to_vec();
+    preimage.extend_from_slice(&reserved);
+    if crypto::sha256d(&preimage) != commitment {
+        return Err(
+            ConsensusError::BlockValidation("witness commitment mismatch".to_string()).into(),
+        );
+    }
+    Ok(())
+}
+
+/// Read the BIP141 witness reserved value from a block's coinbase
+///
+/// `witnesses` holds each transaction's input witness stacks, as returned by
+/// `encoding::deserialize_block`. A block with a witness commitment must give
+/// its coinbase input a witness of exactly one 32-byte element; anything else
+/// is rejected.
+pub fn coinbase_witness_reserved(
+    block: &Block,
+    witnesses: &[Vec<WitnessStack>],
+) -> Result<[u8; 32]> {
+    if block.transactions.is_empty() {
+        return Err(ConsensusError::BlockValidation("no coinbase".to_string()).into());
+    }
+    match witnesses.first().map(Vec::as_slice) {
+        Some([stack]) => match stack.as_slice() {
+            [item] => item.as_slice().try_into().map_err(|_| {
+                ConsensusError::BlockValidation(format!(
+                    "coinbase witness reserved value is {} bytes, expected 32",
+                    item.len()
+                ))
+                .into()
+            }),
+            items => Err(ConsensusError::BlockValidation(format!(
+                "coinbase witness has {} elements, expected one reserved value",
+                items.len()
+            ))
+            .into()),
+        },
+        _ => Err(ConsensusError::BlockValidation(
+            "coinbase witness reserved value missing".to_string(),
+        )
+        .into()),
+    }
+}
+
+/// Check that a block carries a well-formed BIP325 signet solution
+///
+/// The coinbase's witness commitment output must contain a push starting
+/// with `SIGNET_HEADER` and followed by a serialized scriptSig and witness
+/// stack. The solution is not yet executed against `challenge`. The genesis
+/// block has no solution and must not be passed.
+pub fn verify_signet_block(block: &Block, challenge: &[u8]) -> Result<()> {
+    let invalid = |msg: &str| Err(ConsensusError::BlockValidation(msg.to_string()).into());
+    if challenge.is_empty() {
+        return invalid("empty signet challenge");
+    }
+    let Some(coinbase) = block.transactions.first().filter(|tx| is_coinbase(tx)) else {
+        return invalid("signet block has no coinbase");
+    };
+    let Some(commitment) = coinbase.outputs.iter().rev().find(|output| {
+        output.script_pubkey.len() >= 38
+            && output.script_pubkey.starts_with(&WITNESS_COMMITMENT_PREFIX)
+    }) else {
+        return invalid("signet block has no witness commitment");
+    };
+
+    let solution = script_pushes(&commitment.script_pubkey[1..])
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|push| push.strip_prefix(&SIGNET_HEADER));
+    let Some(solution) = solution else {
+        return invalid("missing signet solution");
+    };
+
+    let mut reader = Reader::new(solution);
+    let parsed = (|| -> Result<()> {
+        reader.read_var_bytes()?;
+        for _ in 0..reader.read_varint()? {
+            reader.read_var_bytes()?;
+        }
+        Ok(())
+    })();
+    if parsed.is_err() || !reader.is_empty() {
+        return invalid("malformed signet solution");
+    }
+    Ok(())
+}
+
+/// Data of every push in `script`, or `None` if it contains anything else
+fn script_pushes(mut script: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut pushes = Vec::new();
+    while let [opcode, rest @ ..] = script {
+        let (len, rest) = match *opcode {
+            0x01..=0x4b => (*opcode as usize, rest),
+            0x4c => (*rest.first()? as usize, rest.get(1..)?),
+            0x4d => (
+                u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize,
+                &rest[2..],
+            ),
+            0x4e => (
+                u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize,
+                &rest[4..],
+            ),
+            _ => return None,
+        };
+        pushes.push(rest.get(..len)?);
+        script = &rest[len..];
+    }
+    Some(pushes)
+}
+
+/// Total expected work of a chain of headers
+pub fn cumulative_work(headers: &[BlockHeader]) -> ChainWork {
+    headers.iter().fold(ChainWork::ZERO, |total, header| {
+        total.add(&ChainWork::from_bits(header.bits as u32))
+    })
+}
+
+/// Reject a block in which any txid appears more than once
+fn check_duplicate_txids(txids: &[Hash]) -> Result<()> {
+    let mut seen = HashSet::new();
+    if !txids.iter().all(|txid| seen.insert(*txid)) {
+        return Err(ConsensusError::BlockValidation("duplicate transaction".to_string()).into());
+    }
+    Ok(())
+}
+
+/// Check that transactions only spend outputs of earlier transactions in the block
+///
+/// An input referencing the txid of its own or a later transaction in the
+/// block is rejected; the coinbase has no real inputs and is skipped.
+pub fn check_intra_block_ordering(block: &Block) -> Result<()> {
+    let positions: HashMap<Hash, usize> = block
+        .transactions
+        .iter()
+        .enumerate()
+        .map(|(position, tx)| (encoding::txid(tx), position))
+        .collect();
+    for (position, tx) in block.transactions.iter().enumerate() {
+        if is_coinbase(tx) {
+            continue;
+        }
+        let spends_later = tx.inputs.iter().any(|input| {
+            positions
+                .get(&input.prevout.hash)
+                .is_some_and(|&parent| parent >= position)
+        });
+        if spends_later {
+            return Err(ConsensusError::BlockValidation(format!(
+                "transaction {position} spends an output created later in the block"
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Structural checks that still apply to a block whose scripts are assumed valid
+fn check_assumed_valid_block(block: &Block) -> Result<()> {
+    check_coinbase_placement(block)?;
+    if encoding::block_merkle_root(block) != block.header.merkle_root {
+        return Err(ConsensusError::BlockValidation("merkle root mismatch".to_string()).into());
+    }
+    Ok(())
+}
+
+/// Check that a block's first transaction, and only that one, is a coinbase
+///
+/// Rejects an empty block as having no coinbase.
+pub fn check_coinbase_placement(block: &Block) -> Result<()> {
+    match block.transactions.first() {
+        Some(tx) if is_coinbase(tx) => {}
+        _ => return Err(ConsensusError::BlockValidation("no coinbase".to_string()).into()),
+    }
+    if block.transactions[1..].iter().any(is_coinbase) {
+        return Err(ConsensusError::BlockValidation("multiple coinbases".to_string()).into());
+    }
+    Ok(())
+}
+
+/// Check that the coinbase scriptSig is 2 to 100 bytes long
+///
+/// A block without a coinbase is left to `check_coinbase_placement`.
+pub fn check_coinbase_script_sig(block: &Block) -> Result<()> {
+    let Some(input) = block.transactions.first().and_then(|tx| tx.inputs.first()) else {
+        return Ok(());
+    };
+    let len = input.script_sig.len();
+    if !(MIN_COINBASE_SCRIPT_SIG_SIZE..=MAX_COINBASE_SCRIPT_SIG_SIZE).contains(&len) {
+        return Err(ConsensusError::BlockValidation(format!(
+            "coinbase scriptSig length {len} outside \
+             {MIN_COINBASE_SCRIPT_SIG_SIZE}..={MAX_COINBASE_SCRIPT_SIG_SIZE}"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Read the BIP34 block height from a coinbase scriptSig
+///
+/// The height must be the first item of the scriptSig, pushed as a minimally
+/// encoded little-endian script number (`OP_0`/`OP_1`..`OP_16` for small values).
+pub fn extract_bip34_height(coinbase: &Transaction) -> Result<u64> {
+    let script = coinbase
+        .inputs
+        .first()
+        .map(|input| input.script_sig.as_slice())
+        .unwrap_or(&[]);
+    let malformed = |reason: &str| {
+        Err(ConsensusError::BlockValidation(format!("bad coinbase height: {reason}")).into())
+    };
+
+    let height = match script.first() {
+        None => return malformed("empty scriptSig"),
+        Some(0x00) => 0,
+        Some(opcode @ 0x51..=0x60) => (opcode - 0x50) as u64,
+        Some(&len @ 0x01..=0x08) => {
+            let data = match script.get(1..1 + len as usize) {
+                Some(data) => data,
+                None => return malformed("truncated push"),
+            };
+            if data[data.len() - 1] & 0x80 != 0 {
+                return malformed("negative height");
+            }
+            data.iter()
+                .rev()
+                .fold(0u64, |value, byte| (value << 8) | *byte as u64)
+        }
+        Some(_) => return malformed("height is not a number push"),
+    };
+
+    if !script.starts_with(&bip34_height_script(height)) {
+        return malformed("height is not minimally encoded");
+    }
+    Ok(height)
+}
+
+/// Minimal script encoding of a block height, as BIP34 requires
+pub(crate) fn bip34_height_script(height: u64) -> Vec<u8> {
+    match height {
+        0 => vec![0x00],
+        1..=16 => vec![0x50 + height as u8],
+        _ => {
+            let mut data: Vec<u8> = height.to_le_bytes().to_vec();
+            while data.last() == Some(&0) {
+                data.pop();
+            }
+            if data.last().is_some_and(|byte| byte & 0x80 != 0) {
+                data.push(0x00);
+            }
+            let mut script = vec![data.len() as u8];
+            script.extend_from_slice(&data);
+            script
+        }
+    }
+}
+
+/// Largest DER signature plus sighash byte
+const MAX_SIG_SIZE: u64 = 72;
+
+/// Compressed public key
+const PUBKEY_SIZE: u64 = 33;
+
+/// Outpoint and sequence of every input
+const INPUT_BASE_SIZE: u64 = 36 + 4;
+
+/// Expected scriptSig and witness sizes of spending an output of this type
+///
+/// Assumes compressed keys and maximum-size signatures. P2SH is taken to be
+/// P2SH-wrapped P2WPKH and P2WSH a 2-of-3 multisig; types with no spending
+/// template (OP_RETURN, non-standard) count as an empty scriptSig.
+fn input_spend_sizes(input_type: &ScriptType) -> (u64, Option<u64>) {
+    // Witness item: length prefix and data
+    let item = |len: u64| varint_len(len) + len;
+    match input_type {
+        ScriptType::P2PKH => (item(MAX_SIG_SIZE) + item(PUBKEY_SIZE), None),
+        ScriptType::P2SH => (item(22), Some(1 + item(MAX_SIG_SIZE) + item(PUBKEY_SIZE))),
+        ScriptType::P2WPKH => (0, Some(1 + item(MAX_SIG_SIZE) + item(PUBKEY_SIZE))),
+        ScriptType::P2WSH => {
+            let witness_script = 1 + 3 * item(PUBKEY_SIZE) + 2;
+            (
+                0,
+                Some(1 + 1 + 2 * item(MAX_SIG_SIZE) + item(witness_script)),
+            )
+        }
+        // Key-path spend: one 64-byte Schnorr signature, default sighash
+        ScriptType::P2TR => (0, Some(1 + item(64))),
+        // OP_0 for the CHECKMULTISIG off-by-one, then the signatures
+        ScriptType::Multisig { required, .. } => (1 + *required as u64 * item(MAX_SIG_SIZE), None),
+        ScriptType::OpReturn | ScriptType::NonStandard => (0, None),
+    }
+}
+
+/// Estimated virtual size of a transaction before it is signed
+///
+/// `input_types` are the types of the outputs being spent, sized with the
+/// assumptions of `input_spend_sizes`; `output_scripts` are the new
+/// outputs. Returns `ceil(weight / 4)`, counting the segwit marker and flag
+/// only if some input has a witness.
+pub fn estimate_vsize(input_types: &[ScriptType], output_scripts: &[Vec<u8>]) -> u64 {
+    let spends: Vec<(u64, Option<u64>)> = input_types.iter().map(input_spend_sizes).collect();
+    let has_witness = spends.iter().any(|(_, witness)| witness.is_some());
+
+    // Version, lock time and the input and output counts
+    let mut base =
+        4 + 4 + varint_len(input_types.len() as u64) + varint_len(output_scripts.len() as u64);
+    let mut witness = if has_witness { 2 } else { 0 };
+    for (script_sig, input_witness) in spends {
+        base += INPUT_BASE_SIZE + varint_len(script_sig) + script_sig;
+        if has_witness {
+            // Inputs without a witness still carry an empty stack count
+            witness += input_witness.unwrap_or(1);
+        }
+    }
+    for script in output_scripts {
+        base += 8 + varint_len(script.len() as u64) + script.len() as u64;
+    }
+    EconomicParameters::vsize_from_weight(base * WITNESS_SCALE_FACTOR + witness)
+}
+
+/// Meaning of an input's sequence number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SequenceInfo {
+    /// Equal to `SEQUENCE_FINAL`: no lock time or replacement applies
+    pub is_final: bool,
+    /// Below `0xfffffffe`: the input opts in to BIP125 replacement
+    pub enables_rbf: bool,
+    /// Bit 31 set: BIP68 relative lock time disabled
+    pub relative_lock_disabled: bool,
+    /// Bit 22 set: the relative lock counts 512-second units, not blocks
+    pub is_time_based: bool,
+    /// Low 16 bits: the relative lock in blocks or 512-second units
+    pub locktime_value: u16,
+}
+
+impl SequenceInfo {
+    /// Decode a sequence number's finality, RBF and BIP68 bits
+    pub fn parse(sequence: u32) -> Self {
+        Self {
+            is_final: sequence == SEQUENCE_FINAL,
+            enables_rbf: sequence < SEQUENCE_FINAL - 1,
+            relative_lock_disabled: sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0,
+            is_time_based: sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0,
+            locktime_value: (sequence & SEQUENCE_LOCKTIME_MASK) as u16,
+        }
+    }
+}
+
+/// Whether a transaction explicitly opts in to BIP125 replacement
+///
+/// True if any input has a sequence number below `0xfffffffe`.
+pub fn signals_rbf(tx: &Transaction) -> bool {
+    tx.inputs
+        .iter()
+        .any(|input| SequenceInfo::parse(input.sequence as u32).enables_rbf)
+}
+
+/// Whether `new` spends at least one outpoint also spent by `old`
+pub fn replaces(new: &Transaction, old: &Transaction) -> bool {
+    let spent: HashSet<&OutPoint> = old.inputs.iter().map(|input| &input.prevout).collect();
+    new.inputs
+        .iter()
+        .any(|input| spent.contains(&input.prevout))
+}
+
+/// Whether a transaction is a coinbase (single input spending the null outpoint)
+pub(crate) fn is_coinbase(tx: &Transaction) -> bool {
+    matches!(tx.inputs.as_slice(), [input] if is_coinbase_input(input))
+}
+
+/// Whether `input` spends the null outpoint (all-zero hash, index 0xffffffff)
+pub fn is_coinbase_input(input: &TransactionInput) -> bool {
+    input.prevout.hash == [0u8; 32] && input.prevout.index == 0xffffffff
+}
+
+/// Check a block timestamp against explicit network constants
+///
+/// The timestamp must be strictly greater than the median of the last
+/// `mtp_window` entries of `prev_timestamps` and at most
+/// `max_future_block_time` seconds ahead of `now`. The median check is
+/// skipped when no timestamps are given.
+pub fn check_block_timestamp_with(
+    header: &BlockHeader,
+    prev_timestamps: &[u32],
+    now: u64,
+    constants: &NetworkConstants,
+) -> Result<()> {
+    if let Some(mtp) = median_time_past(prev_timestamps, constants.mtp_window) {
+        if header.timestamp <= mtp {
+            return Err(ConsensusError::BlockValidation(
+                "timestamp not after median time past".to_string(),
+            )
+            .into());
+        }
+    }
+    if header.timestamp > now.saturating_add(constants.max_future_block_time) {
+        return Err(
+            ConsensusError::BlockValidation("timestamp too far in the future".to_string()).into(),
+        );
+    }
+    Ok(())
+}
+
+/// Median of the last `window` timestamps, if any were supplied
+///
+/// A window of 0 is treated as 1.
+fn median_time_past(timestamps: &[u32], window: usize) -> Option<u64> {
+    if timestamps.is_empty() {
+        return None;
+    }
+    let start = timestamps.len().saturating_sub(window.max(1));
+    let mut window = timestamps[start..].to_vec();
+    window.sort_unstable();
+    Some(window[window.len() / 2] as u64)
+}
+
+/// Sum of a transaction's output values, rejecting negative amounts and any
+/// output or total above `max_money`
+fn sum_output_values(tx: &Transaction, max_money: u64) -> Result<i64> {
+    let max_money = i64::try_from(max_money).unwrap_or(i64::MAX);
+    tx.outputs.iter().try_fold(0i64, |total, output| {
+        if output.value < 0 {
+            return Err(
+                ConsensusError::TransactionValidation("negative output value".to_string()).into(),
+            );
+        }
+        total
+            .checked_add(output.value)
+            .filter(|total| output.value <= max_money && *total <= max_money)
+            .ok_or_else(|| {
+                ConsensusError::TransactionValidation("output value overflow".to_string()).into()
+            })
+    })
+}
+
+/// Spend a transaction's inputs from `utxos` and add its outputs, returning its fee
+///
+/// A coinbase spends nothing and pays no fee. The outputs are added even
+/// when an input is missing, so a caller collecting every problem doesn't
+/// also report the transaction's children.
+fn connect_transaction(
+    tx: &Transaction,
+    txid: &Hash,
+    height: u64,
+    max_money: u64,
+    utxos: &mut HashMap<OutPoint, UTXO>,
+) -> Result<u64> {
+    let fee = if is_coinbase(tx) {
+        sum_output_values(tx, max_money).map(|_| 0)
+    } else {
+        spend_inputs(tx, max_money, utxos)
+    };
+    for (index, output) in tx.outputs.iter().enumerate() {
+        utxos.insert(
+            OutPoint {
+                hash: *txid,
+                index: index as u64,
+            },
+            UTXO {
+                value: output.value,
+                script_pubkey: output.script_pubkey.clone(),
+                height,
+            },
+        );
+    }
+    fee
+}
+
+/// Remove a non-coinbase transaction's inputs from `utxos`, returning its fee
+fn spend_inputs(
+    tx: &Transaction,
+    max_money: u64,
+    utxos: &mut HashMap<OutPoint, UTXO>,
+) -> Result<u64> {
+    let output_value = sum_output_values(tx, max_money)?;
+    let mut input_value: i64 = 0;
+    for (index, input) in tx.inputs.iter().enumerate() {
+        let spent = utxos.remove(&input.prevout).ok_or_else(|| {
+            ConsensusError::TransactionValidation(format!("missing input {index}"))
+        })?;
+        input_value = input_value.checked_add(spent.value).ok_or_else(|| {
+            ConsensusError::TransactionValidation("input value overflow".to_string())
+        })?;
+    }
+    if input_value < output_value {
+        return Err(
+            ConsensusError::TransactionValidation("inputs less than outputs".to_string()).into(),
+        );
+    }
+    Ok((input_value - output_value) as u64)
+}
+
+/// BIP141 block weight: base size times three plus total size
+///
+/// `witnesses` holds the per-input witness stacks of each transaction, as in
+/// `validate_block_with_witnesses`; transactions without an entry have none.
+fn block_weight(block: &Block, witnesses: &[Vec<WitnessStack>]) -> u64 {
+    let overhead = 80 + varint_len(block.transactions.len() as u64);
+    let (base, total) = block.transactions.iter().enumerate().fold(
+        (overhead, overhead),
+        |(base, total), (index, tx)| {
+            let stacks = witnesses.get(index).map(Vec::as_slice).unwrap_or(&[]);
+            (
+                base + encoding::serialize_transaction(tx).len() as u64,
+                total + encoding::serialize_transaction_with_witness(tx, stacks).len() as u64,
+            )
+        },
+    );
+    base * (WITNESS_SCALE_FACTOR - 1) + total
+}
+
+/// Legacy (pre-SegWit) sigop count over all scriptSigs and scriptPubKeys
+fn legacy_sigop_count(tx: &Transaction) -> u64 {
+    tx.inputs
+        .iter()
+        .map(|input| script_sigop_count(&input.script_sig))
+        .chain(
+            tx.outputs
+                .iter()
+                .map(|output| script_sigop_count(&output.script_pubkey)),
+        )
+        .sum()
+}
+
+/// Opcode-level script limits
+///
+/// Rejects scripts with more than `MAX_OPS_PER_SCRIPT` non-push opcodes, a
+/// push larger than `MAX_SCRIPT_ELEMENT_SIZE` or running past the end of the
+/// script, or any of the `DISABLED_OPCODES`.
+pub fn check_script_limits(script: &[u8]) -> Result<()> {
+    let invalid = |msg: String| Err(ConsensusError::ScriptExecution(msg).into());
+    let mut ops = 0;
+    let mut i = 0;
+    while i < script.len() {
+        let opcode = script[i];
+        i += 1;
+        let (len_bytes, push_len) = match opcode {
+            0x01..=0x4b => (0, Some(opcode as usize)),
+            0x4c => (1, script.get(i).map(|&b| b as usize)),
+            0x4d => (
+                2,
+                script
+                    .get(i..i + 2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize),
+            ),
+            0x4e => (
+                4,
+                script
+                    .get(i..i + 4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize),
+            ),
+            _ => {
+                if DISABLED_OPCODES.contains(&opcode) {
+                    return invalid(format!("disabled opcode 0x{opcode:02x}"));
+                }
+                // OP_0, OP_1NEGATE and OP_1..OP_16 push without counting
+                if opcode > 0x60 {
+                    ops += 1;
+                    if ops > MAX_OPS_PER_SCRIPT {
+                        return invalid(format!(
+                            "script has more than {MAX_OPS_PER_SCRIPT} operations"
+                        ));
+                    }
+                }
+                continue;
+            }
+        };
+        let Some(push_len) = push_len else {
+            return invalid(format!("truncated push at offset {}", i - 1));
+        };
+        if push_len > MAX_SCRIPT_ELEMENT_SIZE {
+            return invalid(format!(
+                "push of {push_len} bytes exceeds {MAX_SCRIPT_ELEMENT_SIZE}"
+            ));
+        }
+        if script.len() - i < len_bytes + push_len {
+            return invalid(format!("truncated push at offset {}", i - 1));
+        }
+        i += len_bytes + push_len;
+    }
+    Ok(())
+}
+
+/// A parsed BIP141 witness program
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitnessProgramInfo {
+    /// Witness version, 0 to 16
+    pub version: u8,
+    /// The 2-40 byte program following the version opcode
+    pub program: Vec<u8>,
+    /// No rules are defined for this version yet: its outputs are
+    /// anyone-can-spend, so spending them is non-mandatory to check
+    pub is_unknown_version: bool,
+}
+
+/// Parse and check a witness program scriptPubKey
+///
+/// Version 0 programs must be 20 (P2WPKH) or 32 (P2WSH) bytes. Version 1
+/// programs are Taproot only when 32 bytes long and Taproot is active; other
+/// version 1 programs, like versions 2-16, are flagged as unknown rather
+/// than rejected.
+pub fn validate_witness_program(script: &[u8], taproot_active: bool) -> Result<WitnessProgramInfo> {
+    let invalid = |msg: String| Err(ConsensusError::ScriptExecution(msg).into());
+    let (version, program) = match script {
+        [0x00, len, program @ ..] if (2..=40).contains(len) && program.len() == *len as usize => {
+            (0, program)
+        }
+        [opcode @ 0x51..=0x60, len, program @ ..]
+            if (2..=40).contains(len) && program.len() == *len as usize =>
+        {
+            (opcode - 0x50, program)
+        }
+        _ => return invalid("not a witness program".to_string()),
+    };
+    let is_unknown_version = match version {
+        0 if program.len() != 20 && program.len() != 32 => {
+            return invalid(format!("witness v0 program of {} bytes", program.len()));
+        }
+        0 => false,
+        1 => !taproot_active || program.len() != 32,
+        _ => true,
+    };
+    Ok(WitnessProgramInfo {
+        version,
+        program: program.to_vec(),
+        is_unknown_version,
+    })
+}
+
+/// Count signature operations in a script, skipping pushed data
+fn script_sigop_count(script: &[u8]) -> u64 {
+    let mut count = 0;
+    let mut i = 0;
+    while i < script.len() {
+        let opcode = script[i];
+        i += 1;
+        let push_len = match opcode {
+            0x01..=0x4b => opcode as usize,
+            0x4c => {
+                let len = script.get(i).copied().unwrap_or(0) as usize;
+                i += 1;
+                len
+            }
+            0x4d => {
+                let len = script
+                    .get(i..i + 2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+                    .unwrap_or(0);
+                i += 2;
+                len
+            }
+            0x4e => {
+                let len = script
+                    .get(i..i + 4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                    .unwrap_or(0);
+                i += 4;
+                len
+            }
+            // OP_CHECKSIG, OP_CHECKSIGVERIFY
+            0xac | 0xad => {
+                count += 1;
+                0
+            }
+            // OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY
+            0xae | 0xaf => {
+                count += 20;
+                0
+            }
+            _ => 0,
+        };
+        i = i.saturating_add(push_len);
+    }
+    count
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
+    use crate::scripts;
     use bllvm_consensus::types::{OutPoint, TransactionInput, TransactionOutput};
     use bllvm_consensus::{Block, BlockHeader, Transaction};
     use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_validation_rules() {
+        let mainnet_rules = ProtocolValidationRules::mainnet();
+        assert_eq!(mainnet_rules.max_block_size, 4_000_000);
+        assert_eq!(mainnet_rules.max_block_weight, 4_000_000);
+        assert!(mainnet_rules.segwit_enabled);
+        assert!(mainnet_rules.taproot_enabled);
+
+        let regtest_rules = ProtocolValidationRules::regtest();
+        assert_eq!(regtest_rules.max_block_size, 4_000_000);
+        assert!(regtest_rules.segwit_enabled);
+        assert_eq!(regtest_rules.min_fee_rate, 0); // No minimum fee for testing
+    }
+
+    #[test]
+    fn test_validation_rules_all_protocols() {
+        let mainnet_rules = ProtocolValidationRules::for_protocol(ProtocolVersion::BitcoinV1);
+        let testnet_rules = ProtocolValidationRules::for_protocol(ProtocolVersion::Testnet3);
+        let regtest_rules = ProtocolValidationRules::for_protocol(ProtocolVersion::Regtest);
+
+        // Mainnet and testnet should have same rules
+        assert_eq!(mainnet_rules.max_block_size, testnet_rules.max_block_size);
+        assert_eq!(mainnet_rules.max_tx_size, testnet_rules.max_tx_size);
+        assert_eq!(mainnet_rules.max_script_size, testnet_rules.max_script_size);
+        assert_eq!(mainnet_rules.segwit_enabled, testnet_rules.segwit_enabled);
+        assert_eq!(mainnet_rules.taproot_enabled, testnet_rules.taproot_enabled);
+        assert_eq!(mainnet_rules.rbf_enabled, testnet_rules.rbf_enabled);
+        assert_eq!(mainnet_rules.min_fee_rate, testnet_rules.min_fee_rate);
+        assert_eq!(mainnet_rules.max_fee_rate, testnet_rules.max_fee_rate);
+
+        // Regtest should have relaxed fee rules
+        assert_eq!(regtest_rules.min_fee_rate, 0);
+        assert_eq!(regtest_rules.max_fee_rate, mainnet_rules.max_fee_rate);
+    }
+
+    #[test]
+    fn test_validation_rules_serialization() {
+        let mainnet_rules = ProtocolValidationRules::mainnet();
+        let json = serde_json::to_string(&mainnet_rules).unwrap();
+        let deserialized: ProtocolValidationRules = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(mainnet_rules.max_block_size, deserialized.max_block_size);
+        assert_eq!(mainnet_rules.max_tx_size, deserialized.max_tx_size);
+        assert_eq!(mainnet_rules.max_script_size, deserialized.max_script_size);
+        assert_eq!(mainnet_rules.segwit_enabled, deserialized.segwit_enabled);
+        assert_eq!(mainnet_rules.taproot_enabled, deserialized.taproot_enabled);
+        assert_eq!(mainnet_rules.rbf_enabled, deserialized.rbf_enabled);
+        assert_eq!(mainnet_rules.min_fee_rate, deserialized.min_fee_rate);
+        assert_eq!(mainnet_rules.max_fee_rate, deserialized.max_fee_rate);
+    }
+
+    #[test]
+    fn test_validation_rules_equality() {
+        let mainnet1 = ProtocolValidationRules::mainnet();
+        let mainnet2 = ProtocolValidationRules::mainnet();
+        let testnet = ProtocolValidationRules::testnet();
+
+        assert_eq!(mainnet1, mainnet2);
+        assert_eq!(mainnet1, testnet); // Mainnet and testnet should be identical
+    }
+
+    #[test]
+    fn test_validation_context() {
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        assert_eq!(context.block_height, 1000);
+        assert!(context.is_feature_enabled("segwit"));
+        assert!(!context.is_feature_enabled("nonexistent"));
+        assert_eq!(context.get_max_size("block"), 4_000_000);
+    }
+
+    #[test]
+    fn test_validation_context_all_protocols() {
+        let mainnet_context =
+            ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        let testnet_context =
+            ProtocolValidationContext::new(ProtocolVersion::Testnet3, 1000).unwrap();
+        let regtest_context =
+            ProtocolValidationContext::new(ProtocolVersion::Regtest, 1000).unwrap();
+
+        // All should have same block height
+        assert_eq!(mainnet_context.block_height, 1000);
+        assert_eq!(testnet_context.block_height, 1000);
+        assert_eq!(regtest_context.block_height, 1000);
+
+        // All should support same features
+        assert!(mainnet_context.is_feature_enabled("segwit"));
+        assert!(testnet_context.is_feature_enabled("segwit"));
+        assert!(regtest_context.is_feature_enabled("segwit"));
+
+        assert!(mainnet_context.is_feature_enabled("taproot"));
+        assert!(testnet_context.is_feature_enabled("taproot"));
+        assert!(regtest_context.is_feature_enabled("taproot"));
+
+        assert!(mainnet_context.is_feature_enabled("rbf"));
+        assert!(testnet_context.is_feature_enabled("rbf"));
+        assert!(regtest_context.is_feature_enabled("rbf"));
+    }
+
+    #[test]
+    fn test_validation_context_feature_queries() {
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+
+        // Test all supported features
+        assert!(context.is_feature_enabled("segwit"));
+        assert!(context.is_feature_enabled("taproot"));
+        assert!(context.is_feature_enabled("rbf"));
+
+        // Test unsupported features
+        assert!(!context.is_feature_enabled("nonexistent"));
+        assert!(!context.is_feature_enabled(""));
+        assert!(!context.is_feature_enabled("fast_mining"));
+    }
+
+    #[test]
+    fn test_validation_context_size_queries() {
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+
+        assert_eq!(context.get_max_size("block"), 4_000_000);
+        assert_eq!(context.get_max_size("transaction"), 1_000_000);
+        assert_eq!(context.get_max_size("script"), 10_000);
+
+        // Test unknown component
+        assert_eq!(context.get_max_size("unknown"), 0);
+        assert_eq!(context.orphan_limits(), (100, 100));
+    }
+
+    #[test]
+    fn test_validation_context_serialization() {
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        let json = serde_json::to_string(&context).unwrap();
+        let deserialized: ProtocolValidationContext = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(context.block_height, deserialized.block_height);
+        assert_eq!(
+            context.network_params.network_name,
+            deserialized.network_params.network_name
+        );
+        assert_eq!(
+            context.validation_rules.max_block_size,
+            deserialized.validation_rules.max_block_size
+        );
+    }
+
+    #[test]
+    fn test_validation_context_equality() {
+        let context1 = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        let context2 = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        let context3 = ProtocolValidationContext::new(ProtocolVersion::Testnet3, 1000).unwrap();
+
+        assert_eq!(context1, context2);
+        assert_ne!(context1, context3); // Different network parameters
+    }
+
+    #[test]
+    fn test_block_size_validation() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+
+        // Create a block that's within size limits
+        let small_block = Block {
+            header: BlockHeader {
+                version: 1,
+                prev_block_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1231006505,
+                bits: 0x1d00ffff,
+                nonce: 0,
+            },
+            transactions: vec![Transaction {
+                outputs: vec![],
+                ..coinbase(0)
+            }],
+        };
+
+        // This should pass validation
+        let result =
+            engine.validate_block_with_protocol(&small_block, &HashMap::new(), 1000, &context);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_block_coinbase_structure() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        let block_of = |transactions: Vec<Transaction>| Block {
+            header: BlockHeader {
+                version: 1,
+                prev_block_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1231006505,
+                bits: 0x1d00ffff,
+                nonce: 0,
+            },
+            transactions,
+        };
+        let reject_reason = |block: &Block| match engine.validate_block_with_protocol(
+            block,
+            &HashMap::new(),
+            1000,
+            &context,
+        ) {
+            Err(ProtocolError::Consensus(ConsensusError::BlockValidation(reason))) => reason,
+            other => panic!("unexpected result: {other:?}"),
+        };
+        let real_prevout = OutPoint {
+            hash: [9; 32],
+            index: 0,
+        };
+
+        assert_eq!(reject_reason(&block_of(vec![])), "no coinbase");
+        assert_eq!(
+            reject_reason(&block_of(vec![spend(real_prevout, 1000)])),
+            "no coinbase"
+        );
+        assert_eq!(
+            reject_reason(&block_of(vec![coinbase(0), coinbase(0)])),
+            "multiple coinbases"
+        );
+    }
+
+    #[test]
+    fn test_coinbase_script_sig_length() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 5).unwrap();
+        let with_script_sig = |len: usize| {
+            let mut block = regtest_block();
+            block.transactions[0].inputs[0].script_sig.resize(len, 0x00);
+            block
+        };
+
+        let reject_reason = |len: usize| match engine.validate_block_with_protocol(
+            &with_script_sig(len),
+            &HashMap::new(),
+            5,
+            &context,
+        ) {
+            Err(ProtocolError::Consensus(ConsensusError::BlockValidation(reason))) => reason,
+            other => panic!("unexpected result: {other:?}"),
+        };
+        assert!(reject_reason(1).contains("coinbase scriptSig length"));
+        assert!(reject_reason(101).contains("coinbase scriptSig length"));
+
+        check_coinbase_script_sig(&with_script_sig(2)).unwrap();
+        check_coinbase_script_sig(&with_script_sig(100)).unwrap();
+    }
+
+    #[test]
+    fn test_sizes_match_serialization() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let tx = Transaction {
+            version: 2,
+            inputs: (0..3)
+                .map(|index| TransactionInput {
+                    prevout: OutPoint {
+                        hash: [index as u8; 32],
+                        index,
+                    },
+                    script_sig: vec![0x51; 300],
+                    sequence: 0xffffffff,
+                })
+                .collect(),
+            outputs: (0..260)
+                .map(|_| TransactionOutput {
+                    value: 1000,
+                    script_pubkey: vec![0x6a; 253],
+                })
+                .collect(),
+            lock_time: 0,
+        };
+        let size = encoding::serialize_transaction(&tx).len();
+        assert_eq!(engine.calculate_transaction_size(&tx) as usize, size);
+
+        let block = Block {
+            header: BlockHeader {
+                version: 1,
+                prev_block_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1231006505,
+                bits: 0x1d00ffff,
+                nonce: 0,
+            },
+            transactions: vec![tx; 300],
+        };
+        assert_eq!(
+            engine.calculate_block_size(&block) as usize,
+            80 + 3 + 300 * size
+        );
+    }
+
+    #[test]
+    fn test_max_block_weight_override() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let default_context =
+            ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        let mut small_context = default_context.clone();
+        small_context.validation_rules = small_context
+            .validation_rules
+            .with_max_block_weight(2_000_000);
+
+        // ~630kB of outputs: 2.5M weight units
+        let block = Block {
+            header: BlockHeader {
+                version: 1,
+                prev_block_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1231006505,
+                bits: 0x1d00ffff,
+                nonce: 0,
+            },
+            transactions: vec![Transaction {
+                outputs: vec![
+                    TransactionOutput {
+                        value: 0,
+                        script_pubkey: vec![0x51; 9_000],
+                    };
+                    70
+                ],
+                ..coinbase(0)
+            }],
+        };
+
+        assert!(engine
+            .validate_block_with_protocol(&block, &HashMap::new(), 1000, &default_context)
+            .is_ok());
+        let err = engine
+            .validate_block_with_protocol(&block, &HashMap::new(), 1000, &small_context)
+            .unwrap_err();
+        assert!(matches!(err, ProtocolError::BlockSizeExceeded(_)), "{err}");
+    }
+
+    #[test]
+    #[should_panic(expected = "below the minimum")]
+    fn test_max_block_weight_minimum() {
+        ProtocolValidationRules::regtest().with_max_block_weight(MIN_BLOCK_WEIGHT - 1);
+    }
+
+    #[test]
+    fn test_transaction_size_validation() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+
+        // Create a small transaction
+        let small_tx = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0,
+                },
+                script_sig: vec![0x01, 0x04], // Small signature
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 50_0000_0000,
+                script_pubkey: vec![
+                    0x76, 0xa9, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x88, 0xac,
+                ], // P2PKH
+            }],
+            lock_time: 0,
+        };
+
+        // This should pass validation
+        let result = engine.validate_transaction_with_protocol(&small_tx, &context);
+        assert!(result.is_ok());
+    }
+
+    fn data_carrier_tx(data_sizes: &[usize]) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0,
+                },
+                script_sig: vec![0x51],
+                sequence: 0xffffffff,
+            }],
+            outputs: data_sizes
+                .iter()
+                .map(|size| TransactionOutput {
+                    value: 0,
+                    // OP_RETURN OP_PUSHDATA1 <size> <data>
+                    script_pubkey: [vec![OP_RETURN, 0x4c, *size as u8], vec![0xdd; *size]].concat(),
+                })
+                .collect(),
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_op_return_policy() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+        let validate = |tx: &Transaction| engine.validate_transaction_with_protocol(tx, &context);
+
+        assert!(validate(&data_carrier_tx(&[40])).is_ok());
+        assert!(validate(&data_carrier_tx(&[80])).is_ok());
+
+        let err = validate(&data_carrier_tx(&[20, 20])).unwrap_err();
+        assert!(err.to_string().contains("too many OP_RETURN"), "{err}");
+
+        let err = validate(&data_carrier_tx(&[90])).unwrap_err();
+        assert!(matches!(err, ProtocolError::PolicyViolation(_)), "{err}");
+        assert!(err.to_string().contains("exceeds maximum size"), "{err}");
+    }
+
+    #[test]
+    fn test_output_values_capped_at_max_money() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        let max_money = engine.get_economic_parameters().max_money_supply as i64;
+        let mut tx = spend(funding_outpoint(), max_money);
+        assert!(engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .is_ok());
+
+        // The 2010 value-overflow bug: outputs whose sum wraps around
+        tx.outputs[0].value = i64::MAX - 1;
+        tx.outputs.push(tx.outputs[0].clone());
+        let err = engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("output value overflow"), "{err}");
+
+        // Each output within the supply, their sum above it
+        tx.outputs[0].value = max_money / 2 + 1;
+        tx.outputs[1].value = max_money / 2 + 1;
+        let err = engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("output value overflow"), "{err}");
+
+        tx.outputs[1].value = -1;
+        let err = engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("negative output value"), "{err}");
+    }
+
+    #[test]
+    fn test_regtest_op_return_policy_is_relaxed() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 1).unwrap();
+
+        assert!(engine
+            .validate_transaction_with_protocol(&data_carrier_tx(&[200, 200]), &context)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_standardness_required_on_mainnet_only() {
+        let mut tx = spend(funding_outpoint(), 9_9999_0000);
+        let keys: Vec<Vec<u8>> = (0..4).map(|i| vec![2 + i; 33]).collect();
+        tx.outputs[0].script_pubkey = scripts::multisig_script(1, &keys);
+
+        let mainnet = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        let err = mainnet
+            .validate_transaction_with_protocol(&tx, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("nonstandard script"), "{err}");
+
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 800_000).unwrap();
+        assert!(regtest
+            .validate_transaction_with_protocol(&tx, &context)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_block_validation_ignores_relay_policy() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 5).unwrap();
+        let utxos = funded_utxos();
+        let mut block = regtest_block();
+        // Bare OP_TRUE: consensus-valid, not standard
+        block.transactions[0].outputs[0].script_pubkey = vec![0x51];
+        let block = mine(block);
+
+        let err = engine
+            .validate_transaction_with_protocol(&block.transactions[0], &context)
+            .unwrap_err();
+        assert!(matches!(err, ProtocolError::PolicyViolation(_)), "{err}");
+        assert!(engine
+            .validate_block_with_protocol(&block, &utxos, 5, &context)
+            .is_ok());
+
+        // Outputs are not executed when created
+        let mut block = regtest_block();
+        block.transactions[1].outputs[0].script_pubkey = vec![0x51, 0x51, 0x7e];
+        let block = mine(block);
+        let err = engine
+            .validate_transaction_with_protocol(&block.transactions[1], &context)
+            .unwrap_err();
+        assert!(matches!(err, ProtocolError::PolicyViolation(_)), "{err}");
+        assert!(engine
+            .validate_block_with_protocol(&block, &utxos, 5, &context)
+            .is_ok());
+
+        // Consensus accepts any version
+        let mut block = regtest_block();
+        block.transactions[1].version = 3;
+        let block = mine(block);
+        let err = engine
+            .validate_transaction_with_protocol(&block.transactions[1], &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported tx version"), "{err}");
+        assert!(engine
+            .validate_block_with_protocol(&block, &utxos, 5, &context)
+            .is_ok());
+
+        // A coinbase with several data outputs, like merge-mining tags
+        let mut block = regtest_block();
+        for tag in [[0xaa; 40], [0xbb; 40]] {
+            block.transactions[0].outputs.push(TransactionOutput {
+                value: 0,
+                script_pubkey: [vec![OP_RETURN, 40], tag.to_vec()].concat(),
+            });
+        }
+        let block = mine(block);
+        assert!(engine
+            .validate_block_with_protocol(&block, &utxos, 5, &context)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_tx_version_range() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut context =
+            ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        let mut tx = spend(funding_outpoint(), 9_9999_0000);
+
+        for version in [0, 3] {
+            tx.version = version;
+            let err = engine
+                .validate_transaction_with_protocol(&tx, &context)
+                .unwrap_err();
+            assert!(matches!(err, ProtocolError::PolicyViolation(_)), "{err}");
+            assert!(err.to_string().contains("unsupported tx version"), "{err}");
+        }
+        tx.version = 2;
+        assert!(engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .is_ok());
+
+        // Regtest accepts v3 out of the box
+        tx.version = 3;
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let regtest_context =
+            ProtocolValidationContext::new(ProtocolVersion::Regtest, 800_000).unwrap();
+        assert!(regtest
+            .validate_transaction_with_protocol(&tx, &regtest_context)
+            .is_ok());
+
+        // A loosened mainnet range lets v3 through to the version check
+        context.validation_rules.max_tx_version = 3;
+        context.validation_rules.require_standard = false;
+        assert!(engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_nonstandard_version_rejected() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut context =
+            ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        // Past the version range, v3 still fails standardness
+        context.validation_rules.max_tx_version = 3;
+        let mut tx = spend(funding_outpoint(), 9_9999_0000);
+        tx.version = 3;
+
+        let err = engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("nonstandard transaction version 3"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_script_size_validation() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+
+        // Create a transaction with small scripts
+        let tx = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0,
+                },
+                script_sig: vec![0x01, 0x04], // Small script sig
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 50_0000_0000,
+                script_pubkey: vec![
+                    0x76, 0xa9, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x88, 0xac,
+                ], // Small script pubkey
+            }],
+            lock_time: 0,
+        };
+
+        // This should pass validation
+        let result = engine.validate_transaction_with_protocol(&tx, &context);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validation_context_data() {
+        let mut context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+
+        // Add some context data
+        context
+            .context_data
+            .insert("test_key".to_string(), "test_value".to_string());
+
+        assert_eq!(
+            context.context_data.get("test_key"),
+            Some(&"test_value".to_string())
+        );
+        assert_eq!(context.context_data.get("nonexistent"), None);
+    }
+
+    const PREV_HASH: [u8; 32] = [0x11; 32];
+    const BLOCK_TIME: u64 = 1_700_000_000;
+
+    fn funding_outpoint() -> OutPoint {
+        OutPoint {
+            hash: [0x22; 32],
+            index: 0,
+        }
+    }
+
+    fn funded_utxos() -> HashMap<OutPoint, UTXO> {
+        let mut utxos = HashMap::new();
+        utxos.insert(
+            funding_outpoint(),
+            UTXO {
+                value: 10_0000_0000,
+                script_pubkey: vec![0x51],
+                height: 1,
+            },
+        );
+        utxos
+    }
+
+    fn coinbase(value: i64) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0xffffffff,
+                },
+                // BIP34 height 5 (OP_5)
+                script_sig: vec![0x55, 0x00],
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value,
+                script_pubkey: scripts::p2wpkh_script([0x33; 20]),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    fn spend(prevout: OutPoint, value: i64) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                prevout,
+                script_sig: vec![0x01, 0x01],
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value,
+                script_pubkey: scripts::p2wpkh_script([0x33; 20]),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    /// Recompute the merkle root and grind the nonce until the regtest target is met
+    fn mine(mut block: Block) -> Block {
+        block.header.merkle_root = encoding::block_merkle_root(&block);
+        while !pow::header_meets_target(&block.header) {
+            block.header.nonce += 1;
+        }
+        block
+    }
+
+    /// Regtest block at height 5 spending the funding output with a 0.1 BTC fee
+    fn regtest_block() -> Block {
+        let subsidy = EconomicParameters::regtest().get_block_subsidy(5) as i64;
+        mine(Block {
+            header: BlockHeader {
+                version: 4,
+                prev_block_hash: PREV_HASH,
+                merkle_root: [0u8; 32],
+                timestamp: BLOCK_TIME,
+                bits: 0x207fffff,
+                nonce: 0,
+            },
+            transactions: vec![
+                coinbase(subsidy + 1000_0000),
+                spend(funding_outpoint(), 9_9000_0000),
+            ],
+        })
+    }
+
+    #[test]
+    fn test_simnet_accepts_any_nonce() {
+        let simnet = BitcoinProtocolEngine::new(ProtocolVersion::Simnet).unwrap();
+        let mainnet = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut utxos = HashMap::new();
+        let mut prev_hash = PREV_HASH;
+
+        for height in 1..=100u64 {
+            let mut reward = coinbase(50_0000_0000);
+            reward.inputs[0].script_sig = bip34_height_script(height);
+            reward.inputs[0].script_sig.push(0x00);
+            let mut block = Block {
+                header: BlockHeader {
+                    version: 4,
+                    prev_block_hash: prev_hash,
+                    merkle_root: [0u8; 32],
+                    timestamp: BLOCK_TIME + height * 600,
+                    bits: 0x1d00ffff,
+                    nonce: 0,
+                },
+                transactions: vec![reward],
+            };
+            block.header.merkle_root = encoding::block_merkle_root(&block);
+
+            let now = block.header.timestamp;
+            simnet
+                .fully_validate_block(&block, &prev_hash, height, &mut utxos, now, &[])
+                .unwrap();
+            let err = mainnet
+                .check_proof_of_work(&block.header, height)
+                .unwrap_err();
+            assert!(err.to_string().contains("above target"), "{err}");
+            prev_hash = encoding::block_hash(&block.header);
+        }
+        assert_eq!(utxos.len(), 100);
+
+        // Regtest still requires proof of work
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut header = regtest_block().header;
+        header.bits = 0x1d00ffff;
+        assert!(regtest.check_proof_of_work(&header, 5).is_err());
+    }
+
+    #[test]
+    fn test_utxo_delta() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let block = regtest_block();
+        let coinbase_txid = encoding::txid(&block.transactions[0]);
+        let spend_txid = encoding::txid(&block.transactions[1]);
+
+        let delta = engine.utxo_delta(&block, 5);
+        let created: Vec<(Hash, u64, i64)> = delta
+            .created
+            .iter()
+            .map(|created| {
+                (
+                    created.outpoint.hash,
+                    created.outpoint.index,
+                    created.utxo.value,
+                )
+            })
+            .collect();
+        assert_eq!(
+            created,
+            vec![
+                (coinbase_txid, 0, block.transactions[0].outputs[0].value),
+                (spend_txid, 0, 9_9000_0000),
+            ]
+        );
+        assert!(delta.created.iter().all(|created| created.utxo.height == 5));
+        assert_eq!(delta.spent, vec![funding_outpoint()]);
+
+        assert!(delta.created[0].is_coinbase);
+        assert!(!delta.created[1].is_coinbase);
+        assert!(delta.is_coinbase_output(&delta.created[0].outpoint));
+        assert!(!delta.is_coinbase_output(&delta.created[1].outpoint));
+    }
+
+    #[test]
+    fn test_validate_block_verbose_reports_every_defect() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 5).unwrap();
+        let utxos = funded_utxos();
+
+        let report = engine.validate_block_verbose(&regtest_block(), &[], &utxos, 5, &context);
+        assert!(report.valid, "{:?}", report.findings);
+        assert!(report.findings.is_empty());
+
+        // Coinbase overpays and the spend's scriptSig uses a disabled opcode
+        let mut block = regtest_block();
+        block.transactions[0].outputs[0].value += 1;
+        block.transactions[1].inputs[0].script_sig = vec![0x51, 0x51, 0x7e];
+        let block = mine(block);
+
+        let report = engine.validate_block_verbose(&block, &[], &utxos, 5, &context);
+        assert!(!report.valid);
+        assert_eq!(report.errors().count(), 2, "{:?}", report.findings);
+        let coinbase: Vec<_> = report.in_category(FindingCategory::CoinbaseValue).collect();
+        assert_eq!(coinbase.len(), 1);
+        assert!(coinbase[0].message.contains("subsidy plus fees"));
+        let script: Vec<_> = report
+            .errors()
+            .filter(|finding| finding.category == FindingCategory::Transaction)
+            .collect();
+        assert_eq!(script.len(), 1);
+        assert_eq!(script[0].tx_index, Some(1));
+        assert!(script[0].message.contains("disabled opcode"));
+
+        // The first-error API only sees one of them
+        assert!(engine
+            .validate_block_with_protocol(&block, &utxos, 5, &context)
+            .is_err());
+
+        // Policy problems are warnings and leave the block valid
+        let mut block = regtest_block();
+        block.transactions[1].outputs[0].script_pubkey = vec![0xff];
+        let block = mine(block);
+        let report = engine.validate_block_verbose(&block, &[], &utxos, 5, &context);
+        assert!(report.valid, "{:?}", report.findings);
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].severity, FindingSeverity::Warning);
+        assert!(report.findings[0].message.contains("nonstandard script"));
+
+        let mut block = regtest_block();
+        block.transactions[1].version = 7;
+        let block = mine(block);
+        let report = engine.validate_block_verbose(&block, &[], &utxos, 5, &context);
+        assert!(report.valid, "{:?}", report.findings);
+        assert!(report
+            .findings
+            .iter()
+            .all(|finding| finding.severity == FindingSeverity::Warning));
+        assert!(report
+            .findings
+            .iter()
+            .any(|finding| finding.message.contains("unsupported tx version")));
+
+        // The witness commitment is checked against the supplied witnesses
+        let (block, mut witnesses) = segwit_block();
+        let block = mine(block);
+        let report = engine.validate_block_verbose(&block, &witnesses, &utxos, 5, &context);
+        assert!(report.valid, "{:?}", report.findings);
+        witnesses[1][0][0][0] ^= 0x01;
+        let report = engine.validate_block_verbose(&block, &witnesses, &utxos, 5, &context);
+        assert!(!report.valid);
+        let structure: Vec<_> = report.in_category(FindingCategory::Structure).collect();
+        assert_eq!(structure.len(), 1, "{:?}", report.findings);
+        assert!(structure[0].message.contains("witness commitment mismatch"));
+    }
+
+    #[test]
+    fn test_utxo_set_hash_ignores_insertion_order() {
+        let entries: Vec<(OutPoint, UTXO)> = (0..50u8)
+            .map(|i| {
+                let outpoint = OutPoint {
+                    hash: [i; 32],
+                    index: i as u64 % 3,
+                };
+                let utxo = UTXO {
+                    value: 1000 * i as i64,
+                    script_pubkey: scripts::p2wpkh_script([i; 20]),
+                    height: i as u64,
+                };
+                (outpoint, utxo)
+            })
+            .collect();
+        let forward: HashMap<OutPoint, UTXO> = entries.iter().cloned().collect();
+        let mut backward = HashMap::with_capacity(200);
+        backward.extend(entries.iter().rev().cloned());
+
+        let hash = BitcoinProtocolEngine::utxo_set_hash(&forward);
+        assert_eq!(BitcoinProtocolEngine::utxo_set_hash(&backward), hash);
+
+        let outpoint = entries[7].0.clone();
+        backward.get_mut(&outpoint).unwrap().value += 1;
+        assert_ne!(BitcoinProtocolEngine::utxo_set_hash(&backward), hash);
+        backward.remove(&outpoint);
+        assert_ne!(BitcoinProtocolEngine::utxo_set_hash(&backward), hash);
+    }
+
+    #[test]
+    fn test_undo_restores_utxo_set() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut block = regtest_block();
+        // Spend the block's own output too: undo must remove, not restore it
+        let intra_block = OutPoint {
+            hash: encoding::txid(&block.transactions[1]),
+            index: 0,
+        };
+        block.transactions.push(spend(intra_block, 9_8000_0000));
+
+        let mut utxos = funded_utxos();
+        utxos.insert(
+            OutPoint {
+                hash: [0x33; 32],
+                index: 7,
+            },
+            UTXO {
+                value: 5000,
+                script_pubkey: vec![0x00, 0x14, 0x01],
+                height: 3,
+            },
+        );
+        let before = utxos.clone();
+
+        let undo = engine.compute_undo(&block, &utxos).unwrap();
+        assert_eq!(
+            undo.spent,
+            vec![(funding_outpoint(), before[&funding_outpoint()].clone())]
+        );
+
+        let delta = engine.utxo_delta(&block, 5);
+        for outpoint in &delta.spent {
+            utxos.remove(outpoint);
+        }
+        utxos.extend(
+            delta
+                .created
+                .into_iter()
+                .map(|created| (created.outpoint, created.utxo)),
+        );
+        assert!(!utxos.contains_key(&funding_outpoint()));
+
+        apply_undo(&mut utxos, &undo, &block);
+        assert_eq!(utxos, before);
+        let sorted = |set: &HashMap<OutPoint, UTXO>| {
+            let mut entries: Vec<(OutPoint, UTXO)> =
+                set.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            entries.sort_by_key(|(outpoint, _)| (outpoint.hash, outpoint.index));
+            serde_json::to_vec(&entries).unwrap()
+        };
+        assert_eq!(sorted(&utxos), sorted(&before));
+
+        // Undo records survive serialization, as they are stored on disk
+        let json = serde_json::to_string(&undo).unwrap();
+        assert_eq!(serde_json::from_str::<BlockUndo>(&json).unwrap(), undo);
+
+        // An input neither in the spent UTXOs nor created earlier in the block
+        let err = engine.compute_undo(&block, &HashMap::new()).unwrap_err();
+        assert!(err.to_string().contains("transaction 1 input 0"), "{err}");
+        let mut reordered = block.clone();
+        reordered.transactions.swap(1, 2);
+        let err = engine.compute_undo(&reordered, &before).unwrap_err();
+        assert!(err.to_string().contains("transaction 1 input 0"), "{err}");
+    }
+
+    fn with_signet_commitment(extra_push: Option<Vec<u8>>) -> Block {
+        let mut block = regtest_block();
+        let mut script = WITNESS_COMMITMENT_PREFIX.to_vec();
+        script.extend_from_slice(&[0u8; 32]);
+        if let Some(data) = extra_push {
+            script.push(data.len() as u8);
+            script.extend_from_slice(&data);
+        }
+        block.transactions[0].outputs.push(TransactionOutput {
+            value: 0,
+            script_pubkey: script,
+        });
+        block
+    }
+
+    #[test]
+    fn test_verify_signet_block() {
+        let challenge = [0x51];
+        // Empty scriptSig, one-item witness
+        let mut solution = SIGNET_HEADER.to_vec();
+        solution.extend_from_slice(&[0x00, 0x01, 0x02, 0xab, 0xcd]);
+        assert!(
+            verify_signet_block(&with_signet_commitment(Some(solution.clone())), &challenge)
+                .is_ok()
+        );
+
+        let err = verify_signet_block(&regtest_block(), &challenge).unwrap_err();
+        assert!(err.to_string().contains("no witness commitment"), "{err}");
+        let err = verify_signet_block(&with_signet_commitment(None), &challenge).unwrap_err();
+        assert!(err.to_string().contains("missing signet solution"), "{err}");
+
+        let mut truncated = solution.clone();
+        truncated.pop();
+        let err =
+            verify_signet_block(&with_signet_commitment(Some(truncated)), &challenge).unwrap_err();
+        assert!(err.to_string().contains("malformed"), "{err}");
+        solution.push(0x00);
+        let err =
+            verify_signet_block(&with_signet_commitment(Some(solution)), &challenge).unwrap_err();
+        assert!(err.to_string().contains("malformed"), "{err}");
+
+        let err = verify_signet_block(&with_signet_commitment(None), &[]).unwrap_err();
+        assert!(err.to_string().contains("empty signet challenge"), "{err}");
+    }
+
+    #[test]
+    fn test_is_coinbase_input() {
+        let block = regtest_block();
+        assert!(is_coinbase_input(&block.transactions[0].inputs[0]));
+        assert!(!is_coinbase_input(&block.transactions[1].inputs[0]));
+
+        // A null hash alone does not make a coinbase input
+        let mut input = block.transactions[0].inputs[0].clone();
+        input.prevout.index = 0;
+        assert!(!is_coinbase_input(&input));
+    }
+
+    fn full_validation_failure(block: &Block, recent_timestamps: &[u32]) -> ProtocolError {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let mut utxos = funded_utxos();
+        let err = engine
+            .fully_validate_block(
+                block,
+                &PREV_HASH,
+                5,
+                &mut utxos,
+                BLOCK_TIME,
+                recent_timestamps,
+            )
+            .unwrap_err();
+        assert_eq!(
+            utxos,
+            funded_utxos(),
+            "UTXO set must be untouched on failure"
+        );
+        err
+    }
+
+    fn full_validation_error(block: &Block, recent_timestamps: &[u32]) -> String {
+        full_validation_failure(block, recent_timestamps).to_string()
+    }
+
+    #[test]
+    fn test_fully_validate_block_connects_utxos() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let block = regtest_block();
+        let mut utxos = funded_utxos();
+        let timestamps: Vec<u32> = (0..11)
+            .map(|i| (BLOCK_TIME - 600 * (11 - i)) as u32)
+            .collect();
+
+        engine
+            .fully_validate_block(&block, &PREV_HASH, 5, &mut utxos, BLOCK_TIME, &timestamps)
+            .unwrap();
+
+        assert!(!utxos.contains_key(&funding_outpoint()));
+        for tx in &block.transactions {
+            let outpoint = OutPoint {
+                hash: encoding::txid(tx),
+                index: 0,
+            };
+            assert_eq!(utxos[&outpoint].height, 5);
+        }
+    }
+
+    #[test]
+    fn test_fully_validate_block_header_failures() {
+        let block = regtest_block();
+
+        let mut wrong_parent = block.clone();
+        wrong_parent.header.prev_block_hash = [0x33; 32];
+        let err = full_validation_error(&mine(wrong_parent), &[]);
+        assert!(err.contains("previous block hash mismatch"), "{err}");
+
+        let mut unmined = block.clone();
+        while pow::header_meets_target(&unmined.header) {
+            unmined.header.nonce += 1;
+        }
+        let err = full_validation_error(&unmined, &[]);
+        assert!(err.contains("above target"), "{err}");
+
+        let err = full_validation_error(&block, &[BLOCK_TIME as u32; 11]);
+        assert!(err.contains("median time past"), "{err}");
+
+        let mut future = block.clone();
+        future.header.timestamp = BLOCK_TIME + MAX_FUTURE_BLOCK_TIME + 1;
+        let err = full_validation_error(&mine(future), &[]);
+        assert!(err.contains("too far in the future"), "{err}");
+    }
+
+    #[test]
+    fn test_block_time_follows_engine_clock() {
+        let clock = Arc::new(MockClock::at_unix(BLOCK_TIME));
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1)
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut header = regtest_block().header;
+        header.timestamp = BLOCK_TIME + 3 * 60 * 60;
+
+        let err = engine.check_block_timestamp_now(&header, &[]).unwrap_err();
+        assert!(err.to_string().contains("too far in the future"), "{err}");
+
+        // An hour later the block is within the two-hour allowance
+        clock.advance(Duration::from_secs(60 * 60));
+        assert!(engine.check_block_timestamp_now(&header, &[]).is_ok());
+    }
+
+    #[test]
+    fn test_check_block_timestamp() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut header = regtest_block().header;
+        let prev: Vec<u32> = (0..11)
+            .map(|i| BLOCK_TIME as u32 - 600 * (11 - i))
+            .collect();
+        let mtp = BLOCK_TIME - 600 * 6;
+
+        assert!(engine
+            .check_block_timestamp(&header, &prev, BLOCK_TIME)
+            .is_ok());
+        assert!(engine
+            .check_block_timestamp(&header, &[], BLOCK_TIME)
+            .is_ok());
+
+        header.timestamp = mtp;
+        let err = engine
+            .check_block_timestamp(&header, &prev, BLOCK_TIME)
+            .unwrap_err();
+        assert!(err.to_string().contains("median time past"), "{err}");
+        header.timestamp = mtp + 1;
+        assert!(engine
+            .check_block_timestamp(&header, &prev, BLOCK_TIME)
+            .is_ok());
+
+        header.timestamp = BLOCK_TIME + 3 * 60 * 60;
+        let err = engine
+            .check_block_timestamp(&header, &prev, BLOCK_TIME)
+            .unwrap_err();
+        assert!(err.to_string().contains("too far in the future"), "{err}");
+        header.timestamp = BLOCK_TIME + MAX_FUTURE_BLOCK_TIME;
+        assert!(engine
+            .check_block_timestamp(&header, &prev, BLOCK_TIME)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_custom_mtp_window() {
+        let mut constants = NetworkConstants::regtest().unwrap();
+        constants.mtp_window = 3;
+        let mut header = regtest_block().header;
+        // Only the last three count: median is BLOCK_TIME - 200
+        let prev = [
+            BLOCK_TIME as u32 + 5000,
+            BLOCK_TIME as u32 - 300,
+            BLOCK_TIME as u32 - 100,
+            BLOCK_TIME as u32 - 200,
+        ];
+
+        header.timestamp = BLOCK_TIME - 200;
+        let err = check_block_timestamp_with(&header, &prev, BLOCK_TIME, &constants).unwrap_err();
+        assert!(err.to_string().contains("median time past"), "{err}");
+        header.timestamp = BLOCK_TIME - 199;
+        assert!(check_block_timestamp_with(&header, &prev, BLOCK_TIME, &constants).is_ok());
+
+        constants.max_future_block_time = 60;
+        header.timestamp = BLOCK_TIME + 61;
+        let err = check_block_timestamp_with(&header, &prev, BLOCK_TIME, &constants).unwrap_err();
+        assert!(err.to_string().contains("too far in the future"), "{err}");
+    }
+
+    #[test]
+    fn test_mtp_window_of_one_requires_increasing_timestamps() {
+        let mut constants = NetworkConstants::regtest().unwrap();
+        constants.mtp_window = 1;
+        let mut header = regtest_block().header;
+        let prev = [BLOCK_TIME as u32 - 50, BLOCK_TIME as u32 - 1000];
+
+        header.timestamp = BLOCK_TIME - 1000;
+        assert!(check_block_timestamp_with(&header, &prev, BLOCK_TIME, &constants).is_err());
+        header.timestamp = BLOCK_TIME - 999;
+        assert!(check_block_timestamp_with(&header, &prev, BLOCK_TIME, &constants).is_ok());
+    }
+
+    #[test]
+    fn test_fully_validate_block_body_failures() {
+        let block = regtest_block();
+
+        let mut bad_merkle = block.clone();
+        bad_merkle.transactions[1].lock_time = 1;
+        let err = full_validation_error(&bad_merkle, &[]);
+        assert!(err.contains("merkle root mismatch"), "{err}");
+
+        let mut duplicated = block.clone();
+        duplicated.transactions.push(block.transactions[1].clone());
+        let err = full_validation_error(&mine(duplicated), &[]);
+        assert!(err.contains("duplicate transaction"), "{err}");
+
+        let mut missing_coinbase = block.clone();
+        missing_coinbase.transactions.remove(0);
+        let err = full_validation_error(&mine(missing_coinbase), &[]);
+        assert!(err.contains("no coinbase"), "{err}");
+
+        let mut missing_input = block.clone();
+        missing_input.transactions[1] = spend(
+            OutPoint {
+                hash: [0x44; 32],
+                index: 0,
+            },
+            1000,
+        );
+        let err = full_validation_error(&mine(missing_input), &[]);
+        assert!(err.contains("missing input"), "{err}");
+
+        let mut overclaim = block.clone();
+        overclaim.transactions[0].outputs[0].value += 1;
+        let err = full_validation_error(&mine(overclaim), &[]);
+        assert!(err.contains("coinbase pays more"), "{err}");
+    }
+
+    fn mempool_error(tx: &Transaction) -> String {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        let feature_ctx = engine.feature_context(800_000, BLOCK_TIME);
+        engine
+            .accept_to_mempool(tx, &funded_utxos(), &context, &feature_ctx)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_accept_to_mempool() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        let feature_ctx = engine.feature_context(800_000, BLOCK_TIME);
+        let mut tx = spend(funding_outpoint(), 9_9999_0000);
+        tx.inputs[0].sequence = 0xfffffffd;
+
+        let accepted = engine
+            .accept_to_mempool(&tx, &funded_utxos(), &context, &feature_ctx)
+            .unwrap();
+        assert_eq!(accepted.fee, 1_0000);
+        assert_eq!(
+            accepted.vsize,
+            encoding::serialize_transaction(&tx).len() as u64
+        );
+        assert!(accepted.fee_rate > 1.0);
+        assert!(accepted.signals_rbf);
+    }
+
+    #[test]
+    fn test_accept_to_mempool_rejections() {
+        let valid = spend(funding_outpoint(), 9_9999_0000);
+
+        let mut no_inputs = valid.clone();
+        no_inputs.inputs.clear();
+        assert!(!mempool_error(&no_inputs).is_empty());
+
+        let mut oversized_script = valid.clone();
+        oversized_script.outputs[0].script_pubkey = vec![0x51; 10_001];
+        assert!(mempool_error(&oversized_script).contains("Script size"));
+
+        assert!(mempool_error(&coinbase(50_0000_0000)).contains("coinbase"));
+
+        let mut missing = valid.clone();
+        missing.inputs[0].prevout.index = 1;
+        assert!(mempool_error(&missing).contains("missing inputs"));
+
+        let overspend = spend(funding_outpoint(), 10_0000_0001);
+        assert!(mempool_error(&overspend).contains("inputs less than outputs"));
+
+        let mut non_final = valid.clone();
+        non_final.lock_time = 800_001;
+        non_final.inputs[0].sequence = 0xfffffffe;
+        assert!(mempool_error(&non_final).contains("non-final"));
+
+        let mut sequence_locked = valid.clone();
+        sequence_locked.version = 2;
+        sequence_locked.inputs[0].sequence = 20; // 20 blocks after the coin confirmed
+        let mut locked_utxos = funded_utxos();
+        locked_utxos.get_mut(&funding_outpoint()).unwrap().height = 799_990;
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        let feature_ctx = engine.feature_context(800_000, BLOCK_TIME);
+        let err = engine
+            .accept_to_mempool(&sequence_locked, &locked_utxos, &context, &feature_ctx)
+            .unwrap_err();
+        assert!(err.to_string().contains("sequence lock"), "{err}");
+
+        let zero_fee = spend(funding_outpoint(), 10_0000_0000);
+        assert!(mempool_error(&zero_fee).contains("below minimum"));
+
+        let mut dust = valid.clone();
+        dust.outputs.push(TransactionOutput {
+            value: 545,
+            script_pubkey: scripts::p2wpkh_script([0x44; 20]),
+        });
+        assert!(mempool_error(&dust).contains("dust"));
+    }
+
+    /// `count` unconfirmed transactions, each spending the previous one,
+    /// followed by the transaction spending the last of them
+    fn unconfirmed_chain(count: usize) -> (Vec<Transaction>, Transaction) {
+        let mut prevout = OutPoint {
+            hash: [0x42; 32],
+            index: 0,
+        };
+        let mut chain = Vec::new();
+        for _ in 0..=count {
+            let tx = spend(prevout, 10_000);
+            prevout = OutPoint {
+                hash: encoding::txid(&tx),
+                index: 0,
+            };
+            chain.push(tx);
+        }
+        let new_tx = chain.pop().unwrap();
+        (chain, new_tx)
+    }
+
+    #[test]
+    fn test_package_ancestor_limits() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+
+        let (ancestors, new_tx) = unconfirmed_chain(24);
+        engine.check_package_limits(&new_tx, &ancestors).unwrap();
+
+        let (ancestors, new_tx) = unconfirmed_chain(26);
+        let err = engine
+            .check_package_limits(&new_tx, &ancestors)
+            .unwrap_err();
+        assert!(matches!(err, ProtocolError::PolicyViolation(_)), "{err}");
+        assert!(
+            err.to_string().contains("too many unconfirmed ancestors"),
+            "{err}"
+        );
+
+        // Few ancestors, but too large together
+        let mut big = spend(
+            OutPoint {
+                hash: [0x43; 32],
+                index: 0,
+            },
+            10_000,
+        );
+        big.outputs[0].script_pubkey = vec![0x51; 60_000];
+        let child = spend(
+            OutPoint {
+                hash: encoding::txid(&big),
+                index: 0,
+            },
+            5_000,
+        );
+        let mut second = big.clone();
+        second.inputs[0].prevout.index = 1;
+        let err = engine
+            .check_package_limits(&child, &[big, second])
+            .unwrap_err();
+        assert!(err.to_string().contains("ancestor package"), "{err}");
+    }
+
+    #[test]
+    fn test_estimate_vsize_reference_sizes() {
+        let p2wpkh_out = crate::scripts::p2wpkh_script([1; 20]);
+        let p2tr_out = crate::scripts::p2tr_script([2; 32]);
+        let p2pkh_out = crate::scripts::p2pkh_script([3; 20]);
+
+        // 1-in-2-out references: 10.5 + 68 + 2 * 31 and 10.5 + 57.5 + 2 * 43
+        assert_eq!(
+            estimate_vsize(&[ScriptType::P2WPKH], &[p2wpkh_out.clone(), p2wpkh_out]),
+            141
+        );
+        assert_eq!(
+            estimate_vsize(&[ScriptType::P2TR], &[p2tr_out.clone(), p2tr_out]),
+            154
+        );
+        // Legacy has no witness discount: 10 + 148 + 2 * 34
+        assert_eq!(
+            estimate_vsize(
+                &[ScriptType::P2PKH],
+                &[p2pkh_out.clone(), p2pkh_out.clone()]
+            ),
+            226
+        );
+
+        // Per-input costs in vbytes, measured as the marginal weight
+        let one = estimate_vsize(&[ScriptType::P2WPKH], &[]) * 4;
+        let two = estimate_vsize(&[ScriptType::P2WPKH; 2], &[]) * 4;
+        assert_eq!(two - one, 272);
+
+        // Mixing in a legacy input adds an empty witness stack for it
+        let mixed = estimate_vsize(&[ScriptType::P2TR, ScriptType::P2PKH], &[p2pkh_out]);
+        assert_eq!(mixed, (42 + 230 + (148 + 34) * 4 + 1_u64).div_ceil(4));
+    }
+
+    #[test]
+    fn test_package_fee_rate_cpfp() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let (mut package, child) = unconfirmed_chain(1);
+        package[0].outputs[0].script_pubkey = vec![0x51; 300];
+        package.push(child);
+        let vsizes: Vec<u64> = package
+            .iter()
+            .map(|tx| encoding::serialize_transaction(tx).len() as u64)
+            .collect();
+
+        // 1 sat/vB parent rescued by a 50 sat/vB child
+        let fees = [vsizes[0], 50 * vsizes[1]];
+        let rate = engine.package_fee_rate(&package, &fees).unwrap();
+        let expected = (vsizes[0] + 50 * vsizes[1]) as f64 / (vsizes[0] + vsizes[1]) as f64;
+        assert!((rate - expected).abs() < 1e-9, "{rate} != {expected}");
+        assert!(rate > 1.0 && rate < 50.0);
+
+        let err = engine.package_fee_rate(&package, &fees[..1]).unwrap_err();
+        assert!(
+            err.to_string().contains("2 transactions but 1 fees"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_tx_metrics_segwit() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut tx = spend(funding_outpoint(), 1000);
+        tx.inputs[0].script_sig.clear();
+        tx.inputs[0].sequence = 0xfffffffd;
+        let witness = vec![vec![vec![0x30; 71], vec![0x02; 33]]];
+
+        let metrics = engine.tx_metrics(&tx, &witness, 1100);
+        // Version 4, one input 41, one P2WPKH output 31, counts 2, lock time 4
+        let base_size = 4 + 1 + 41 + 1 + 31 + 4;
+        // Marker and flag, stack count, then each item with its length prefix
+        let total_size = base_size + 2 + 1 + (1 + 71) + (1 + 33);
+        let weight = 3 * base_size + total_size;
+        assert_eq!(
+            metrics,
+            TxMetrics {
+                weight,
+                vsize: 110,
+                base_size,
+                total_size,
+                fee: 1100,
+                fee_rate_sat_per_vbyte: 10.0,
+                is_segwit: true,
+                signals_rbf: true,
+            }
+        );
+        assert_eq!(weight, 437);
+
+        let legacy = engine.tx_metrics(&tx, &[], 0);
+        assert!(!legacy.is_segwit);
+        assert_eq!(legacy.total_size, legacy.base_size);
+        assert_eq!(legacy.weight, 4 * base_size);
+        assert_eq!(legacy.fee_rate_sat_per_vbyte, 0.0);
+    }
+
+    #[test]
+    fn test_sequence_info() {
+        let sequence = SequenceInfo::parse(0xffffffff);
+        assert!(sequence.is_final);
+        assert!(!sequence.enables_rbf);
+        assert!(sequence.relative_lock_disabled);
+
+        let sequence = SequenceInfo::parse(0xfffffffd);
+        assert!(!sequence.is_final);
+        assert!(sequence.enables_rbf);
+        assert!(sequence.relative_lock_disabled);
+
+        // 16 * 512 seconds
+        assert_eq!(
+            SequenceInfo::parse(0x00400010),
+            SequenceInfo {
+                is_final: false,
+                enables_rbf: true,
+                relative_lock_disabled: false,
+                is_time_based: true,
+                locktime_value: 16,
+            }
+        );
+        assert!(!SequenceInfo::parse(20).is_time_based);
+        assert_eq!(SequenceInfo::parse(20).locktime_value, 20);
+    }
+
+    #[test]
+    fn test_signals_rbf() {
+        let mut tx = spend(funding_outpoint(), 1000);
+        assert!(!signals_rbf(&tx));
+
+        tx.inputs[0].sequence = 0xfffffffe;
+        assert!(!signals_rbf(&tx));
+
+        tx.inputs[0].sequence = 0xfffffffd;
+        assert!(signals_rbf(&tx));
+    }
+
+    #[test]
+    fn test_replaces_requires_shared_outpoint() {
+        let original = spend(funding_outpoint(), 1000);
+        let replacement = spend(funding_outpoint(), 900);
+        let unrelated = spend(
+            OutPoint {
+                hash: [0x55; 32],
+                index: 0,
+            },
+            900,
+        );
+
+        assert!(replaces(&replacement, &original));
+        assert!(!replaces(&unrelated, &original));
+    }
+
+    #[test]
+    fn test_rbf_replacement_rules() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let mut original = spend(funding_outpoint(), 9_9999_0000);
+        original.inputs[0].sequence = 0xfffffffd;
+        let replacement = spend(funding_outpoint(), 9_9998_0000);
+
+        // Pays more in total and covers its own size
+        assert!(engine
+            .check_rbf_replacement(&replacement, &[original.clone()], 2_0000, 1_0000)
+            .is_ok());
+
+        // Rule 3: lower absolute fee
+        let err = engine
+            .check_rbf_replacement(&replacement, &[original.clone()], 9_000, 1_0000)
+            .unwrap_err();
+        assert!(err.to_string().contains("lower than original"), "{err}");
+
+        // Rule 4: same fee does not pay for the new bandwidth
+        let err = engine
+            .check_rbf_replacement(&replacement, &[original.clone()], 1_0000, 1_0000)
+            .unwrap_err();
+        assert!(err.to_string().contains("bandwidth"), "{err}");
+
+        // Original without opt-in
+        original.inputs[0].sequence = 0xffffffff;
+        let err = engine
+            .check_rbf_replacement(&replacement, &[original], 2_0000, 1_0000)
+            .unwrap_err();
+        assert!(err.to_string().contains("signal"), "{err}");
+    }
+
+    #[test]
+    fn test_policy_failures_map_to_protocol_errors() {
+        let block = regtest_block();
+
+        let mut oversized = block.clone();
+        oversized.transactions[1].outputs[0].script_pubkey = vec![0x00; 1_000_000];
+        assert!(matches!(
+            full_validation_failure(&mine(oversized), &[]),
+            ProtocolError::BlockSizeExceeded(_)
+        ));
+
+        let mut sigop_heavy = block.clone();
+        sigop_heavy.transactions[1].outputs[0].script_pubkey = vec![0xac; 20_001];
+        assert!(matches!(
+            full_validation_failure(&mine(sigop_heavy), &[]),
+            ProtocolError::SigopLimitExceeded(_)
+        ));
+
+        let mut bad_merkle = block.clone();
+        bad_merkle.transactions[1].lock_time = 1;
+        assert!(matches!(
+            full_validation_failure(&bad_merkle, &[]),
+            ProtocolError::Consensus(ConsensusError::BlockValidation(_))
+        ));
+
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 800_000).unwrap();
+        let feature_ctx = engine.feature_context(800_000, BLOCK_TIME);
+
+        let zero_fee = spend(funding_outpoint(), 10_0000_0000);
+        assert!(matches!(
+            engine.accept_to_mempool(&zero_fee, &funded_utxos(), &context, &feature_ctx),
+            Err(ProtocolError::FeeOutOfRange(_))
+        ));
+
+        let mut oversized_script = spend(funding_outpoint(), 9_9999_0000);
+        oversized_script.outputs[0].script_pubkey = vec![0x51; 10_001];
+        assert!(matches!(
+            engine.accept_to_mempool(&oversized_script, &funded_utxos(), &context, &feature_ctx),
+            Err(ProtocolError::TransactionSizeExceeded(_))
+        ));
+
+        let mut original = spend(funding_outpoint(), 9_9999_0000);
+        original.inputs[0].sequence = 0xfffffffd;
+        let replacement = spend(funding_outpoint(), 9_9998_0000);
+        assert!(matches!(
+            engine.check_rbf_replacement(&replacement, &[original], 9_000, 1_0000),
+            Err(ProtocolError::FeeOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_coinbase_value() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let subsidy = 6_2500_0000; // 6.25 BTC at height 700,000
+        let fees = 1234;
+        let block_paying = |value: i64| Block {
+            header: regtest_block().header,
+            transactions: vec![coinbase(value)],
+        };
+
+        let exact = block_paying(subsidy + fees);
+        assert!(engine
+            .check_coinbase_value(&exact, 700_000, fees as u64)
+            .is_ok());
+
+        let under = block_paying(subsidy);
+        assert!(engine
+            .check_coinbase_value(&under, 700_000, fees as u64)
+            .is_ok());
+
+        let over = block_paying(subsidy + fees + 1);
+        let err = engine
+            .check_coinbase_value(&over, 700_000, fees as u64)
+            .unwrap_err();
+        assert!(err.to_string().contains("coinbase pays more"), "{err}");
+    }
+
+    fn headers_with_bits(count: usize, bits: u64) -> Vec<BlockHeader> {
+        (0..count)
+            .map(|i| BlockHeader {
+                version: 4,
+                prev_block_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: BLOCK_TIME + i as u64 * 600,
+                bits,
+                nonce: 0,
+            })
+            .collect()
+    }
 
     #[test]
-    fn test_validation_rules() {
-        let mainnet_rules = ProtocolValidationRules::mainnet();
-        assert_eq!(mainnet_rules.max_block_size, 4_000_000);
-        assert!(mainnet_rules.segwit_enabled);
-        assert!(mainnet_rules.taproot_enabled);
+    fn test_cumulative_work_over_retarget_interval() {
+        let headers = headers_with_bits(2016, 0x1d00ffff);
+        assert_eq!(
+            cumulative_work(&headers),
+            ChainWork::from(2016 * 0x0100010001u128)
+        );
+        assert_eq!(cumulative_work(&[]), ChainWork::ZERO);
+    }
 
-        let regtest_rules = ProtocolValidationRules::regtest();
-        assert_eq!(regtest_rules.max_block_size, 4_000_000);
-        assert!(regtest_rules.segwit_enabled);
-        assert_eq!(regtest_rules.min_fee_rate, 0); // No minimum fee for testing
+    #[test]
+    fn test_shorter_harder_chain_wins() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let long_easy = headers_with_bits(100, 0x1d00ffff);
+        let short_hard = headers_with_bits(10, 0x1b04864c);
+
+        assert!(engine.is_better_chain(&short_hard, &long_easy));
+        assert!(!engine.is_better_chain(&long_easy, &short_hard));
+        assert!(!engine.is_better_chain(&long_easy, &long_easy));
+    }
+
+    /// Regtest headers, each linked to and mined on top of the previous one
+    fn mined_header_chain(count: usize) -> Vec<BlockHeader> {
+        let mut chain: Vec<BlockHeader> = Vec::new();
+        for mut header in headers_with_bits(count, 0x207fffff) {
+            if let Some(prev) = chain.last() {
+                header.prev_block_hash = encoding::block_hash(prev);
+            }
+            while !pow::header_meets_target(&header) {
+                header.nonce += 1;
+            }
+            chain.push(header);
+        }
+        chain
     }
 
     #[test]
-    fn test_validation_rules_all_protocols() {
-        let mainnet_rules = ProtocolValidationRules::for_protocol(ProtocolVersion::BitcoinV1);
-        let testnet_rules = ProtocolValidationRules::for_protocol(ProtocolVersion::Testnet3);
-        let regtest_rules = ProtocolValidationRules::for_protocol(ProtocolVersion::Regtest);
+    fn test_validate_header_chain() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let chain = mined_header_chain(3);
+        assert!(engine.validate_header_chain(&chain, 1).is_ok());
+        assert!(engine.validate_header_chain(&[], 1).is_ok());
 
-        // Mainnet and testnet should have same rules
-        assert_eq!(mainnet_rules.max_block_size, testnet_rules.max_block_size);
-        assert_eq!(mainnet_rules.max_tx_size, testnet_rules.max_tx_size);
-        assert_eq!(mainnet_rules.max_script_size, testnet_rules.max_script_size);
-        assert_eq!(mainnet_rules.segwit_enabled, testnet_rules.segwit_enabled);
-        assert_eq!(mainnet_rules.taproot_enabled, testnet_rules.taproot_enabled);
-        assert_eq!(mainnet_rules.rbf_enabled, testnet_rules.rbf_enabled);
-        assert_eq!(mainnet_rules.min_fee_rate, testnet_rules.min_fee_rate);
-        assert_eq!(mainnet_rules.max_fee_rate, testnet_rules.max_fee_rate);
+        let mut broken = chain.clone();
+        broken[2].prev_block_hash = [0xab; 32];
+        let err = engine.validate_header_chain(&broken, 1).unwrap_err();
+        assert!(
+            err.to_string().contains("header 2 does not connect"),
+            "{err}"
+        );
 
-        // Regtest should have relaxed fee rules
-        assert_eq!(regtest_rules.min_fee_rate, 0);
-        assert_eq!(regtest_rules.max_fee_rate, mainnet_rules.max_fee_rate);
+        let mut unmined = chain.clone();
+        unmined[1].bits = 0x1d00ffff;
+        while pow::header_meets_target(&unmined[1]) {
+            unmined[1].nonce += 1;
+        }
+        let err = engine.validate_header_chain(&unmined[..2], 1).unwrap_err();
+        assert!(
+            err.to_string().contains("header 1 hash above target"),
+            "{err}"
+        );
+
+        // Simnet skips proof of work, but headers must still connect
+        let simnet = BitcoinProtocolEngine::new(ProtocolVersion::Simnet).unwrap();
+        assert!(simnet.validate_header_chain(&unmined[..2], 1).is_ok());
+        assert!(simnet.validate_header_chain(&broken, 1).is_err());
+    }
+
+    fn coinbase_with_script(script_sig: Vec<u8>) -> Transaction {
+        let mut tx = coinbase(50_0000_0000);
+        tx.inputs[0].script_sig = script_sig;
+        tx
     }
 
     #[test]
-    fn test_validation_rules_serialization() {
-        let mainnet_rules = ProtocolValidationRules::mainnet();
-        let json = serde_json::to_string(&mainnet_rules).unwrap();
-        let deserialized: ProtocolValidationRules = serde_json::from_str(&json).unwrap();
+    fn test_extract_bip34_height() {
+        assert_eq!(
+            extract_bip34_height(&coinbase_with_script(vec![0x53, 0xff])).unwrap(),
+            3
+        );
+        // Mainnet block 227,931: push of 0x5b7a03
+        let script = vec![0x03, 0x5b, 0x7a, 0x03, 0xab];
+        assert_eq!(
+            extract_bip34_height(&coinbase_with_script(script)).unwrap(),
+            227_931
+        );
+        // 128 needs a sign byte
+        let script = vec![0x02, 0x80, 0x00];
+        assert_eq!(
+            extract_bip34_height(&coinbase_with_script(script)).unwrap(),
+            128
+        );
 
-        assert_eq!(mainnet_rules.max_block_size, deserialized.max_block_size);
-        assert_eq!(mainnet_rules.max_tx_size, deserialized.max_tx_size);
-        assert_eq!(mainnet_rules.max_script_size, deserialized.max_script_size);
-        assert_eq!(mainnet_rules.segwit_enabled, deserialized.segwit_enabled);
-        assert_eq!(mainnet_rules.taproot_enabled, deserialized.taproot_enabled);
-        assert_eq!(mainnet_rules.rbf_enabled, deserialized.rbf_enabled);
-        assert_eq!(mainnet_rules.min_fee_rate, deserialized.min_fee_rate);
-        assert_eq!(mainnet_rules.max_fee_rate, deserialized.max_fee_rate);
+        // Non-minimal (3 pushed as data) and negative encodings are rejected
+        assert!(extract_bip34_height(&coinbase_with_script(vec![0x01, 0x03])).is_err());
+        assert!(extract_bip34_height(&coinbase_with_script(vec![0x02, 0x05, 0x00])).is_err());
+        assert!(extract_bip34_height(&coinbase_with_script(vec![0x01, 0x80])).is_err());
+        assert!(extract_bip34_height(&coinbase_with_script(vec![0x02, 0x05])).is_err());
+        assert!(extract_bip34_height(&coinbase_with_script(vec![0x6a])).is_err());
     }
 
     #[test]
-    fn test_validation_rules_equality() {
-        let mainnet1 = ProtocolValidationRules::mainnet();
-        let mainnet2 = ProtocolValidationRules::mainnet();
-        let testnet = ProtocolValidationRules::testnet();
+    fn test_bip34_height_checked_in_protocol_validation() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 3).unwrap();
+        let mut block = regtest_block();
+        block.transactions = vec![coinbase_with_script(vec![0x53, 0x00])];
 
-        assert_eq!(mainnet1, mainnet2);
-        assert_eq!(mainnet1, testnet); // Mainnet and testnet should be identical
+        assert!(engine
+            .validate_block_with_protocol(&block, &HashMap::new(), 3, &context)
+            .is_ok());
+
+        let err = engine
+            .validate_block_with_protocol(&block, &HashMap::new(), 4, &context)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("does not match block height 4"),
+            "{err}"
+        );
     }
 
     #[test]
-    fn test_validation_context() {
+    fn test_bip34_skipped_before_activation() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
         let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
-        assert_eq!(context.block_height, 1000);
-        assert!(context.is_feature_enabled("segwit"));
-        assert!(!context.is_feature_enabled("nonexistent"));
-        assert_eq!(context.get_max_size("block"), 4_000_000);
+        let mut block = regtest_block();
+        block.transactions = vec![coinbase_with_script(vec![0x53, 0x00])];
+
+        assert!(engine
+            .validate_block_with_protocol(&block, &HashMap::new(), 1000, &context)
+            .is_ok());
     }
 
     #[test]
-    fn test_validation_context_all_protocols() {
-        let mainnet_context =
-            ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
-        let testnet_context =
-            ProtocolValidationContext::new(ProtocolVersion::Testnet3, 1000).unwrap();
-        let regtest_context =
-            ProtocolValidationContext::new(ProtocolVersion::Regtest, 1000).unwrap();
-
-        // All should have same block height
-        assert_eq!(mainnet_context.block_height, 1000);
-        assert_eq!(testnet_context.block_height, 1000);
-        assert_eq!(regtest_context.block_height, 1000);
+    fn test_protocol_validation_rejects_duplicate_transactions() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 5).unwrap();
+        let utxos = funded_utxos();
+        let block = regtest_block();
+        assert!(engine
+            .validate_block_with_protocol(&block, &utxos, 5, &context)
+            .is_ok());
 
-        // All should support same features
-        assert!(mainnet_context.is_feature_enabled("segwit"));
-        assert!(testnet_context.is_feature_enabled("segwit"));
-        assert!(regtest_context.is_feature_enabled("segwit"));
+        let mut duplicated = block.clone();
+        duplicated.transactions.push(block.transactions[1].clone());
+        let err = engine
+            .validate_block_with_protocol(&mine(duplicated), &utxos, 5, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate transaction"), "{err}");
+    }
 
-        assert!(mainnet_context.is_feature_enabled("taproot"));
-        assert!(testnet_context.is_feature_enabled("taproot"));
-        assert!(regtest_context.is_feature_enabled("taproot"));
+    #[test]
+    fn test_intra_block_ordering() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 5).unwrap();
+        let mut block = regtest_block();
+        let parent_outpoint = OutPoint {
+            hash: encoding::txid(&block.transactions[1]),
+            index: 0,
+        };
+        block.transactions.push(spend(parent_outpoint, 9_8000_0000));
+        let block = mine(block);
+        check_intra_block_ordering(&block).unwrap();
 
-        assert!(mainnet_context.is_feature_enabled("rbf"));
-        assert!(testnet_context.is_feature_enabled("rbf"));
-        assert!(regtest_context.is_feature_enabled("rbf"));
+        let mut reordered = block.clone();
+        reordered.transactions.swap(1, 2);
+        let reordered = mine(reordered);
+        let err = check_intra_block_ordering(&reordered).unwrap_err();
+        assert!(err.to_string().contains("created later"), "{err}");
+        let err = engine
+            .validate_block_with_protocol(&reordered, &funded_utxos(), 5, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("created later"), "{err}");
     }
 
     #[test]
-    fn test_validation_context_feature_queries() {
-        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+    fn test_check_bip30() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let block = regtest_block();
+        let mut utxos = funded_utxos();
+        assert!(engine.check_bip30(&block, &utxos).is_ok());
 
-        // Test all supported features
-        assert!(context.is_feature_enabled("segwit"));
-        assert!(context.is_feature_enabled("taproot"));
-        assert!(context.is_feature_enabled("rbf"));
+        // An earlier block with the same coinbase left its output unspent
+        let coinbase_txid = encoding::txid(&block.transactions[0]);
+        utxos.insert(
+            OutPoint {
+                hash: coinbase_txid,
+                index: 0,
+            },
+            UTXO {
+                value: 50_0000_0000,
+                script_pubkey: vec![0x51],
+                height: 1,
+            },
+        );
+        let err = engine.check_bip30(&block, &utxos).unwrap_err();
+        assert!(err.to_string().contains("BIP30"), "{err}");
+        let err = engine
+            .fully_validate_block(&block, &PREV_HASH, 5, &mut utxos, BLOCK_TIME, &[])
+            .unwrap_err();
+        assert!(err.to_string().contains("BIP30"), "{err}");
+    }
 
-        // Test unsupported features
-        assert!(!context.is_feature_enabled("nonexistent"));
-        assert!(!context.is_feature_enabled(""));
-        assert!(!context.is_feature_enabled("fast_mining"));
+    #[test]
+    fn test_bip30_exceptions_are_mainnet_only() {
+        let mainnet = NetworkConstants::mainnet().unwrap();
+        let heights: Vec<u64> = mainnet.bip30_exceptions.iter().map(|(h, _)| *h).collect();
+        assert_eq!(heights, vec![91842, 91880]);
+        assert!(NetworkConstants::testnet()
+            .unwrap()
+            .bip30_exceptions
+            .is_empty());
+        assert!(NetworkConstants::regtest()
+            .unwrap()
+            .bip30_exceptions
+            .is_empty());
     }
 
     #[test]
-    fn test_validation_context_size_queries() {
-        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+    fn test_assume_valid_skips_script_checks() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        // The funding output can never be spent: OP_RETURN
+        let mut utxos = funded_utxos();
+        utxos.get_mut(&funding_outpoint()).unwrap().script_pubkey = vec![0x6a];
+        let block = regtest_block();
 
-        assert_eq!(context.get_max_size("block"), 4_000_000);
-        assert_eq!(context.get_max_size("transaction"), 1_000_000);
-        assert_eq!(context.get_max_size("script"), 10_000);
+        let mut context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 5).unwrap();
+        context.assume_valid_height = Some(100);
+        assert!(context.is_assumed_valid());
+        assert!(matches!(
+            engine.validate_block_with_protocol(&block, &utxos, 5, &context),
+            Ok(ValidationResult::Valid)
+        ));
 
-        // Test unknown component
-        assert_eq!(context.get_max_size("unknown"), 0);
+        // Structure is still checked below the trusted height
+        let mut bad_merkle = block.clone();
+        bad_merkle.header.merkle_root = [0u8; 32];
+        let err = engine
+            .validate_block_with_protocol(&bad_merkle, &utxos, 5, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("merkle root mismatch"), "{err}");
+
+        // Amounts are still checked below the trusted height
+        let mut overspend = block.clone();
+        overspend.transactions[1].outputs[0].value = 20_0000_0000;
+        let err = engine
+            .validate_block_with_protocol(&mine(overspend), &utxos, 5, &context)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("inputs less than outputs"),
+            "{err}"
+        );
+        let err = engine
+            .validate_block_with_protocol(&block, &HashMap::new(), 5, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("missing input"), "{err}");
+
+        // The height argument decides, not the context's own height
+        let stale_context = ProtocolValidationContext {
+            block_height: 200,
+            ..context.clone()
+        };
+        assert!(!stale_context.is_assumed_valid());
+        assert!(matches!(
+            engine.validate_block_with_protocol(&block, &utxos, 5, &stale_context),
+            Ok(ValidationResult::Valid)
+        ));
+
+        // At or above the trusted height the broken script is caught
+        context.assume_valid_height = Some(5);
+        assert!(!context.is_assumed_valid());
+        assert!(!matches!(
+            engine.validate_block_with_protocol(&block, &utxos, 5, &context),
+            Ok(ValidationResult::Valid)
+        ));
+    }
+
+    /// Block with a witness-spending transaction and a matching commitment
+    fn segwit_block() -> (Block, Vec<Vec<WitnessStack>>) {
+        let witnesses = vec![
+            vec![vec![vec![0u8; 32]]],
+            vec![vec![vec![0x30; 71], vec![0x02; 33]]],
+        ];
+        let mut block = regtest_block();
+
+        let wtxids = [
+            [0u8; 32],
+            encoding::wtxid(&block.transactions[1], &witnesses[1]),
+        ];
+        let mut preimage = encoding::merkle_root(&wtxids).to_vec();
+        preimage.extend_from_slice(&[0u8; 32]);
+        let commitment = crypto::sha256d(&preimage);
+
+        let mut script = WITNESS_COMMITMENT_PREFIX.to_vec();
+        script.extend_from_slice(&commitment);
+        block.transactions[0].outputs.push(TransactionOutput {
+            value: 0,
+            script_pubkey: script,
+        });
+        (block, witnesses)
     }
 
     #[test]
-    fn test_validation_context_serialization() {
-        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
-        let json = serde_json::to_string(&context).unwrap();
-        let deserialized: ProtocolValidationContext = serde_json::from_str(&json).unwrap();
+    fn test_witness_commitment_valid() {
+        let (block, witnesses) = segwit_block();
+        assert!(verify_witness_commitment(&block, &witnesses).is_ok());
 
-        assert_eq!(context.block_height, deserialized.block_height);
+        // Blocks without witness data need no commitment
+        assert!(verify_witness_commitment(&regtest_block(), &[]).is_ok());
+    }
+
+    #[test]
+    fn test_coinbase_witness_reserved() {
+        let (block, mut witnesses) = segwit_block();
+        witnesses[0][0][0] = vec![0x42; 32];
         assert_eq!(
-            context.network_params.network_name,
-            deserialized.network_params.network_name
+            coinbase_witness_reserved(&block, &witnesses).unwrap(),
+            [0x42; 32]
         );
-        assert_eq!(
-            context.validation_rules.max_block_size,
-            deserialized.validation_rules.max_block_size
+        // The commitment was made over an all-zero reserved value
+        let err = verify_witness_commitment(&block, &witnesses).unwrap_err();
+        assert!(
+            err.to_string().contains("witness commitment mismatch"),
+            "{err}"
         );
     }
 
     #[test]
-    fn test_validation_context_equality() {
-        let context1 = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
-        let context2 = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
-        let context3 = ProtocolValidationContext::new(ProtocolVersion::Testnet3, 1000).unwrap();
+    fn test_coinbase_missing_witness_reserved() {
+        let (block, mut witnesses) = segwit_block();
+        witnesses[0] = vec![vec![]];
+        let err = verify_witness_commitment(&block, &witnesses).unwrap_err();
+        assert!(err.to_string().contains("0 elements"), "{err}");
 
-        assert_eq!(context1, context2);
-        assert_ne!(context1, context3); // Different network parameters
+        witnesses[0] = vec![];
+        let err = verify_witness_commitment(&block, &witnesses).unwrap_err();
+        assert!(err.to_string().contains("reserved value missing"), "{err}");
+
+        witnesses[0] = vec![vec![vec![0u8; 31]]];
+        let err = verify_witness_commitment(&block, &witnesses).unwrap_err();
+        assert!(err.to_string().contains("31 bytes"), "{err}");
     }
 
     #[test]
-    fn test_block_size_validation() {
-        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
-        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+    fn test_witness_commitment_tampered() {
+        let (block, mut witnesses) = segwit_block();
+        witnesses[1][0][0][0] ^= 0x01;
 
-        // Create a block that's within size limits
-        let small_block = Block {
-            header: BlockHeader {
-                version: 1,
-                prev_block_hash: [0u8; 32],
-                merkle_root: [0u8; 32],
-                timestamp: 1231006505,
-                bits: 0x1d00ffff,
-                nonce: 0,
-            },
-            transactions: vec![Transaction {
-                version: 1,
-                inputs: vec![],
-                outputs: vec![],
-                lock_time: 0,
-            }],
-        };
+        let err = verify_witness_commitment(&block, &witnesses).unwrap_err();
+        assert!(
+            err.to_string().contains("witness commitment mismatch"),
+            "{err}"
+        );
 
-        // This should pass validation
-        let result =
-            engine.validate_block_with_protocol(&small_block, &HashMap::new(), 1000, &context);
-        assert!(result.is_ok());
+        let mut uncommitted = block.clone();
+        uncommitted.transactions[0].outputs.pop();
+        let err = verify_witness_commitment(&uncommitted, &witnesses).unwrap_err();
+        assert!(
+            err.to_string().contains("missing witness commitment"),
+            "{err}"
+        );
     }
 
     #[test]
-    fn test_transaction_size_validation() {
-        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
-        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+    fn test_witness_commitment_gated_on_segwit_activation() {
+        let (block, mut witnesses) = segwit_block();
+        witnesses[1][0][0][0] ^= 0x01;
+        let utxos = funded_utxos();
 
-        // Create a small transaction
-        let small_tx = Transaction {
-            version: 1,
-            inputs: vec![TransactionInput {
-                prevout: OutPoint {
-                    hash: [0u8; 32],
-                    index: 0,
-                },
-                script_sig: vec![0x41, 0x04], // Small signature
-                sequence: 0xffffffff,
-            }],
-            outputs: vec![TransactionOutput {
-                value: 50_0000_0000,
-                script_pubkey: vec![
-                    0x76, 0xa9, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                ], // P2PKH
-            }],
-            lock_time: 0,
-        };
+        let regtest = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 5).unwrap();
+        assert!(regtest
+            .validate_block_with_witnesses(&block, &witnesses, &utxos, 5, &context)
+            .is_err());
 
-        // This should pass validation
-        let result = engine.validate_transaction_with_protocol(&small_tx, &context);
-        assert!(result.is_ok());
+        // Mainnet before SegWit activation ignores the commitment
+        let mainnet = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 100_000).unwrap();
+        let mut early = block.clone();
+        early.header.timestamp = 1_293_623_863;
+        assert!(mainnet
+            .validate_block_with_witnesses(&early, &witnesses, &utxos, 100_000, &context)
+            .is_ok());
     }
 
     #[test]
-    fn test_script_size_validation() {
-        let engine = BitcoinProtocolEngine::new(ProtocolVersion::BitcoinV1).unwrap();
-        let context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+    fn test_validate_witness_program() {
+        let v0 = validate_witness_program(&scripts::p2wpkh_script([1; 20]), true).unwrap();
+        assert_eq!((v0.version, v0.program.len()), (0, 20));
+        assert!(!v0.is_unknown_version);
 
-        // Create a transaction with small scripts
-        let tx = Transaction {
-            version: 1,
-            inputs: vec![TransactionInput {
-                prevout: OutPoint {
-                    hash: [0u8; 32],
-                    index: 0,
-                },
-                script_sig: vec![0x41, 0x04], // Small script sig
-                sequence: 0xffffffff,
-            }],
-            outputs: vec![TransactionOutput {
-                value: 50_0000_0000,
-                script_pubkey: vec![
-                    0x76, 0xa9, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                ], // Small script pubkey
-            }],
-            lock_time: 0,
+        let v1 = validate_witness_program(&scripts::p2tr_script([2; 32]), true).unwrap();
+        assert_eq!((v1.version, v1.program), (1, vec![2; 32]));
+        assert!(!v1.is_unknown_version);
+        let v1 = validate_witness_program(&scripts::p2tr_script([2; 32]), false).unwrap();
+        assert!(v1.is_unknown_version);
+
+        let err = validate_witness_program(&scripts::witness_program_script(0, &[3; 19]), true)
+            .unwrap_err();
+        assert!(err.to_string().contains("v0 program of 19 bytes"), "{err}");
+        // Only 32-byte v1 programs are Taproot; other lengths stay unknown
+        let v1 =
+            validate_witness_program(&scripts::witness_program_script(1, &[3; 20]), true).unwrap();
+        assert_eq!((v1.version, v1.program.len()), (1, 20));
+        assert!(v1.is_unknown_version);
+
+        let v2 =
+            validate_witness_program(&scripts::witness_program_script(2, &[4; 40]), true).unwrap();
+        assert_eq!(v2.version, 2);
+        assert!(v2.is_unknown_version);
+
+        assert!(validate_witness_program(&scripts::p2pkh_script([5; 20]), true).is_err());
+    }
+
+    #[test]
+    fn test_check_script_limits() {
+        let push = |len: usize| {
+            let mut script = vec![0x4d];
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+            script.resize(3 + len, 0xab);
+            script
         };
+        assert!(check_script_limits(&push(520)).is_ok());
+        let err = check_script_limits(&push(521)).unwrap_err();
+        assert!(err.to_string().contains("exceeds 520"), "{err}");
 
-        // This should pass validation
-        let result = engine.validate_transaction_with_protocol(&tx, &context);
-        assert!(result.is_ok());
+        // OP_NOP is a non-push operation; small-integer pushes are not
+        assert!(check_script_limits(&[0x61; 201]).is_ok());
+        let err = check_script_limits(&[0x61; 202]).unwrap_err();
+        assert!(err.to_string().contains("more than 201"), "{err}");
+        assert!(check_script_limits(&[0x51; 300]).is_ok());
+
+        // OP_CAT, even inside otherwise valid code
+        let err = check_script_limits(&[0x01, 0x02, 0x7e]).unwrap_err();
+        assert!(err.to_string().contains("disabled opcode 0x7e"), "{err}");
+        // ... but not when it is pushed data
+        assert!(check_script_limits(&[0x01, 0x7e]).is_ok());
+
+        let err = check_script_limits(&[0x05, 0x01]).unwrap_err();
+        assert!(err.to_string().contains("truncated push"), "{err}");
+        assert!(check_script_limits(&[0x4d, 0x01]).is_err());
     }
 
     #[test]
-    fn test_validation_context_data() {
-        let mut context = ProtocolValidationContext::new(ProtocolVersion::BitcoinV1, 1000).unwrap();
+    fn test_transaction_rejects_oversized_script_ops() {
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest).unwrap();
+        let context = ProtocolValidationContext::new(ProtocolVersion::Regtest, 1000).unwrap();
 
-        // Add some context data
-        context
-            .context_data
-            .insert("test_key".to_string(), "test_value".to_string());
+        let mut tx = spend(funding_outpoint(), 10_000);
+        tx.outputs[0].script_pubkey = vec![0x61; 202];
+        let err = engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("more than 201"), "{err}");
 
-        assert_eq!(
-            context.context_data.get("test_key"),
-            Some(&"test_value".to_string())
-        );
-        assert_eq!(context.context_data.get("nonexistent"), None);
+        tx.outputs[0].script_pubkey = scripts::p2wpkh_script([1; 20]);
+        tx.inputs[0].script_sig = vec![0x95];
+        let err = engine
+            .validate_transaction_with_protocol(&tx, &context)
+            .unwrap_err();
+        assert!(err.to_string().contains("disabled opcode"), "{err}");
+    }
+
+    #[test]
+    fn test_script_sigop_count_skips_push_data() {
+        // OP_CHECKSIG inside pushed data is not an operation
+        assert_eq!(script_sigop_count(&[0x01, 0xac]), 0);
+        assert_eq!(script_sigop_count(&[0xac, 0xae]), 21);
+        assert_eq!(script_sigop_count(&[0x4c, 0x02, 0xac, 0xac, 0xad]), 1);
     }
 
     #[test]
@@ -0,0 +1,124 @@
+//! Signature hash types
+//!
+//! Interpretation of the sighash byte appended to signatures (legacy and
+//! SegWit v0) or optionally present in Taproot signatures (BIP341).
+
+use crate::{ConsensusError, Result};
+
+/// Taproot-only default sighash, equivalent to ALL
+pub const SIGHASH_DEFAULT: u8 = 0x00;
+/// Sign all inputs and outputs
+pub const SIGHASH_ALL: u8 = 0x01;
+/// Sign all inputs and no outputs
+pub const SIGHASH_NONE: u8 = 0x02;
+/// Sign all inputs and the output with the same index
+pub const SIGHASH_SINGLE: u8 = 0x03;
+/// Modifier: sign only the current input
+pub const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Which outputs a signature commits to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SighashBase {
+    All,
+    None,
+    Single,
+}
+
+/// A parsed sighash byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SighashType {
+    byte: u8,
+}
+
+impl SighashType {
+    /// Parse a legacy or SegWit v0 sighash byte
+    ///
+    /// Only ALL, NONE and SINGLE, optionally combined with ANYONECANPAY, are
+    /// defined; any other value is rejected.
+    pub fn from_byte(byte: u8) -> Result<Self> {
+        match byte & !SIGHASH_ANYONECANPAY {
+            SIGHASH_ALL | SIGHASH_NONE | SIGHASH_SINGLE => Ok(Self { byte }),
+            _ => Err(ConsensusError::ScriptExecution(format!(
+                "undefined sighash type 0x{byte:02x}"
+            ))
+            .into()),
+        }
+    }
+
+    /// Parse a Taproot sighash byte, which additionally allows `SIGHASH_DEFAULT`
+    pub fn from_taproot_byte(byte: u8) -> Result<Self> {
+        if byte == SIGHASH_DEFAULT {
+            return Ok(Self { byte });
+        }
+        Self::from_byte(byte)
+    }
+
+    /// Which outputs are committed to (`SIGHASH_DEFAULT` behaves as ALL)
+    pub fn base(&self) -> SighashBase {
+        match self.byte & !SIGHASH_ANYONECANPAY {
+            SIGHASH_NONE => SighashBase::None,
+            SIGHASH_SINGLE => SighashBase::Single,
+            _ => SighashBase::All,
+        }
+    }
+
+    /// Whether only the signing input is committed to
+    pub fn anyone_can_pay(&self) -> bool {
+        self.byte & SIGHASH_ANYONECANPAY != 0
+    }
+
+    /// Whether this is Taproot's implicit `SIGHASH_DEFAULT`
+    pub fn is_default(&self) -> bool {
+        self.byte == SIGHASH_DEFAULT
+    }
+
+    /// The sighash byte this type was parsed from
+    pub fn to_byte(&self) -> u8 {
+        self.byte
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_anyonecanpay() {
+        let sighash = SighashType::from_byte(0x81).unwrap();
+        assert_eq!(sighash.base(), SighashBase::All);
+        assert!(sighash.anyone_can_pay());
+        assert!(!sighash.is_default());
+        assert_eq!(sighash.to_byte(), 0x81);
+    }
+
+    #[test]
+    fn test_single() {
+        let sighash = SighashType::from_byte(0x03).unwrap();
+        assert_eq!(sighash.base(), SighashBase::Single);
+        assert!(!sighash.anyone_can_pay());
+        assert_eq!(sighash.to_byte(), SIGHASH_SINGLE);
+    }
+
+    #[test]
+    fn test_undefined_types_rejected() {
+        for byte in [0x04, 0x84, 0x00, 0x80, 0x41] {
+            assert!(SighashType::from_byte(byte).is_err(), "0x{byte:02x}");
+        }
+        let err = SighashType::from_byte(0x04).unwrap_err();
+        assert!(err.to_string().contains("0x04"), "{err}");
+    }
+
+    #[test]
+    fn test_taproot_default() {
+        assert!(SighashType::from_byte(SIGHASH_DEFAULT).is_err());
+
+        let sighash = SighashType::from_taproot_byte(SIGHASH_DEFAULT).unwrap();
+        assert!(sighash.is_default());
+        assert_eq!(sighash.base(), SighashBase::All);
+        assert!(!sighash.anyone_can_pay());
+        assert_eq!(sighash.to_byte(), 0x00);
+
+        assert!(SighashType::from_taproot_byte(0x82).is_ok());
+        assert!(SighashType::from_taproot_byte(0x04).is_err());
+    }
+}
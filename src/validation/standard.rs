@@ -0,0 +1,252 @@
+//! Standard output script classification
+//!
+//! Recognises the canonical scriptPubKey templates built by `crate::scripts`
+//! so relay policy can reject outputs that match none of them.
+
+use super::{is_coinbase, OP_RETURN};
+use crate::{ProtocolError, Result, Transaction};
+
+/// Largest number of keys in a standard bare multisig output
+pub const MAX_STANDARD_MULTISIG_KEYS: u8 = 3;
+
+/// Highest transaction version relayed by default
+pub const MAX_STANDARD_TX_VERSION: u64 = 2;
+
+/// Template matched by an output script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptType {
+    /// Pay to public key hash
+    P2PKH,
+    /// Pay to script hash (BIP16)
+    P2SH,
+    /// Pay to witness public key hash (BIP141)
+    P2WPKH,
+    /// Pay to witness script hash (BIP141)
+    P2WSH,
+    /// Pay to Taproot (BIP341)
+    P2TR,
+    /// Bare `required`-of-`keys` multisig
+    Multisig { required: u8, keys: u8 },
+    /// OP_RETURN followed only by data pushes
+    OpReturn,
+    /// Anything else
+    NonStandard,
+}
+
+/// Classify an output script by matching the canonical templates
+pub fn classify_script(script: &[u8]) -> ScriptType {
+    match script {
+        [0x76, 0xa9, 0x14, _hash @ .., 0x88, 0xac] if script.len() == 25 => ScriptType::P2PKH,
+        [0xa9, 0x14, _hash @ .., 0x87] if script.len() == 23 => ScriptType::P2SH,
+        [0x00, 0x14, _hash @ ..] if script.len() == 22 => ScriptType::P2WPKH,
+        [0x00, 0x20, _hash @ ..] if script.len() == 34 => ScriptType::P2WSH,
+        [0x51, 0x20, _key @ ..] if script.len() == 34 => ScriptType::P2TR,
+        [OP_RETURN, data @ ..] if is_push_only(data) => ScriptType::OpReturn,
+        _ => classify_multisig(script).unwrap_or(ScriptType::NonStandard),
+    }
+}
+
+/// Whether relay policy accepts outputs of this type
+///
+/// Bare multisig is only standard up to `MAX_STANDARD_MULTISIG_KEYS` keys.
+pub fn is_standard(script_type: &ScriptType) -> bool {
+    match script_type {
+        ScriptType::NonStandard => false,
+        ScriptType::Multisig { keys, .. } => *keys <= MAX_STANDARD_MULTISIG_KEYS,
+        _ => true,
+    }
+}
+
+/// Relay standardness of a whole transaction, mirroring Core's `IsStandardTx`
+///
+/// The version must be 1 or 2, every output must match a standard template,
+/// and every non-coinbase scriptSig must only push data.
+pub fn check_standard_tx(tx: &Transaction) -> Result<()> {
+    if !(1..=MAX_STANDARD_TX_VERSION).contains(&tx.version) {
+        return Err(ProtocolError::PolicyViolation(format!(
+            "nonstandard transaction version {}",
+            tx.version
+        )));
+    }
+    if !is_coinbase(tx)
+        && !tx
+            .inputs
+            .iter()
+            .all(|input| is_push_only(&input.script_sig))
+    {
+        return Err(ProtocolError::PolicyViolation(
+            "scriptSig not push-only".to_string(),
+        ));
+    }
+    if let Some(index) = tx
+        .outputs
+        .iter()
+        .position(|output| !is_standard(&classify_script(&output.script_pubkey)))
+    {
+        return Err(ProtocolError::PolicyViolation(format!(
+            "nonstandard script in output {index}"
+        )));
+    }
+    Ok(())
+}
+
+/// Decode OP_1..OP_16
+fn small_int(opcode: u8) -> Option<u8> {
+    (0x51..=0x60).contains(&opcode).then(|| opcode - 0x50)
+}
+
+/// OP_m <33- or 65-byte keys> OP_n OP_CHECKMULTISIG with 1 <= m <= n
+fn classify_multisig(script: &[u8]) -> Option<ScriptType> {
+    let [first, body @ .., last_count, 0xae] = script else {
+        return None;
+    };
+    let required = small_int(*first)?;
+    let keys = small_int(*last_count)?;
+
+    let mut rest = body;
+    let mut found = 0;
+    while let [len @ (33 | 65), tail @ ..] = rest {
+        let len = *len as usize;
+        if tail.len() < len {
+            return None;
+        }
+        rest = &tail[len..];
+        found += 1;
+    }
+
+    (rest.is_empty() && found == keys && required <= keys)
+        .then_some(ScriptType::Multisig { required, keys })
+}
+
+/// Whether `script` consists only of push opcodes (up to OP_16)
+fn is_push_only(mut script: &[u8]) -> bool {
+    while let [opcode, rest @ ..] = script {
+        let (len, rest) = match *opcode {
+            0x01..=0x4b => (*opcode as usize, rest),
+            0x4c => match rest {
+                [len, rest @ ..] => (*len as usize, rest),
+                _ => return false,
+            },
+            0x4d => match rest {
+                [a, b, rest @ ..] => (u16::from_le_bytes([*a, *b]) as usize, rest),
+                _ => return false,
+            },
+            0x4e => match rest {
+                [a, b, c, d, rest @ ..] => (u32::from_le_bytes([*a, *b, *c, *d]) as usize, rest),
+                _ => return false,
+            },
+            0x00 | 0x4f..=0x60 => (0, rest),
+            _ => return false,
+        };
+        if rest.len() < len {
+            return false;
+        }
+        script = &rest[len..];
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripts;
+    use crate::{OutPoint, TransactionInput, TransactionOutput};
+
+    fn paying_to(script_pubkey: Vec<u8>) -> Transaction {
+        Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                prevout: OutPoint {
+                    hash: [1; 32],
+                    index: 0,
+                },
+                script_sig: vec![0x01, 0x02],
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 10_000,
+                script_pubkey,
+            }],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_standard_tx() {
+        let tx = paying_to(scripts::p2wpkh_script([7; 20]));
+        assert!(check_standard_tx(&tx).is_ok());
+
+        let mut non_push = tx.clone();
+        non_push.inputs[0].script_sig = vec![0x76, 0xac];
+        let err = check_standard_tx(&non_push).unwrap_err();
+        assert!(err.to_string().contains("push-only"), "{err}");
+
+        let err = check_standard_tx(&paying_to(vec![0xff])).unwrap_err();
+        assert!(err.to_string().contains("output 0"), "{err}");
+    }
+
+    #[test]
+    fn test_classify_standard_templates() {
+        let cases = [
+            (scripts::p2pkh_script([1; 20]), ScriptType::P2PKH),
+            (scripts::p2sh_script([2; 20]), ScriptType::P2SH),
+            (scripts::p2wpkh_script([3; 20]), ScriptType::P2WPKH),
+            (scripts::p2wsh_script([4; 32]), ScriptType::P2WSH),
+            (scripts::p2tr_script([5; 32]), ScriptType::P2TR),
+            (
+                scripts::multisig_script(2, &[vec![2; 33], vec![3; 33], vec![4; 65]]),
+                ScriptType::Multisig {
+                    required: 2,
+                    keys: 3,
+                },
+            ),
+            (scripts::op_return_script(b"hello"), ScriptType::OpReturn),
+            (scripts::op_return_script(&[0xdd; 80]), ScriptType::OpReturn),
+            (scripts::op_return_script(&[]), ScriptType::OpReturn),
+        ];
+        for (script, expected) in cases {
+            assert_eq!(classify_script(&script), expected, "{script:02x?}");
+            assert!(is_standard(&expected));
+        }
+    }
+
+    #[test]
+    fn test_classify_nonstandard() {
+        let garbage = [0xff, 0x00, 0x13, 0x37];
+        assert_eq!(classify_script(&garbage), ScriptType::NonStandard);
+        assert!(!is_standard(&classify_script(&garbage)));
+        assert_eq!(classify_script(&[]), ScriptType::NonStandard);
+
+        // Right prefix, wrong length
+        assert_eq!(
+            classify_script(&scripts::p2wpkh_script([3; 20])[..21]),
+            ScriptType::NonStandard
+        );
+        // OP_RETURN followed by a non-push opcode or a truncated push
+        assert_eq!(classify_script(&[0x6a, 0xac]), ScriptType::NonStandard);
+        assert_eq!(
+            classify_script(&[0x6a, 0x05, 0x01]),
+            ScriptType::NonStandard
+        );
+        // Key count disagreeing with OP_n
+        let mut multisig = scripts::multisig_script(1, &[vec![2; 33], vec![3; 33]]);
+        let count_index = multisig.len() - 2;
+        multisig[count_index] = 0x53;
+        assert_eq!(classify_script(&multisig), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn test_large_bare_multisig_is_not_standard() {
+        let keys: Vec<Vec<u8>> = (0..4).map(|i| vec![2 + i; 33]).collect();
+        let script_type = classify_script(&scripts::multisig_script(1, &keys));
+
+        assert_eq!(
+            script_type,
+            ScriptType::Multisig {
+                required: 1,
+                keys: 4
+            }
+        );
+        assert!(!is_standard(&script_type));
+    }
+}
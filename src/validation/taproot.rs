@@ -0,0 +1,204 @@
+//! Taproot witness parsing
+//!
+//! Splits a Taproot (SegWit v1) witness stack into its BIP341 components:
+//! the optional annex, and either a key-path signature or a script-path
+//! script, its inputs and the control block.
+
+use crate::{ConsensusError, Result};
+
+/// First byte identifying an annex as the last witness element
+pub const ANNEX_TAG: u8 = 0x50;
+
+/// Mask selecting the leaf version from the first control block byte
+pub const TAPROOT_LEAF_MASK: u8 = 0xfe;
+
+/// Size of the control block without merkle path: header byte plus internal key
+pub const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+
+/// Size of each merkle path node in the control block
+pub const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+
+/// Maximum depth of the script tree
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+/// Parsed script-path control block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlBlock {
+    /// Tapscript leaf version (0xc0 for BIP342 scripts)
+    pub leaf_version: u8,
+    /// Parity of the output key's Y coordinate
+    pub output_key_parity: u8,
+    /// X-only internal key
+    pub internal_key: [u8; 32],
+    /// Merkle path from the leaf to the root
+    pub merkle_path: Vec<[u8; 32]>,
+}
+
+impl ControlBlock {
+    /// Parse a control block, whose length must be `33 + 32k` with `k <= 128`
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let path_len = bytes.len().checked_sub(TAPROOT_CONTROL_BASE_SIZE);
+        let node_count = match path_len {
+            Some(len) if len % TAPROOT_CONTROL_NODE_SIZE == 0 => len / TAPROOT_CONTROL_NODE_SIZE,
+            _ => {
+                return Err(ConsensusError::ScriptExecution(format!(
+                    "invalid control block length {}",
+                    bytes.len()
+                ))
+                .into())
+            }
+        };
+        if node_count > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(ConsensusError::ScriptExecution(format!(
+                "control block merkle path too long ({node_count} nodes)"
+            ))
+            .into());
+        }
+
+        let mut internal_key = [0u8; 32];
+        internal_key.copy_from_slice(&bytes[1..TAPROOT_CONTROL_BASE_SIZE]);
+        let merkle_path = bytes[TAPROOT_CONTROL_BASE_SIZE..]
+            .chunks_exact(TAPROOT_CONTROL_NODE_SIZE)
+            .map(|node| node.try_into().expect("chunk is 32 bytes"))
+            .collect();
+
+        Ok(Self {
+            leaf_version: bytes[0] & TAPROOT_LEAF_MASK,
+            output_key_parity: bytes[0] & 1,
+            internal_key,
+            merkle_path,
+        })
+    }
+}
+
+/// Components of a Taproot witness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaprootSpend {
+    /// Spend with a single signature for the output key
+    KeyPath {
+        signature: Vec<u8>,
+        annex: Option<Vec<u8>>,
+    },
+    /// Spend revealing a script from the script tree
+    ScriptPath {
+        /// Inputs consumed by the script
+        stack: Vec<Vec<u8>>,
+        script: Vec<u8>,
+        control_block: ControlBlock,
+        annex: Option<Vec<u8>>,
+    },
+}
+
+impl TaprootSpend {
+    /// Annex, if the witness carried one
+    pub fn annex(&self) -> Option<&[u8]> {
+        match self {
+            TaprootSpend::KeyPath { annex, .. } | TaprootSpend::ScriptPath { annex, .. } => {
+                annex.as_deref()
+            }
+        }
+    }
+}
+
+/// Split a Taproot witness stack into its key-path or script-path components
+///
+/// With two or more elements, a last element starting with `ANNEX_TAG` is the
+/// annex. One remaining element is a key-path signature; otherwise the last
+/// two are the script and its control block.
+pub fn parse_control_block(witness: &[Vec<u8>]) -> Result<TaprootSpend> {
+    let (annex, stack) = match witness.split_last() {
+        Some((last, rest)) if !rest.is_empty() && last.first() == Some(&ANNEX_TAG) => {
+            (Some(last.clone()), rest)
+        }
+        _ => (None, witness),
+    };
+
+    match stack {
+        [] => Err(ConsensusError::ScriptExecution("empty taproot witness".to_string()).into()),
+        [signature] => Ok(TaprootSpend::KeyPath {
+            signature: signature.clone(),
+            annex,
+        }),
+        [inputs @ .., script, control] => Ok(TaprootSpend::ScriptPath {
+            stack: inputs.to_vec(),
+            script: script.clone(),
+            control_block: ControlBlock::parse(control)?,
+            annex,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_block(first: u8, depth: usize) -> Vec<u8> {
+        let mut bytes = vec![first];
+        bytes.extend_from_slice(&[0x11; 32]);
+        for node in 0..depth {
+            bytes.extend_from_slice(&[node as u8; 32]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_key_path() {
+        let signature = vec![0xaa; 64];
+        let spend = parse_control_block(&[signature.clone()]).unwrap();
+        assert_eq!(
+            spend,
+            TaprootSpend::KeyPath {
+                signature: signature.clone(),
+                annex: None
+            }
+        );
+
+        let annex = vec![ANNEX_TAG, 0x01];
+        let spend = parse_control_block(&[signature, annex.clone()]).unwrap();
+        assert!(matches!(spend, TaprootSpend::KeyPath { .. }));
+        assert_eq!(spend.annex(), Some(annex.as_slice()));
+    }
+
+    #[test]
+    fn test_script_path_with_annex() {
+        let script = vec![0x51];
+        let witness = vec![
+            vec![0x01],
+            vec![0x02],
+            script.clone(),
+            control_block(0xc1, 2),
+            vec![ANNEX_TAG, 0xff],
+        ];
+
+        let spend = parse_control_block(&witness).unwrap();
+        assert_eq!(spend.annex(), Some(&[ANNEX_TAG, 0xff][..]));
+        match spend {
+            TaprootSpend::ScriptPath {
+                stack,
+                script: revealed,
+                control_block,
+                ..
+            } => {
+                assert_eq!(stack, vec![vec![0x01], vec![0x02]]);
+                assert_eq!(revealed, script);
+                assert_eq!(control_block.leaf_version, 0xc0);
+                assert_eq!(control_block.output_key_parity, 1);
+                assert_eq!(control_block.internal_key, [0x11; 32]);
+                assert_eq!(control_block.merkle_path, vec![[0u8; 32], [1u8; 32]]);
+            }
+            other => panic!("expected script path, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_control_block_length() {
+        let mut control = control_block(0xc0, 1);
+        control.push(0x00);
+        let err = parse_control_block(&[vec![0x51], control]).unwrap_err();
+        assert!(err.to_string().contains("control block length 66"), "{err}");
+
+        assert!(parse_control_block(&[vec![0x51], vec![0xc0; 32]]).is_err());
+        assert!(parse_control_block(&[vec![0x51], control_block(0xc0, 129)]).is_err());
+        assert!(parse_control_block(&[]).is_err());
+    }
+}
@@ -4,8 +4,11 @@
 //! - Mainnet: Production Bitcoin network
 //! - Testnet: Bitcoin test network
 //! - Regtest: Regression testing network
+//! - Signet: Test network whose blocks are signed by a challenge script
+//! - Simnet: Regtest without proof-of-work, for test harnesses
+//! - Educational: Minimal Bitcoin with simplified parameters, for learning
 
-use crate::ProtocolVersion;
+use crate::{BitcoinProtocolEngine, ProtocolVersion, Result};
 use serde::{Deserialize, Serialize};
 
 /// Protocol variant configuration
@@ -47,6 +50,33 @@ impl ProtocolVariant {
                 supports_mining: true,
                 supports_wallet: true,
             },
+            ProtocolVariant {
+                version: ProtocolVersion::Signet,
+                name: "Bitcoin Signet".to_string(),
+                description: "Test network with blocks signed by a challenge script".to_string(),
+                is_production: false,
+                supports_mining: true,
+                supports_wallet: true,
+            },
+            ProtocolVariant {
+                version: ProtocolVersion::Simnet,
+                name: "Bitcoin Simnet".to_string(),
+                description: "Test-only network that accepts blocks without proof-of-work"
+                    .to_string(),
+                is_production: false,
+                supports_mining: true,
+                supports_wallet: true,
+            },
+            ProtocolVariant {
+                version: ProtocolVersion::Educational,
+                name: "Bitcoin Educational".to_string(),
+                description: "Minimal Bitcoin for learning: all features active from genesis, \
+                              trivial proof-of-work and a halving every 10 blocks"
+                    .to_string(),
+                is_production: false,
+                supports_mining: true,
+                supports_wallet: true,
+            },
         ]
     }
 
@@ -71,6 +101,11 @@ impl ProtocolVariant {
     pub fn supports_wallet_operations(&self) -> bool {
         self.supports_wallet
     }
+
+    /// Create a protocol engine whose capabilities are gated by this variant
+    pub fn create_engine(&self) -> Result<BitcoinProtocolEngine> {
+        BitcoinProtocolEngine::with_variant(self.clone())
+    }
 }
 
 /// Protocol evolution support
@@ -86,6 +121,23 @@ pub struct ProtocolEvolution {
     pub breaking_changes: Vec<String>,
 }
 
+/// Changes between two protocol evolution versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationReport {
+    /// Version migrated from
+    pub from_version: u32,
+    /// Version migrated to
+    pub to_version: u32,
+    /// Features enabled in the new version only
+    pub added_features: Vec<String>,
+    /// Features newly deprecated in the new version
+    pub deprecated_features: Vec<String>,
+    /// Features enabled in the old version but not the new one
+    pub removed_features: Vec<String>,
+    /// Breaking changes introduced by the new version
+    pub breaking_changes: Vec<String>,
+}
+
 impl ProtocolEvolution {
     /// Bitcoin V1 (current)
     pub fn bitcoin_v1() -> Self {
@@ -131,16 +183,36 @@ impl ProtocolEvolution {
     pub fn is_deprecated(&self, feature: &str) -> bool {
         self.deprecated_features.contains(&feature.to_string())
     }
+
+    /// Describe what changes when moving from `prev` to this version
+    pub fn migration_from(&self, prev: &ProtocolEvolution) -> MigrationReport {
+        let missing_from = |list: &[String], other: &[String]| -> Vec<String> {
+            list.iter()
+                .filter(|item| !other.contains(item))
+                .cloned()
+                .collect()
+        };
+
+        MigrationReport {
+            from_version: prev.version,
+            to_version: self.version,
+            added_features: missing_from(&self.enabled_features, &prev.enabled_features),
+            deprecated_features: missing_from(&self.deprecated_features, &prev.deprecated_features),
+            removed_features: missing_from(&prev.enabled_features, &self.enabled_features),
+            breaking_changes: missing_from(&self.breaking_changes, &prev.breaking_changes),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ProtocolError;
 
     #[test]
     fn test_protocol_variants() {
         let variants = ProtocolVariant::all_variants();
-        assert_eq!(variants.len(), 3);
+        assert_eq!(variants.len(), 6);
 
         let mainnet = ProtocolVariant::for_version(ProtocolVersion::BitcoinV1).unwrap();
         assert_eq!(mainnet.name, "Bitcoin Mainnet");
@@ -151,6 +223,10 @@ mod tests {
         assert_eq!(regtest.name, "Bitcoin Regtest");
         assert!(!regtest.is_production_ready());
         assert!(regtest.supports_mining_operations());
+
+        let educational = ProtocolVariant::for_version(ProtocolVersion::Educational).unwrap();
+        assert!(!educational.is_production_ready());
+        assert!(educational.description.contains("learning"));
     }
 
     #[test]
@@ -381,4 +457,60 @@ mod tests {
         // V2 should have deprecated features
         assert!(!v2.deprecated_features.is_empty());
     }
+
+    #[test]
+    fn test_protocol_evolution_migration_report() {
+        let v1 = ProtocolEvolution::bitcoin_v1();
+        let v2 = ProtocolEvolution::bitcoin_v2();
+
+        let report = v2.migration_from(&v1);
+        assert_eq!((report.from_version, report.to_version), (1, 2));
+        assert_eq!(
+            report.added_features,
+            vec!["advanced_scripting", "privacy_features"]
+        );
+        assert_eq!(report.deprecated_features, vec!["legacy_addresses"]);
+        assert!(report.removed_features.is_empty());
+        assert_eq!(report.breaking_changes, v2.breaking_changes);
+
+        // Going back removes what V2 added
+        let rollback = v1.migration_from(&v2);
+        assert_eq!(rollback.removed_features, report.added_features);
+        assert!(rollback.added_features.is_empty());
+
+        let json = serde_json::to_string(&report).unwrap();
+        let deserialized: MigrationReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(report, deserialized);
+    }
+
+    #[test]
+    fn test_create_engine_mainnet_supports_mining() {
+        let variant = ProtocolVariant::for_version(ProtocolVersion::BitcoinV1).unwrap();
+        let engine = variant.create_engine().unwrap();
+
+        assert_eq!(engine.get_protocol_version(), ProtocolVersion::BitcoinV1);
+        assert_eq!(engine.get_variant(), &variant);
+        assert!(engine.assert_mining_supported().is_ok());
+        assert!(engine.assert_wallet_supported().is_ok());
+    }
+
+    #[test]
+    fn test_create_engine_observe_only_variant() {
+        let mut variant = ProtocolVariant::for_version(ProtocolVersion::Regtest).unwrap();
+        variant.name = "Observe-only Regtest".to_string();
+        variant.supports_mining = false;
+        variant.supports_wallet = false;
+
+        let engine = variant.create_engine().unwrap();
+        assert!(matches!(
+            engine.assert_mining_supported(),
+            Err(ProtocolError::FeatureNotActive(_))
+        ));
+        let err = engine.assert_mining_supported().unwrap_err().to_string();
+        assert!(err.contains("Observe-only Regtest"), "{err}");
+        assert!(err.contains("mining"), "{err}");
+
+        let err = engine.assert_wallet_supported().unwrap_err().to_string();
+        assert!(err.contains("wallet"), "{err}");
+    }
 }